@@ -102,6 +102,24 @@ class QubeeManager @Inject constructor(
         }
     }
 
+    /**
+     * Open a messenger bound to [sessionId] for repeated encrypt/decrypt
+     * calls. Returns its handle; release it with [destroyMessenger].
+     * Unlike the session-id calls above, the handle calls throw
+     * `IllegalStateException` on failure instead of returning null.
+     */
+    fun createMessenger(sessionId: String): Long = nativeCreateMessenger(sessionId)
+
+    fun destroyMessenger(handle: Long) = nativeDestroyMessenger(handle)
+
+    suspend fun messengerEncrypt(handle: Long, plaintext: ByteArray): ByteArray = withContext(Dispatchers.IO) {
+        nativeMessengerEncrypt(handle, plaintext)
+    }
+
+    suspend fun messengerDecrypt(handle: Long, encryptedEnvelope: ByteArray): ByteArray = withContext(Dispatchers.IO) {
+        nativeMessengerDecrypt(handle, encryptedEnvelope)
+    }
+
     suspend fun encryptFile(sessionId: String, fileData: ByteArray): EncryptedFile? = withContext(Dispatchers.IO) {
         if (!isInitialized) return@withContext null
         try {
@@ -510,6 +528,12 @@ class QubeeManager @Inject constructor(
         nativeListAcceptedInvites()
     }
 
+    /** Security audit report as JSON, or `{"error": ...}` if it couldn't run. */
+    suspend fun runSecurityAudit(): String? = withContext(Dispatchers.IO) {
+        if (!isInitialized) return@withContext null
+        nativeRunSecurityAudit()
+    }
+
     private external fun nativeInitialize(dataDir: String, keystorePassphrase: String): Boolean
     private external fun nativeRegisterCallback(callback: NetworkCallback)
     private external fun nativeStartNetwork(bootstrapNodes: String): Boolean
@@ -518,6 +542,10 @@ class QubeeManager @Inject constructor(
     // Direct-message/session JNI owned by Rust.
     private external fun nativeEncryptMessage(sessionId: String, plaintext: String): ByteArray?
     private external fun nativeDecryptMessage(sessionId: String, encryptedEnvelope: ByteArray): String?
+    private external fun nativeCreateMessenger(sessionId: String): Long
+    private external fun nativeDestroyMessenger(handle: Long)
+    private external fun nativeMessengerEncrypt(handle: Long, plaintext: ByteArray): ByteArray
+    private external fun nativeMessengerDecrypt(handle: Long, encryptedEnvelope: ByteArray): ByteArray
     private external fun nativeEncryptFile(sessionId: String, fileData: ByteArray): ByteArray?
     private external fun nativeDecryptFile(sessionId: String, encryptedEnvelope: ByteArray): ByteArray?
     private external fun nativeVerifyIdentityKey(contactId: String, identityKey: ByteArray, verificationData: ByteArray): Boolean
@@ -566,6 +594,7 @@ class QubeeManager @Inject constructor(
         plaintext: ByteArray,
     ): String?
     private external fun nativeResetIdentity(dataDir: String): Boolean
+    private external fun nativeRunSecurityAudit(): String
 
     external fun nativeCleanup()
 }
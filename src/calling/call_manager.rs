@@ -1,36 +1,103 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::{mpsc, RwLock};
+use tokio::task::{AbortHandle, JoinSet};
+use zeroize::Zeroizing;
 
+use crate::calling::media_backend::MediaBackend;
 use crate::calling::media_encryption::{MediaEncryption, MediaKey};
-use crate::calling::peer_connection::{PeerConnection, PeerConnectionState};
-use crate::calling::signaling::{SignalingClient, SignalingMessage, SignalingServer};
-use crate::calling::webrtc_manager::{WebRTCConfig, WebRTCManager};
+use crate::calling::peer_connection::PeerConnectionState;
+use crate::calling::quality_controller::{BitrateStep, QualityController, QualityControllerConfig};
+use crate::calling::signaling::{SignalingMessage, SignalingServer};
+use crate::calling::webrtc_manager::{MediaStats, WebRTCConfig, WebRTCManager};
 use crate::groups::group_manager::GroupId;
 use crate::identity::contact_manager::ContactManager;
 use crate::identity::identity_key::{IdentityId, IdentityKey, IdentityKeyPair};
 
-/// Comprehensive call management system
-pub struct CallManager {
+/// Comprehensive call management system. Generic over the media
+/// layer so call-flow logic can be tested against
+/// [`MockMediaBackend`](crate::calling::media_backend::MockMediaBackend).
+pub struct CallManager<B: MediaBackend = WebRTCManager> {
     /// Active calls
     calls: Arc<RwLock<HashMap<CallId, Call>>>,
-    /// WebRTC manager for media handling
-    webrtc_manager: WebRTCManager,
-    /// Media encryption for secure streams
-    media_encryption: MediaEncryption,
+    /// Media backend (WebRTC in production)
+    media: B,
     /// Signaling server for call setup
     signaling_server: Arc<SignalingServer>,
     /// Event sender for call events
     event_sender: mpsc::UnboundedSender<CallEvent>,
     /// Configuration
     config: CallManagerConfig,
-    /// Contact manager for resolving identity keys and display names
-    contact_manager: Arc<ContactManager>,
+    /// Looks up identity keys and display names for participants
+    participant_resolver: Arc<dyn ParticipantResolver>,
+    /// Local identity that signs outgoing signaling messages
+    identity: Option<Arc<IdentityKeyPair>>,
+    /// Session exporter secrets that media keys are derived from
+    session_exporters: Option<Arc<dyn SessionExporterProvider>>,
+    /// Background tasks (ring timeouts). Owned here so they're aborted
+    /// when the manager is dropped instead of outliving it.
+    tasks: Mutex<JoinSet<()>>,
+    /// Pending ring timeouts by call, so answering or cancelling a call
+    /// can stop its timer before it fires.
+    ring_timeouts: Mutex<HashMap<CallId, AbortHandle>>,
+    /// Set by [`CallManager::shutdown`]; refuses new calls afterwards.
+    shutting_down: AtomicBool,
+    /// State changes from the WebRTC backend, until
+    /// [`CallManager::monitor_connections`] takes them.
+    connection_states: Mutex<Option<mpsc::Receiver<ConnectionStateEvent>>>,
+    /// Per-call recording state: objections and the active recording.
+    recordings: RwLock<HashMap<CallId, RecordingState>>,
+    /// Adaptive bitrate state per peer connection, fed by
+    /// [`CallManager::sample_connection_quality`].
+    quality_controllers: Mutex<HashMap<(CallId, IdentityId), QualityController>>,
+    /// Quality samples per call, folded into its
+    /// [`CallQualityStats`] when the call ends.
+    quality_samples: Mutex<HashMap<CallId, QualitySamples>>,
 }
 
+/// Running sums of a call's [`ConnectionQuality`] samples across all
+/// participants, plus the latest byte counters seen per participant
+/// (the backend reports them cumulatively).
+#[derive(Default)]
+struct QualitySamples {
+    count: u32,
+    packet_loss: f64,
+    latency: u64,
+    jitter: u64,
+    bytes: HashMap<IdentityId, (u64, u64)>,
+}
+
+impl QualitySamples {
+    fn record(&mut self, quality: &ConnectionQuality) {
+        self.count += 1;
+        self.packet_loss += f64::from(quality.packet_loss);
+        self.latency += u64::from(quality.latency);
+        self.jitter += u64::from(quality.jitter);
+    }
+
+    /// Write the averages and byte totals into `stats`. Averages are
+    /// left alone if nothing was sampled.
+    fn finalize(&self, stats: &mut CallQualityStats) {
+        if self.count > 0 {
+            let count = u64::from(self.count);
+            stats.avg_packet_loss = (self.packet_loss / count as f64) as f32;
+            stats.avg_latency = (self.latency / count) as u32;
+            stats.avg_jitter = (self.jitter / count) as u32;
+        }
+        stats.total_bytes_sent = self.bytes.values().map(|(sent, _)| sent).sum();
+        stats.total_bytes_received = self.bytes.values().map(|(_, received)| received).sum();
+    }
+}
+
+/// `(call, participant, new state)` as reported by a peer connection.
+pub type ConnectionStateEvent = (CallId, IdentityId, PeerConnectionState);
+
 /// Individual call instance
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Call {
@@ -45,6 +112,7 @@ pub struct Call {
     pub ended_at: Option<u64>,
     pub settings: CallSettings,
     pub quality_stats: CallQualityStats,
+    pub topology: CallTopology,
 }
 
 /// Unique identifier for a call
@@ -52,7 +120,7 @@ pub struct Call {
 pub struct CallId([u8; 16]);
 
 /// Types of calls supported
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum CallType {
     /// One-on-one voice call
     VoiceCall,
@@ -69,7 +137,7 @@ pub enum CallType {
 }
 
 /// Current state of a call
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum CallState {
     /// Call is being initiated
     Initiating,
@@ -93,6 +161,47 @@ pub enum CallState {
     TimedOut,
 }
 
+/// How media flows between the participants of a call.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CallTopology {
+    /// Every participant has a peer connection to every other one.
+    /// Each uploads its media once per peer, so this only scales to a
+    /// handful of people.
+    Mesh,
+    /// Everyone connects to `relay` alone, which forwards each
+    /// participant's (still end-to-end encrypted) tracks to the rest.
+    /// Uploads stay at one stream per participant; the relay carries
+    /// the fan-out.
+    Sfu { relay: IdentityId },
+}
+
+/// Largest call, initiator included, that runs as a full mesh.
+pub const MESH_MAX_PARTICIPANTS: usize = 4;
+
+impl CallTopology {
+    /// Pick a topology for a call of `size` people, initiator
+    /// included. Past [`MESH_MAX_PARTICIPANTS`] the initiator relays,
+    /// since they're the one participant known to be there.
+    pub fn for_call_size(initiator: IdentityId, size: usize) -> Self {
+        if size <= MESH_MAX_PARTICIPANTS {
+            CallTopology::Mesh
+        } else {
+            CallTopology::Sfu { relay: initiator }
+        }
+    }
+
+    /// The peer whose connection carries `participant`'s media in
+    /// `call`, or `None` if it arrives forwarded over the relay's.
+    fn media_peer(self, call: &Call, participant: IdentityId) -> Option<IdentityId> {
+        match self {
+            CallTopology::Mesh => Some(participant),
+            // We are the relay: one connection per participant.
+            CallTopology::Sfu { relay } if relay == call.initiator => Some(participant),
+            CallTopology::Sfu { relay } => (participant == relay).then_some(relay),
+        }
+    }
+}
+
 /// Call participant information
 #[derive(Clone, Serialize, Deserialize)]
 pub struct CallParticipant {
@@ -151,7 +260,7 @@ pub struct ConnectionQuality {
 }
 
 /// Call settings and preferences
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CallSettings {
     pub max_participants: Option<usize>,
     pub require_encryption: bool,
@@ -165,7 +274,7 @@ pub struct CallSettings {
 }
 
 /// Video quality settings
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum VideoQuality {
     Low,    // 240p
     Medium, // 480p
@@ -176,7 +285,7 @@ pub enum VideoQuality {
 }
 
 /// Audio quality settings
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum AudioQuality {
     Low,    // 8kHz, mono
     Medium, // 16kHz, mono
@@ -236,17 +345,109 @@ pub enum CallEvent {
         participant: IdentityId,
         quality: ConnectionQuality,
     },
+    /// Nobody answered before the ring timeout; sent once per invited
+    /// participant who never picked up, for missed-call UI. Followed by
+    /// the `CallStateChanged` to `TimedOut`.
+    CallMissed { call_id: CallId, callee: IdentityId },
     /// Call error occurred
     CallError { call_id: CallId, error: String },
+    /// Someone started recording the call. Sent before any frame is
+    /// written so clients can show a recording indicator.
+    RecordingStarted {
+        call_id: CallId,
+        requester: IdentityId,
+    },
+    /// Recording stopped, either by the requester or because a
+    /// participant objected.
+    RecordingStopped { call_id: CallId },
+    /// A decrypted message arrived on a participant's data channel.
+    DataReceived {
+        call_id: CallId,
+        participant: IdentityId,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Default)]
+struct RecordingState {
+    objections: HashSet<IdentityId>,
+    active: Option<ActiveRecording>,
+}
+
+struct ActiveRecording {
+    started_by: IdentityId,
+    started_at: u64,
+    key: MediaKey,
+    /// Length-prefixed encrypted frames. Zeroed on drop so a discarded
+    /// recording doesn't linger in freed memory.
+    sink: Zeroizing<Vec<u8>>,
+}
+
+/// A finished recording. Every frame in `frames` is encrypted under
+/// `key`, a random key generated for this recording alone; store the
+/// key separately (e.g. in the keystore) from the frames.
+pub struct RecordingArtifact {
+    pub call_id: CallId,
+    pub started_by: IdentityId,
+    pub started_at: u64,
+    pub stopped_at: u64,
+    pub key: MediaKey,
+    /// Repeated `participant(32) | stream_id(8 LE) | len(4 LE) | frame`.
+    pub frames: Vec<u8>,
+}
+
+/// One frame recovered from a [`RecordingArtifact`].
+pub struct RecordedFrame {
+    pub participant: IdentityId,
+    pub stream_id: u64,
+    pub data: Vec<u8>,
+}
+
+impl RecordingArtifact {
+    /// Decrypt every frame in recording order.
+    pub fn decrypt_frames(&self) -> Result<Vec<RecordedFrame>> {
+        let encryption = MediaEncryption::new(self.key.clone());
+        let mut frames = Vec::new();
+        let mut rest = self.frames.as_slice();
+        while !rest.is_empty() {
+            if rest.len() < 44 {
+                return Err(anyhow::anyhow!("Truncated recording frame header"));
+            }
+            let (header, tail) = rest.split_at(44);
+            let mut participant = [0u8; 32];
+            participant.copy_from_slice(&header[..32]);
+            let stream_id = u64::from_le_bytes(header[32..40].try_into()?);
+            let len = u32::from_le_bytes(header[40..44].try_into()?) as usize;
+            if tail.len() < len {
+                return Err(anyhow::anyhow!("Truncated recording frame"));
+            }
+            let (frame, tail) = tail.split_at(len);
+            frames.push(RecordedFrame {
+                participant: IdentityId::from(participant),
+                stream_id,
+                data: encryption.decrypt_frame(stream_id, frame)?,
+            });
+            rest = tail;
+        }
+        Ok(frames)
+    }
 }
 
 /// Call manager configuration
 #[derive(Clone)]
 pub struct CallManagerConfig {
     pub max_concurrent_calls: usize,
+    /// Node-wide cap on connecting/connected participants summed over
+    /// every call. Independent of each call's
+    /// `CallSettings::max_participants`; protects the device's
+    /// bandwidth and decoder budget when several calls overlap.
+    pub max_total_participants: usize,
     pub call_timeout: Duration,
     pub ring_timeout: Duration,
     pub reconnection_attempts: u32,
+    /// Wait before the second reconnection attempt after a failure;
+    /// doubles with each further attempt.
+    pub reconnection_backoff: Duration,
     pub enable_p2p_optimization: bool,
     pub stun_servers: Vec<String>,
     pub turn_servers: Vec<TurnServer>,
@@ -260,7 +461,68 @@ pub struct TurnServer {
     pub credential: String,
 }
 
-impl CallManager {
+/// Boxed future returned by [`ParticipantResolver`], so resolvers can
+/// be stored as trait objects.
+pub type ParticipantFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Where [`CallManager`] gets the identity key and display name of
+/// each participant it invites or admits. The key is what media keys
+/// are bound to, so a resolver must fail rather than guess when it
+/// doesn't know a participant.
+pub trait ParticipantResolver: Send + Sync {
+    fn resolve_identity_key(&self, participant: IdentityId) -> ParticipantFuture<'_, IdentityKey>;
+
+    fn resolve_display_name(&self, participant: IdentityId) -> ParticipantFuture<'_, String>;
+}
+
+/// Where [`CallManager`] gets the exporter secret of the session with
+/// a participant, which that call's media keys are derived from. The
+/// session derives it under a label of its own; it must never be the
+/// session's root or a chain key. Must fail if there's no established
+/// session: media keyed any other way isn't bound to the participant's
+/// identity.
+pub trait SessionExporterProvider: Send + Sync {
+    fn session_exporter(
+        &self,
+        participant: IdentityId,
+    ) -> ParticipantFuture<'_, Zeroizing<[u8; 32]>>;
+}
+
+/// Default [`ParticipantResolver`]: the local contact list. Unknown
+/// participants have no identity key; their display name falls back
+/// to "Unknown".
+pub struct ContactResolver {
+    contacts: Arc<ContactManager>,
+}
+
+impl ContactResolver {
+    pub fn new(contacts: Arc<ContactManager>) -> Self {
+        ContactResolver { contacts }
+    }
+}
+
+impl ParticipantResolver for ContactResolver {
+    fn resolve_identity_key(&self, participant: IdentityId) -> ParticipantFuture<'_, IdentityKey> {
+        Box::pin(async move {
+            self.contacts
+                .get_identity_key(&participant)
+                .await
+                .ok_or_else(|| anyhow::anyhow!("No identity key known for {:?}", participant))
+        })
+    }
+
+    fn resolve_display_name(&self, participant: IdentityId) -> ParticipantFuture<'_, String> {
+        Box::pin(async move {
+            Ok(self
+                .contacts
+                .get_display_name(&participant)
+                .await
+                .unwrap_or_else(|| "Unknown".to_string()))
+        })
+    }
+}
+
+impl CallManager<WebRTCManager> {
     /// Create a new call manager
     pub async fn new(
         config: CallManagerConfig,
@@ -273,22 +535,288 @@ impl CallManager {
             enable_srtp: true,
         };
 
-        let webrtc_manager = WebRTCManager::new(webrtc_config).await?;
-        let media_encryption = MediaEncryption::new()?;
+        let (state_tx, state_rx) = mpsc::channel(64);
+        let webrtc_manager = WebRTCManager::new(webrtc_config)
+            .await?
+            .with_connection_state_events(state_tx);
+        let manager = Self::with_backend(config, event_sender, webrtc_manager).await?;
+        *manager.connection_states.lock().unwrap() = Some(state_rx);
+        Ok(manager)
+    }
+}
+
+impl<B: MediaBackend> CallManager<B> {
+    /// Create a call manager over an explicit media backend
+    pub async fn with_backend(
+        config: CallManagerConfig,
+        event_sender: mpsc::UnboundedSender<CallEvent>,
+        media: B,
+    ) -> Result<Self> {
         let signaling_server = Arc::new(SignalingServer::new().await?);
-        let contact_manager = Arc::new(ContactManager::new());
+        let participant_resolver = Arc::new(ContactResolver::new(Arc::new(ContactManager::new())));
 
         Ok(CallManager {
             calls: Arc::new(RwLock::new(HashMap::new())),
-            webrtc_manager,
-            media_encryption,
+            media,
             signaling_server,
             event_sender,
             config,
-            contact_manager,
+            participant_resolver,
+            identity: None,
+            session_exporters: None,
+            tasks: Mutex::new(JoinSet::new()),
+            ring_timeouts: Mutex::new(HashMap::new()),
+            shutting_down: AtomicBool::new(false),
+            connection_states: Mutex::new(None),
+            recordings: RwLock::new(HashMap::new()),
+            quality_controllers: Mutex::new(HashMap::new()),
+            quality_samples: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Resolve participants through `resolver` instead of an empty
+    /// contact list.
+    pub fn with_participant_resolver(mut self, resolver: Arc<dyn ParticipantResolver>) -> Self {
+        self.participant_resolver = resolver;
+        self
+    }
+
+    /// Sign outgoing signaling messages as `identity`. Without one,
+    /// calls can't be placed: recipients refuse unsigned invitations.
+    pub fn with_identity(mut self, identity: Arc<IdentityKeyPair>) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Key each call's media from the session with the other
+    /// participant, whose exporter secret is looked up through
+    /// `exporters`. Without it, no peer connection can be set up.
+    pub fn with_session_exporters(mut self, exporters: Arc<dyn SessionExporterProvider>) -> Self {
+        self.session_exporters = Some(exporters);
+        self
+    }
+
+    /// Apply peer-connection state changes from the WebRTC backend
+    /// until the manager is dropped. Run this on its own task after
+    /// [`CallManager::new`]; returns at once if it's already running
+    /// or the backend doesn't report state.
+    pub async fn monitor_connections(&self) {
+        let receiver = self.connection_states.lock().unwrap().take();
+        if let Some(receiver) = receiver {
+            self.process_connection_states(receiver).await;
+        }
+    }
+
+    /// Apply every state change from `receiver` until it closes.
+    pub async fn process_connection_states(
+        &self,
+        mut receiver: mpsc::Receiver<ConnectionStateEvent>,
+    ) {
+        while let Some((call_id, participant, state)) = receiver.recv().await {
+            if let Err(e) = self
+                .handle_connection_state(call_id, participant, state)
+                .await
+            {
+                let _ = self.event_sender.send(CallEvent::CallError {
+                    call_id,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    /// React to one participant's media path changing state.
+    ///
+    /// `Connected` marks the participant connected. `Disconnected` marks
+    /// them disconnected and waits, since ICE can recover on its own.
+    /// `Failed` tears the connection down and builds a new one, up to
+    /// `reconnection_attempts` times per call (counted in
+    /// `quality_stats.reconnection_count`). A rebuild that fails is
+    /// retried after `reconnection_backoff`, doubling each time, while
+    /// attempts remain; after that the participant is dropped from the
+    /// call.
+    pub async fn handle_connection_state(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        state: PeerConnectionState,
+    ) -> Result<()> {
+        enum Action {
+            None,
+            Reconnect,
+            Drop,
+        }
+
+        let action = {
+            let mut calls = self.calls.write().await;
+            let Some(call) = calls.get_mut(&call_id) else {
+                return Ok(());
+            };
+            let can_retry =
+                call.quality_stats.reconnection_count < self.config.reconnection_attempts;
+            let Some(info) = call.participants.get_mut(&participant) else {
+                return Ok(());
+            };
+            if !matches!(
+                info.participant_state,
+                ParticipantState::Connecting
+                    | ParticipantState::Connected
+                    | ParticipantState::Disconnected
+            ) {
+                return Ok(());
+            }
+            match state {
+                PeerConnectionState::Connected => {
+                    info.participant_state = ParticipantState::Connected;
+                    Action::None
+                }
+                PeerConnectionState::Disconnected => {
+                    info.participant_state = ParticipantState::Disconnected;
+                    Action::None
+                }
+                PeerConnectionState::Failed if can_retry => {
+                    info.participant_state = ParticipantState::Disconnected;
+                    call.quality_stats.reconnection_count += 1;
+                    Action::Reconnect
+                }
+                PeerConnectionState::Failed => {
+                    info.participant_state = ParticipantState::Left;
+                    info.left_at = Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+                    Action::Drop
+                }
+                _ => Action::None,
+            }
+        };
+
+        match action {
+            Action::None => {}
+            Action::Reconnect => self.reconnect(call_id, participant).await?,
+            Action::Drop => self.drop_participant(call_id, participant).await?,
+        }
+        Ok(())
+    }
+
+    /// Rebuild a failed peer connection. The first attempt has already
+    /// been counted by the caller; each retry counts another, with the
+    /// wait between them doubling.
+    async fn reconnect(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        let mut backoff = self.config.reconnection_backoff;
+        loop {
+            self.close_peer_connection(call_id, participant).await?;
+            let established = self.establish_peer_connection(call_id, participant).await;
+
+            let mut calls = self.calls.write().await;
+            let Some(call) = calls.get_mut(&call_id) else {
+                return Ok(());
+            };
+            let can_retry =
+                call.quality_stats.reconnection_count < self.config.reconnection_attempts;
+            let Some(info) = call.participants.get_mut(&participant) else {
+                return Ok(());
+            };
+            if info.participant_state != ParticipantState::Disconnected {
+                // Left or ended while we were reconnecting.
+                return Ok(());
+            }
+            match established {
+                Ok(()) => {
+                    info.participant_state = ParticipantState::Connecting;
+                    return Ok(());
+                }
+                Err(_) if can_retry => {
+                    call.quality_stats.reconnection_count += 1;
+                }
+                Err(_) => {
+                    info.participant_state = ParticipantState::Left;
+                    info.left_at = Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+                    drop(calls);
+                    return self.drop_participant(call_id, participant).await;
+                }
+            }
+            drop(calls);
+
+            tokio::time::sleep(backoff).await;
+            backoff = backoff.saturating_mul(2);
+        }
+    }
+
+    /// Tear down the connection of a participant already marked `Left`
+    /// after their link failed for good, and tell the app.
+    async fn drop_participant(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        self.close_peer_connection(call_id, participant).await?;
+        self.event_sender
+            .send(CallEvent::ParticipantLeft {
+                call_id,
+                participant,
+                reason: "Connection failed".to_string(),
+            })
+            .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
+        Ok(())
+    }
+
+    /// Stop the manager: cancel pending ring timeouts, end every live
+    /// call (closing its peer connections and emitting the usual state
+    /// events), and wait for background tasks to finish. New calls are
+    /// refused once this has started. Idempotent.
+    pub async fn shutdown(&self) -> Result<()> {
+        if self.shutting_down.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let mut tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        tasks.abort_all();
+        while tasks.join_next().await.is_some() {}
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut to_close = Vec::new();
+        let mut ended = Vec::new();
+        {
+            let mut calls = self.calls.write().await;
+            for (call_id, call) in calls.iter_mut() {
+                if matches!(call.state, CallState::Ended | CallState::TimedOut) {
+                    continue;
+                }
+                ended.push((*call_id, call.state.clone()));
+                call.state = CallState::Ended;
+                call.ended_at = Some(now);
+                self.finalize_quality_stats(call);
+                for (participant, info) in call.participants.iter_mut() {
+                    if matches!(
+                        info.participant_state,
+                        ParticipantState::Connecting | ParticipantState::Connected
+                    ) {
+                        info.participant_state = ParticipantState::Left;
+                        info.left_at = Some(now);
+                        to_close.push((*call_id, *participant));
+                    }
+                }
+            }
+        }
+
+        // Close everything even if one close fails; report the first
+        // error at the end.
+        let mut first_err = None;
+        for (call_id, participant) in to_close {
+            if let Err(e) = self.close_peer_connection(call_id, participant).await {
+                first_err.get_or_insert(e);
+            }
+        }
+        for (call_id, old_state) in ended {
+            // The app may already have dropped its receiver during
+            // shutdown; that's not an error here.
+            let _ = self.event_sender.send(CallEvent::CallStateChanged {
+                call_id,
+                old_state,
+                new_state: CallState::Ended,
+            });
+        }
+
+        match first_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
     /// Initiate a new call
     pub async fn initiate_call(
         &self,
@@ -298,6 +826,9 @@ impl CallManager {
         group_id: Option<GroupId>,
         settings: CallSettings,
     ) -> Result<CallId> {
+        if self.shutting_down.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("Call manager is shutting down"));
+        }
         let call_id = self.generate_call_id()?;
 
         // Validate participants
@@ -325,13 +856,21 @@ impl CallManager {
 
         let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
 
+        // Size the topology for the largest the call may grow to, so it
+        // doesn't have to switch mid-call as people join.
+        let expected_size = participants
+            .len()
+            .max(settings.max_participants.unwrap_or(0))
+            + 1;
+        let topology = CallTopology::for_call_size(initiator, expected_size);
+
         // Create call participants
         let mut call_participants = HashMap::new();
         for participant_id in participants {
             let participant = CallParticipant {
                 identity_id: participant_id,
-                identity_key: self.get_identity_key(participant_id).await?, // Would need to be implemented
-                display_name: self.get_display_name(participant_id).await?, // Would need to be implemented
+                identity_key: self.get_identity_key(participant_id).await?,
+                display_name: self.get_display_name(participant_id).await?,
                 participant_state: ParticipantState::Invited,
                 media_state: MediaState::default(),
                 connection_quality: ConnectionQuality::default(),
@@ -359,6 +898,7 @@ impl CallManager {
             ended_at: None,
             settings,
             quality_stats: CallQualityStats::default(),
+            topology,
         };
 
         // Store the call
@@ -381,6 +921,13 @@ impl CallManager {
     /// Accept an incoming call
     pub async fn accept_call(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
         let mut calls = self.calls.write().await;
+        if let Err(e) = self.check_join_capacity(&calls, call_id, participant) {
+            let _ = self.event_sender.send(CallEvent::CallError {
+                call_id,
+                error: e.to_string(),
+            });
+            return Err(e);
+        }
         let call = calls
             .get_mut(&call_id)
             .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
@@ -413,6 +960,7 @@ impl CallManager {
         }
 
         drop(calls);
+        self.cancel_ring_timeout(call_id);
 
         // Establish WebRTC connection
         self.establish_peer_connection(call_id, participant).await?;
@@ -428,6 +976,79 @@ impl CallManager {
         Ok(())
     }
 
+    /// Join an ongoing group call without a prior invitation. Any
+    /// member of the call's group may join while it's ringing or
+    /// active, subject to the same participant caps as
+    /// [`accept_call`](Self::accept_call).
+    pub async fn join_call(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        let identity_key = self.get_identity_key(participant).await?;
+        let display_name = self.get_display_name(participant).await?;
+
+        let mut calls = self.calls.write().await;
+        if let Err(e) = self.check_join_capacity(&calls, call_id, participant) {
+            let _ = self.event_sender.send(CallEvent::CallError {
+                call_id,
+                error: e.to_string(),
+            });
+            return Err(e);
+        }
+        let call = calls
+            .get_mut(&call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
+
+        if call.group_id.is_none() {
+            return Err(anyhow::anyhow!("Only group calls can be joined uninvited"));
+        }
+        if !matches!(call.state, CallState::Ringing | CallState::Active) {
+            return Err(anyhow::anyhow!("Call is not joinable in its current state"));
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let is_video = matches!(
+            call.call_type,
+            CallType::VideoCall | CallType::GroupVideoCall
+        );
+        let auto_mute = call.settings.auto_mute_on_join;
+        let entry = call
+            .participants
+            .entry(participant)
+            .or_insert_with(|| CallParticipant {
+                identity_id: participant,
+                identity_key,
+                display_name,
+                participant_state: ParticipantState::Invited,
+                media_state: MediaState::default(),
+                connection_quality: ConnectionQuality::default(),
+                joined_at: None,
+                left_at: None,
+                is_muted: auto_mute,
+                is_video_enabled: is_video,
+                is_screen_sharing: false,
+            });
+        entry.participant_state = ParticipantState::Connecting;
+        entry.joined_at = Some(now);
+        entry.left_at = None;
+
+        if call.state == CallState::Ringing {
+            call.state = CallState::Active;
+            call.started_at = Some(now);
+        }
+
+        drop(calls);
+        self.cancel_ring_timeout(call_id);
+
+        self.establish_peer_connection(call_id, participant).await?;
+
+        self.event_sender
+            .send(CallEvent::ParticipantJoined {
+                call_id,
+                participant,
+            })
+            .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
+
+        Ok(())
+    }
+
     /// Reject an incoming call
     pub async fn reject_call(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
         let mut calls = self.calls.write().await;
@@ -464,8 +1085,12 @@ impl CallManager {
             call.state = CallState::Rejected;
             call.ended_at = Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
         }
+        let still_ringing = call.state == CallState::Ringing;
 
         drop(calls);
+        if !still_ringing {
+            self.cancel_ring_timeout(call_id);
+        }
 
         // Send event
         self.event_sender
@@ -490,6 +1115,7 @@ impl CallManager {
         if participant == call.initiator {
             call.state = CallState::Ended;
             call.ended_at = Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+            self.finalize_quality_stats(call);
 
             // Update all participants
             for participant_info in call.participants.values_mut() {
@@ -517,10 +1143,15 @@ impl CallManager {
             if active_participants <= 1 {
                 call.state = CallState::Ended;
                 call.ended_at = Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+                self.finalize_quality_stats(call);
             }
         }
+        let ended = call.state == CallState::Ended;
 
         drop(calls);
+        if ended {
+            self.cancel_ring_timeout(call_id);
+        }
 
         // Close peer connections
         self.close_peer_connection(call_id, participant).await?;
@@ -548,12 +1179,13 @@ impl CallManager {
             participant_info.is_muted = !participant_info.is_muted;
             participant_info.media_state.audio_enabled = !participant_info.is_muted;
 
+            let is_muted = participant_info.is_muted;
             let new_state = participant_info.media_state.clone();
             drop(calls);
 
             // Update WebRTC audio track
-            self.webrtc_manager
-                .set_audio_enabled(call_id, participant, !participant_info.is_muted)
+            self.media
+                .set_audio_enabled(call_id, participant, !is_muted)
                 .await?;
 
             // Send event
@@ -565,7 +1197,7 @@ impl CallManager {
                 })
                 .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
 
-            Ok(participant_info.is_muted)
+            Ok(is_muted)
         } else {
             Err(anyhow::anyhow!("Participant not found in call"))
         }
@@ -582,12 +1214,13 @@ impl CallManager {
             participant_info.is_video_enabled = !participant_info.is_video_enabled;
             participant_info.media_state.video_enabled = participant_info.is_video_enabled;
 
+            let is_video_enabled = participant_info.is_video_enabled;
             let new_state = participant_info.media_state.clone();
             drop(calls);
 
             // Update WebRTC video track
-            self.webrtc_manager
-                .set_video_enabled(call_id, participant, participant_info.is_video_enabled)
+            self.media
+                .set_video_enabled(call_id, participant, is_video_enabled)
                 .await?;
 
             // Send event
@@ -599,7 +1232,7 @@ impl CallManager {
                 })
                 .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
 
-            Ok(participant_info.is_video_enabled)
+            Ok(is_video_enabled)
         } else {
             Err(anyhow::anyhow!("Participant not found in call"))
         }
@@ -620,7 +1253,7 @@ impl CallManager {
             drop(calls);
 
             // Start screen capture
-            self.webrtc_manager
+            self.media
                 .start_screen_capture(call_id, participant)
                 .await?;
 
@@ -639,44 +1272,515 @@ impl CallManager {
         }
     }
 
-    /// Get call information
-    pub async fn get_call(&self, call_id: CallId) -> Option<Call> {
-        let calls = self.calls.read().await;
-        calls.get(&call_id).cloned()
+    /// Stop screen sharing started with
+    /// [`start_screen_share`](Self::start_screen_share). Does nothing if
+    /// the participant isn't sharing.
+    pub async fn stop_screen_share(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        let mut calls = self.calls.write().await;
+        let call = calls
+            .get_mut(&call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
+        let participant_info = call
+            .participants
+            .get_mut(&participant)
+            .ok_or_else(|| anyhow::anyhow!("Participant not found in call"))?;
+        if !participant_info.is_screen_sharing {
+            return Ok(());
+        }
+
+        participant_info.is_screen_sharing = false;
+        participant_info.media_state.screen_share_enabled = false;
+        let new_state = participant_info.media_state.clone();
+        drop(calls);
+
+        self.media.stop_screen_capture(call_id, participant).await?;
+
+        self.event_sender
+            .send(CallEvent::MediaStateChanged {
+                call_id,
+                participant,
+                media_state: new_state,
+            })
+            .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
+
+        Ok(())
     }
 
-    /// Get all active calls
-    pub async fn get_active_calls(&self) -> Vec<Call> {
-        let calls = self.calls.read().await;
-        calls
-            .values()
-            .filter(|call| matches!(call.state, CallState::Active | CallState::Ringing))
-            .cloned()
-            .collect()
+    /// Put `call_id` on hold: the call moves from `Active` to `OnHold`
+    /// and `participant`'s audio and video tracks are disabled, so no
+    /// media flows until [`resume_call`](Self::resume_call). The
+    /// participant's `media_state` is left alone — it records what they
+    /// had on before the hold, which is what resuming restores.
+    pub async fn hold_call(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        self.transition_hold(call_id, participant, CallState::Active, CallState::OnHold)
+            .await?;
+        self.media
+            .set_audio_enabled(call_id, participant, false)
+            .await?;
+        self.media
+            .set_video_enabled(call_id, participant, false)
+            .await?;
+        Ok(())
     }
 
-    /// Update call quality statistics
-    pub async fn update_quality_stats(
+    /// Take `call_id` off hold and re-enable whichever of
+    /// `participant`'s tracks were on before it was held.
+    pub async fn resume_call(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        let media_state = self
+            .transition_hold(call_id, participant, CallState::OnHold, CallState::Active)
+            .await?;
+        self.media
+            .set_audio_enabled(call_id, participant, media_state.audio_enabled)
+            .await?;
+        self.media
+            .set_video_enabled(call_id, participant, media_state.video_enabled)
+            .await?;
+        Ok(())
+    }
+
+    /// Move `call_id` from `from` to `to` for a hold or resume by
+    /// `participant`, announce it, and return the participant's media
+    /// state. Anything but `from` — an ended call in particular — is
+    /// refused.
+    async fn transition_hold(
         &self,
         call_id: CallId,
         participant: IdentityId,
-        quality: ConnectionQuality,
-    ) -> Result<()> {
+        from: CallState,
+        to: CallState,
+    ) -> Result<MediaState> {
         let mut calls = self.calls.write().await;
-        if let Some(call) = calls.get_mut(&call_id) {
-            if let Some(participant_info) = call.participants.get_mut(&participant) {
-                participant_info.connection_quality = quality.clone();
+        let call = calls
+            .get_mut(&call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
+        let media_state = call
+            .participants
+            .get(&participant)
+            .ok_or_else(|| anyhow::anyhow!("Participant not found in call"))?
+            .media_state
+            .clone();
+        if call.state != from {
+            let (action, required) = match from {
+                CallState::OnHold => ("resume", "on hold"),
+                _ => ("hold", "active"),
+            };
+            return Err(anyhow::anyhow!(
+                "Cannot {action} a call that is not {required}"
+            ));
+        }
+        call.state = to.clone();
+        drop(calls);
 
-                // Send event
-                self.event_sender
-                    .send(CallEvent::QualityChanged {
-                        call_id,
-                        participant,
-                        quality,
-                    })
-                    .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
+        self.event_sender
+            .send(CallEvent::CallStateChanged {
+                call_id,
+                old_state: from,
+                new_state: to,
+            })
+            .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
+        Ok(media_state)
+    }
+
+    /// Override the topology picked when the call was initiated. An
+    /// SFU relay must be the initiator or one of the participants.
+    /// Applies to connections established from here on.
+    pub async fn set_topology(&self, call_id: CallId, topology: CallTopology) -> Result<()> {
+        let mut calls = self.calls.write().await;
+        let call = calls
+            .get_mut(&call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
+        if let CallTopology::Sfu { relay } = topology {
+            if relay != call.initiator && !call.participants.contains_key(&relay) {
+                return Err(anyhow::anyhow!("Relay is not part of the call"));
             }
         }
+        call.topology = topology;
+        Ok(())
+    }
+
+    /// Open the in-call data channel to `participant`. Both sides open
+    /// it (the channel is pre-negotiated, not announced); from then on
+    /// every message the participant sends surfaces as
+    /// [`CallEvent::DataReceived`].
+    pub async fn open_data_channel(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        label: &str,
+    ) -> Result<()> {
+        let calls = self.calls.read().await;
+        let call = calls
+            .get(&call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
+        if !call.participants.contains_key(&participant) {
+            return Err(anyhow::anyhow!("Participant not found in call"));
+        }
+        drop(calls);
+
+        let mut inbound = self
+            .media
+            .open_data_channel(call_id, participant, label)
+            .await?;
+        let event_sender = self.event_sender.clone();
+        let mut tasks = self.tasks.lock().unwrap();
+        while tasks.try_join_next().is_some() {}
+        tasks.spawn(async move {
+            while let Some(data) = inbound.recv().await {
+                let event = CallEvent::DataReceived {
+                    call_id,
+                    participant,
+                    data,
+                };
+                if event_sender.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Send an encrypted message to `participant` on the call's data
+    /// channel, opened earlier with
+    /// [`open_data_channel`](Self::open_data_channel).
+    pub async fn send_data(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        data: &[u8],
+    ) -> Result<()> {
+        if !self.calls.read().await.contains_key(&call_id) {
+            return Err(anyhow::anyhow!("Call not found"));
+        }
+        self.media.send_data(call_id, participant, data).await
+    }
+
+    /// Start recording `call_id` on behalf of `requester`.
+    ///
+    /// Refused unless the call's settings allow recording, no
+    /// participant has objected, and `requester` is the initiator.
+    /// Calls don't carry group roles, so a group admin who didn't start
+    /// the call has to ask the initiator. Every participant is notified
+    /// with [`CallEvent::RecordingStarted`] before any frame is kept.
+    pub async fn start_recording(&self, call_id: CallId, requester: IdentityId) -> Result<()> {
+        let calls = self.calls.read().await;
+        let call = calls
+            .get(&call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
+        if !call.settings.allow_recording {
+            return Err(anyhow::anyhow!("Recording is not allowed for this call"));
+        }
+        if requester != call.initiator {
+            return Err(anyhow::anyhow!("Only the call initiator may record"));
+        }
+        if !matches!(call.state, CallState::Ringing | CallState::Active) {
+            return Err(anyhow::anyhow!("Call is not active"));
+        }
+        drop(calls);
+
+        let mut recordings = self.recordings.write().await;
+        let state = recordings.entry(call_id).or_default();
+        if !state.objections.is_empty() {
+            return Err(anyhow::anyhow!("A participant objected to recording"));
+        }
+        if state.active.is_some() {
+            return Err(anyhow::anyhow!("Call is already being recorded"));
+        }
+        state.active = Some(ActiveRecording {
+            started_by: requester,
+            started_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            key: MediaKey::new(crate::security::secure_rng::random::array::<32>()?),
+            sink: Zeroizing::new(Vec::new()),
+        });
+        drop(recordings);
+
+        self.event_sender
+            .send(CallEvent::RecordingStarted { call_id, requester })
+            .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
+
+        Ok(())
+    }
+
+    /// Record `participant`'s objection to recording `call_id`. Any
+    /// recording in progress is discarded, and later
+    /// [`start_recording`](Self::start_recording) calls are refused.
+    pub async fn object_to_recording(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+    ) -> Result<()> {
+        let calls = self.calls.read().await;
+        let call = calls
+            .get(&call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
+        if participant != call.initiator && !call.participants.contains_key(&participant) {
+            return Err(anyhow::anyhow!("Participant not found in call"));
+        }
+        drop(calls);
+
+        let mut recordings = self.recordings.write().await;
+        let state = recordings.entry(call_id).or_default();
+        state.objections.insert(participant);
+        let discarded = state.active.take().is_some();
+        drop(recordings);
+
+        if discarded {
+            self.event_sender
+                .send(CallEvent::RecordingStopped { call_id })
+                .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
+        }
+        Ok(())
+    }
+
+    /// Append one media frame from `participant` to the call's
+    /// recording, encrypted under the recording key. Returns `false`
+    /// (and keeps nothing) when the call isn't being recorded.
+    pub async fn record_frame(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        stream_id: u64,
+        frame: &[u8],
+    ) -> Result<bool> {
+        let mut recordings = self.recordings.write().await;
+        let Some(recording) = recordings
+            .get_mut(&call_id)
+            .and_then(|state| state.active.as_mut())
+        else {
+            return Ok(false);
+        };
+
+        let encrypted =
+            MediaEncryption::new(recording.key.clone()).encrypt_frame(stream_id, frame)?;
+        let len = u32::try_from(encrypted.len()).context("Recorded frame too large")?;
+        recording.sink.extend_from_slice(participant.as_ref());
+        recording.sink.extend_from_slice(&stream_id.to_le_bytes());
+        recording.sink.extend_from_slice(&len.to_le_bytes());
+        recording.sink.extend_from_slice(&encrypted);
+        Ok(true)
+    }
+
+    /// Stop recording `call_id` and hand back the encrypted recording.
+    /// Only the participant who started it may stop it.
+    pub async fn stop_recording(
+        &self,
+        call_id: CallId,
+        requester: IdentityId,
+    ) -> Result<RecordingArtifact> {
+        let mut recordings = self.recordings.write().await;
+        let state = recordings
+            .get_mut(&call_id)
+            .filter(|state| state.active.is_some())
+            .ok_or_else(|| anyhow::anyhow!("Call is not being recorded"))?;
+        if state.active.as_ref().map(|r| r.started_by) != Some(requester) {
+            return Err(anyhow::anyhow!(
+                "Only the participant who started the recording may stop it"
+            ));
+        }
+        let mut recording = state.active.take().expect("checked above");
+        drop(recordings);
+
+        self.event_sender
+            .send(CallEvent::RecordingStopped { call_id })
+            .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
+
+        Ok(RecordingArtifact {
+            call_id,
+            started_by: recording.started_by,
+            started_at: recording.started_at,
+            stopped_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            key: recording.key,
+            frames: std::mem::take(&mut *recording.sink),
+        })
+    }
+
+    /// Get call information
+    pub async fn get_call(&self, call_id: CallId) -> Option<Call> {
+        let calls = self.calls.read().await;
+        calls.get(&call_id).cloned()
+    }
+
+    /// Final quality statistics of an ended call: averages over every
+    /// sample taken during it, its duration and the bytes moved. `None`
+    /// while the call is still going or if it's unknown.
+    pub async fn get_call_quality(&self, call_id: CallId) -> Option<CallQualityStats> {
+        let calls = self.calls.read().await;
+        calls
+            .get(&call_id)
+            .filter(|call| call.state == CallState::Ended)
+            .map(|call| call.quality_stats.clone())
+    }
+
+    /// Get all active calls
+    pub async fn get_active_calls(&self) -> Vec<Call> {
+        let calls = self.calls.read().await;
+        calls
+            .values()
+            .filter(|call| matches!(call.state, CallState::Active | CallState::Ringing))
+            .cloned()
+            .collect()
+    }
+
+    /// Update call quality statistics
+    pub async fn update_quality_stats(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        quality: ConnectionQuality,
+    ) -> Result<()> {
+        let mut calls = self.calls.write().await;
+        if let Some(call) = calls.get_mut(&call_id) {
+            if let Some(participant_info) = call.participants.get_mut(&participant) {
+                participant_info.connection_quality = quality.clone();
+                self.quality_samples
+                    .lock()
+                    .unwrap()
+                    .entry(call_id)
+                    .or_default()
+                    .record(&quality);
+
+                // Send event
+                self.event_sender
+                    .send(CallEvent::QualityChanged {
+                        call_id,
+                        participant,
+                        quality,
+                    })
+                    .map_err(|_| anyhow::anyhow!("Failed to send event"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pull current media stats for a participant from the backend,
+    /// fold them into a [`ConnectionQuality`], and record it via
+    /// [`update_quality_stats`](Self::update_quality_stats). The sample
+    /// also drives adaptive bitrate, so calling this periodically is
+    /// what keeps the bandwidth limit matched to the link.
+    pub async fn sample_connection_quality(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+    ) -> Result<ConnectionQuality> {
+        let stats = self.media.get_media_stats(call_id, participant).await?;
+        let quality = ConnectionQuality::from_media_stats(&stats);
+        self.quality_samples
+            .lock()
+            .unwrap()
+            .entry(call_id)
+            .or_default()
+            .bytes
+            .insert(participant, (stats.bytes_sent, stats.bytes_received));
+        self.update_quality_stats(call_id, participant, quality.clone())
+            .await?;
+        self.adapt_bitrate(call_id, participant, &quality).await?;
+        Ok(quality)
+    }
+
+    /// Fold the call's collected quality samples into its
+    /// `quality_stats` and set its duration. Called once, as the call
+    /// moves to `Ended`.
+    fn finalize_quality_stats(&self, call: &mut Call) {
+        if let (Some(started), Some(ended)) = (call.started_at, call.ended_at) {
+            call.quality_stats.duration = Some(ended.saturating_sub(started));
+        }
+        if let Some(samples) = self.quality_samples.lock().unwrap().remove(&call.id) {
+            samples.finalize(&mut call.quality_stats);
+        }
+    }
+
+    /// Feed `quality` to the participant's [`QualityController`] and
+    /// apply the bitrate step it decides on, if any. Each step down
+    /// counts as a quality degradation event in the call's stats. The
+    /// call's `bandwidth_limit` setting, when set, caps the controller.
+    async fn adapt_bitrate(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        quality: &ConnectionQuality,
+    ) -> Result<()> {
+        let max_kbps = self
+            .calls
+            .read()
+            .await
+            .get(&call_id)
+            .and_then(|call| call.settings.bandwidth_limit);
+        let step = self
+            .quality_controllers
+            .lock()
+            .unwrap()
+            .entry((call_id, participant))
+            .or_insert_with(|| {
+                let mut config = QualityControllerConfig::default();
+                if let Some(max_kbps) = max_kbps {
+                    config.max_kbps = max_kbps;
+                }
+                QualityController::new(config)
+            })
+            .observe(quality);
+        let Some(step) = step else {
+            return Ok(());
+        };
+
+        self.media
+            .set_bandwidth_limit(call_id, participant, step.limit_kbps())
+            .await?;
+        if let BitrateStep::Down(_) = step {
+            if let Some(call) = self.calls.write().await.get_mut(&call_id) {
+                call.quality_stats.quality_degradation_events += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if letting `joining` into `call_id` would exceed the call's
+    /// `max_participants` or the node-wide `max_total_participants`.
+    /// Only connecting/connected participants count — an unanswered
+    /// invite doesn't hold a peer connection. A participant already
+    /// counted (e.g. a duplicate accept) doesn't count twice.
+    fn check_join_capacity(
+        &self,
+        calls: &HashMap<CallId, Call>,
+        call_id: CallId,
+        joining: IdentityId,
+    ) -> Result<()> {
+        let in_call = |p: &CallParticipant| {
+            matches!(
+                p.participant_state,
+                ParticipantState::Connecting | ParticipantState::Connected
+            )
+        };
+
+        let call = calls
+            .get(&call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
+        if call.participants.get(&joining).is_some_and(in_call) {
+            return Ok(());
+        }
+
+        let in_this_call = call.participants.values().filter(|p| in_call(p)).count();
+        if let Some(max) = call.settings.max_participants {
+            if in_this_call + 1 > max {
+                return Err(anyhow::anyhow!(
+                    "Call is full ({} of {} participants)",
+                    in_this_call,
+                    max
+                ));
+            }
+        }
+
+        let in_all_calls: usize = calls
+            .values()
+            .filter(|c| matches!(c.state, CallState::Active | CallState::Ringing))
+            .map(|c| c.participants.values().filter(|p| in_call(p)).count())
+            .sum();
+        if in_all_calls + 1 > self.config.max_total_participants {
+            return Err(anyhow::anyhow!(
+                "Node participant limit reached ({} of {})",
+                in_all_calls,
+                self.config.max_total_participants
+            ));
+        }
+
         Ok(())
     }
 
@@ -694,6 +1798,10 @@ impl CallManager {
             .get(&call_id)
             .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
 
+        let identity = self
+            .identity
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No local identity to sign call invitations with"))?;
         for participant_id in call.participants.keys() {
             if *participant_id != call.initiator {
                 let message = SignalingMessage::CallInvitation {
@@ -701,7 +1809,8 @@ impl CallManager {
                     caller: call.initiator,
                     call_type: call.call_type.clone(),
                     settings: call.settings.clone(),
-                };
+                }
+                .sign(identity, *participant_id)?;
 
                 self.signaling_server
                     .send_message(*participant_id, message)
@@ -740,50 +1849,99 @@ impl CallManager {
         Ok(())
     }
 
-    /// Start ring timeout
+    /// Start ring timeout. If the call is still ringing when it fires,
+    /// it moves to `TimedOut` and every participant who never answered
+    /// gets a [`CallEvent::CallMissed`].
     async fn start_ring_timeout(&self, call_id: CallId) {
         let calls = self.calls.clone();
         let timeout = self.config.ring_timeout;
         let event_sender = self.event_sender.clone();
 
-        tokio::spawn(async move {
+        let mut tasks = self.tasks.lock().unwrap();
+        // Reap finished timeouts so the set doesn't grow with every call.
+        while tasks.try_join_next().is_some() {}
+        let handle = tasks.spawn(async move {
             tokio::time::sleep(timeout).await;
 
             let mut calls = calls.write().await;
-            if let Some(call) = calls.get_mut(&call_id) {
-                if call.state == CallState::Ringing {
-                    call.state = CallState::TimedOut;
-                    call.ended_at = Some(
-                        SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs(),
-                    );
-
-                    let _ = event_sender.send(CallEvent::CallStateChanged {
+            let Some(call) = calls.get_mut(&call_id) else {
+                return;
+            };
+            if call.state != CallState::Ringing {
+                return;
+            }
+            call.state = CallState::TimedOut;
+            // A clock before 1970 only loses the end time, not the
+            // timeout itself.
+            call.ended_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs());
+
+            for (callee, info) in &call.participants {
+                if info.participant_state == ParticipantState::Invited {
+                    let _ = event_sender.send(CallEvent::CallMissed {
                         call_id,
-                        old_state: CallState::Ringing,
-                        new_state: CallState::TimedOut,
+                        callee: *callee,
                     });
                 }
             }
+            let _ = event_sender.send(CallEvent::CallStateChanged {
+                call_id,
+                old_state: CallState::Ringing,
+                new_state: CallState::TimedOut,
+            });
         });
+
+        let mut ring_timeouts = self.ring_timeouts.lock().unwrap();
+        ring_timeouts.retain(|_, handle| !handle.is_finished());
+        ring_timeouts.insert(call_id, handle);
+    }
+
+    /// Stop the call's ring timeout, if it hasn't fired yet.
+    fn cancel_ring_timeout(&self, call_id: CallId) {
+        if let Some(handle) = self.ring_timeouts.lock().unwrap().remove(&call_id) {
+            handle.abort();
+        }
     }
 
-    /// Establish peer connection for a participant
+    /// Establish peer connection for a participant. Under an SFU
+    /// topology relayed by someone else, only the relay gets a
+    /// connection; everyone else's media comes through it.
     async fn establish_peer_connection(
         &self,
         call_id: CallId,
         participant: IdentityId,
     ) -> Result<()> {
-        // Generate media encryption key
-        let media_key = self
-            .media_encryption
-            .generate_media_key(call_id, participant)?;
+        let peer = {
+            let calls = self.calls.read().await;
+            let call = calls
+                .get(&call_id)
+                .ok_or_else(|| anyhow::anyhow!("Call not found"))?;
+            call.topology.media_peer(call, participant)
+        };
+        if peer.is_none() {
+            return Ok(());
+        }
+
+        let local = self
+            .identity
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No local identity to key media with"))?
+            .identity_id();
+        let exporter = self
+            .session_exporters
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No sessions to key media from"))?
+            .session_exporter(participant)
+            .await
+            .context("No session with participant")?;
+        let media_keys =
+            MediaEncryption::derive_from_session(call_id, &local, &participant, &exporter);
 
         // Create WebRTC peer connection
-        self.webrtc_manager
-            .create_peer_connection(call_id, participant, media_key)
+        self.media
+            .create_peer_connection(call_id, participant, media_keys)
             .await?;
 
         Ok(())
@@ -791,39 +1949,27 @@ impl CallManager {
 
     /// Close peer connection for a participant
     async fn close_peer_connection(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
-        self.webrtc_manager
+        self.media
             .close_peer_connection(call_id, participant)
             .await?;
+        self.quality_controllers
+            .lock()
+            .unwrap()
+            .remove(&(call_id, participant));
         Ok(())
     }
 
-    /// Get identity key for a participant (placeholder)
     async fn get_identity_key(&self, participant: IdentityId) -> Result<IdentityKey> {
-        // Try to look up the contact first. If none exists we fall back
-        // to generating a fresh identity key pair. This ensures that
-        // calls can be initiated against unknown identities in tests and
-        // offline scenarios. A production implementation should fetch
-        // the remote party's identity from a trusted directory or
-        // signalling service instead.
-        if let Some(key) = self.contact_manager.get_identity_key(&participant).await {
-            return Ok(key);
-        }
-        // Generate a new identity key pair. Any errors here should be
-        // propagated back to the caller.
-        let pair = IdentityKeyPair::generate()?;
-        Ok(pair.public_key())
-    }
-
-    /// Get display name for a participant (placeholder)
+        self.participant_resolver
+            .resolve_identity_key(participant)
+            .await
+            .context("Failed to resolve participant identity key")
+    }
+
     async fn get_display_name(&self, participant: IdentityId) -> Result<String> {
-        if let Some(name) = self.contact_manager.get_display_name(&participant).await {
-            Ok(name)
-        } else {
-            // If no contact exists, return a generic placeholder. This
-            // mirrors the previous behaviour but goes through the
-            // contact manager for consistency.
-            Ok("Unknown".to_string())
-        }
+        self.participant_resolver
+            .resolve_display_name(participant)
+            .await
     }
 }
 
@@ -855,6 +2001,38 @@ impl Default for ConnectionQuality {
     }
 }
 
+impl ConnectionQuality {
+    /// Derive quality metrics from raw media stats. `round_trip_time`
+    /// and `jitter` are in seconds, as webrtc-rs reports them.
+    pub fn from_media_stats(stats: &MediaStats) -> Self {
+        let expected = stats.packets_received + stats.packets_lost;
+        let packet_loss = if expected == 0 {
+            0.0
+        } else {
+            stats.packets_lost as f32 * 100.0 / expected as f32
+        };
+        let latency = (stats.round_trip_time * 1000.0) as u32;
+        let jitter = (stats.jitter * 1000.0) as u32;
+
+        let quality_score = match (packet_loss, latency) {
+            (l, rtt) if l < 1.0 && rtt < 150 => 5,
+            (l, rtt) if l < 3.0 && rtt < 300 => 4,
+            (l, rtt) if l < 5.0 && rtt < 500 => 3,
+            (l, _) if l < 10.0 => 2,
+            _ => 1,
+        };
+
+        ConnectionQuality {
+            signal_strength: quality_score * 20,
+            packet_loss,
+            latency,
+            jitter,
+            bandwidth: stats.bitrate / 1000,
+            quality_score,
+        }
+    }
+}
+
 impl Default for CallQualityStats {
     fn default() -> Self {
         CallQualityStats {
@@ -890,9 +2068,11 @@ impl Default for CallManagerConfig {
     fn default() -> Self {
         CallManagerConfig {
             max_concurrent_calls: 10,
+            max_total_participants: 32,
             call_timeout: Duration::from_secs(300), // 5 minutes
             ring_timeout: Duration::from_secs(60),  // 1 minute
             reconnection_attempts: 3,
+            reconnection_backoff: Duration::from_millis(500),
             enable_p2p_optimization: true,
             stun_servers: vec![
                 "stun:stun.l.google.com:19302".to_string(),
@@ -903,6 +2083,18 @@ impl Default for CallManagerConfig {
     }
 }
 
+impl From<[u8; 16]> for CallId {
+    fn from(bytes: [u8; 16]) -> Self {
+        CallId(bytes)
+    }
+}
+
+impl AsRef<[u8]> for CallId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl std::fmt::Display for CallId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", hex::encode(&self.0[..8]))
@@ -918,17 +2110,93 @@ impl std::fmt::Debug for CallId {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::calling::media_backend::{MediaOp, MockMediaBackend};
+    use crate::calling::media_encryption::AUDIO_STREAM_ID;
     use tokio::sync::mpsc;
 
+    /// Resolves any participant, to a key generated on first sight and
+    /// the name "Participant N" after the first id byte.
+    #[derive(Default)]
+    struct TestResolver {
+        keys: Mutex<HashMap<IdentityId, IdentityKey>>,
+    }
+
+    impl ParticipantResolver for TestResolver {
+        fn resolve_identity_key(
+            &self,
+            participant: IdentityId,
+        ) -> ParticipantFuture<'_, IdentityKey> {
+            let mut keys = self.keys.lock().unwrap();
+            let key = keys
+                .entry(participant)
+                .or_insert_with(|| IdentityKeyPair::generate().unwrap().public_key())
+                .clone();
+            Box::pin(async move { Ok(key) })
+        }
+
+        fn resolve_display_name(&self, participant: IdentityId) -> ParticipantFuture<'_, String> {
+            let name = format!("Participant {}", participant.as_ref()[0]);
+            Box::pin(async move { Ok(name) })
+        }
+    }
+
+    /// Every participant shares the same exporter secret.
+    struct TestSessions;
+
+    impl SessionExporterProvider for TestSessions {
+        fn session_exporter(
+            &self,
+            _participant: IdentityId,
+        ) -> ParticipantFuture<'_, Zeroizing<[u8; 32]>> {
+            Box::pin(async { Ok(Zeroizing::new([7u8; 32])) })
+        }
+    }
+
+    /// Give `manager` a local identity, the test resolver and sessions,
+    /// and signaling clients for participants `[2; 32]` to `[8; 32]`,
+    /// so it can place signed calls to them.
+    async fn callable<B: MediaBackend>(manager: CallManager<B>) -> CallManager<B> {
+        let manager = manager
+            .with_identity(Arc::new(IdentityKeyPair::generate().unwrap()))
+            .with_participant_resolver(Arc::new(TestResolver::default()))
+            .with_session_exporters(Arc::new(TestSessions));
+        for id in 2..=8u8 {
+            let mut client = manager
+                .signaling_server
+                .register_client(IdentityId::from([id; 32]))
+                .await;
+            tokio::spawn(async move { while client.recv().await.is_some() {} });
+        }
+        manager
+    }
+
+    async fn mock_manager(
+        config: CallManagerConfig,
+        event_sender: mpsc::UnboundedSender<CallEvent>,
+    ) -> CallManager<MockMediaBackend> {
+        let manager = CallManager::with_backend(config, event_sender, MockMediaBackend::new())
+            .await
+            .expect("Should create call manager");
+        callable(manager).await
+    }
+
+    /// The identity `manager` places calls as.
+    fn local_id<B: MediaBackend>(manager: &CallManager<B>) -> IdentityId {
+        manager.identity.as_ref().unwrap().identity_id()
+    }
+
     #[tokio::test]
     async fn test_call_creation() {
         let (event_sender, _event_receiver) = mpsc::unbounded_channel();
         let config = CallManagerConfig::default();
-        let call_manager = CallManager::new(config, event_sender)
-            .await
-            .expect("Should create call manager");
-
-        let initiator = IdentityId::from([1u8; 32]);
+        let call_manager = callable(
+            CallManager::new(config, event_sender)
+                .await
+                .expect("Should create call manager"),
+        )
+        .await;
+
+        let initiator = local_id(&call_manager);
         let participants = vec![IdentityId::from([2u8; 32])];
 
         let call_id = call_manager
@@ -950,4 +2218,696 @@ mod tests {
         assert_eq!(call.initiator, initiator);
         assert_eq!(call.participants.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_join_rejected_past_max_participants() {
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        let call_manager = callable(
+            CallManager::new(CallManagerConfig::default(), event_sender)
+                .await
+                .expect("Should create call manager"),
+        )
+        .await;
+
+        let settings = CallSettings {
+            max_participants: Some(2),
+            ..CallSettings::default()
+        };
+        let call_id = call_manager
+            .initiate_call(
+                local_id(&call_manager),
+                vec![IdentityId::from([2u8; 32])],
+                CallType::GroupVoiceCall,
+                Some(GroupId::from_bytes([9u8; 32])),
+                settings,
+            )
+            .await
+            .expect("Should initiate call");
+
+        call_manager
+            .accept_call(call_id, IdentityId::from([2u8; 32]))
+            .await
+            .expect("First participant fits");
+        call_manager
+            .join_call(call_id, IdentityId::from([3u8; 32]))
+            .await
+            .expect("Second participant fits");
+        assert!(call_manager
+            .join_call(call_id, IdentityId::from([4u8; 32]))
+            .await
+            .is_err());
+
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert!(!call.participants.contains_key(&IdentityId::from([4u8; 32])));
+
+        let mut saw_error = false;
+        while let Ok(event) = event_receiver.try_recv() {
+            if matches!(event, CallEvent::CallError { call_id: id, .. } if id == call_id) {
+                saw_error = true;
+            }
+        }
+        assert!(saw_error, "rejected join must emit CallError");
+    }
+
+    async fn mock_call() -> (CallManager<MockMediaBackend>, CallId, IdentityId) {
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        // Nobody here reads the events, but the manager fails calls
+        // whose events can't be delivered.
+        tokio::spawn(async move { while event_receiver.recv().await.is_some() {} });
+        let call_manager = mock_manager(CallManagerConfig::default(), event_sender).await;
+        let callee = IdentityId::from([2u8; 32]);
+        let call_id = call_manager
+            .initiate_call(
+                local_id(&call_manager),
+                vec![callee],
+                CallType::VoiceCall,
+                None,
+                CallSettings::default(),
+            )
+            .await
+            .expect("Should initiate call");
+        (call_manager, call_id, callee)
+    }
+
+    #[tokio::test]
+    async fn test_participants_resolved_through_resolver() {
+        let (call_manager, call_id, callee) = mock_call().await;
+        let expected_key = call_manager
+            .participant_resolver
+            .resolve_identity_key(callee)
+            .await
+            .unwrap();
+        let call = call_manager.get_call(call_id).await.unwrap();
+        let participant = &call.participants[&callee];
+        assert_eq!(participant.identity_id, callee);
+        assert!(participant.identity_key == expected_key);
+        assert_eq!(participant.display_name, "Participant 2");
+
+        // The default resolver only knows contacts, and won't make up a
+        // key for anyone else.
+        let (event_sender, _event_receiver) = mpsc::unbounded_channel();
+        let contacts_only = CallManager::with_backend(
+            CallManagerConfig::default(),
+            event_sender,
+            MockMediaBackend::new(),
+        )
+        .await
+        .unwrap()
+        .with_identity(Arc::new(IdentityKeyPair::generate().unwrap()));
+        assert!(contacts_only
+            .initiate_call(
+                local_id(&contacts_only),
+                vec![callee],
+                CallType::VoiceCall,
+                None,
+                CallSettings::default(),
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_accept_and_mute_drive_media_backend() {
+        let (call_manager, call_id, callee) = mock_call().await;
+
+        call_manager
+            .accept_call(call_id, callee)
+            .await
+            .expect("Should accept call");
+        assert!(call_manager.media.is_connected(call_id, callee));
+
+        assert!(call_manager.toggle_mute(call_id, callee).await.unwrap());
+        assert!(!call_manager.toggle_mute(call_id, callee).await.unwrap());
+        let ops = call_manager.media.ops();
+        assert!(ops.contains(&MediaOp::SetAudioEnabled(call_id, callee, false)));
+        assert_eq!(
+            ops.last(),
+            Some(&MediaOp::SetAudioEnabled(call_id, callee, true))
+        );
+
+        call_manager
+            .end_call(call_id, callee)
+            .await
+            .expect("Should end call");
+        assert!(!call_manager.media.is_connected(call_id, callee));
+    }
+
+    #[tokio::test]
+    async fn test_hold_pauses_media_and_resume_restores_it() {
+        let (call_manager, call_id, callee) = mock_call().await;
+        call_manager.accept_call(call_id, callee).await.unwrap();
+        call_manager.toggle_video(call_id, callee).await.unwrap();
+        let before_hold = call_manager.media.ops().len();
+
+        call_manager.hold_call(call_id, callee).await.unwrap();
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert!(call.state == CallState::OnHold);
+        assert_eq!(
+            call_manager.media.ops()[before_hold..],
+            [
+                MediaOp::SetAudioEnabled(call_id, callee, false),
+                MediaOp::SetVideoEnabled(call_id, callee, false),
+            ]
+        );
+        assert!(call_manager.hold_call(call_id, callee).await.is_err());
+
+        call_manager.resume_call(call_id, callee).await.unwrap();
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert!(call.state == CallState::Active);
+        assert_eq!(
+            call_manager.media.ops()[before_hold + 2..],
+            [
+                MediaOp::SetAudioEnabled(call_id, callee, true),
+                MediaOp::SetVideoEnabled(call_id, callee, true),
+            ]
+        );
+        assert!(call_manager.resume_call(call_id, callee).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_screen_share_stops_capture_and_reports_state() {
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        let call_manager = mock_manager(CallManagerConfig::default(), event_sender).await;
+        let callee = IdentityId::from([2u8; 32]);
+        let call_id = call_manager
+            .initiate_call(
+                local_id(&call_manager),
+                vec![callee],
+                CallType::VideoCall,
+                None,
+                CallSettings::default(),
+            )
+            .await
+            .unwrap();
+        call_manager.accept_call(call_id, callee).await.unwrap();
+        while event_receiver.try_recv().is_ok() {}
+
+        call_manager
+            .start_screen_share(call_id, callee)
+            .await
+            .unwrap();
+        call_manager
+            .stop_screen_share(call_id, callee)
+            .await
+            .unwrap();
+        // Stopping twice is harmless and doesn't touch the backend again.
+        call_manager
+            .stop_screen_share(call_id, callee)
+            .await
+            .unwrap();
+
+        let call = call_manager.get_call(call_id).await.unwrap();
+        let participant = &call.participants[&callee];
+        assert!(!participant.is_screen_sharing);
+        assert!(!participant.media_state.screen_share_enabled);
+        let ops = call_manager.media.ops();
+        assert_eq!(
+            ops.iter()
+                .filter(|op| **op == MediaOp::StopScreenCapture(call_id, callee))
+                .count(),
+            1
+        );
+
+        let mut sharing = Vec::new();
+        while let Ok(event) = event_receiver.try_recv() {
+            if let CallEvent::MediaStateChanged { media_state, .. } = event {
+                sharing.push(media_state.screen_share_enabled);
+            }
+        }
+        assert_eq!(sharing, vec![true, false]);
+    }
+
+    #[tokio::test]
+    async fn test_hold_rejected_once_call_has_ended() {
+        let (call_manager, call_id, callee) = mock_call().await;
+        call_manager.accept_call(call_id, callee).await.unwrap();
+        call_manager
+            .end_call(call_id, local_id(&call_manager))
+            .await
+            .unwrap();
+
+        let err = call_manager.hold_call(call_id, callee).await.unwrap_err();
+        assert!(err.to_string().contains("not active"), "{err}");
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert!(call.state == CallState::Ended);
+    }
+
+    #[tokio::test]
+    async fn test_topology_follows_call_size() {
+        let (event_sender, _event_receiver) = mpsc::unbounded_channel();
+        let call_manager = mock_manager(CallManagerConfig::default(), event_sender).await;
+        let initiator = local_id(&call_manager);
+        // The topology is sized by `max_participants`, so each call is
+        // capped at the people invited to it.
+        let start = |size: u8| {
+            call_manager.initiate_call(
+                initiator,
+                (2..size + 1).map(|i| IdentityId::from([i; 32])).collect(),
+                CallType::Conference,
+                None,
+                CallSettings {
+                    max_participants: Some(usize::from(size) - 1),
+                    ..CallSettings::default()
+                },
+            )
+        };
+
+        let pair = start(2).await.unwrap();
+        let pair = call_manager.get_call(pair).await.unwrap();
+        assert!(pair.topology == CallTopology::Mesh);
+
+        let six = start(6).await.unwrap();
+        let call = call_manager.get_call(six).await.unwrap();
+        assert!(call.topology == CallTopology::Sfu { relay: initiator });
+
+        // With a participant relaying, only their connection is opened.
+        let relay = IdentityId::from([3u8; 32]);
+        let outsider = IdentityId::from([9u8; 32]);
+        assert!(call_manager
+            .set_topology(six, CallTopology::Sfu { relay: outsider })
+            .await
+            .is_err());
+        call_manager
+            .set_topology(six, CallTopology::Sfu { relay })
+            .await
+            .unwrap();
+        call_manager
+            .accept_call(six, IdentityId::from([2u8; 32]))
+            .await
+            .unwrap();
+        assert!(call_manager.media.ops().is_empty());
+
+        let relayed = start(6).await.unwrap();
+        call_manager
+            .set_topology(relayed, CallTopology::Sfu { relay })
+            .await
+            .unwrap();
+        call_manager.accept_call(relayed, relay).await.unwrap();
+        assert_eq!(
+            call_manager.media.ops(),
+            [MediaOp::CreatePeerConnection(relayed, relay)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quality_sampled_from_backend_stats() {
+        let (call_manager, call_id, callee) = mock_call().await;
+        call_manager.accept_call(call_id, callee).await.unwrap();
+        call_manager.media.set_stats(
+            call_id,
+            callee,
+            MediaStats {
+                bytes_sent: 0,
+                bytes_received: 0,
+                packets_sent: 100,
+                packets_received: 96,
+                packets_lost: 4,
+                jitter: 0.02,
+                round_trip_time: 0.2,
+                bitrate: 64_000,
+                frame_rate: None,
+                resolution: None,
+            },
+        );
+
+        let quality = call_manager
+            .sample_connection_quality(call_id, callee)
+            .await
+            .expect("Should sample quality");
+        assert_eq!(quality.quality_score, 3);
+        assert_eq!(quality.latency, 200);
+        assert_eq!(quality.bandwidth, 64);
+
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert_eq!(call.participants[&callee].connection_quality.latency, 200);
+    }
+
+    #[tokio::test]
+    async fn test_quality_samples_averaged_at_call_end() {
+        let (call_manager, call_id, callee) = mock_call().await;
+        call_manager.accept_call(call_id, callee).await.unwrap();
+
+        for i in 1..=3u64 {
+            call_manager.media.set_stats(
+                call_id,
+                callee,
+                MediaStats {
+                    bytes_sent: 1_000 * i,
+                    bytes_received: 500 * i,
+                    packets_sent: 100,
+                    packets_received: 100 - i,
+                    packets_lost: i,
+                    jitter: 0.01 * i as f64,
+                    round_trip_time: 0.1 * i as f64,
+                    bitrate: 64_000,
+                    frame_rate: None,
+                    resolution: None,
+                },
+            );
+            call_manager
+                .sample_connection_quality(call_id, callee)
+                .await
+                .expect("Should sample quality");
+        }
+        assert!(call_manager.get_call_quality(call_id).await.is_none());
+
+        call_manager
+            .end_call(call_id, callee)
+            .await
+            .expect("Should end call");
+        let stats = call_manager
+            .get_call_quality(call_id)
+            .await
+            .expect("Ended call has final stats");
+        assert!((stats.avg_packet_loss - 2.0).abs() < 0.01);
+        assert_eq!(stats.avg_latency, 200);
+        assert_eq!(stats.avg_jitter, 20);
+        // Byte counters are cumulative; the last sample is the total.
+        assert_eq!(stats.total_bytes_sent, 3_000);
+        assert_eq!(stats.total_bytes_received, 1_500);
+        assert!(stats.duration.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rising_loss_steps_bandwidth_limit_down() {
+        let (call_manager, call_id, callee) = mock_call().await;
+        call_manager.accept_call(call_id, callee).await.unwrap();
+
+        for lost in [0, 2, 4, 6, 8, 12, 20] {
+            call_manager.media.set_stats(
+                call_id,
+                callee,
+                MediaStats {
+                    bytes_sent: 0,
+                    bytes_received: 0,
+                    packets_sent: 100,
+                    packets_received: 100 - lost,
+                    packets_lost: lost,
+                    jitter: 0.01,
+                    round_trip_time: 0.05,
+                    bitrate: 500_000,
+                    frame_rate: None,
+                    resolution: None,
+                },
+            );
+            call_manager
+                .sample_connection_quality(call_id, callee)
+                .await
+                .expect("Should sample quality");
+        }
+
+        let limits: Vec<u32> = call_manager
+            .media
+            .ops()
+            .into_iter()
+            .filter_map(|op| match op {
+                MediaOp::SetBandwidthLimit(id, p, kbps) if id == call_id && p == callee => {
+                    Some(kbps)
+                }
+                _ => None,
+            })
+            .collect();
+        // Loss stays under the threshold for the first three samples,
+        // then every sample past it cuts the limit further.
+        assert_eq!(limits, vec![1_750, 1_225, 857, 599]);
+
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert_eq!(call.quality_stats.quality_degradation_events, 4);
+    }
+
+    #[tokio::test]
+    async fn test_ring_timeout_reports_missed_call_unless_answered() {
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        let call_manager = mock_manager(
+            CallManagerConfig {
+                ring_timeout: Duration::from_millis(50),
+                ..CallManagerConfig::default()
+            },
+            event_sender,
+        )
+        .await;
+        let callee = IdentityId::from([2u8; 32]);
+        let ring = || {
+            call_manager.initiate_call(
+                local_id(&call_manager),
+                vec![callee],
+                CallType::VoiceCall,
+                None,
+                CallSettings::default(),
+            )
+        };
+
+        let answered = ring().await.unwrap();
+        call_manager.accept_call(answered, callee).await.unwrap();
+        assert!(call_manager.ring_timeouts.lock().unwrap().is_empty());
+        let missed = ring().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(call_manager.get_call(answered).await.unwrap().state == CallState::Active);
+        assert!(call_manager.get_call(missed).await.unwrap().state == CallState::TimedOut);
+        let mut missed_events = Vec::new();
+        while let Ok(event) = event_receiver.try_recv() {
+            if let CallEvent::CallMissed { call_id, callee } = event {
+                missed_events.push((call_id, callee));
+            }
+        }
+        assert_eq!(missed_events, vec![(missed, callee)]);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_ends_calls_and_cancels_ring_timeout() {
+        let (call_manager, call_id, callee) = mock_call().await;
+        call_manager.accept_call(call_id, callee).await.unwrap();
+        let ringing = call_manager
+            .initiate_call(
+                local_id(&call_manager),
+                vec![IdentityId::from([5u8; 32])],
+                CallType::VoiceCall,
+                None,
+                CallSettings::default(),
+            )
+            .await
+            .unwrap();
+
+        call_manager.shutdown().await.expect("Should shut down");
+
+        assert!(!call_manager.media.is_connected(call_id, callee));
+        assert_eq!(
+            call_manager.get_call(call_id).await.unwrap().state,
+            CallState::Ended
+        );
+        // Ended by shutdown, not by the (aborted) ring timeout.
+        assert_eq!(
+            call_manager.get_call(ringing).await.unwrap().state,
+            CallState::Ended
+        );
+        assert!(call_manager.tasks.lock().unwrap().is_empty());
+        assert!(call_manager
+            .initiate_call(
+                local_id(&call_manager),
+                vec![callee],
+                CallType::VoiceCall,
+                None,
+                CallSettings::default(),
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_connection_reconnects_then_drops_participant() {
+        let (call_manager, call_id, callee) = mock_call().await;
+        call_manager
+            .accept_call(call_id, callee)
+            .await
+            .expect("Should accept call");
+
+        let (tx, rx) = mpsc::channel(8);
+        tx.send((call_id, callee, PeerConnectionState::Connected))
+            .await
+            .unwrap();
+        tx.send((call_id, callee, PeerConnectionState::Failed))
+            .await
+            .unwrap();
+        drop(tx);
+        call_manager.process_connection_states(rx).await;
+
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert!(call.participants[&callee].participant_state == ParticipantState::Connecting);
+        assert_eq!(call.quality_stats.reconnection_count, 1);
+        let creates = call_manager
+            .media
+            .ops()
+            .iter()
+            .filter(|op| matches!(op, MediaOp::CreatePeerConnection(..)))
+            .count();
+        assert_eq!(creates, 2);
+
+        // Out of attempts: the participant is dropped, not reconnected.
+        for _ in 0..CallManagerConfig::default().reconnection_attempts {
+            call_manager
+                .handle_connection_state(call_id, callee, PeerConnectionState::Failed)
+                .await
+                .unwrap();
+        }
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert!(call.participants[&callee].participant_state == ParticipantState::Left);
+        assert!(!call_manager.media.is_connected(call_id, callee));
+    }
+
+    #[tokio::test]
+    async fn test_failed_reconnect_retried_with_backoff() {
+        let (event_sender, _event_receiver) = mpsc::unbounded_channel();
+        let call_manager = mock_manager(
+            CallManagerConfig {
+                reconnection_backoff: Duration::from_millis(1),
+                ..CallManagerConfig::default()
+            },
+            event_sender,
+        )
+        .await;
+        let callee = IdentityId::from([2u8; 32]);
+        let call_id = call_manager
+            .initiate_call(
+                local_id(&call_manager),
+                vec![callee],
+                CallType::VoiceCall,
+                None,
+                CallSettings::default(),
+            )
+            .await
+            .unwrap();
+        call_manager.accept_call(call_id, callee).await.unwrap();
+
+        // The link fails, the first rebuild fails too, the second works.
+        call_manager.media.fail_next_connections(1);
+        for state in [
+            PeerConnectionState::Connected,
+            PeerConnectionState::Failed,
+            PeerConnectionState::Connected,
+        ] {
+            call_manager
+                .handle_connection_state(call_id, callee, state)
+                .await
+                .unwrap();
+        }
+
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert!(call.participants[&callee].participant_state == ParticipantState::Connected);
+        assert_eq!(call.quality_stats.reconnection_count, 2);
+        assert!(call_manager.media.is_connected(call_id, callee));
+
+        // With every rebuild failing, the remaining attempt is used up
+        // and the participant dropped.
+        call_manager.media.fail_next_connections(u32::MAX);
+        call_manager
+            .handle_connection_state(call_id, callee, PeerConnectionState::Failed)
+            .await
+            .unwrap();
+        let call = call_manager.get_call(call_id).await.unwrap();
+        assert!(call.participants[&callee].participant_state == ParticipantState::Left);
+        assert_eq!(call.quality_stats.reconnection_count, 3);
+    }
+
+    async fn recordable_call(
+        allow_recording: bool,
+    ) -> (
+        CallManager<MockMediaBackend>,
+        mpsc::UnboundedReceiver<CallEvent>,
+        CallId,
+    ) {
+        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+        let call_manager = mock_manager(CallManagerConfig::default(), event_sender).await;
+        let call_id = call_manager
+            .initiate_call(
+                local_id(&call_manager),
+                vec![IdentityId::from([2u8; 32])],
+                CallType::VoiceCall,
+                None,
+                CallSettings {
+                    allow_recording,
+                    ..CallSettings::default()
+                },
+            )
+            .await
+            .expect("Should initiate call");
+        (call_manager, event_receiver, call_id)
+    }
+
+    #[tokio::test]
+    async fn test_recording_refused_without_permission() {
+        let callee = IdentityId::from([2u8; 32]);
+
+        let (call_manager, _events, call_id) = recordable_call(false).await;
+        assert!(call_manager
+            .start_recording(call_id, local_id(&call_manager))
+            .await
+            .is_err());
+
+        let (call_manager, _events, call_id) = recordable_call(true).await;
+        assert!(call_manager.start_recording(call_id, callee).await.is_err());
+
+        call_manager
+            .object_to_recording(call_id, callee)
+            .await
+            .unwrap();
+        let err = call_manager
+            .start_recording(call_id, local_id(&call_manager))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("objected"));
+        assert!(!call_manager
+            .record_frame(call_id, callee, AUDIO_STREAM_ID, b"frame")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_recording_start_stop_cycle() {
+        let callee = IdentityId::from([2u8; 32]);
+        let (call_manager, mut events, call_id) = recordable_call(true).await;
+        let initiator = local_id(&call_manager);
+
+        call_manager
+            .start_recording(call_id, initiator)
+            .await
+            .expect("Initiator may record");
+        let mut notified = false;
+        while let Ok(event) = events.try_recv() {
+            if matches!(
+                event,
+                CallEvent::RecordingStarted { call_id: id, requester }
+                    if id == call_id && requester == initiator
+            ) {
+                notified = true;
+            }
+        }
+        assert!(notified, "starting a recording must notify participants");
+
+        for frame in [b"first".as_slice(), b"second".as_slice()] {
+            assert!(call_manager
+                .record_frame(call_id, callee, AUDIO_STREAM_ID, frame)
+                .await
+                .unwrap());
+        }
+        assert!(call_manager.stop_recording(call_id, callee).await.is_err());
+
+        let artifact = call_manager
+            .stop_recording(call_id, initiator)
+            .await
+            .expect("Should stop recording");
+        assert!(!artifact
+            .frames
+            .windows(b"second".len())
+            .any(|w| w == b"second"));
+        let frames = artifact.decrypt_frames().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].data, b"second");
+        assert!(frames[0].participant == callee);
+
+        assert!(call_manager
+            .stop_recording(call_id, initiator)
+            .await
+            .is_err());
+    }
 }
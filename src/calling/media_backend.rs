@@ -0,0 +1,365 @@
+//! Seam between call orchestration and the media stack.
+//!
+//! [`CallManager`](crate::calling::call_manager::CallManager) only
+//! needs a handful of operations from the media layer: open/close a
+//! peer connection, flip tracks, read stats. [`MediaBackend`] names
+//! exactly those, [`WebRTCManager`] implements them against webrtc-rs,
+//! and [`MockMediaBackend`] records them so the accept/reject/mute/
+//! quality flow can be exercised without a real WebRTC stack or media
+//! devices.
+
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+use crate::calling::call_manager::CallId;
+use crate::calling::media_encryption::MediaKeyPair;
+use crate::calling::webrtc_manager::{MediaStats, WebRTCManager};
+use crate::identity::identity_key::IdentityId;
+
+/// Media operations `CallManager` drives. All futures are `Send` so
+/// the manager's own futures stay spawnable on a multi-threaded
+/// runtime.
+pub trait MediaBackend: Send + Sync {
+    fn create_peer_connection(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        media_keys: MediaKeyPair,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn close_peer_connection(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn set_audio_enabled(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        enabled: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn set_video_enabled(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        enabled: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn start_screen_capture(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn stop_screen_capture(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn get_media_stats(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+    ) -> impl Future<Output = Result<MediaStats>> + Send;
+
+    fn set_bandwidth_limit(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        limit_kbps: u32,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn open_data_channel(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        label: &str,
+    ) -> impl Future<Output = Result<mpsc::Receiver<Vec<u8>>>> + Send;
+
+    fn send_data(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        data: &[u8],
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl MediaBackend for WebRTCManager {
+    async fn create_peer_connection(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        media_keys: MediaKeyPair,
+    ) -> Result<()> {
+        WebRTCManager::create_peer_connection(self, call_id, participant, media_keys).await
+    }
+
+    async fn close_peer_connection(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        WebRTCManager::close_peer_connection(self, call_id, participant).await
+    }
+
+    async fn set_audio_enabled(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        enabled: bool,
+    ) -> Result<()> {
+        WebRTCManager::set_audio_enabled(self, call_id, participant, enabled).await
+    }
+
+    async fn set_video_enabled(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        enabled: bool,
+    ) -> Result<()> {
+        WebRTCManager::set_video_enabled(self, call_id, participant, enabled).await
+    }
+
+    async fn start_screen_capture(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        WebRTCManager::start_screen_capture(self, call_id, participant).await
+    }
+
+    async fn stop_screen_capture(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        WebRTCManager::stop_screen_capture(self, call_id, participant).await
+    }
+
+    async fn get_media_stats(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+    ) -> Result<MediaStats> {
+        WebRTCManager::get_media_stats(self, call_id, participant).await
+    }
+
+    async fn set_bandwidth_limit(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        limit_kbps: u32,
+    ) -> Result<()> {
+        WebRTCManager::set_bandwidth_limit(self, call_id, participant, limit_kbps).await
+    }
+
+    async fn open_data_channel(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        label: &str,
+    ) -> Result<mpsc::Receiver<Vec<u8>>> {
+        WebRTCManager::open_data_channel(self, call_id, participant, label).await
+    }
+
+    async fn send_data(&self, call_id: CallId, participant: IdentityId, data: &[u8]) -> Result<()> {
+        WebRTCManager::send_data(self, call_id, participant, data).await
+    }
+}
+
+/// One recorded [`MediaBackend`] call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MediaOp {
+    CreatePeerConnection(CallId, IdentityId),
+    ClosePeerConnection(CallId, IdentityId),
+    SetAudioEnabled(CallId, IdentityId, bool),
+    SetVideoEnabled(CallId, IdentityId, bool),
+    StartScreenCapture(CallId, IdentityId),
+    StopScreenCapture(CallId, IdentityId),
+    SetBandwidthLimit(CallId, IdentityId, u32),
+    OpenDataChannel(CallId, IdentityId, String),
+    SendData(CallId, IdentityId, Vec<u8>),
+}
+
+/// Inbound data-channel senders per open connection.
+type DataChannels = HashMap<(CallId, IdentityId), mpsc::Sender<Vec<u8>>>;
+
+/// In-memory backend for tests. Records every operation in order,
+/// tracks which peer connections are open, and returns whatever stats
+/// were registered with [`MockMediaBackend::set_stats`]. Operations on
+/// a connection that isn't open behave like `WebRTCManager`: track
+/// toggles are silently ignored, stats and data channels fail with
+/// "not found". Inbound data-channel messages are injected with
+/// [`MockMediaBackend::deliver_data`], and connection failures with
+/// [`MockMediaBackend::fail_next_connections`].
+#[derive(Default)]
+pub struct MockMediaBackend {
+    ops: Mutex<Vec<MediaOp>>,
+    open: Mutex<HashSet<(CallId, IdentityId)>>,
+    stats: Mutex<HashMap<(CallId, IdentityId), MediaStats>>,
+    data_channels: Mutex<DataChannels>,
+    failing_connections: Mutex<u32>,
+}
+
+impl MockMediaBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every operation seen so far, oldest first.
+    pub fn ops(&self) -> Vec<MediaOp> {
+        self.ops.lock().unwrap().clone()
+    }
+
+    /// `true` if a peer connection for `participant` in `call_id` is
+    /// currently open.
+    pub fn is_connected(&self, call_id: CallId, participant: IdentityId) -> bool {
+        self.open.lock().unwrap().contains(&(call_id, participant))
+    }
+
+    /// Canned stats returned by `get_media_stats` for this connection.
+    pub fn set_stats(&self, call_id: CallId, participant: IdentityId, stats: MediaStats) {
+        self.stats
+            .lock()
+            .unwrap()
+            .insert((call_id, participant), stats);
+    }
+
+    /// Make the next `count` `create_peer_connection` calls fail, as a
+    /// dead network would. They are still recorded.
+    pub fn fail_next_connections(&self, count: u32) {
+        *self.failing_connections.lock().unwrap() = count;
+    }
+
+    /// Hand `data` to the data-channel receiver for this connection,
+    /// as if the peer had sent it. Fails if no channel is open.
+    pub fn deliver_data(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let sender = self
+            .data_channels
+            .lock()
+            .unwrap()
+            .get(&(call_id, participant))
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Data channel is not open"))?;
+        sender
+            .try_send(data)
+            .map_err(|_| anyhow::anyhow!("Data channel receiver is gone or full"))
+    }
+
+    fn record(&self, op: MediaOp) {
+        self.ops.lock().unwrap().push(op);
+    }
+}
+
+impl MediaBackend for MockMediaBackend {
+    async fn create_peer_connection(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        _media_keys: MediaKeyPair,
+    ) -> Result<()> {
+        self.record(MediaOp::CreatePeerConnection(call_id, participant));
+        let mut failing = self.failing_connections.lock().unwrap();
+        if *failing > 0 {
+            *failing -= 1;
+            return Err(anyhow::anyhow!("Peer connection failed"));
+        }
+        self.open.lock().unwrap().insert((call_id, participant));
+        Ok(())
+    }
+
+    async fn close_peer_connection(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        self.record(MediaOp::ClosePeerConnection(call_id, participant));
+        self.open.lock().unwrap().remove(&(call_id, participant));
+        self.data_channels
+            .lock()
+            .unwrap()
+            .remove(&(call_id, participant));
+        Ok(())
+    }
+
+    async fn set_audio_enabled(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        enabled: bool,
+    ) -> Result<()> {
+        self.record(MediaOp::SetAudioEnabled(call_id, participant, enabled));
+        Ok(())
+    }
+
+    async fn set_video_enabled(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        enabled: bool,
+    ) -> Result<()> {
+        self.record(MediaOp::SetVideoEnabled(call_id, participant, enabled));
+        Ok(())
+    }
+
+    async fn start_screen_capture(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        self.record(MediaOp::StartScreenCapture(call_id, participant));
+        Ok(())
+    }
+
+    async fn stop_screen_capture(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
+        self.record(MediaOp::StopScreenCapture(call_id, participant));
+        Ok(())
+    }
+
+    async fn get_media_stats(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+    ) -> Result<MediaStats> {
+        if !self.is_connected(call_id, participant) {
+            return Err(anyhow::anyhow!("Peer connection not found"));
+        }
+        self.stats
+            .lock()
+            .unwrap()
+            .get(&(call_id, participant))
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No stats available"))
+    }
+
+    async fn set_bandwidth_limit(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        limit_kbps: u32,
+    ) -> Result<()> {
+        self.record(MediaOp::SetBandwidthLimit(call_id, participant, limit_kbps));
+        Ok(())
+    }
+
+    async fn open_data_channel(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        label: &str,
+    ) -> Result<mpsc::Receiver<Vec<u8>>> {
+        self.record(MediaOp::OpenDataChannel(
+            call_id,
+            participant,
+            label.to_string(),
+        ));
+        if !self.is_connected(call_id, participant) {
+            return Err(anyhow::anyhow!("Peer connection not found"));
+        }
+        let (tx, rx) = mpsc::channel(64);
+        self.data_channels
+            .lock()
+            .unwrap()
+            .insert((call_id, participant), tx);
+        Ok(rx)
+    }
+
+    async fn send_data(&self, call_id: CallId, participant: IdentityId, data: &[u8]) -> Result<()> {
+        self.record(MediaOp::SendData(call_id, participant, data.to_vec()));
+        if !self.is_connected(call_id, participant) {
+            return Err(anyhow::anyhow!("Peer connection not found"));
+        }
+        Ok(())
+    }
+}
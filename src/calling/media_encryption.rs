@@ -7,7 +7,8 @@
 //!
 //! The design keeps encryption orthogonal to the WebRTC transport layer – it
 //! assumes the caller already has an agreed [`MediaKey`] (e.g., derived from
-//! the double ratchet) and that frame boundaries are preserved by the caller.
+//! the session's exporter secret) and that frame boundaries are preserved by
+//! the caller.
 
 use anyhow::{Context, Result};
 use chacha20poly1305::{
@@ -16,20 +17,35 @@ use chacha20poly1305::{
 };
 use hkdf::Hkdf;
 use rand::RngCore;
-use secrecy::{ExposeSecret, Secret};
+use secrecy::{ExposeSecret, SecretBox};
 use sha2::Sha256;
 
+use crate::calling::call_manager::CallId;
+use crate::identity::identity_key::IdentityId;
+
+/// `stream_id` used for a connection's audio frames.
+pub const AUDIO_STREAM_ID: u64 = 0;
+/// `stream_id` used for a connection's video frames.
+pub const VIDEO_STREAM_ID: u64 = 1;
+/// `stream_id` used for a connection's data-channel messages.
+pub const DATA_STREAM_ID: u64 = 2;
+
 /// Opaque wrapper around a 32‑byte media key used for deriving stream keys.
 ///
-/// The contents are kept in a [`Secret`] to ensure they are cleared from
+/// The contents are kept in a [`SecretBox`] to ensure they are cleared from
 /// memory on drop.
-#[derive(Clone)]
-pub struct MediaKey(Secret<[u8; 32]>);
+pub struct MediaKey(SecretBox<[u8; 32]>);
+
+impl Clone for MediaKey {
+    fn clone(&self) -> Self {
+        Self::new(*self.as_bytes())
+    }
+}
 
 impl MediaKey {
     /// Creates a new `MediaKey` from raw bytes.
     pub fn new(bytes: [u8; 32]) -> Self {
-        Self(Secret::new(bytes))
+        Self(SecretBox::new(Box::new(bytes)))
     }
 
     /// Returns the raw key material.  This should be used sparingly since it
@@ -39,6 +55,16 @@ impl MediaKey {
     }
 }
 
+const MEDIA_KEY_INFO: &[u8] = b"qubee call media key v1";
+
+/// Directional media keys for one participant pair in one call.
+/// `send` is this side's outgoing key and equals the peer's `receive`.
+#[derive(Clone)]
+pub struct MediaKeyPair {
+    pub send: MediaKey,
+    pub receive: MediaKey,
+}
+
 /// Context used to perform encryption/decryption of media frames.
 ///
 /// A `MediaEncryption` instance derives a unique cipher key for each stream
@@ -55,11 +81,42 @@ impl MediaEncryption {
         Self { media_key }
     }
 
+    /// Derive the media keys for a call with `participant` from the
+    /// exporter secret of the session already established with them.
+    ///
+    /// A key chosen by whoever relays signaling would let them sit in
+    /// the media path; deriving from the authenticated session means
+    /// only the two identities that ran the key exchange can produce
+    /// these keys. The exporter is a secret the session hands out for
+    /// derivations like this one, never its root or a chain key, so
+    /// media keys can't be used to open or forge chat messages. The
+    /// `call_id` is the HKDF salt, so every call gets fresh keys, and
+    /// each direction gets its own key by putting the sender's identity
+    /// first in the info string.
+    pub fn derive_from_session(
+        call_id: CallId,
+        local: &IdentityId,
+        participant: &IdentityId,
+        exporter_secret: &[u8; 32],
+    ) -> MediaKeyPair {
+        let hk = Hkdf::<Sha256>::new(Some(call_id.as_ref()), exporter_secret);
+        let expand = |from: &IdentityId, to: &IdentityId| {
+            let mut okm = [0u8; 32];
+            hk.expand_multi_info(&[MEDIA_KEY_INFO, from.as_ref(), to.as_ref()], &mut okm)
+                .expect("32 bytes is a valid HKDF-SHA256 output length");
+            MediaKey::new(okm)
+        };
+        MediaKeyPair {
+            send: expand(local, participant),
+            receive: expand(participant, local),
+        }
+    }
+
     /// Derive a per‑stream key using HKDF.  The `stream_id` should be unique
     /// per logical media stream (for example, `0` for audio and `1` for
     /// video).  Reusing a `stream_id` with the same `media_key` will produce
     /// the same derived key.
-    fn derive_stream_key(&self, stream_id: u64) -> Key<ChaCha20Poly1305> {
+    fn derive_stream_key(&self, stream_id: u64) -> Key {
         let hk = Hkdf::<Sha256>::new(None, self.media_key.as_bytes());
         let mut okm = [0u8; 32];
         let info = stream_id.to_le_bytes();
@@ -132,3 +189,34 @@ impl<'a> StreamEncryption<'a> {
         self.inner.decrypt_frame(self.stream_id, data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_derived_keys_match_across_peers_and_differ_per_call() {
+        let alice = IdentityId::from([1u8; 32]);
+        let bob = IdentityId::from([2u8; 32]);
+        let exporter = [9u8; 32];
+        let call = CallId::from([5u8; 16]);
+
+        let on_alice = MediaEncryption::derive_from_session(call, &alice, &bob, &exporter);
+        let on_bob = MediaEncryption::derive_from_session(call, &bob, &alice, &exporter);
+        assert_eq!(on_alice.send.as_bytes(), on_bob.receive.as_bytes());
+        assert_eq!(on_alice.receive.as_bytes(), on_bob.send.as_bytes());
+        assert_ne!(on_alice.send.as_bytes(), on_alice.receive.as_bytes());
+
+        let frame = MediaEncryption::new(on_alice.send.clone())
+            .encrypt_frame(AUDIO_STREAM_ID, b"opus")
+            .unwrap();
+        let opened = MediaEncryption::new(on_bob.receive)
+            .decrypt_frame(AUDIO_STREAM_ID, &frame)
+            .unwrap();
+        assert_eq!(opened, b"opus");
+
+        let other_call =
+            MediaEncryption::derive_from_session(CallId::from([6u8; 16]), &alice, &bob, &exporter);
+        assert_ne!(other_call.send.as_bytes(), on_alice.send.as_bytes());
+    }
+}
@@ -62,13 +62,20 @@
 //!   missing; it actually lives at `webrtc_manager.rs:38`. False alarm.
 
 pub mod call_manager;
+pub mod media_backend;
 pub mod media_encryption;
 pub mod peer_connection;
+pub mod quality_controller;
 pub mod signaling;
 pub mod webrtc_manager;
 
-pub use call_manager::{Call, CallManager, CallState, CallType};
-pub use media_encryption::{MediaEncryption, MediaKey, StreamEncryption};
+pub use call_manager::{
+    Call, CallManager, CallState, CallTopology, CallType, ContactResolver, ParticipantResolver,
+    RecordedFrame, RecordingArtifact, SessionExporterProvider,
+};
+pub use media_backend::{MediaBackend, MockMediaBackend};
+pub use media_encryption::{MediaEncryption, MediaKey, MediaKeyPair, StreamEncryption};
 pub use peer_connection::{ICECandidate, PeerConnection, PeerConnectionState};
-pub use signaling::{SignalingClient, SignalingMessage, SignalingServer};
-pub use webrtc_manager::{WebRTCConfig, WebRTCManager};
+pub use quality_controller::{BitrateStep, QualityController, QualityControllerConfig};
+pub use signaling::{SignalingClient, SignalingMessage, SignalingServer, SignedSignalingMessage};
+pub use webrtc_manager::{TurnCredentialProvider, TurnCredentials, WebRTCConfig, WebRTCManager};
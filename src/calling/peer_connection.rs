@@ -2,7 +2,9 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
 use crate::calling::call_manager::CallId;
-use crate::calling::media_encryption::MediaKey;
+use crate::calling::media_encryption::{
+    MediaEncryption, MediaKeyPair, AUDIO_STREAM_ID, DATA_STREAM_ID,
+};
 use crate::calling::webrtc_manager::MediaStats;
 use crate::calling::webrtc_manager::WebRTCConfig;
 use crate::identity::identity_key::IdentityId;
@@ -14,21 +16,31 @@ use crate::identity::identity_key::IdentityId;
 //     `sdp_type` module.
 //   * The `media::` namespace was flattened — track types now live
 //     directly under `webrtc::track::`.
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use webrtc::api::interceptor_registry::register_default_interceptors;
+use webrtc::api::media_engine::MediaEngine;
 use webrtc::api::APIBuilder;
+use webrtc::data_channel::data_channel_init::RTCDataChannelInit;
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::RTCDataChannel;
 use webrtc::ice_transport::ice_candidate::RTCIceCandidateInit;
 use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::interceptor::registry::Registry;
+use webrtc::media::Sample;
 use webrtc::peer_connection::configuration::RTCConfiguration;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
-use webrtc::rtp_transceiver::rtp_codec::RTCRtpCodecCapability;
+use webrtc::rtp_transceiver::rtp_codec::{RTCRtpCodecCapability, RTPCodecType};
 use webrtc::rtp_transceiver::rtp_sender::RTCRtpSender;
 use webrtc::stats::StatsReportType;
 use webrtc::track::track_local::track_local_static_sample::TrackLocalStaticSample;
 use webrtc::track::track_local::TrackLocal;
+use webrtc::track::track_remote::TrackRemote;
 
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 
 /// Represents the state of a peer connection. Mirrors the variants
 /// exposed by webrtc-rs's `RTCPeerConnectionState` so `state()` can
@@ -66,9 +78,23 @@ impl From<RTCPeerConnectionState> for PeerConnectionState {
     }
 }
 
+/// SCTP stream id of the pre-negotiated in-call data channel.
+pub const DATA_CHANNEL_ID: u16 = 0;
+
+/// Inbound data-channel messages buffered before the reader falls
+/// behind and the channel applies backpressure.
+const DATA_CHANNEL_BUFFER: usize = 64;
+
+/// Where a connection reports its state transitions, tagged with the
+/// call and participant it belongs to.
+pub type ConnectionStateSender = mpsc::Sender<(CallId, IdentityId, PeerConnectionState)>;
+
+/// Receives each decrypted audio frame from the remote participant.
+pub type AudioSampleHandler = Arc<dyn Fn(Vec<u8>) + Send + Sync>;
+
 /// ICE candidate information used during WebRTC negotiation. These
 /// correspond to the candidate fields in the SDP specification.
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
 pub struct ICECandidate {
     /// Media identification string as used in SDP.
     pub sdp_mid: String,
@@ -93,8 +119,9 @@ pub struct PeerConnection {
     pub call_id: CallId,
     /// Identity of the remote participant.
     pub participant: IdentityId,
-    /// Media encryption key used for SRTP/SRTCP once established.
-    pub media_key: MediaKey,
+    /// Media keys for this participant pair: frames we send are
+    /// encrypted under `send`, frames we receive opened with `receive`.
+    pub media_keys: MediaKeyPair,
     /// Current signalling and media state of the connection.
     pub(crate) state: PeerConnectionState,
     /// Underlying WebRTC peer connection. This is the main handle
@@ -112,6 +139,16 @@ pub struct PeerConnection {
     /// protected by mutexes so that toggling video is thread safe.
     video_track: Arc<Mutex<Option<Arc<TrackLocalStaticSample>>>>,
     video_sender: Arc<Mutex<Option<Arc<RTCRtpSender>>>>,
+
+    /// Data channel for in-call messages, once opened.
+    data_channel: Mutex<Option<Arc<RTCDataChannel>>>,
+
+    /// When the previous `get_stats` ran and how many bytes had been
+    /// sent by then, for turning the cumulative counters into a rate.
+    last_stats: Mutex<Option<(Instant, u64)>>,
+
+    /// Set once the remote peer has said it won't send more candidates.
+    remote_gathering_complete: AtomicBool,
 }
 
 impl PeerConnection {
@@ -121,11 +158,16 @@ impl PeerConnection {
     /// `RTCPeerConnection`. ICE gathering and DTLS setup happen lazily
     /// once a local description is installed via `create_offer` /
     /// `create_answer`.
+    ///
+    /// With `state_events`, every peer-connection state change (which
+    /// aggregates ICE and DTLS health) is forwarded there, so the call
+    /// layer can notice a dropped media path.
     pub async fn new(
         config: WebRTCConfig,
-        media_key: MediaKey,
+        media_keys: MediaKeyPair,
         call_id: CallId,
         participant: IdentityId,
+        state_events: Option<ConnectionStateSender>,
     ) -> Result<Self> {
         // Convert CallManager's WebRTCConfig into the lower-level RTCConfiguration
         // used by webrtc-rs. Each STUN/TURN server becomes an RTCIceServer.
@@ -150,26 +192,47 @@ impl PeerConnection {
             ice_servers,
             ..Default::default()
         };
-        // Build the WebRTC API and create a new peer connection. The API
-        // builder allows customising the media engine and interceptor
-        // registry; for now we stick with defaults. If SRTP/DTLS is
-        // disabled in the config, we could disable corresponding
-        // interceptors here.
-        let api = APIBuilder::new().build();
+        // Build the WebRTC API and create a new peer connection. A bare
+        // APIBuilder has an empty media engine, so no codec could be
+        // negotiated and RTP senders refuse to start; register the
+        // default codecs (Opus, VP8, ...) and the default interceptors
+        // (NACK, RTCP reports) that `get_stats` reads from.
+        let mut media_engine = MediaEngine::default();
+        media_engine
+            .register_default_codecs()
+            .context("Failed to register default codecs")?;
+        let registry = register_default_interceptors(Registry::new(), &mut media_engine)
+            .context("Failed to register default interceptors")?;
+        let api = APIBuilder::new()
+            .with_media_engine(media_engine)
+            .with_interceptor_registry(registry)
+            .build();
         let pc = api
             .new_peer_connection(rtc_config)
             .await
             .context("Failed to create WebRTC peer connection")?;
+        if let Some(tx) = state_events {
+            pc.on_peer_connection_state_change(Box::new(move |state: RTCPeerConnectionState| {
+                let tx = tx.clone();
+                Box::pin(async move {
+                    // Nobody listening any more is fine; the call is gone.
+                    let _ = tx.send((call_id, participant, state.into())).await;
+                })
+            }));
+        }
         Ok(PeerConnection {
             call_id,
             participant,
-            media_key,
+            media_keys,
             state: PeerConnectionState::New,
             webrtc_pc: Arc::new(pc),
             audio_track: Arc::new(Mutex::new(None)),
             audio_sender: Arc::new(Mutex::new(None)),
             video_track: Arc::new(Mutex::new(None)),
             video_sender: Arc::new(Mutex::new(None)),
+            data_channel: Mutex::new(None),
+            last_stats: Mutex::new(None),
+            remote_gathering_complete: AtomicBool::new(false),
         })
     }
 
@@ -352,6 +415,121 @@ impl PeerConnection {
         Ok(())
     }
 
+    /// Send one encoded audio frame (e.g. a 20 ms Opus packet) on the
+    /// local audio track. The frame is encrypted under this
+    /// connection's `send` key first, so the SFU/TURN path only ever
+    /// sees ciphertext. Fails if audio hasn't been enabled.
+    pub async fn push_audio_sample(&self, data: &[u8], duration: Duration) -> Result<()> {
+        let track = self
+            .audio_track
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Audio is not enabled on this connection"))?;
+        let encrypted = MediaEncryption::new(self.media_keys.send.clone())
+            .encrypt_frame(AUDIO_STREAM_ID, data)?;
+        track
+            .write_sample(&Sample {
+                data: encrypted.into(),
+                duration,
+                ..Default::default()
+            })
+            .await
+            .context("Failed to write audio sample")?;
+        Ok(())
+    }
+
+    /// Register `handler` for audio frames from the remote participant.
+    /// Each RTP payload on an incoming audio track is decrypted under
+    /// the connection's `receive` key and passed on; payloads that fail
+    /// to decrypt are dropped. Replaces any earlier registration.
+    pub fn on_audio_sample(&self, handler: AudioSampleHandler) {
+        let media_key = self.media_keys.receive.clone();
+        self.webrtc_pc
+            .on_track(Box::new(move |track: Arc<TrackRemote>, _, _| {
+                let handler = handler.clone();
+                let media_key = media_key.clone();
+                Box::pin(async move {
+                    if track.kind() != RTPCodecType::Audio {
+                        return;
+                    }
+                    tokio::spawn(async move {
+                        let decryptor = MediaEncryption::new(media_key);
+                        while let Ok((packet, _)) = track.read_rtp().await {
+                            if let Ok(frame) =
+                                decryptor.decrypt_frame(AUDIO_STREAM_ID, &packet.payload)
+                            {
+                                handler(frame);
+                            }
+                        }
+                    });
+                })
+            }));
+    }
+
+    /// Open the data channel for in-call text and control messages and
+    /// return the receiver for what the peer sends on it.
+    ///
+    /// The channel is pre-negotiated on [`DATA_CHANNEL_ID`] rather than
+    /// announced in-band, so both sides call this, before the
+    /// offer/answer exchange, and neither has to wait for an
+    /// `on_data_channel` callback. Every message is encrypted under the
+    /// connection's media keys (their own derived stream key), so as
+    /// with media the SFU/TURN path only sees ciphertext; inbound
+    /// messages that fail to decrypt are dropped.
+    pub async fn open_data_channel(&self, label: &str) -> Result<mpsc::Receiver<Vec<u8>>> {
+        let mut slot = self.data_channel.lock().await;
+        if slot.is_some() {
+            return Err(anyhow::anyhow!("Data channel is already open"));
+        }
+        let channel = self
+            .webrtc_pc
+            .create_data_channel(
+                label,
+                Some(RTCDataChannelInit {
+                    negotiated: Some(DATA_CHANNEL_ID),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .context("Failed to create data channel")?;
+
+        let (tx, rx) = mpsc::channel(DATA_CHANNEL_BUFFER);
+        let media_key = self.media_keys.receive.clone();
+        channel.on_message(Box::new(move |msg: DataChannelMessage| {
+            let tx = tx.clone();
+            let decrypted =
+                MediaEncryption::new(media_key.clone()).decrypt_frame(DATA_STREAM_ID, &msg.data);
+            Box::pin(async move {
+                if let Ok(data) = decrypted {
+                    // The receiver being gone just means nobody reads
+                    // in-call messages any more.
+                    let _ = tx.send(data).await;
+                }
+            })
+        }));
+        *slot = Some(channel);
+        Ok(rx)
+    }
+
+    /// Encrypt `data` and send it on the data channel. Fails if the
+    /// channel hasn't been opened or isn't connected yet.
+    pub async fn send_data(&self, data: &[u8]) -> Result<()> {
+        let channel = self
+            .data_channel
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Data channel is not open on this connection"))?;
+        let encrypted = MediaEncryption::new(self.media_keys.send.clone())
+            .encrypt_frame(DATA_STREAM_ID, data)?;
+        channel
+            .send(&encrypted.into())
+            .await
+            .context("Failed to send on data channel")?;
+        Ok(())
+    }
+
     /// Begin screen capture on this peer connection. In this simple
     /// implementation we map screen sharing to enabling the video
     /// track. A more complete implementation would create a second
@@ -377,25 +555,17 @@ impl PeerConnection {
     /// * Inbound counts come from `InboundRTP` entries.
     /// * `packets_lost` is reported by the *remote* end via RTCP and
     ///   surfaces as `RemoteInboundRTP`.
-    /// * RTT and the live bandwidth estimate come from the nominated
-    ///   `CandidatePair`.
+    /// * RTT comes from the nominated `CandidatePair`.
+    /// * `bitrate` is the outgoing rate since the previous call, from
+    ///   the change in `bytes_sent`. The first call has nothing to
+    ///   compare against and reports the candidate pair's bandwidth
+    ///   estimate instead.
     ///
     /// `jitter`, `frame_rate` and `resolution` aren't produced by
     /// webrtc-rs 0.14 (jitter buffer / decoder values are out of scope
     /// since the crate doesn't decode), so they remain at their default
     /// zero/None values.
     pub async fn get_stats(&self) -> Result<MediaStats> {
-        // Fetch the internal WebRTC stats report. This returns a map of
-        // statistics for each transport and track. Summarising these into
-        // a high‑level MediaStats struct requires parsing the report.
-        let _report = self
-            .webrtc_pc
-            .get_stats()
-            .await
-            .context("Failed to retrieve WebRTC stats")?;
-        // TODO: Parse stats report into our MediaStats struct. This is
-        // left as an exercise because the report structure is complex.
-        // For now we return zeroed metrics.
         let report = self.webrtc_pc.get_stats().await;
 
         let mut bytes_sent: u64 = 0;
@@ -438,6 +608,16 @@ impl PeerConnection {
             }
         }
 
+        let now = Instant::now();
+        let mut last = self.last_stats.lock().await;
+        if let Some((at, prev_sent)) = *last {
+            let secs = now.duration_since(at).as_secs_f64();
+            if secs > 0.0 {
+                bitrate = (bytes_sent.saturating_sub(prev_sent) as f64 * 8.0 / secs) as u32;
+            }
+        }
+        *last = Some((now, bytes_sent));
+
         Ok(MediaStats {
             bytes_sent,
             bytes_received,
@@ -469,6 +649,37 @@ impl PeerConnection {
         Ok(())
     }
 
+    /// Tell the transport the remote peer has finished gathering, so ICE
+    /// can conclude once the candidates it has are checked instead of
+    /// waiting for more. An empty candidate is the end-of-candidates
+    /// marker webrtc-rs understands.
+    pub async fn end_of_candidates(&self) -> Result<()> {
+        let init = RTCIceCandidateInit {
+            candidate: String::new(),
+            sdp_mid: None,
+            sdp_mline_index: None,
+            username_fragment: None,
+        };
+        self.webrtc_pc
+            .add_ice_candidate(init)
+            .await
+            .context("Failed to signal end of ICE candidates")?;
+        self.remote_gathering_complete.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Whether [`end_of_candidates`](Self::end_of_candidates) has been
+    /// applied to this connection.
+    pub fn remote_gathering_complete(&self) -> bool {
+        self.remote_gathering_complete.load(Ordering::SeqCst)
+    }
+
+    /// Whether a remote description has been installed. Remote
+    /// candidates can't be applied before then.
+    pub async fn has_remote_description(&self) -> bool {
+        self.webrtc_pc.remote_description().await.is_some()
+    }
+
     /// Create an SDP offer and install it as the local description so
     /// ICE gathering can begin. Returns the SDP string the caller is
     /// expected to forward to the remote peer over the signalling
@@ -533,3 +744,130 @@ impl PeerConnection {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calling::media_encryption::MediaKey;
+
+    /// A connection keyed with `send`/`receive`; a loopback pair uses
+    /// mirrored keys, as the two ends of a call derive them.
+    async fn connection_keyed(send: u8, receive: u8) -> PeerConnection {
+        let config = WebRTCConfig {
+            stun_servers: Vec::new(),
+            turn_servers: Vec::new(),
+            enable_dtls: true,
+            enable_srtp: true,
+        };
+        PeerConnection::new(
+            config,
+            MediaKeyPair {
+                send: MediaKey::new([send; 32]),
+                receive: MediaKey::new([receive; 32]),
+            },
+            CallId::from([1u8; 16]),
+            IdentityId::from([2u8; 32]),
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn connection() -> PeerConnection {
+        connection_keyed(7, 8).await
+    }
+
+    #[tokio::test]
+    async fn audio_sample_reaches_track_once_audio_is_enabled() {
+        let pc = connection().await;
+        // Opus TOC byte for a 20 ms CELT frame, then filler.
+        let mut frame = vec![0xFCu8];
+        frame.extend_from_slice(&[0u8; 59]);
+
+        assert!(pc
+            .push_audio_sample(&frame, Duration::from_millis(20))
+            .await
+            .is_err());
+
+        pc.set_audio_enabled(true).await.unwrap();
+        pc.push_audio_sample(&frame, Duration::from_millis(20))
+            .await
+            .unwrap();
+    }
+
+    /// Run `make_description` and wait for ICE gathering, so the SDP
+    /// carries every local candidate and no trickle is needed.
+    async fn gathered_sdp<F, Fut>(pc: &PeerConnection, make_description: F) -> String
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<String>>,
+    {
+        let mut gathered = pc.webrtc_pc.gathering_complete_promise().await;
+        make_description().await.unwrap();
+        let _ = gathered.recv().await;
+        pc.webrtc_pc.local_description().await.unwrap().sdp
+    }
+
+    #[tokio::test]
+    async fn loopback_pair_reports_sent_packets() {
+        let caller = connection_keyed(7, 8).await;
+        let callee = connection_keyed(8, 7).await;
+        caller.set_audio_enabled(true).await.unwrap();
+
+        let offer = gathered_sdp(&caller, || caller.create_offer()).await;
+        let answer = gathered_sdp(&callee, || callee.create_answer(&offer)).await;
+        caller.set_remote_description(&answer).await.unwrap();
+
+        let connected = async {
+            while caller.state() != PeerConnectionState::Connected {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), connected)
+            .await
+            .expect("loopback pair did not connect");
+
+        caller.get_stats().await.unwrap();
+        for _ in 0..50 {
+            caller
+                .push_audio_sample(&[0xFC; 60], Duration::from_millis(20))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let stats = caller.get_stats().await.unwrap();
+        assert!(stats.packets_sent > 0);
+        assert!(stats.bytes_sent > 0);
+        assert!(stats.bitrate > 0);
+    }
+
+    #[tokio::test]
+    async fn data_channel_message_arrives_decrypted() {
+        let caller = connection_keyed(7, 8).await;
+        let callee = connection_keyed(8, 7).await;
+        let _caller_rx = caller.open_data_channel("chat").await.unwrap();
+        let mut callee_rx = callee.open_data_channel("chat").await.unwrap();
+        assert!(caller.open_data_channel("chat").await.is_err());
+
+        let offer = gathered_sdp(&caller, || caller.create_offer()).await;
+        let answer = gathered_sdp(&callee, || callee.create_answer(&offer)).await;
+        caller.set_remote_description(&answer).await.unwrap();
+
+        // The channel opens a little after the connection does; keep
+        // sending until one goes through.
+        let message = b"are you still there?";
+        let delivered = async {
+            loop {
+                let _ = caller.send_data(message).await;
+                tokio::select! {
+                    Some(data) = callee_rx.recv() => break data,
+                    _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+                }
+            }
+        };
+        let data = tokio::time::timeout(Duration::from_secs(10), delivered)
+            .await
+            .expect("data channel message did not arrive");
+        assert_eq!(data, message);
+    }
+}
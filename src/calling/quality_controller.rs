@@ -0,0 +1,175 @@
+//! Adaptive bitrate: turns a stream of [`ConnectionQuality`] samples
+//! into bandwidth limits.
+//!
+//! The controller is a plain state machine with no I/O, so the policy
+//! can be tested on synthetic samples. [`CallManager`] owns one per
+//! peer connection, feeds it every quality sample, and applies each
+//! [`BitrateStep`] through the media backend's `set_bandwidth_limit`.
+//!
+//! Backing off is immediate: one bad sample cuts the limit. Recovery is
+//! slow on purpose — the limit only rises after several good samples
+//! in a row, and by a smaller factor — so a link hovering around the
+//! threshold doesn't oscillate.
+//!
+//! [`CallManager`]: crate::calling::call_manager::CallManager
+
+use crate::calling::call_manager::ConnectionQuality;
+
+/// Thresholds and step sizes for a [`QualityController`].
+#[derive(Clone, Debug)]
+pub struct QualityControllerConfig {
+    /// Starting and highest limit, in kbps.
+    pub max_kbps: u32,
+    /// The limit never drops below this, in kbps.
+    pub min_kbps: u32,
+    /// Step down when packet loss (percent) exceeds this...
+    pub loss_threshold: f32,
+    /// ...or when the quality score falls below this.
+    pub min_quality_score: u8,
+    /// Consecutive good samples needed before stepping back up.
+    pub recovery_samples: u32,
+    /// Each step down sets the limit to this percentage of the old one.
+    pub decrease_percent: u32,
+    /// Each step up sets the limit to this percentage of the old one.
+    pub increase_percent: u32,
+}
+
+impl Default for QualityControllerConfig {
+    fn default() -> Self {
+        QualityControllerConfig {
+            max_kbps: 2_500,
+            min_kbps: 64,
+            loss_threshold: 5.0,
+            min_quality_score: 3,
+            recovery_samples: 3,
+            decrease_percent: 70,
+            increase_percent: 115,
+        }
+    }
+}
+
+/// A change of bandwidth limit decided by the controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitrateStep {
+    /// Conditions degraded; lower the limit to this many kbps.
+    Down(u32),
+    /// Conditions recovered; raise the limit to this many kbps.
+    Up(u32),
+}
+
+impl BitrateStep {
+    pub fn limit_kbps(self) -> u32 {
+        match self {
+            BitrateStep::Down(kbps) | BitrateStep::Up(kbps) => kbps,
+        }
+    }
+}
+
+/// Bitrate controller for one peer connection.
+pub struct QualityController {
+    config: QualityControllerConfig,
+    limit_kbps: u32,
+    good_samples: u32,
+}
+
+impl QualityController {
+    pub fn new(config: QualityControllerConfig) -> Self {
+        QualityController {
+            limit_kbps: config.max_kbps,
+            good_samples: 0,
+            config,
+        }
+    }
+
+    /// Current bandwidth limit, in kbps.
+    pub fn limit_kbps(&self) -> u32 {
+        self.limit_kbps
+    }
+
+    /// Feed one quality sample. Returns the new limit if it changed;
+    /// `None` while conditions are steady, or when already at the
+    /// floor or ceiling.
+    pub fn observe(&mut self, quality: &ConnectionQuality) -> Option<BitrateStep> {
+        let degraded = quality.packet_loss > self.config.loss_threshold
+            || quality.quality_score < self.config.min_quality_score;
+
+        if degraded {
+            self.good_samples = 0;
+            let lowered =
+                (self.limit_kbps * self.config.decrease_percent / 100).max(self.config.min_kbps);
+            return self.step_to(lowered).then_some(BitrateStep::Down(lowered));
+        }
+
+        // Only clearly good samples count towards recovery; a sample
+        // just under the threshold holds the limit where it is.
+        let good = quality.packet_loss < self.config.loss_threshold / 2.0
+            && quality.quality_score > self.config.min_quality_score;
+        if !good {
+            self.good_samples = 0;
+            return None;
+        }
+        self.good_samples += 1;
+        if self.good_samples < self.config.recovery_samples {
+            return None;
+        }
+        self.good_samples = 0;
+        let raised =
+            (self.limit_kbps * self.config.increase_percent / 100).min(self.config.max_kbps);
+        self.step_to(raised).then_some(BitrateStep::Up(raised))
+    }
+
+    fn step_to(&mut self, kbps: u32) -> bool {
+        let changed = kbps != self.limit_kbps;
+        self.limit_kbps = kbps;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(packet_loss: f32, quality_score: u8) -> ConnectionQuality {
+        ConnectionQuality {
+            packet_loss,
+            quality_score,
+            ..ConnectionQuality::default()
+        }
+    }
+
+    #[test]
+    fn recovers_only_after_consecutive_good_samples() {
+        let mut controller = QualityController::new(QualityControllerConfig::default());
+        assert_eq!(
+            controller.observe(&sample(12.0, 1)),
+            Some(BitrateStep::Down(1_750))
+        );
+
+        // Good, good, borderline: the streak resets, no step up yet.
+        assert_eq!(controller.observe(&sample(0.0, 5)), None);
+        assert_eq!(controller.observe(&sample(0.0, 5)), None);
+        assert_eq!(controller.observe(&sample(4.0, 3)), None);
+        assert_eq!(controller.limit_kbps(), 1_750);
+
+        for _ in 0..2 {
+            assert_eq!(controller.observe(&sample(0.0, 5)), None);
+        }
+        assert_eq!(
+            controller.observe(&sample(0.0, 5)),
+            Some(BitrateStep::Up(2_012))
+        );
+
+        // Capped at the configured maximum, then steady.
+        for _ in 0..3 {
+            controller.observe(&sample(0.0, 5));
+        }
+        assert_eq!(controller.limit_kbps(), 2_313);
+        for _ in 0..3 {
+            controller.observe(&sample(0.0, 5));
+        }
+        assert_eq!(controller.limit_kbps(), 2_500);
+        for _ in 0..3 {
+            assert_eq!(controller.observe(&sample(0.0, 5)), None);
+        }
+    }
+}
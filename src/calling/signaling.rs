@@ -5,16 +5,55 @@
 //! network I/O itself; instead it routes messages through an in‑memory
 //! server so that unit tests and local peer connections can be
 //! exercised without external dependencies.
+//!
+//! Every message travels as a [`SignedSignalingMessage`]: signed by the
+//! identity it claims to come from, for the one recipient it's sent to,
+//! and checked before delivery, so a peer can't forge a call invitation
+//! from someone else or replay one meant for another participant. The
+//! signature covers [`canonical_signaling_message`].
+//!
+//! SDP and ICE candidates reveal the participants' IP addresses and
+//! media setup. [`SignalingMessage::seal_for`] wraps a message for one
+//! recipient device so whoever relays it only sees
+//! [`SignalingMessage::Sealed`]; the key comes from the same hybrid
+//! X25519 + ML-KEM-768 construction as device sync:
+//!
+//! ```text
+//! key  = BLAKE3-derive("qubee signaling seal v1",
+//!                      x25519_ss || kem_ss || ephemeral_pub || kem_ct)
+//! body = ChaCha20-Poly1305(key, nonce, bincode(message),
+//!                          aad = call_id || sender || recipient_device)
+//! ```
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
+use zeroize::Zeroize;
 
-use crate::calling::call_manager::{CallId, CallSettings, CallType};
+use crate::calling::call_manager::{AudioQuality, CallId, CallSettings, CallType, VideoQuality};
 use crate::calling::peer_connection::ICECandidate;
-use crate::identity::identity_key::IdentityId;
+use crate::identity::identity_key::{
+    DeviceId, DeviceKey, DevicePublicKey, HybridSignature, IdentityId, IdentityKey, IdentityKeyPair,
+};
+use crate::security::secure_rng;
+
+// Per-variant domain tags for the signed canonical bytes. `_v2`: v1
+// signed a bincode dump of the message and didn't cover the recipient.
+const CALL_INVITATION_TAG: &[u8] = b"qubee_signaling_call_invitation_v2";
+const SDP_OFFER_TAG: &[u8] = b"qubee_signaling_sdp_offer_v2";
+const SDP_ANSWER_TAG: &[u8] = b"qubee_signaling_sdp_answer_v2";
+const ICE_CANDIDATE_TAG: &[u8] = b"qubee_signaling_ice_candidate_v2";
+const END_OF_CANDIDATES_TAG: &[u8] = b"qubee_signaling_end_of_candidates_v2";
+const HANG_UP_TAG: &[u8] = b"qubee_signaling_hang_up_v2";
+const SEALED_TAG: &[u8] = b"qubee_signaling_sealed_v2";
+/// Key derivation context for sealed signaling messages.
+const SIGNALING_SEAL_TAG: &str = "qubee signaling seal v1";
 
 /// High‑level signaling message exchanged between peers. Each
 /// variant carries the metadata necessary for the recipient to act
@@ -53,9 +92,360 @@ pub enum SignalingMessage {
         candidate: ICECandidate,
         sender: IdentityId,
     },
+    /// The sender has finished gathering ICE candidates; no more
+    /// `IceCandidate` messages will follow for this call.
+    EndOfCandidates { call_id: CallId, sender: IdentityId },
     /// Signal that a participant has hung up. The recipient should
     /// close its local peer connection and mark the call as ended.
     HangUp { call_id: CallId, sender: IdentityId },
+    /// Another message encrypted to one device of the recipient; see
+    /// [`SignalingMessage::seal_for`]. Only the call, the sender and the
+    /// target device are visible.
+    Sealed {
+        call_id: CallId,
+        sender: IdentityId,
+        recipient_device: DeviceId,
+        ephemeral_public: [u8; 32],
+        kem_ciphertext: Vec<u8>,
+        nonce: [u8; 12],
+        ciphertext: Vec<u8>,
+    },
+}
+
+/// A [`SignalingMessage`] signed by its sender for one recipient.
+/// `sender_key` travels with the message; since an [`IdentityId`] is
+/// derived from the public keys, checking that the key hashes to the
+/// claimed sender and that the signature verifies under it proves the
+/// message came from that identity without the recipient knowing the
+/// key beforehand. The signature also covers `recipient`, so a message
+/// can't be replayed to another participant of the same call.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SignedSignalingMessage {
+    pub message: SignalingMessage,
+    pub recipient: IdentityId,
+    pub sender_key: IdentityKey,
+    pub signature: HybridSignature,
+}
+
+impl SignalingMessage {
+    /// The identity this message claims to come from: the caller of an
+    /// invitation, the sender of everything else.
+    pub fn sender(&self) -> IdentityId {
+        match self {
+            SignalingMessage::CallInvitation { caller, .. } => *caller,
+            SignalingMessage::SdpOffer { sender, .. }
+            | SignalingMessage::SdpAnswer { sender, .. }
+            | SignalingMessage::IceCandidate { sender, .. }
+            | SignalingMessage::EndOfCandidates { sender, .. }
+            | SignalingMessage::HangUp { sender, .. }
+            | SignalingMessage::Sealed { sender, .. } => *sender,
+        }
+    }
+
+    /// The call this message belongs to.
+    pub fn call_id(&self) -> CallId {
+        match self {
+            SignalingMessage::CallInvitation { call_id, .. }
+            | SignalingMessage::SdpOffer { call_id, .. }
+            | SignalingMessage::SdpAnswer { call_id, .. }
+            | SignalingMessage::IceCandidate { call_id, .. }
+            | SignalingMessage::EndOfCandidates { call_id, .. }
+            | SignalingMessage::HangUp { call_id, .. }
+            | SignalingMessage::Sealed { call_id, .. } => *call_id,
+        }
+    }
+
+    /// Encrypt this message to `device`, so the signaling server (or
+    /// anyone else relaying it) sees only a [`Sealed`](Self::Sealed)
+    /// message. Sign the result as usual.
+    pub fn seal_for(&self, device: &DevicePublicKey) -> Result<SignalingMessage> {
+        use pqcrypto_traits::kem::{Ciphertext as _, SharedSecret as _};
+
+        if matches!(self, SignalingMessage::Sealed { .. }) {
+            bail!("Signaling message is already sealed");
+        }
+        let call_id = self.call_id();
+        let sender = self.sender();
+        let plaintext = self.to_bytes().context("encode signaling message")?;
+
+        let ephemeral = x25519_dalek::StaticSecret::from(secure_rng::random::array::<32>()?);
+        let ephemeral_public = x25519_dalek::PublicKey::from(&ephemeral).to_bytes();
+        let mut x25519_ss = ephemeral.diffie_hellman(&device.x25519_public).to_bytes();
+        let (kem_ss, kem_ct) = pqcrypto_mlkem::mlkem768::encapsulate(&device.kyber_public);
+        let mut kem_ss_bytes = [0u8; 32];
+        kem_ss_bytes.copy_from_slice(&kem_ss.as_bytes()[..32]);
+        let kem_ciphertext = kem_ct.as_bytes().to_vec();
+        let mut key = derive_seal_key(
+            &x25519_ss,
+            &kem_ss_bytes,
+            &ephemeral_public,
+            &kem_ciphertext,
+        );
+        x25519_ss.zeroize();
+        kem_ss_bytes.zeroize();
+
+        let nonce = secure_rng::random::array::<12>()?;
+        let sealed = ChaCha20Poly1305::new((&key).into()).encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &seal_aad(&call_id, &sender, &device.device_id),
+            },
+        );
+        key.zeroize();
+        let ciphertext = sealed.map_err(|_| anyhow!("Signaling message encryption failed"))?;
+
+        Ok(SignalingMessage::Sealed {
+            call_id,
+            sender,
+            recipient_device: device.device_id,
+            ephemeral_public,
+            kem_ciphertext,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Decrypt a [`Sealed`](Self::Sealed) message addressed to `device`.
+    /// Other messages come back unchanged. The inner message must name
+    /// the same call and sender as the envelope, so a verified sender
+    /// can't smuggle in a message attributed to someone else.
+    pub fn open(&self, device: &DeviceKey) -> Result<SignalingMessage> {
+        let SignalingMessage::Sealed {
+            call_id,
+            sender,
+            recipient_device,
+            ephemeral_public,
+            kem_ciphertext,
+            nonce,
+            ciphertext,
+        } = self
+        else {
+            return Ok(self.clone());
+        };
+        if *recipient_device != device.device_id() {
+            bail!("Sealed signaling message is for another device");
+        }
+
+        let ephemeral = x25519_dalek::PublicKey::from(*ephemeral_public);
+        let mut x25519_ss = device.x25519_agree(&ephemeral);
+        let mut kem_ss = device.kyber_decapsulate(kem_ciphertext)?;
+        let mut key = derive_seal_key(&x25519_ss, &kem_ss, ephemeral_public, kem_ciphertext);
+        x25519_ss.zeroize();
+        kem_ss.zeroize();
+        let opened = ChaCha20Poly1305::new((&key).into()).decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &seal_aad(call_id, sender, recipient_device),
+            },
+        );
+        key.zeroize();
+        let plaintext =
+            opened.map_err(|_| anyhow!("Sealed signaling message failed to decrypt"))?;
+
+        let inner = SignalingMessage::from_bytes(&plaintext).context("decode sealed message")?;
+        if matches!(inner, SignalingMessage::Sealed { .. })
+            || inner.call_id() != *call_id
+            || inner.sender() != *sender
+        {
+            bail!("Sealed signaling message doesn't match its envelope");
+        }
+        Ok(inner)
+    }
+
+    /// Sign the message as `identity` for `recipient`. `identity` must
+    /// be the identity the message claims as its sender.
+    pub fn sign(
+        self,
+        identity: &IdentityKeyPair,
+        recipient: IdentityId,
+    ) -> Result<SignedSignalingMessage> {
+        if identity.identity_id() != self.sender() {
+            bail!("Signaling message claims a different sender than the signing identity");
+        }
+        let signature = identity.sign(&canonical_signaling_message(&self, &recipient))?;
+        Ok(SignedSignalingMessage {
+            message: self,
+            recipient,
+            sender_key: identity.public_key(),
+            signature,
+        })
+    }
+}
+
+impl SignedSignalingMessage {
+    /// Verify the message and, if it is sealed, decrypt it for `device`.
+    /// What the client hands to `set_remote_description` /
+    /// `add_ice_candidate`.
+    pub fn open(&self, device: &DeviceKey) -> Result<SignalingMessage> {
+        self.verify()?;
+        self.message.open(device)
+    }
+
+    /// Fail unless `sender_key` belongs to the message's claimed sender
+    /// and the signature over the message verifies under it.
+    pub fn verify(&self) -> Result<()> {
+        if !self.sender_key.has_consistent_identity_id()
+            || self.sender_key.identity_id != self.message.sender()
+        {
+            bail!("Signaling message signed by someone other than its claimed sender");
+        }
+        let data = canonical_signaling_message(&self.message, &self.recipient);
+        if !self.sender_key.verify(&data, &self.signature)? {
+            bail!("Signaling message signature is invalid or expired");
+        }
+        Ok(())
+    }
+}
+
+fn derive_seal_key(
+    x25519_ss: &[u8; 32],
+    kem_ss: &[u8; 32],
+    ephemeral_public: &[u8; 32],
+    kem_ciphertext: &[u8],
+) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key(SIGNALING_SEAL_TAG);
+    hasher.update(x25519_ss);
+    hasher.update(kem_ss);
+    hasher.update(ephemeral_public);
+    hasher.update(kem_ciphertext);
+    *hasher.finalize().as_bytes()
+}
+
+fn seal_aad(call_id: &CallId, sender: &IdentityId, device: &DeviceId) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(16 + 32 + 16);
+    aad.extend_from_slice(call_id.as_ref());
+    aad.extend_from_slice(sender.as_ref());
+    aad.extend_from_slice(device.as_ref());
+    aad
+}
+
+/// Canonical bytes a [`SignedSignalingMessage`] signature covers: the
+/// variant's tag, then the recipient, call and sender, then the
+/// variant's own fields. Built by hand so signatures don't depend on
+/// bincode's layout of the enum.
+pub fn canonical_signaling_message(message: &SignalingMessage, recipient: &IdentityId) -> Vec<u8> {
+    let tag = match message {
+        SignalingMessage::CallInvitation { .. } => CALL_INVITATION_TAG,
+        SignalingMessage::SdpOffer { .. } => SDP_OFFER_TAG,
+        SignalingMessage::SdpAnswer { .. } => SDP_ANSWER_TAG,
+        SignalingMessage::IceCandidate { .. } => ICE_CANDIDATE_TAG,
+        SignalingMessage::EndOfCandidates { .. } => END_OF_CANDIDATES_TAG,
+        SignalingMessage::HangUp { .. } => HANG_UP_TAG,
+        SignalingMessage::Sealed { .. } => SEALED_TAG,
+    };
+    let mut out = Vec::with_capacity(128);
+    out.extend_from_slice(tag);
+    out.push(0u8);
+    out.extend_from_slice(recipient.as_ref());
+    out.push(0u8);
+    out.extend_from_slice(message.call_id().as_ref());
+    out.push(0u8);
+    out.extend_from_slice(message.sender().as_ref());
+    match message {
+        SignalingMessage::CallInvitation {
+            call_type,
+            settings,
+            ..
+        } => {
+            out.push(0u8);
+            out.push(call_type_byte(call_type));
+            out.push(0u8);
+            push_call_settings(&mut out, settings);
+        }
+        SignalingMessage::SdpOffer { sdp, .. } | SignalingMessage::SdpAnswer { sdp, .. } => {
+            out.push(0u8);
+            push_len_prefixed(&mut out, sdp.as_bytes());
+        }
+        SignalingMessage::IceCandidate { candidate, .. } => {
+            out.push(0u8);
+            push_len_prefixed(&mut out, candidate.sdp_mid.as_bytes());
+            out.push(0u8);
+            out.extend_from_slice(&candidate.sdp_mline_index.to_le_bytes());
+            out.push(0u8);
+            push_len_prefixed(&mut out, candidate.candidate.as_bytes());
+        }
+        SignalingMessage::EndOfCandidates { .. } | SignalingMessage::HangUp { .. } => {}
+        SignalingMessage::Sealed {
+            recipient_device,
+            ephemeral_public,
+            kem_ciphertext,
+            nonce,
+            ciphertext,
+            ..
+        } => {
+            out.push(0u8);
+            out.extend_from_slice(recipient_device.as_ref());
+            out.push(0u8);
+            out.extend_from_slice(ephemeral_public);
+            out.push(0u8);
+            push_len_prefixed(&mut out, kem_ciphertext);
+            out.push(0u8);
+            out.extend_from_slice(nonce);
+            out.push(0u8);
+            push_len_prefixed(&mut out, ciphertext);
+        }
+    }
+    out
+}
+
+fn push_len_prefixed(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn call_type_byte(call_type: &CallType) -> u8 {
+    match call_type {
+        CallType::VoiceCall => 0,
+        CallType::VideoCall => 1,
+        CallType::GroupVoiceCall => 2,
+        CallType::GroupVideoCall => 3,
+        CallType::ScreenShare => 4,
+        CallType::Conference => 5,
+    }
+}
+
+/// `max_participants` and `bandwidth_limit` are a presence byte and
+/// then the value (u64 / u32 LE) if present; the flags are one byte
+/// each; the qualities are one byte in declaration order.
+fn push_call_settings(out: &mut Vec<u8>, settings: &CallSettings) {
+    match settings.max_participants {
+        Some(max) => {
+            out.push(1);
+            out.extend_from_slice(&(max as u64).to_le_bytes());
+        }
+        None => out.push(0),
+    }
+    out.extend_from_slice(&[
+        settings.require_encryption.into(),
+        settings.allow_recording.into(),
+        settings.auto_mute_on_join.into(),
+        settings.enable_noise_cancellation.into(),
+        settings.enable_echo_cancellation.into(),
+    ]);
+    out.push(match settings.video_quality {
+        VideoQuality::Low => 0,
+        VideoQuality::Medium => 1,
+        VideoQuality::High => 2,
+        VideoQuality::HD => 3,
+        VideoQuality::UHD => 4,
+        VideoQuality::Auto => 5,
+    });
+    out.push(match settings.audio_quality {
+        AudioQuality::Low => 0,
+        AudioQuality::Medium => 1,
+        AudioQuality::High => 2,
+        AudioQuality::Studio => 3,
+        AudioQuality::Auto => 4,
+    });
+    match settings.bandwidth_limit {
+        Some(limit) => {
+            out.push(1);
+            out.extend_from_slice(&limit.to_le_bytes());
+        }
+        None => out.push(0),
+    }
 }
 
 /// In‑memory signaling server that routes messages between
@@ -65,7 +455,7 @@ pub enum SignalingMessage {
 /// identities. This avoids requiring an external signalling service
 /// for tests and local operation.
 pub struct SignalingServer {
-    clients: Arc<RwLock<HashMap<IdentityId, mpsc::UnboundedSender<SignalingMessage>>>>,
+    clients: Arc<RwLock<HashMap<IdentityId, mpsc::UnboundedSender<SignedSignalingMessage>>>>,
 }
 
 impl SignalingServer {
@@ -96,13 +486,21 @@ impl SignalingServer {
         }
     }
 
-    /// Send a signaling message to the specified recipient. If the
-    /// recipient is not currently registered, an error is returned.
+    /// Send a signaling message to the specified recipient. Messages
+    /// that don't [`verify`](SignedSignalingMessage::verify), or were
+    /// signed for someone else, are refused rather than delivered. If
+    /// the recipient is not currently registered, an error is returned.
     pub async fn send_message(
         &self,
         recipient: IdentityId,
-        message: SignalingMessage,
+        message: SignedSignalingMessage,
     ) -> Result<()> {
+        message
+            .verify()
+            .context("Refusing unauthenticated signaling message")?;
+        if message.recipient != recipient {
+            bail!("Signaling message was signed for a different recipient");
+        }
         let clients = self.clients.read().await;
         if let Some(tx) = clients.get(&recipient) {
             tx.send(message)
@@ -131,20 +529,32 @@ impl Clone for SignalingServer {
 pub struct SignalingClient {
     identity: IdentityId,
     server: Arc<SignalingServer>,
-    receiver: mpsc::UnboundedReceiver<SignalingMessage>,
+    receiver: mpsc::UnboundedReceiver<SignedSignalingMessage>,
 }
 
 impl SignalingClient {
     /// Receive the next signaling message destined for this client.
-    /// Returns `None` if the channel has been closed.
-    pub async fn recv(&mut self) -> Option<SignalingMessage> {
-        self.receiver.recv().await
+    /// Returns `None` if the channel has been closed. The server has
+    /// already checked the signature; it is checked again here so a
+    /// client never acts on a message it can't authenticate itself, or
+    /// one signed for somebody else, and any that fail are dropped.
+    pub async fn recv(&mut self) -> Option<SignedSignalingMessage> {
+        loop {
+            let message = self.receiver.recv().await?;
+            if message.recipient == self.identity && message.verify().is_ok() {
+                return Some(message);
+            }
+        }
     }
 
     /// Send a signaling message to another participant via the
     /// associated server. This is simply a convenience wrapper around
     /// [`SignalingServer::send_message`].
-    pub async fn send_to(&self, recipient: IdentityId, message: SignalingMessage) -> Result<()> {
+    pub async fn send_to(
+        &self,
+        recipient: IdentityId,
+        message: SignedSignalingMessage,
+    ) -> Result<()> {
         self.server.send_message(recipient, message).await
     }
 }
@@ -164,3 +574,127 @@ impl SignalingMessage {
         bincode::deserialize(bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invitation(caller: IdentityId) -> SignalingMessage {
+        SignalingMessage::CallInvitation {
+            call_id: CallId::from([7u8; 16]),
+            caller,
+            call_type: CallType::VoiceCall,
+            settings: CallSettings::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn signed_invitation_is_delivered() {
+        let server = SignalingServer::new().await.unwrap();
+        let caller = IdentityKeyPair::generate().unwrap();
+        let callee = IdentityKeyPair::generate().unwrap();
+        let mut client = server.register_client(callee.identity_id()).await;
+
+        let signed = invitation(caller.identity_id())
+            .sign(&caller, callee.identity_id())
+            .unwrap();
+        server
+            .send_message(callee.identity_id(), signed)
+            .await
+            .unwrap();
+
+        let received = client.recv().await.unwrap();
+        assert!(received.message.sender() == caller.identity_id());
+        assert!(received.sender_key == caller.public_key());
+    }
+
+    #[tokio::test]
+    async fn sealed_sdp_is_opaque_to_the_server() {
+        let server = SignalingServer::new().await.unwrap();
+        let caller = IdentityKeyPair::generate().unwrap();
+        let callee = IdentityKeyPair::generate().unwrap();
+        let callee_device = callee.derive_device_key(b"phone").unwrap();
+        let other_device = callee.derive_device_key(b"tablet").unwrap();
+        let mut client = server.register_client(callee.identity_id()).await;
+
+        let sdp = "v=0\r\no=- 42 2 IN IP4 192.0.2.7\r\nc=IN IP4 192.0.2.7\r\n".to_string();
+        let offer = SignalingMessage::SdpOffer {
+            call_id: CallId::from([7u8; 16]),
+            sdp: sdp.clone(),
+            sender: caller.identity_id(),
+        };
+        let sealed = offer.seal_for(&callee_device.public_key()).unwrap();
+        server
+            .send_message(
+                callee.identity_id(),
+                sealed.sign(&caller, callee.identity_id()).unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // What the server relayed carries no trace of the SDP.
+        let received = client.recv().await.unwrap();
+        assert!(matches!(received.message, SignalingMessage::Sealed { .. }));
+        let relayed = received.message.to_bytes().unwrap();
+        assert!(!relayed.windows(9).any(|w| w == b"192.0.2.7"));
+
+        match received.open(&callee_device).unwrap() {
+            SignalingMessage::SdpOffer { sdp: opened, .. } => assert_eq!(opened, sdp),
+            _ => panic!("expected the original SDP offer"),
+        }
+        assert!(received.open(&other_device).is_err());
+    }
+
+    #[tokio::test]
+    async fn forged_invitation_is_refused() {
+        let server = SignalingServer::new().await.unwrap();
+        let caller = IdentityKeyPair::generate().unwrap();
+        let forger = IdentityKeyPair::generate().unwrap();
+        let callee = IdentityKeyPair::generate().unwrap();
+        let _client = server.register_client(callee.identity_id()).await;
+
+        // Can't sign in someone else's name directly...
+        assert!(invitation(caller.identity_id())
+            .sign(&forger, callee.identity_id())
+            .is_err());
+
+        // ...nor by swapping in a message or a key after signing.
+        let mut swapped = invitation(forger.identity_id())
+            .sign(&forger, callee.identity_id())
+            .unwrap();
+        swapped.message = invitation(caller.identity_id());
+        assert!(swapped.verify().is_err());
+        let mut rekeyed = swapped.clone();
+        rekeyed.sender_key = caller.public_key();
+        assert!(rekeyed.verify().is_err());
+
+        for forged in [swapped, rekeyed] {
+            assert!(server
+                .send_message(callee.identity_id(), forged)
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn message_signed_for_one_recipient_is_refused_for_another() {
+        let server = SignalingServer::new().await.unwrap();
+        let caller = IdentityKeyPair::generate().unwrap();
+        let callee = IdentityKeyPair::generate().unwrap();
+        let bystander = IdentityKeyPair::generate().unwrap();
+        let _bystander_client = server.register_client(bystander.identity_id()).await;
+
+        let signed = invitation(caller.identity_id())
+            .sign(&caller, callee.identity_id())
+            .unwrap();
+        assert!(server
+            .send_message(bystander.identity_id(), signed.clone())
+            .await
+            .is_err());
+
+        // Relabelling the recipient breaks the signature.
+        let mut redirected = signed;
+        redirected.recipient = bystander.identity_id();
+        assert!(redirected.verify().is_err());
+    }
+}
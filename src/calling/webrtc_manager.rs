@@ -1,14 +1,27 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex, RwLock};
 
 use crate::calling::call_manager::{CallId, TurnServer};
-use crate::calling::media_encryption::MediaKey;
-use crate::calling::peer_connection::{ICECandidate, PeerConnection, PeerConnectionState};
+use crate::calling::media_encryption::MediaKeyPair;
+use crate::calling::peer_connection::{ConnectionStateSender, ICECandidate, PeerConnection};
 use crate::identity::identity_key::IdentityId;
 
+/// Remote trickle-ICE state for one connection: every candidate seen so
+/// far, those still waiting for the connection and its remote
+/// description, and whether the remote side has finished gathering.
+#[derive(Default)]
+struct RemoteCandidates {
+    seen: HashSet<ICECandidate>,
+    pending: Vec<ICECandidate>,
+    end_of_candidates: bool,
+}
+
 /// WebRTC manager for handling real-time media communication
 pub struct WebRTCManager {
     /// Active peer connections
@@ -17,8 +30,12 @@ pub struct WebRTCManager {
     config: WebRTCConfig,
     /// Media devices manager
     media_devices: MediaDevicesManager,
-    /// ICE candidate cache
-    ice_candidates: Arc<RwLock<HashMap<(CallId, IdentityId), Vec<ICECandidate>>>>,
+    /// Remote ICE candidates per connection
+    ice_candidates: Arc<RwLock<HashMap<(CallId, IdentityId), RemoteCandidates>>>,
+    /// Short-lived TURN credentials, if a provider was configured
+    turn_credentials: Option<TurnCredentialCache>,
+    /// Connection state changes are forwarded here, if set
+    state_events: Option<ConnectionStateSender>,
 }
 
 /// WebRTC configuration
@@ -148,6 +165,65 @@ pub struct MediaStats {
     pub resolution: Option<(u32, u32)>,
 }
 
+/// Time-limited TURN credentials, as issued by a coturn-style REST
+/// endpoint.
+#[derive(Clone)]
+pub struct TurnCredentials {
+    pub server: TurnServer,
+    /// How long the server will accept `server.username`/`credential`
+    pub ttl: Duration,
+}
+
+/// Boxed future returned by [`TurnCredentialProvider`], so providers
+/// can be stored as trait objects.
+pub type TurnCredentialFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<TurnCredentials>> + Send + 'a>>;
+
+/// Source of fresh TURN credentials. The static `turn_servers` in
+/// [`WebRTCConfig`] stop working once REST credentials expire; a
+/// provider is asked again whenever the cached ones are about to.
+pub trait TurnCredentialProvider: Send + Sync {
+    fn fresh_credentials(&self) -> TurnCredentialFuture<'_>;
+}
+
+/// Refresh credentials this long before they expire, so a connection
+/// set up right at the boundary doesn't start with dead ones.
+pub const TURN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Caches the last credentials from a [`TurnCredentialProvider`] until
+/// they are within [`TURN_REFRESH_MARGIN`] of expiring.
+pub struct TurnCredentialCache {
+    provider: Arc<dyn TurnCredentialProvider>,
+    cached: Mutex<Option<(TurnServer, Instant)>>,
+}
+
+impl TurnCredentialCache {
+    pub fn new(provider: Arc<dyn TurnCredentialProvider>) -> Self {
+        TurnCredentialCache {
+            provider,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Current credentials, fetching new ones if none are cached or the
+    /// cached ones are about to expire.
+    pub async fn current(&self) -> Result<TurnServer> {
+        let mut cached = self.cached.lock().await;
+        if let Some((server, expires_at)) = cached.as_ref() {
+            if Instant::now() + TURN_REFRESH_MARGIN < *expires_at {
+                return Ok(server.clone());
+            }
+        }
+        let fresh = self
+            .provider
+            .fresh_credentials()
+            .await
+            .context("Failed to fetch TURN credentials")?;
+        *cached = Some((fresh.server.clone(), Instant::now() + fresh.ttl));
+        Ok(fresh.server)
+    }
+}
+
 impl WebRTCManager {
     /// Create a new WebRTC manager
     pub async fn new(config: WebRTCConfig) -> Result<Self> {
@@ -158,18 +234,54 @@ impl WebRTCManager {
             config,
             media_devices,
             ice_candidates: Arc::new(RwLock::new(HashMap::new())),
+            turn_credentials: None,
+            state_events: None,
         })
     }
 
+    /// Report state changes of every peer connection created from now
+    /// on to `sender`.
+    pub fn with_connection_state_events(mut self, sender: ConnectionStateSender) -> Self {
+        self.state_events = Some(sender);
+        self
+    }
+
+    /// Fetch TURN credentials from `provider` for each new peer
+    /// connection, in addition to the static `turn_servers`.
+    pub fn with_turn_credential_provider(
+        mut self,
+        provider: Arc<dyn TurnCredentialProvider>,
+    ) -> Self {
+        self.turn_credentials = Some(TurnCredentialCache::new(provider));
+        self
+    }
+
+    /// Configuration for the next peer connection: the static config
+    /// plus current credentials from the TURN provider, if any.
+    async fn connection_config(&self) -> Result<WebRTCConfig> {
+        let mut config = self.config.clone();
+        if let Some(cache) = &self.turn_credentials {
+            config.turn_servers.push(cache.current().await?);
+        }
+        Ok(config)
+    }
+
     /// Create a new peer connection
     pub async fn create_peer_connection(
         &self,
         call_id: CallId,
         participant: IdentityId,
-        media_key: MediaKey,
+        media_keys: MediaKeyPair,
     ) -> Result<()> {
-        let peer_connection =
-            PeerConnection::new(self.config.clone(), media_key, call_id, participant).await?;
+        let config = self.connection_config().await?;
+        let peer_connection = PeerConnection::new(
+            config,
+            media_keys,
+            call_id,
+            participant,
+            self.state_events.clone(),
+        )
+        .await?;
 
         let mut connections = self.peer_connections.write().await;
         connections.insert((call_id, participant), peer_connection);
@@ -249,6 +361,37 @@ impl WebRTCManager {
         Ok(())
     }
 
+    /// Open the in-call data channel to a participant; see
+    /// [`PeerConnection::open_data_channel`].
+    pub async fn open_data_channel(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        label: &str,
+    ) -> Result<mpsc::Receiver<Vec<u8>>> {
+        let connections = self.peer_connections.read().await;
+        if let Some(connection) = connections.get(&(call_id, participant)) {
+            connection.open_data_channel(label).await
+        } else {
+            Err(anyhow::anyhow!("Peer connection not found"))
+        }
+    }
+
+    /// Send an encrypted message on a participant's data channel
+    pub async fn send_data(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        data: &[u8],
+    ) -> Result<()> {
+        let connections = self.peer_connections.read().await;
+        if let Some(connection) = connections.get(&(call_id, participant)) {
+            connection.send_data(data).await
+        } else {
+            Err(anyhow::anyhow!("Peer connection not found"))
+        }
+    }
+
     /// Get media statistics for a connection
     pub async fn get_media_stats(
         &self,
@@ -270,16 +413,66 @@ impl WebRTCManager {
         participant: IdentityId,
         candidate: ICECandidate,
     ) -> Result<()> {
+        self.add_ice_candidates(call_id, participant, vec![candidate])
+            .await
+            .map(|_| ())
+    }
+
+    /// Add a batch of remote ICE candidates. Candidates already seen for
+    /// this connection are dropped; the rest are applied in one pass if
+    /// the remote description is set, or held until it is. Returns how
+    /// many were new.
+    pub async fn add_ice_candidates(
+        &self,
+        call_id: CallId,
+        participant: IdentityId,
+        candidates: Vec<ICECandidate>,
+    ) -> Result<usize> {
+        let connections = self.peer_connections.read().await;
+        let mut ice_candidates = self.ice_candidates.write().await;
+        let remote = ice_candidates.entry((call_id, participant)).or_default();
+        let fresh: Vec<ICECandidate> = candidates
+            .into_iter()
+            .filter(|candidate| remote.seen.insert(candidate.clone()))
+            .collect();
+        let count = fresh.len();
+        remote.pending.extend(fresh);
+
+        if let Some(connection) = connections.get(&(call_id, participant)) {
+            if connection.has_remote_description().await {
+                Self::apply_remote_candidates(connection, remote).await?;
+            }
+        }
+        Ok(count)
+    }
+
+    /// The remote participant has finished gathering candidates. Passed
+    /// on to the connection after any candidates still pending.
+    pub async fn end_of_candidates(&self, call_id: CallId, participant: IdentityId) -> Result<()> {
         let connections = self.peer_connections.read().await;
+        let mut ice_candidates = self.ice_candidates.write().await;
+        let remote = ice_candidates.entry((call_id, participant)).or_default();
+        remote.end_of_candidates = true;
+
         if let Some(connection) = connections.get(&(call_id, participant)) {
+            if connection.has_remote_description().await {
+                Self::apply_remote_candidates(connection, remote).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand pending candidates, then end-of-candidates if it has
+    /// arrived, to a connection whose remote description is set.
+    async fn apply_remote_candidates(
+        connection: &PeerConnection,
+        remote: &mut RemoteCandidates,
+    ) -> Result<()> {
+        for candidate in std::mem::take(&mut remote.pending) {
             connection.add_ice_candidate(candidate).await?;
-        } else {
-            // Store candidate for later if connection doesn't exist yet
-            let mut ice_candidates = self.ice_candidates.write().await;
-            ice_candidates
-                .entry((call_id, participant))
-                .or_insert_with(Vec::new)
-                .push(candidate);
+        }
+        if remote.end_of_candidates && !connection.remote_gathering_complete() {
+            connection.end_of_candidates().await?;
         }
         Ok(())
     }
@@ -303,7 +496,10 @@ impl WebRTCManager {
     ) -> Result<String> {
         let connections = self.peer_connections.read().await;
         if let Some(connection) = connections.get(&(call_id, participant)) {
-            connection.create_answer(offer).await
+            let answer = connection.create_answer(offer).await?;
+            self.flush_remote_candidates(connection, call_id, participant)
+                .await?;
+            Ok(answer)
         } else {
             Err(anyhow::anyhow!("Peer connection not found"))
         }
@@ -319,14 +515,22 @@ impl WebRTCManager {
         let connections = self.peer_connections.read().await;
         if let Some(connection) = connections.get(&(call_id, participant)) {
             connection.set_remote_description(description).await?;
+            self.flush_remote_candidates(connection, call_id, participant)
+                .await?;
+        }
+        Ok(())
+    }
 
-            // Add any cached ICE candidates
-            let mut ice_candidates = self.ice_candidates.write().await;
-            if let Some(candidates) = ice_candidates.remove(&(call_id, participant)) {
-                for candidate in candidates {
-                    connection.add_ice_candidate(candidate).await?;
-                }
-            }
+    /// Apply whatever trickled in before the remote description was set.
+    async fn flush_remote_candidates(
+        &self,
+        connection: &PeerConnection,
+        call_id: CallId,
+        participant: IdentityId,
+    ) -> Result<()> {
+        let mut ice_candidates = self.ice_candidates.write().await;
+        if let Some(remote) = ice_candidates.get_mut(&(call_id, participant)) {
+            Self::apply_remote_candidates(connection, remote).await?;
         }
         Ok(())
     }
@@ -604,3 +808,144 @@ impl Default for VideoConstraints {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calling::media_encryption::MediaEncryption;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Hands out `user-1`, `user-2`, … with a fixed TTL.
+    struct RotatingProvider {
+        issued: AtomicU32,
+        ttl: Duration,
+    }
+
+    impl TurnCredentialProvider for RotatingProvider {
+        fn fresh_credentials(&self) -> TurnCredentialFuture<'_> {
+            Box::pin(async move {
+                let n = self.issued.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(TurnCredentials {
+                    server: TurnServer {
+                        url: "turn:turn.example:3478".to_string(),
+                        username: format!("user-{n}"),
+                        credential: format!("secret-{n}"),
+                    },
+                    ttl: self.ttl,
+                })
+            })
+        }
+    }
+
+    async fn manager_with(ttl: Duration) -> WebRTCManager {
+        let config = WebRTCConfig {
+            stun_servers: vec!["stun:stun.example:3478".to_string()],
+            turn_servers: Vec::new(),
+            enable_dtls: true,
+            enable_srtp: true,
+        };
+        WebRTCManager::new(config)
+            .await
+            .unwrap()
+            .with_turn_credential_provider(Arc::new(RotatingProvider {
+                issued: AtomicU32::new(0),
+                ttl,
+            }))
+    }
+
+    #[tokio::test]
+    async fn turn_credentials_are_cached_until_near_expiry() {
+        let manager = manager_with(Duration::from_secs(3600)).await;
+        let first = manager.connection_config().await.unwrap();
+        let second = manager.connection_config().await.unwrap();
+        assert_eq!(first.turn_servers[0].username, "user-1");
+        assert_eq!(second.turn_servers[0].username, "user-1");
+
+        // Credentials that expire inside the refresh margin are replaced
+        // on every connection.
+        let manager = manager_with(TURN_REFRESH_MARGIN / 2).await;
+        let first = manager.connection_config().await.unwrap();
+        let second = manager.connection_config().await.unwrap();
+        assert_eq!(first.turn_servers[0].username, "user-1");
+        assert_eq!(second.turn_servers[0].username, "user-2");
+        assert_eq!(second.turn_servers[0].credential, "secret-2");
+    }
+
+    #[tokio::test]
+    async fn trickled_candidates_apply_once_then_end_of_candidates() {
+        let manager = manager_with(Duration::from_secs(3600)).await;
+        let call_id = CallId::from([1u8; 16]);
+        let alice = IdentityId::from([2u8; 32]);
+        let bob = IdentityId::from([3u8; 32]);
+        // Alice's side of the call talks to Bob and vice versa.
+        let exporter = [7u8; 32];
+        manager
+            .create_peer_connection(
+                call_id,
+                bob,
+                MediaEncryption::derive_from_session(call_id, &alice, &bob, &exporter),
+            )
+            .await
+            .unwrap();
+        manager
+            .create_peer_connection(
+                call_id,
+                alice,
+                MediaEncryption::derive_from_session(call_id, &bob, &alice, &exporter),
+            )
+            .await
+            .unwrap();
+        manager
+            .set_audio_enabled(call_id, alice, true)
+            .await
+            .unwrap();
+
+        let candidate = |port: u16| ICECandidate {
+            sdp_mid: "0".to_string(),
+            sdp_mline_index: 0,
+            candidate: format!("candidate:1 1 udp 2130706431 192.0.2.1 {port} typ host"),
+        };
+
+        // Bob's candidates trickle in before his offer does: held, and
+        // repeats are dropped.
+        let batch = vec![candidate(50000), candidate(50000), candidate(50001)];
+        assert_eq!(
+            manager
+                .add_ice_candidates(call_id, bob, batch)
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            manager
+                .add_ice_candidates(call_id, bob, vec![candidate(50001)])
+                .await
+                .unwrap(),
+            0
+        );
+        manager.end_of_candidates(call_id, bob).await.unwrap();
+        assert_eq!(
+            manager.ice_candidates.read().await[&(call_id, bob)]
+                .pending
+                .len(),
+            2
+        );
+
+        let offer = manager.create_offer(call_id, alice).await.unwrap();
+        manager.create_answer(call_id, bob, &offer).await.unwrap();
+
+        assert!(manager.ice_candidates.read().await[&(call_id, bob)]
+            .pending
+            .is_empty());
+        assert!(manager.peer_connections.read().await[&(call_id, bob)].remote_gathering_complete());
+
+        // A candidate already applied isn't applied again.
+        assert_eq!(
+            manager
+                .add_ice_candidates(call_id, bob, vec![candidate(50000)])
+                .await
+                .unwrap(),
+            0
+        );
+    }
+}
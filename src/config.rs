@@ -1,6 +1,10 @@
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct AppConfig {
     pub enable_cover_traffic: bool,
     pub dummy_packet_frequency_secs: u64,
@@ -16,3 +20,165 @@ impl Default for AppConfig {
         }
     }
 }
+
+/// Accepted range for `dummy_packet_frequency_secs`. Below a second the
+/// cover traffic is mostly battery drain; above an hour it covers
+/// nothing.
+const DUMMY_PACKET_FREQUENCY_SECS: std::ops::RangeInclusive<u64> = 1..=3600;
+
+const TRUST_MODELS: &[&str] = &["TOFU", "pinned"];
+
+/// A setting that parsed but is out of range, or an override that
+/// didn't parse. Names the setting and, for env overrides, the
+/// variable it came from, so the message is enough to fix the input.
+#[derive(Debug, Error)]
+#[error("invalid config value for {setting}: {reason}")]
+pub struct InvalidConfig {
+    pub setting: String,
+    pub reason: String,
+}
+
+impl AppConfig {
+    /// Load a TOML settings file, then apply `QUBEE_*` environment
+    /// overrides (see [`from_env`](Self::from_env)), then validate.
+    /// Settings missing from the file keep their defaults; unknown keys
+    /// are an error so a typo can't silently fall back to a default.
+    pub fn from_toml<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_toml_with(path.as_ref(), |var| std::env::var(var).ok())
+    }
+
+    /// Defaults plus environment overrides, validated:
+    ///
+    /// * `QUBEE_COVER_TRAFFIC` — `true` / `false`
+    /// * `QUBEE_DUMMY_PACKET_SECS` — seconds, 1 to 3600
+    /// * `QUBEE_TRUST_MODEL` — `TOFU` or `pinned`
+    pub fn from_env() -> Result<Self> {
+        let mut config = AppConfig::default();
+        config.apply_env(|var| std::env::var(var).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_toml_with(path: &Path, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let mut config: AppConfig = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        config.apply_env(env)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(value) = env("QUBEE_COVER_TRAFFIC") {
+            self.enable_cover_traffic = parse_env("QUBEE_COVER_TRAFFIC", &value)?;
+        }
+        if let Some(value) = env("QUBEE_DUMMY_PACKET_SECS") {
+            self.dummy_packet_frequency_secs = parse_env("QUBEE_DUMMY_PACKET_SECS", &value)?;
+        }
+        if let Some(value) = env("QUBEE_TRUST_MODEL") {
+            self.trust_model = value;
+        }
+        Ok(())
+    }
+
+    /// Range-check every setting.
+    pub fn validate(&self) -> Result<(), InvalidConfig> {
+        if !DUMMY_PACKET_FREQUENCY_SECS.contains(&self.dummy_packet_frequency_secs) {
+            return Err(InvalidConfig {
+                setting: "dummy_packet_frequency_secs".to_string(),
+                reason: format!(
+                    "{} is outside {}..={}",
+                    self.dummy_packet_frequency_secs,
+                    DUMMY_PACKET_FREQUENCY_SECS.start(),
+                    DUMMY_PACKET_FREQUENCY_SECS.end()
+                ),
+            });
+        }
+        if !TRUST_MODELS.contains(&self.trust_model.as_str()) {
+            return Err(InvalidConfig {
+                setting: "trust_model".to_string(),
+                reason: format!(
+                    "{:?} is not one of {}",
+                    self.trust_model,
+                    TRUST_MODELS.join(", ")
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn parse_env<T: std::str::FromStr>(var: &str, value: &str) -> Result<T, InvalidConfig>
+where
+    T::Err: std::fmt::Display,
+{
+    value.trim().parse().map_err(|e| InvalidConfig {
+        setting: var.to_string(),
+        reason: format!("{value:?}: {e}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn write_config(dir: &TempDir, text: &str) -> std::path::PathBuf {
+        let path = dir.path().join("qubee.toml");
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn toml_file_loads_and_keeps_defaults_for_missing_settings() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            "enable_cover_traffic = false\ntrust_model = \"pinned\"\n",
+        );
+        let config = AppConfig::from_toml_with(&path, |_| None).unwrap();
+        assert_eq!(
+            config,
+            AppConfig {
+                enable_cover_traffic: false,
+                trust_model: "pinned".to_string(),
+                ..AppConfig::default()
+            }
+        );
+
+        let path = write_config(&dir, "cover_trafic = true\n");
+        assert!(AppConfig::from_toml_with(&path, |_| None).is_err());
+    }
+
+    #[test]
+    fn out_of_range_value_names_the_setting() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(&dir, "dummy_packet_frequency_secs = 0\n");
+        let err = AppConfig::from_toml_with(&path, |_| None).unwrap_err();
+        let invalid = err.downcast_ref::<InvalidConfig>().expect("typed error");
+        assert_eq!(invalid.setting, "dummy_packet_frequency_secs");
+        assert!(err.to_string().contains("0 is outside 1..=3600"), "{err}");
+    }
+
+    #[test]
+    fn env_overrides_take_precedence_over_the_file() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            "dummy_packet_frequency_secs = 30\ntrust_model = \"pinned\"\n",
+        );
+        let env: HashMap<&str, &str> = [("QUBEE_DUMMY_PACKET_SECS", "5")].into();
+        let config =
+            AppConfig::from_toml_with(&path, |var| env.get(var).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.dummy_packet_frequency_secs, 5);
+        assert_eq!(config.trust_model, "pinned");
+
+        let env: HashMap<&str, &str> = [("QUBEE_COVER_TRAFFIC", "sometimes")].into();
+        let err = AppConfig::from_toml_with(&path, |var| env.get(var).map(|v| v.to_string()))
+            .unwrap_err();
+        let invalid = err.downcast_ref::<InvalidConfig>().expect("typed error");
+        assert_eq!(invalid.setting, "QUBEE_COVER_TRAFFIC");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::groups::GroupError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -19,6 +20,9 @@ pub enum MessengerError {
 
     #[error("General error: {0}")]
     General(String),
+
+    #[error(transparent)]
+    Group(#[from] GroupError),
 }
 
 impl From<anyhow::Error> for MessengerError {
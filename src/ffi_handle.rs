@@ -0,0 +1,114 @@
+//! Boxed Rust objects handed across the FFI boundary as plain integers.
+//!
+//! The JNI bridge gives Kotlin a `jlong` for objects it owns (a
+//! messenger, say) and takes it back on every call. Casting that
+//! number straight back to a pointer trusts the caller completely: a
+//! stale handle after `destroy`, a double destroy, or a handle of the
+//! wrong kind is undefined behaviour. A [`HandleTable`] only
+//! dereferences handles it issued and has not yet released, so those
+//! mistakes become errors instead.
+//!
+//! Nothing here touches the JVM, so it builds and tests on the host.
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+/// Live handles for one object type. Meant to live in a `static`, one
+/// per type, so a handle from one table is never valid in another.
+pub struct HandleTable<T> {
+    live: Mutex<BTreeSet<usize>>,
+    _owns: PhantomData<T>,
+}
+
+impl<T> Default for HandleTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> HandleTable<T> {
+    pub const fn new() -> Self {
+        HandleTable {
+            live: Mutex::new(BTreeSet::new()),
+            _owns: PhantomData,
+        }
+    }
+
+    /// Box `value` and return its handle. Never 0, so callers can keep
+    /// 0 as "no object".
+    pub fn insert(&self, value: T) -> i64 {
+        let ptr = Box::into_raw(Box::new(value)) as usize;
+        self.live.lock().unwrap().insert(ptr);
+        ptr as i64
+    }
+
+    /// Run `f` on the object behind `handle`. The table stays locked
+    /// while `f` runs, so a concurrent [`remove`](Self::remove) waits
+    /// for it rather than freeing the object underneath it.
+    pub fn with<R>(&self, handle: i64, f: impl FnOnce(&T) -> R) -> Result<R> {
+        let live = self.live.lock().unwrap();
+        let ptr = Self::checked(&live, handle)?;
+        // SAFETY: `ptr` came from `Box::into_raw` in `insert` and is
+        // still in `live`, so it has not been freed; holding the lock
+        // keeps `remove` from freeing it while `f` borrows it.
+        Ok(f(unsafe { &*(ptr as *const T) }))
+    }
+
+    /// Release `handle` and hand back its object. The handle is invalid
+    /// from here on, including for a second `remove`.
+    pub fn remove(&self, handle: i64) -> Result<T> {
+        let mut live = self.live.lock().unwrap();
+        let ptr = Self::checked(&live, handle)?;
+        live.remove(&ptr);
+        // SAFETY: as in `with`; removing it from `live` first means no
+        // other call can reach this pointer again.
+        Ok(*unsafe { Box::from_raw(ptr as *mut T) })
+    }
+
+    pub fn len(&self) -> usize {
+        self.live.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn checked(live: &BTreeSet<usize>, handle: i64) -> Result<usize> {
+        let ptr = usize::try_from(handle).map_err(|_| anyhow!("invalid handle {handle}"))?;
+        if !live.contains(&ptr) {
+            return Err(anyhow!("invalid or released handle {handle:#x}"));
+        }
+        Ok(ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_are_validated_until_released() {
+        let table: HandleTable<Vec<u8>> = HandleTable::new();
+        let a = table.insert(vec![1, 2, 3]);
+        let b = table.insert(vec![4]);
+        assert_ne!(a, 0);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.with(a, |v| v.clone()).unwrap(), vec![1, 2, 3]);
+
+        assert!(table.with(0, |_| ()).is_err());
+        assert!(table.with(-1, |_| ()).is_err());
+        assert!(table.with(a + 1, |_| ()).is_err());
+
+        assert_eq!(table.remove(a).unwrap(), vec![1, 2, 3]);
+        assert!(table.with(a, |_| ()).is_err());
+        assert!(table.remove(a).is_err());
+        assert_eq!(table.with(b, |v| v.len()).unwrap(), 1);
+
+        let other: HandleTable<Vec<u8>> = HandleTable::new();
+        assert!(other.with(b, |_| ()).is_err());
+        table.remove(b).unwrap();
+        assert!(table.is_empty());
+    }
+}
@@ -1,6 +1,11 @@
+//! The prototype's whole-file `send_file` / `receive_file`, kept for
+//! porting reference. Every chunk is sealed under the ratchet's root
+//! key; new code uses [`super::FileTransferSender`] instead.
+
 use crate::ephemeral_keys::{verify_and_pin_ephemeral_key, EphemeralKeyStore};
 use crate::{HybridRatchet, PQ_REKEY_PERIOD};
 use anyhow::{Context, Result};
+use chacha20poly1305::aead::Aead;
 use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
 use pqcrypto_dilithium::dilithium2;
 use rand::rngs::OsRng;
@@ -0,0 +1,715 @@
+#[cfg(feature = "legacy")]
+mod legacy;
+#[cfg(feature = "legacy")]
+pub use legacy::{receive_file, send_file, FileChunk};
+
+use crate::security::secure_rng;
+use crate::storage::secure_keystore::{KeyMetadata, KeyType, KeyUsage, SecureKeyStore};
+use anyhow::{Context, Result};
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use hkdf::Hkdf;
+use secrecy::{ExposeSecret, SecretBox};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use zeroize::Zeroize;
+
+/// Default plaintext size of one [`EncryptedChunk`].
+pub const DEFAULT_CHUNK_SIZE: usize = 65536;
+
+const CHUNK_KEY_INFO: &[u8] = b"qubee file transfer chunk key v1";
+
+/// Per-transfer chunk key: HKDF-SHA256 over the session's exporter
+/// secret, salted with `file_id` so two transfers never share a key.
+/// The exporter is a secret the session hands out for derivations like
+/// this one, never its root key, so the key survives no longer than
+/// the exporter does and can't be used to forge chat messages.
+fn transfer_key(exporter_secret: &[u8; 32], file_id: u64) -> SecretBox<[u8; 32]> {
+    let hk = Hkdf::<Sha256>::new(Some(&file_id.to_le_bytes()), exporter_secret);
+    let mut key = [0u8; 32];
+    hk.expand(CHUNK_KEY_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    SecretBox::new(Box::new(key))
+}
+
+/// AEAD associated data for one chunk. Binding `seq` and `total_chunks`
+/// means a relay can't reorder, drop the tail, or splice chunks from
+/// another transfer without the AEAD failing.
+fn chunk_aad(file_id: u64, seq: u64, total_chunks: u64) -> [u8; 24] {
+    let mut aad = [0u8; 24];
+    aad[..8].copy_from_slice(&file_id.to_le_bytes());
+    aad[8..16].copy_from_slice(&seq.to_le_bytes());
+    aad[16..].copy_from_slice(&total_chunks.to_le_bytes());
+    aad
+}
+
+/// One encrypted slice of a streamed file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EncryptedChunk {
+    pub file_id: u64,
+    /// 0-based position of this chunk.
+    pub seq: u64,
+    pub total_chunks: u64,
+    pub nonce: [u8; 12],
+    pub ciphertext: Vec<u8>,
+}
+
+/// Streaming sender: reads a file from any [`Read`] one chunk at a
+/// time, so memory use is bounded by `chunk_size` regardless of file
+/// size.
+pub struct FileTransferSender {
+    exporter_secret: SecretBox<[u8; 32]>,
+    chunk_size: usize,
+}
+
+impl FileTransferSender {
+    /// `exporter_secret` is the session's exporter secret for the peer
+    /// the file goes to; the receiver must be given the same one.
+    pub fn new(exporter_secret: &[u8; 32], chunk_size: usize) -> Result<Self> {
+        if chunk_size == 0 {
+            return Err(anyhow::anyhow!("chunk size must be non-zero"));
+        }
+        Ok(FileTransferSender {
+            exporter_secret: SecretBox::new(Box::new(*exporter_secret)),
+            chunk_size,
+        })
+    }
+
+    /// Number of chunks a file of `total_len` bytes is split into. An
+    /// empty file is still sent as one (empty) chunk so the receiver
+    /// sees the transfer complete.
+    pub fn chunk_count(&self, total_len: u64) -> u64 {
+        total_len.div_ceil(self.chunk_size as u64).max(1)
+    }
+
+    /// Lazily encrypt `reader`, which must yield exactly `total_len`
+    /// bytes. Each item is one chunk; a short or long source surfaces
+    /// as an `Err` item and ends the stream.
+    pub fn chunks<'a, R: Read + 'a>(
+        &'a self,
+        file_id: u64,
+        mut reader: R,
+        total_len: u64,
+    ) -> impl Iterator<Item = Result<EncryptedChunk>> + 'a {
+        let key = transfer_key(self.exporter_secret.expose_secret(), file_id);
+        let total_chunks = self.chunk_count(total_len);
+        let mut remaining = total_len;
+        let mut buffer = vec![0u8; self.chunk_size];
+        let mut seq = 0u64;
+        let mut failed = false;
+        std::iter::from_fn(move || {
+            if failed || seq == total_chunks {
+                return None;
+            }
+            let result = (|| {
+                let want = remaining.min(self.chunk_size as u64) as usize;
+                reader
+                    .read_exact(&mut buffer[..want])
+                    .context("file source ended early")?;
+                remaining -= want as u64;
+                if remaining == 0 && seq + 1 == total_chunks {
+                    let mut probe = [0u8; 1];
+                    if reader.read(&mut probe)? != 0 {
+                        return Err(anyhow::anyhow!("file source longer than declared"));
+                    }
+                }
+                let chunk = seal_chunk(&key, file_id, seq, total_chunks, &buffer[..want]);
+                buffer[..want].zeroize();
+                chunk
+            })();
+            failed = result.is_err();
+            seq += 1;
+            Some(result)
+        })
+    }
+}
+
+impl FileTransferSender {
+    /// Describe `reader`'s contents for a resumable transfer: BLAKE3
+    /// hash, size and chunking. Reads the whole source once and rewinds
+    /// it. Send the manifest to the receiver before any chunks.
+    pub fn manifest<R: Read + Seek>(
+        &self,
+        file_id: u64,
+        reader: &mut R,
+    ) -> Result<TransferManifest> {
+        reader.seek(SeekFrom::Start(0))?;
+        let mut hasher = blake3::Hasher::new();
+        let total_len = std::io::copy(reader, &mut hasher).context("hashing file source")?;
+        reader.seek(SeekFrom::Start(0))?;
+        Ok(TransferManifest::new(
+            file_id,
+            *hasher.finalize().as_bytes(),
+            total_len,
+            self.chunk_size as u32,
+            self.chunk_count(total_len),
+        ))
+    }
+
+    /// Encrypt only the chunks at `indices` (as reported by
+    /// [`FileTransferReceiver::missing_chunks`]), seeking in `reader`
+    /// for each one.
+    pub fn send_chunks<'a, R: Read + Seek>(
+        &'a self,
+        manifest: &'a TransferManifest,
+        reader: &'a mut R,
+        indices: &'a [u64],
+    ) -> impl Iterator<Item = Result<EncryptedChunk>> + 'a {
+        let key = transfer_key(self.exporter_secret.expose_secret(), manifest.file_id);
+        let mut buffer = vec![0u8; manifest.chunk_size as usize];
+        indices.iter().map(move |&seq| {
+            let len = manifest.chunk_len(seq)?;
+            reader.seek(SeekFrom::Start(seq * manifest.chunk_size as u64))?;
+            reader
+                .read_exact(&mut buffer[..len])
+                .context("file source ended early")?;
+            let chunk = seal_chunk(
+                &key,
+                manifest.file_id,
+                seq,
+                manifest.total_chunks,
+                &buffer[..len],
+            );
+            buffer[..len].zeroize();
+            chunk
+        })
+    }
+}
+
+/// Everything a receiver needs to resume an interrupted transfer:
+/// what the finished file must hash to, how it's chunked, and which
+/// chunks have already been written.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferManifest {
+    pub file_id: u64,
+    pub file_hash: [u8; 32],
+    pub total_len: u64,
+    pub total_chunks: u64,
+    pub chunk_size: u32,
+    /// Bitmap of received chunks, bit `i % 8` of byte `i / 8`.
+    pub received: Vec<u8>,
+}
+
+impl TransferManifest {
+    fn new(
+        file_id: u64,
+        file_hash: [u8; 32],
+        total_len: u64,
+        chunk_size: u32,
+        total_chunks: u64,
+    ) -> Self {
+        TransferManifest {
+            file_id,
+            file_hash,
+            total_len,
+            total_chunks,
+            chunk_size,
+            received: vec![0u8; total_chunks.div_ceil(8) as usize],
+        }
+    }
+
+    /// Reject a manifest whose chunking doesn't add up. It may come
+    /// from the peer, and the bitmap and length arithmetic below trust
+    /// these fields.
+    fn check_consistent(&self) -> Result<()> {
+        if self.chunk_size == 0 {
+            return Err(anyhow::anyhow!("manifest chunk size must be non-zero"));
+        }
+        if self.total_chunks != self.total_len.div_ceil(self.chunk_size as u64).max(1) {
+            return Err(anyhow::anyhow!(
+                "manifest chunk count {} does not match {} bytes in {}-byte chunks",
+                self.total_chunks,
+                self.total_len,
+                self.chunk_size
+            ));
+        }
+        if self.received.len() as u64 != self.total_chunks.div_ceil(8) {
+            return Err(anyhow::anyhow!(
+                "manifest bitmap is {} bytes for {} chunks",
+                self.received.len(),
+                self.total_chunks
+            ));
+        }
+        Ok(())
+    }
+
+    fn keystore_id(file_id: u64) -> String {
+        format!("file_transfer_manifest_{file_id:016x}")
+    }
+
+    pub fn is_received(&self, seq: u64) -> bool {
+        seq < self.total_chunks && self.received[(seq / 8) as usize] & (1 << (seq % 8)) != 0
+    }
+
+    fn mark_received(&mut self, seq: u64) {
+        self.received[(seq / 8) as usize] |= 1 << (seq % 8);
+    }
+
+    /// Indices of chunks not yet received, ascending.
+    pub fn missing_chunks(&self) -> Vec<u64> {
+        (0..self.total_chunks)
+            .filter(|&i| !self.is_received(i))
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        (0..self.total_chunks).all(|i| self.is_received(i))
+    }
+
+    /// Plaintext length of chunk `seq`; only the last one may be short.
+    fn chunk_len(&self, seq: u64) -> Result<usize> {
+        if seq >= self.total_chunks {
+            return Err(anyhow::anyhow!(
+                "chunk {} out of range (transfer has {})",
+                seq,
+                self.total_chunks
+            ));
+        }
+        let start = seq * self.chunk_size as u64;
+        Ok((self.total_len - start).min(self.chunk_size as u64) as usize)
+    }
+
+    /// Persist to `keystore` so the transfer survives a restart.
+    pub fn save(&self, keystore: &mut SecureKeyStore) -> Result<()> {
+        let data = bincode::serialize(self).context("manifest serialize")?;
+        let metadata = KeyMetadata {
+            algorithm: "bincode".to_string(),
+            key_size: data.len(),
+            usage: vec![KeyUsage::Encryption],
+            expiry: None,
+            tags: HashMap::new(),
+        };
+        keystore.store_key(
+            &Self::keystore_id(self.file_id),
+            &data,
+            KeyType::EncryptionKey,
+            metadata,
+        )
+    }
+
+    /// Load a previously saved manifest for `file_id`, if any.
+    pub fn load(keystore: &mut SecureKeyStore, file_id: u64) -> Result<Option<Self>> {
+        keystore
+            .retrieve_key(&Self::keystore_id(file_id))?
+            .map(|data| bincode::deserialize(data.expose_secret()).context("manifest deserialize"))
+            .transpose()
+    }
+
+    /// Drop the persisted manifest once the transfer is done.
+    pub fn remove(keystore: &mut SecureKeyStore, file_id: u64) -> Result<()> {
+        keystore.delete_key(&Self::keystore_id(file_id))?;
+        Ok(())
+    }
+}
+
+fn seal_chunk(
+    key: &SecretBox<[u8; 32]>,
+    file_id: u64,
+    seq: u64,
+    total_chunks: u64,
+    plaintext: &[u8],
+) -> Result<EncryptedChunk> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key.expose_secret()));
+    let nonce = secure_rng::random::array::<12>()?;
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &chunk_aad(file_id, seq, total_chunks),
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("chunk encryption failed"))?;
+    Ok(EncryptedChunk {
+        file_id,
+        seq,
+        total_chunks,
+        nonce,
+        ciphertext,
+    })
+}
+
+/// Streaming receiver: accepts chunks strictly in order, decrypts them
+/// and writes the plaintext straight to `sink`.
+pub struct FileTransferReceiver<W: Write> {
+    key: SecretBox<[u8; 32]>,
+    file_id: u64,
+    total_chunks: Option<u64>,
+    next_seq: u64,
+    sink: W,
+    manifest: Option<TransferManifest>,
+}
+
+impl<W: Write> FileTransferReceiver<W> {
+    pub fn new(exporter_secret: &[u8; 32], file_id: u64, sink: W) -> Self {
+        FileTransferReceiver {
+            key: transfer_key(exporter_secret, file_id),
+            file_id,
+            total_chunks: None,
+            next_seq: 0,
+            sink,
+            manifest: None,
+        }
+    }
+
+    /// Decrypt and write one chunk. Returns `true` once the last chunk
+    /// has been written. A chunk for another transfer, out of order
+    /// (including one arriving after a gap) or disagreeing about the
+    /// chunk count is rejected without writing anything.
+    ///
+    /// Resumable receivers take chunks through
+    /// [`accept_at`](Self::accept_at) instead.
+    pub fn accept(&mut self, chunk: &EncryptedChunk) -> Result<bool> {
+        if self.manifest.is_some() {
+            return Err(anyhow::anyhow!("resumable receiver: use accept_at"));
+        }
+        if chunk.file_id != self.file_id {
+            return Err(anyhow::anyhow!("chunk belongs to another transfer"));
+        }
+        let total = self.total_chunks.unwrap_or(chunk.total_chunks);
+        if chunk.total_chunks != total {
+            return Err(anyhow::anyhow!("chunk count changed mid-transfer"));
+        }
+        if self.next_seq == total {
+            return Err(anyhow::anyhow!("transfer already complete"));
+        }
+        if chunk.seq != self.next_seq {
+            return Err(anyhow::anyhow!(
+                "chunk out of order: expected {}, got {}",
+                self.next_seq,
+                chunk.seq
+            ));
+        }
+
+        let mut plaintext = open_chunk(&self.key, chunk, total)?;
+        // Only an authenticated chunk may fix the count; a forged first
+        // chunk would otherwise lock out the real transfer.
+        self.total_chunks = Some(total);
+        let written = self.sink.write_all(&plaintext);
+        plaintext.zeroize();
+        written.context("file write error")?;
+
+        self.next_seq += 1;
+        Ok(self.next_seq == total)
+    }
+
+    /// Finish the transfer and hand back the sink. Errors if any chunks
+    /// are still missing.
+    pub fn finish(mut self) -> Result<W> {
+        match self.total_chunks {
+            Some(total) if self.next_seq == total => {
+                self.sink.flush()?;
+                Ok(self.sink)
+            }
+            Some(total) => Err(anyhow::anyhow!(
+                "transfer incomplete: missing chunks {}..{}",
+                self.next_seq,
+                total
+            )),
+            None => Err(anyhow::anyhow!("transfer incomplete: no chunks received")),
+        }
+    }
+}
+
+impl<W: Read + Write + Seek> FileTransferReceiver<W> {
+    /// Resumable receiver driven by `manifest` — a fresh one from the
+    /// sender, or one loaded back with [`TransferManifest::load`] after
+    /// an interruption. `sink` must be the same partially written file
+    /// in the latter case. Chunks may then arrive in any order.
+    ///
+    /// Errors if the manifest's length, chunk size, chunk count and
+    /// bitmap don't agree with each other.
+    pub fn resume(exporter_secret: &[u8; 32], manifest: TransferManifest, sink: W) -> Result<Self> {
+        manifest.check_consistent()?;
+        Ok(FileTransferReceiver {
+            key: transfer_key(exporter_secret, manifest.file_id),
+            file_id: manifest.file_id,
+            total_chunks: Some(manifest.total_chunks),
+            next_seq: 0,
+            sink,
+            manifest: Some(manifest),
+        })
+    }
+
+    /// Current transfer state; persist it with [`TransferManifest::save`].
+    pub fn manifest(&self) -> Option<&TransferManifest> {
+        self.manifest.as_ref()
+    }
+
+    /// Chunks still to request from the sender.
+    pub fn missing_chunks(&self) -> Vec<u64> {
+        self.manifest
+            .as_ref()
+            .map(TransferManifest::missing_chunks)
+            .unwrap_or_default()
+    }
+
+    /// Decrypt one chunk and write it at its position in the file.
+    /// Duplicates of an already received chunk are ignored. Returns
+    /// `true` once every chunk is in.
+    pub fn accept_at(&mut self, chunk: &EncryptedChunk) -> Result<bool> {
+        let manifest = self
+            .manifest
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("not a resumable receiver"))?;
+        if chunk.file_id != manifest.file_id || chunk.total_chunks != manifest.total_chunks {
+            return Err(anyhow::anyhow!("chunk does not belong to this transfer"));
+        }
+        let expected_len = manifest.chunk_len(chunk.seq)?;
+        if manifest.is_received(chunk.seq) {
+            return Ok(manifest.is_complete());
+        }
+        let mut plaintext = open_chunk(&self.key, chunk, manifest.total_chunks)?;
+        if plaintext.len() != expected_len {
+            plaintext.zeroize();
+            return Err(anyhow::anyhow!("chunk {} has the wrong length", chunk.seq));
+        }
+        let written = self
+            .sink
+            .seek(SeekFrom::Start(chunk.seq * manifest.chunk_size as u64))
+            .and_then(|_| self.sink.write_all(&plaintext));
+        plaintext.zeroize();
+        written.context("file write error")?;
+        manifest.mark_received(chunk.seq);
+        Ok(manifest.is_complete())
+    }
+
+    /// Check the assembled file against the manifest's BLAKE3 hash and
+    /// hand back the sink. Errors if chunks are missing or the hash
+    /// doesn't match.
+    pub fn finish_verified(mut self) -> Result<W> {
+        let manifest = self
+            .manifest
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("not a resumable receiver"))?;
+        let missing = manifest.missing_chunks();
+        if !missing.is_empty() {
+            return Err(anyhow::anyhow!(
+                "transfer incomplete: {} chunks missing",
+                missing.len()
+            ));
+        }
+        self.sink.flush()?;
+        self.sink.seek(SeekFrom::Start(0))?;
+        let mut hasher = blake3::Hasher::new();
+        std::io::copy(&mut (&mut self.sink).take(manifest.total_len), &mut hasher)
+            .context("re-reading assembled file")?;
+        if hasher.finalize().as_bytes() != &manifest.file_hash {
+            return Err(anyhow::anyhow!("File hash mismatch"));
+        }
+        Ok(self.sink)
+    }
+}
+
+fn open_chunk(
+    key: &SecretBox<[u8; 32]>,
+    chunk: &EncryptedChunk,
+    total_chunks: u64,
+) -> Result<Vec<u8>> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key.expose_secret()));
+    cipher
+        .decrypt(
+            Nonce::from_slice(&chunk.nonce),
+            Payload {
+                msg: &chunk.ciphertext,
+                aad: &chunk_aad(chunk.file_id, chunk.seq, total_chunks),
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("chunk {} failed to decrypt", chunk.seq))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+
+    fn random_file(len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        OsRng.fill_bytes(&mut data);
+        data
+    }
+
+    fn random_exporter() -> [u8; 32] {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        secret
+    }
+
+    #[test]
+    fn streams_ten_megabytes_in_order() {
+        let exporter = random_exporter();
+        let data = random_file(10 * 1024 * 1024);
+        let sender = FileTransferSender::new(&exporter, DEFAULT_CHUNK_SIZE).unwrap();
+        let mut receiver = FileTransferReceiver::new(&exporter, 7, Vec::new());
+
+        let mut done = false;
+        for chunk in sender.chunks(7, data.as_slice(), data.len() as u64) {
+            assert!(!done);
+            done = receiver.accept(&chunk.unwrap()).unwrap();
+        }
+        assert!(done);
+        assert_eq!(receiver.finish().unwrap(), data);
+    }
+
+    #[test]
+    fn reordered_and_missing_chunks_are_rejected() {
+        let exporter = random_exporter();
+        let data = random_file(4 * 1024);
+        let sender = FileTransferSender::new(&exporter, 1024).unwrap();
+        let chunks: Vec<EncryptedChunk> = sender
+            .chunks(9, data.as_slice(), data.len() as u64)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(chunks.len(), 4);
+
+        let mut receiver = FileTransferReceiver::new(&exporter, 9, Vec::new());
+        receiver.accept(&chunks[0]).unwrap();
+        assert!(receiver.accept(&chunks[2]).is_err());
+
+        // Relabelling chunk 2 as chunk 1 breaks the AEAD binding.
+        let mut relabelled = chunks[2].clone();
+        relabelled.seq = 1;
+        assert!(receiver.accept(&relabelled).is_err());
+
+        receiver.accept(&chunks[1]).unwrap();
+        assert!(receiver.finish().is_err());
+    }
+
+    #[test]
+    fn forged_chunk_does_not_pin_the_chunk_count() {
+        let exporter = random_exporter();
+        let data = random_file(3000);
+        let sender = FileTransferSender::new(&exporter, 1024).unwrap();
+        let chunks: Vec<EncryptedChunk> = sender
+            .chunks(13, data.as_slice(), data.len() as u64)
+            .collect::<Result<_>>()
+            .unwrap();
+
+        let mut receiver = FileTransferReceiver::new(&exporter, 13, Vec::new());
+        let mut forged = chunks[0].clone();
+        forged.total_chunks = 1;
+        assert!(receiver.accept(&forged).is_err());
+
+        for chunk in &chunks {
+            receiver.accept(chunk).unwrap();
+        }
+        assert_eq!(receiver.finish().unwrap(), data);
+    }
+
+    #[test]
+    fn chunks_only_open_under_the_same_exporter_secret() {
+        let data = random_file(100);
+        let sender = FileTransferSender::new(&random_exporter(), 1024).unwrap();
+        let chunk = sender
+            .chunks(10, data.as_slice(), data.len() as u64)
+            .next()
+            .unwrap()
+            .unwrap();
+        let mut receiver = FileTransferReceiver::new(&random_exporter(), 10, Vec::new());
+        assert!(receiver.accept(&chunk).is_err());
+    }
+
+    #[test]
+    fn interrupted_transfer_resumes_from_persisted_manifest() {
+        use std::io::Cursor;
+        use tempfile::TempDir;
+
+        let dir = TempDir::new().unwrap();
+        let mut keystore =
+            SecureKeyStore::new(dir.path().join("keystore.db"), b"test-keystore-passphrase")
+                .unwrap();
+        let exporter = random_exporter();
+        let mut source = Cursor::new(random_file(10 * 1024 + 100));
+        let sender = FileTransferSender::new(&exporter, 1024).unwrap();
+        let manifest = sender.manifest(11, &mut source).unwrap();
+        assert_eq!(manifest.total_chunks, 11);
+
+        // First session: chunks 3..=5 are lost, then the app is killed.
+        let mut receiver =
+            FileTransferReceiver::resume(&exporter, manifest.clone(), Cursor::new(Vec::new()))
+                .unwrap();
+        let all: Vec<u64> = (0..manifest.total_chunks).collect();
+        for chunk in sender.send_chunks(&manifest, &mut source, &all) {
+            let chunk = chunk.unwrap();
+            if (3..=5).contains(&chunk.seq) {
+                continue;
+            }
+            assert!(!receiver.accept_at(&chunk).unwrap());
+        }
+        receiver.manifest().unwrap().save(&mut keystore).unwrap();
+        let partial_file = receiver.sink;
+
+        // Second session: reload, request only what's missing.
+        let saved = TransferManifest::load(&mut keystore, 11).unwrap().unwrap();
+        let mut receiver = FileTransferReceiver::resume(&exporter, saved, partial_file).unwrap();
+        let missing = receiver.missing_chunks();
+        assert_eq!(missing, vec![3, 4, 5]);
+        for chunk in sender.send_chunks(&manifest, &mut source, &missing) {
+            receiver.accept_at(&chunk.unwrap()).unwrap();
+        }
+        let assembled = receiver.finish_verified().unwrap().into_inner();
+        assert_eq!(assembled, source.into_inner());
+        TransferManifest::remove(&mut keystore, 11).unwrap();
+    }
+
+    #[test]
+    fn assembled_file_must_match_manifest_hash() {
+        use std::io::Cursor;
+
+        let exporter = random_exporter();
+        let mut source = Cursor::new(random_file(3000));
+        let sender = FileTransferSender::new(&exporter, 1024).unwrap();
+        let mut manifest = sender.manifest(12, &mut source).unwrap();
+        manifest.file_hash[0] ^= 1;
+
+        let mut receiver =
+            FileTransferReceiver::resume(&exporter, manifest.clone(), Cursor::new(Vec::new()))
+                .unwrap();
+        for chunk in sender.send_chunks(&manifest, &mut source, &[0, 1, 2]) {
+            receiver.accept_at(&chunk.unwrap()).unwrap();
+        }
+        assert!(receiver.finish_verified().is_err());
+    }
+
+    #[test]
+    fn resume_rejects_inconsistent_manifests() {
+        use std::io::Cursor;
+
+        let exporter = random_exporter();
+        let sender = FileTransferSender::new(&exporter, 1024).unwrap();
+        let manifest = sender
+            .manifest(14, &mut Cursor::new(random_file(3000)))
+            .unwrap();
+        let resume = |manifest: TransferManifest| {
+            FileTransferReceiver::resume(&exporter, manifest, Cursor::new(Vec::new()))
+        };
+        assert!(resume(manifest.clone()).is_ok());
+
+        let mut zero_chunk_size = manifest.clone();
+        zero_chunk_size.chunk_size = 0;
+        assert!(resume(zero_chunk_size).is_err());
+
+        // More chunks than the length needs: chunk_len would underflow.
+        let mut extra_chunks = manifest.clone();
+        extra_chunks.total_chunks = 8;
+        assert!(resume(extra_chunks).is_err());
+
+        let mut huge_chunk_count = manifest.clone();
+        huge_chunk_count.total_chunks = u64::MAX;
+        huge_chunk_count.received = Vec::new();
+        assert!(resume(huge_chunk_count).is_err());
+
+        // Bitmap too short: is_received would index past its end.
+        let mut short_bitmap = manifest.clone();
+        short_bitmap.total_len = 20 * 1024;
+        short_bitmap.total_chunks = 20;
+        assert!(resume(short_bitmap).is_err());
+
+        let mut long_bitmap = manifest;
+        long_bitmap.received.push(0);
+        assert!(resume(long_bitmap).is_err());
+    }
+}
@@ -1,14 +1,48 @@
 use anyhow::Result;
 use secrecy::SecretBox;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::groups::group_manager::GroupId;
+use crate::identity::identity_key::IdentityId;
 use crate::security::secure_rng;
 
-use chacha20poly1305::aead::Aead;
+use chacha20poly1305::aead::{Aead, Payload};
 use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
 use secrecy::ExposeSecret;
+use zeroize::Zeroize;
+
+/// BLAKE3 `derive_key` contexts for sender-keys chains: the starting
+/// chain key, a message key, and the step to the next chain key.
+const SENDER_CHAIN_CONTEXT: &str = "qubee group sender chain v1";
+const SENDER_MESSAGE_KEY_CONTEXT: &str = "qubee group sender message key v1";
+const SENDER_CHAIN_STEP_CONTEXT: &str = "qubee group sender chain step v1";
+
+/// How far ahead of the last index seen from a sender a message may
+/// be, and how many skipped message keys a chain keeps.
+pub const MAX_SENDER_CHAIN_SKIP: u32 = 1000;
+
+/// How many keys per group `GroupCrypto` keeps: the current one plus
+/// `GROUP_KEY_HISTORY - 1` predecessors. Messages encrypted just before
+/// a rotation are still in flight when it lands; a short history lets
+/// them decrypt without keeping old keys around indefinitely.
+pub const GROUP_KEY_HISTORY: usize = 4;
+
+/// Position of a key in a group's rotation sequence. Starts at 0 for
+/// the first key and increments on every rotation or install.
+///
+/// The counter is local to one `GroupCrypto`: two members only agree
+/// on it if they've seen the same sequence of keys (a late joiner
+/// starts at 0). It's a lookup hint, not an identifier — see
+/// [`GroupCrypto::decrypt_message`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GroupKeyEpoch(pub u64);
+
+impl GroupKeyEpoch {
+    fn next(self) -> Self {
+        GroupKeyEpoch(self.0 + 1)
+    }
+}
 
 /// Symmetric key used for encrypting group messages. It stores the raw
 /// 256‑bit secret along with the creation timestamp. In a complete
@@ -20,6 +54,11 @@ pub struct GroupKey {
     pub key: SecretBox<[u8; 32]>,
     /// Unix timestamp when the key was created.
     pub created_at: u64,
+    /// Epoch this key was installed at.
+    pub epoch: GroupKeyEpoch,
+    /// The group's `version` when this key was installed. Frames
+    /// claiming an older generation can't have been sealed under it.
+    pub generation: u64,
 }
 
 /// Metadata describing a key rotation event. When a group key is
@@ -32,98 +71,143 @@ pub struct GroupKeyRotation {
     pub old_key_created_at: u64,
     /// Creation time of the new key.
     pub new_key_created_at: u64,
+    /// Epoch of the new key.
+    pub new_epoch: GroupKeyEpoch,
     /// Timestamp when the rotation occurred.
     pub rotated_at: u64,
 }
 
 /// Manages symmetric keys for group chats. Keys are stored in a simple
-/// in‑memory map keyed by `GroupId`. In a production system keys
+/// in‑memory map keyed by `GroupId`, each entry holding the last
+/// [`GROUP_KEY_HISTORY`] keys oldest first. In a production system keys
 /// should be stored in secure hardware or an encrypted keystore and
 /// derived via a ratchet mechanism. This module provides just enough
 /// functionality to allow the rest of the group manager to compile.
 pub struct GroupCrypto {
-    keys: HashMap<GroupId, GroupKey>,
+    keys: HashMap<GroupId, VecDeque<GroupKey>>,
+    sending_chains: HashMap<SenderChainSlot, SenderChain>,
+    receiving_chains: HashMap<SenderChainSlot, SenderChain>,
 }
 
+type SenderChainSlot = (GroupId, GroupKeyEpoch, IdentityId);
+
 impl GroupCrypto {
     /// Create a new `GroupCrypto` instance with no keys loaded.
     pub fn new() -> Result<Self> {
         Ok(GroupCrypto {
             keys: HashMap::new(),
+            sending_chains: HashMap::new(),
+            receiving_chains: HashMap::new(),
         })
     }
 
     /// Generate a new symmetric key for a group. If a key already
-    /// exists for the group it will be overwritten. Returns `Ok(())`
-    /// on success.
-    pub fn create_group_key(&mut self, group_id: GroupId) -> Result<()> {
+    /// exists for the group it is superseded (and kept as history, like
+    /// a rotation). Returns `Ok(())` on success.
+    pub fn create_group_key(&mut self, group_id: GroupId, generation: u64) -> Result<()> {
         let key_bytes = secure_rng::random::array::<32>()?;
-        let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-        let group_key = GroupKey {
-            key: SecretBox::new(Box::new(key_bytes)),
-            created_at,
-        };
-        self.keys.insert(group_id, group_key);
+        self.push_key(group_id, key_bytes, generation);
         Ok(())
     }
 
-    /// Rotate the symmetric key for a group. The old key is replaced
-    /// with a newly generated one. Returns a `GroupKeyRotation`
-    /// describing the change.
-    pub fn rotate_group_key(&mut self, group_id: GroupId) -> Result<GroupKeyRotation> {
-        let old_created_at = self.keys.get(&group_id).map(|k| k.created_at).unwrap_or(0);
+    /// Rotate the symmetric key for a group. A newly generated key
+    /// becomes current under the next epoch; the old one is kept for
+    /// decrypting in-flight messages until it ages out of the history.
+    /// Returns a `GroupKeyRotation` describing the change.
+    pub fn rotate_group_key(
+        &mut self,
+        group_id: GroupId,
+        generation: u64,
+    ) -> Result<GroupKeyRotation> {
+        let old_created_at = self
+            .get_group_key(&group_id)
+            .map(|k| k.created_at)
+            .unwrap_or(0);
         let new_key_bytes = secure_rng::random::array::<32>()?;
-        let new_created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-        let group_key = GroupKey {
-            key: SecretBox::new(Box::new(new_key_bytes)),
-            created_at: new_created_at,
-        };
-        self.keys.insert(group_id, group_key);
+        let new_key = self.push_key(group_id, new_key_bytes, generation);
         Ok(GroupKeyRotation {
             group_id,
             old_key_created_at: old_created_at,
-            new_key_created_at: new_created_at,
-            rotated_at: new_created_at,
+            new_key_created_at: new_key.created_at,
+            new_epoch: new_key.epoch,
+            rotated_at: new_key.created_at,
         })
     }
 
     /// Install a group key received over the network (e.g. via the
-    /// invite handshake's KEM-wrapped key transport). Replaces any
-    /// existing key for the group.
-    pub fn set_group_key(&mut self, group_id: GroupId, key_bytes: [u8; 32]) {
+    /// invite handshake's KEM-wrapped key transport). It becomes the
+    /// current key under the next epoch.
+    pub fn set_group_key(&mut self, group_id: GroupId, key_bytes: [u8; 32], generation: u64) {
+        self.push_key(group_id, key_bytes, generation);
+    }
+
+    /// Make `key_bytes` the group's current key, evicting the oldest
+    /// history entry if the ring is full.
+    fn push_key(&mut self, group_id: GroupId, key_bytes: [u8; 32], generation: u64) -> &GroupKey {
         let created_at = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .map(|d| d.as_secs())
             .unwrap_or(0);
-        self.keys.insert(
-            group_id,
-            GroupKey {
-                key: SecretBox::new(Box::new(key_bytes)),
-                created_at,
-            },
-        );
+        let ring = self.keys.entry(group_id).or_default();
+        let epoch = ring.back().map(|k| k.epoch.next()).unwrap_or_default();
+        ring.push_back(GroupKey {
+            key: SecretBox::new(Box::new(key_bytes)),
+            created_at,
+            epoch,
+            generation,
+        });
+        if ring.len() > GROUP_KEY_HISTORY {
+            ring.pop_front();
+        }
+        let oldest = ring.front().expect("just pushed").epoch;
+        self.sending_chains
+            .retain(|(group, chain_epoch, _), _| *group != group_id || *chain_epoch == epoch);
+        self.receiving_chains
+            .retain(|(group, chain_epoch, _), _| *group != group_id || *chain_epoch >= oldest);
+        ring.back().expect("just pushed")
     }
 
     /// Read a copy of the raw group-key bytes for transport to a new
     /// member. Callers should immediately KEM-wrap the result and not
     /// hold onto the plaintext.
     pub fn export_group_key(&self, group_id: &GroupId) -> Option<[u8; 32]> {
-        self.keys.get(group_id).map(|k| *k.key.expose_secret())
+        self.get_group_key(group_id).map(|k| *k.key.expose_secret())
+    }
+
+    /// Every retained key for the group as `(generation, key bytes)`,
+    /// newest first. Used to open frames sealed just before a rotation.
+    pub fn export_retained_keys(&self, group_id: &GroupId) -> Vec<(u64, [u8; 32])> {
+        self.keys
+            .get(group_id)
+            .map(|ring| {
+                ring.iter()
+                    .rev()
+                    .map(|k| (k.generation, *k.key.expose_secret()))
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
     /// Retrieve the current key for a group, if any.
     pub fn get_group_key(&self, group_id: &GroupId) -> Option<&GroupKey> {
-        self.keys.get(group_id)
+        self.keys.get(group_id).and_then(|ring| ring.back())
+    }
+
+    /// Epoch of the group's current key, if it has one.
+    pub fn current_epoch(&self, group_id: &GroupId) -> Option<GroupKeyEpoch> {
+        self.get_group_key(group_id).map(|k| k.epoch)
     }
 
     /// Encrypt a plaintext message for the given group using the
     /// current group key. A fresh nonce is generated for each
-    /// encryption and is prefixed to the returned ciphertext. The
-    /// resulting vector has the format `[nonce | ciphertext]`.
+    /// encryption. The resulting vector has the format
+    /// `[epoch (8, LE) | nonce (12) | ciphertext]`; the epoch is also
+    /// the AEAD associated data, so it can't be rewritten in transit.
     pub fn encrypt_message(&self, group_id: &GroupId, plaintext: &[u8]) -> Result<Vec<u8>> {
         let key = self
             .get_group_key(group_id)
             .ok_or_else(|| anyhow::anyhow!("Group key not found"))?;
+        let epoch_bytes = key.epoch.0.to_le_bytes();
         // Derive a cipher from the 256‑bit group key
         let cipher = ChaCha20Poly1305::new(key.key.expose_secret().into());
         // Generate a random 96‑bit nonce
@@ -132,31 +216,382 @@ impl GroupCrypto {
         // Encrypt the plaintext. chacha20poly1305::Error doesn't impl
         // Display so we can't use anyhow's `.context`; map manually.
         let ciphertext = cipher
-            .encrypt(nonce, plaintext)
+            .encrypt(
+                nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &epoch_bytes,
+                },
+            )
             .map_err(|e| anyhow::anyhow!("Group message encryption failed: {e:?}"))?;
-        // Prepend nonce to ciphertext
-        let mut output = Vec::with_capacity(12 + ciphertext.len());
+        // Prepend epoch and nonce to ciphertext
+        let mut output = Vec::with_capacity(8 + 12 + ciphertext.len());
+        output.extend_from_slice(&epoch_bytes);
         output.extend_from_slice(&nonce_bytes);
         output.extend_from_slice(&ciphertext);
         Ok(output)
     }
 
-    /// Decrypt a group message. Expects the input to have the nonce
-    /// prepended as returned by `encrypt_message`. Returns the
-    /// plaintext on success.
+    /// Decrypt a group message in the format produced by
+    /// `encrypt_message`. Returns the plaintext on success.
+    ///
+    /// The key at the tagged epoch is tried first. Because epochs are
+    /// local counters (see [`GroupKeyEpoch`]), a sender whose count
+    /// differs from ours can tag a key we hold under another number,
+    /// so the remaining retained keys are tried newest first after
+    /// that. At most [`GROUP_KEY_HISTORY`] AEAD attempts.
     pub fn decrypt_message(&self, group_id: &GroupId, data: &[u8]) -> Result<Vec<u8>> {
-        if data.len() < 12 {
+        if data.len() < 8 + 12 {
             return Err(anyhow::anyhow!("Ciphertext too short"));
         }
-        let key = self
-            .get_group_key(group_id)
+        let ring = self
+            .keys
+            .get(group_id)
+            .filter(|ring| !ring.is_empty())
             .ok_or_else(|| anyhow::anyhow!("Group key not found"))?;
-        let cipher = ChaCha20Poly1305::new(key.key.expose_secret().into());
-        let (nonce_bytes, ciphertext) = data.split_at(12);
+        let (epoch_bytes, rest) = data.split_at(8);
+        let epoch = GroupKeyEpoch(u64::from_le_bytes(epoch_bytes.try_into()?));
+        let (nonce_bytes, ciphertext) = rest.split_at(12);
         let nonce = Nonce::from_slice(nonce_bytes);
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| anyhow::anyhow!("Group message decryption failed: {e:?}"))?;
-        Ok(plaintext)
+
+        let tagged = ring.iter().find(|k| k.epoch == epoch);
+        let others = ring.iter().rev().filter(|k| k.epoch != epoch);
+        for key in tagged.into_iter().chain(others) {
+            let cipher = ChaCha20Poly1305::new(key.key.expose_secret().into());
+            if let Ok(plaintext) = cipher.decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: epoch_bytes,
+                },
+            ) {
+                return Ok(plaintext);
+            }
+        }
+        Err(anyhow::anyhow!(
+            "Group message decryption failed: no retained key for epoch {}",
+            epoch.0
+        ))
+    }
+
+    /// Sender-keys variant of [`encrypt_message`](Self::encrypt_message).
+    /// Each sender runs a hash chain per group key: the chain starts at
+    /// `BLAKE3-derive_key(SENDER_CHAIN_CONTEXT, group_key || sender_id)`
+    /// and every message steps it, so the key for message `n` is used
+    /// once and then forgotten: a leaked chain state doesn't open earlier
+    /// messages. (A leaked group key still opens every chain under it,
+    /// which is what key rotation bounds.) The output is
+    /// `[sender_id (32) | epoch (8, LE) | index (4, LE) | nonce (12) | ciphertext]`
+    /// with everything before the nonce as associated data.
+    ///
+    /// Every member can derive every sender's chain, so this alone does
+    /// not prove authorship — pair it with the sender's signature (see
+    /// [`GroupManager::encrypt_group_message_authenticated`](crate::groups::group_manager::GroupManager::encrypt_group_message_authenticated)).
+    /// What it adds is that a ciphertext can't be re-attributed to a
+    /// different sender without failing to decrypt.
+    pub fn encrypt_sender_message(
+        &mut self,
+        group_id: &GroupId,
+        sender_id: &IdentityId,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>> {
+        let key = self
+            .keys
+            .get(group_id)
+            .and_then(|ring| ring.back())
+            .ok_or_else(|| anyhow::anyhow!("Group key not found"))?;
+        let chain = self
+            .sending_chains
+            .entry((*group_id, key.epoch, *sender_id))
+            .or_insert_with(|| SenderChain::start(key, sender_id));
+        let index = chain.next_index;
+        let next_index = index
+            .checked_add(1)
+            .ok_or_else(|| anyhow::anyhow!("Sender chain exhausted; rotate the group key"))?;
+        let (mut message_key, next_chain_key) = chain_step(chain.chain_key.expose_secret());
+        chain.chain_key = SecretBox::new(Box::new(next_chain_key));
+        chain.next_index = next_index;
+
+        let header = sender_header(sender_id, key.epoch, index);
+        let nonce_bytes = secure_rng::random::array::<12>()?;
+        let sealed = ChaCha20Poly1305::new(&message_key.into()).encrypt(
+            Nonce::from_slice(&nonce_bytes),
+            Payload {
+                msg: plaintext,
+                aad: &header,
+            },
+        );
+        message_key.zeroize();
+        let ciphertext =
+            sealed.map_err(|e| anyhow::anyhow!("Group message encryption failed: {e:?}"))?;
+        let mut output = header;
+        output.extend_from_slice(&nonce_bytes);
+        output.extend_from_slice(&ciphertext);
+        Ok(output)
+    }
+
+    /// Inverse of [`encrypt_sender_message`](Self::encrypt_sender_message).
+    /// Returns the *claimed* sender id alongside the plaintext; the
+    /// caller still has to verify the sender's signature.
+    ///
+    /// Each index opens at most once: a replayed message fails, as does
+    /// one more than [`MAX_SENDER_CHAIN_SKIP`] ahead of the last index
+    /// seen from that sender. Keys for skipped indices are kept (at most
+    /// `MAX_SENDER_CHAIN_SKIP` per chain) so out-of-order delivery works.
+    pub fn decrypt_sender_message(
+        &mut self,
+        group_id: &GroupId,
+        data: &[u8],
+    ) -> Result<(IdentityId, Vec<u8>)> {
+        if data.len() < SENDER_HEADER_LEN + 12 {
+            return Err(anyhow::anyhow!("Ciphertext too short"));
+        }
+        let (header, rest) = data.split_at(SENDER_HEADER_LEN);
+        let sender_id = IdentityId::from(<[u8; 32]>::try_from(&header[..32])?);
+        let epoch = GroupKeyEpoch(u64::from_le_bytes(header[32..40].try_into()?));
+        let index = u32::from_le_bytes(header[40..].try_into()?);
+        let (nonce_bytes, ciphertext) = rest.split_at(12);
+        let ring = self
+            .keys
+            .get(group_id)
+            .filter(|ring| !ring.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Group key not found"))?;
+
+        let mut replayed = false;
+        let tagged = ring.iter().find(|k| k.epoch == epoch);
+        let others = ring.iter().rev().filter(|k| k.epoch != epoch);
+        for key in tagged.into_iter().chain(others) {
+            let slot = (*group_id, key.epoch, sender_id);
+            let step = match receive_step(self.receiving_chains.get(&slot), key, &sender_id, index)
+            {
+                Ok(step) => step,
+                Err(ReceiveError::Replayed) => {
+                    replayed = true;
+                    continue;
+                }
+                Err(ReceiveError::TooFarAhead) => continue,
+            };
+            let opened = ChaCha20Poly1305::new(step.message_key.expose_secret().into()).decrypt(
+                Nonce::from_slice(nonce_bytes),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            );
+            if let Ok(plaintext) = opened {
+                let chain = self
+                    .receiving_chains
+                    .entry(slot)
+                    .or_insert_with(|| SenderChain::start(key, &sender_id));
+                step.commit(chain, index);
+                return Ok((sender_id, plaintext));
+            }
+        }
+        if replayed {
+            return Err(anyhow::anyhow!(
+                "Sender message {index} was already received"
+            ));
+        }
+        Err(anyhow::anyhow!(
+            "Group message decryption failed: no retained key for epoch {}",
+            epoch.0
+        ))
+    }
+}
+
+/// One sender's hash chain under one group key. `chain_key` is the key
+/// for index `next_index`; `skipped` holds message keys for indices
+/// passed over but not yet received.
+struct SenderChain {
+    next_index: u32,
+    chain_key: SecretBox<[u8; 32]>,
+    skipped: BTreeMap<u32, SecretBox<[u8; 32]>>,
+}
+
+impl SenderChain {
+    fn start(key: &GroupKey, sender_id: &IdentityId) -> Self {
+        let mut material = [0u8; 64];
+        material[..32].copy_from_slice(key.key.expose_secret());
+        material[32..].copy_from_slice(sender_id.as_ref());
+        let chain_key = blake3::derive_key(SENDER_CHAIN_CONTEXT, &material);
+        material.zeroize();
+        SenderChain {
+            next_index: 0,
+            chain_key: SecretBox::new(Box::new(chain_key)),
+            skipped: BTreeMap::new(),
+        }
+    }
+}
+
+/// `(message key, next chain key)` for the chain key of one index.
+fn chain_step(chain_key: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+    (
+        blake3::derive_key(SENDER_MESSAGE_KEY_CONTEXT, chain_key),
+        blake3::derive_key(SENDER_CHAIN_STEP_CONTEXT, chain_key),
+    )
+}
+
+type SkippedKey = (u32, SecretBox<[u8; 32]>);
+
+enum ReceiveError {
+    Replayed,
+    TooFarAhead,
+}
+
+/// The key for one received index, plus how the chain moves if that key
+/// turns out to open the message. Worked out without touching the
+/// chain so a forgery can't advance it.
+struct ReceiveStep {
+    message_key: SecretBox<[u8; 32]>,
+    /// `None` for a skipped index; otherwise the chain key after this
+    /// index and the keys for the indices passed over to reach it.
+    advance: Option<(SecretBox<[u8; 32]>, Vec<SkippedKey>)>,
+}
+
+impl ReceiveStep {
+    fn commit(self, chain: &mut SenderChain, index: u32) {
+        match self.advance {
+            None => {
+                chain.skipped.remove(&index);
+            }
+            Some((chain_key, skipped)) => {
+                chain.chain_key = chain_key;
+                chain.next_index = index + 1;
+                chain.skipped.extend(skipped);
+                while chain.skipped.len() > MAX_SENDER_CHAIN_SKIP as usize {
+                    chain.skipped.pop_first();
+                }
+            }
+        }
+    }
+}
+
+fn receive_step(
+    chain: Option<&SenderChain>,
+    key: &GroupKey,
+    sender_id: &IdentityId,
+    index: u32,
+) -> Result<ReceiveStep, ReceiveError> {
+    let fresh;
+    let chain = match chain {
+        Some(chain) => chain,
+        None => {
+            fresh = SenderChain::start(key, sender_id);
+            &fresh
+        }
+    };
+    if index < chain.next_index {
+        let message_key = chain.skipped.get(&index).ok_or(ReceiveError::Replayed)?;
+        return Ok(ReceiveStep {
+            message_key: SecretBox::new(Box::new(*message_key.expose_secret())),
+            advance: None,
+        });
+    }
+    if index - chain.next_index > MAX_SENDER_CHAIN_SKIP || index == u32::MAX {
+        return Err(ReceiveError::TooFarAhead);
+    }
+    let mut chain_key = *chain.chain_key.expose_secret();
+    let mut skipped = Vec::new();
+    for skipped_index in chain.next_index..index {
+        let (message_key, next) = chain_step(&chain_key);
+        skipped.push((skipped_index, SecretBox::new(Box::new(message_key))));
+        chain_key = next;
+    }
+    let (message_key, next) = chain_step(&chain_key);
+    chain_key.zeroize();
+    Ok(ReceiveStep {
+        message_key: SecretBox::new(Box::new(message_key)),
+        advance: Some((SecretBox::new(Box::new(next)), skipped)),
+    })
+}
+
+const SENDER_HEADER_LEN: usize = 32 + 8 + 4;
+
+/// `sender_id || epoch || index`: the sender-keys prefix and AAD.
+fn sender_header(sender_id: &IdentityId, epoch: GroupKeyEpoch, index: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(SENDER_HEADER_LEN);
+    header.extend_from_slice(sender_id.as_ref());
+    header.extend_from_slice(&epoch.0.to_le_bytes());
+    header.extend_from_slice(&index.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_from_recent_epochs_still_decrypt() {
+        let mut crypto = GroupCrypto::new().unwrap();
+        let group_id = GroupId::from_bytes([9u8; 32]);
+        crypto.create_group_key(group_id, 1).unwrap();
+        assert_eq!(crypto.current_epoch(&group_id), Some(GroupKeyEpoch(0)));
+
+        let under_0 = crypto.encrypt_message(&group_id, b"epoch zero").unwrap();
+        crypto.rotate_group_key(group_id, 2).unwrap();
+        let under_1 = crypto.encrypt_message(&group_id, b"epoch one").unwrap();
+        let rotation = crypto.rotate_group_key(group_id, 3).unwrap();
+        assert_eq!(rotation.new_epoch, GroupKeyEpoch(2));
+        assert_eq!(crypto.current_epoch(&group_id), Some(GroupKeyEpoch(2)));
+
+        assert_eq!(
+            crypto.decrypt_message(&group_id, &under_1).unwrap(),
+            b"epoch one"
+        );
+        assert_eq!(
+            crypto.decrypt_message(&group_id, &under_0).unwrap(),
+            b"epoch zero"
+        );
+
+        // Rewriting the epoch tag breaks authentication.
+        let mut retagged = under_1.clone();
+        retagged[0] ^= 1;
+        assert!(crypto.decrypt_message(&group_id, &retagged).is_err());
+
+        // Once epoch 0 ages out of the history it's gone for good.
+        for generation in 0..GROUP_KEY_HISTORY as u64 {
+            crypto.rotate_group_key(group_id, 4 + generation).unwrap();
+        }
+        assert!(crypto.decrypt_message(&group_id, &under_0).is_err());
+    }
+
+    #[test]
+    fn sender_chain_opens_each_index_once() {
+        let group_id = GroupId::from_bytes([9u8; 32]);
+        let alice = IdentityId::from([1u8; 32]);
+        let mut sender = GroupCrypto::new().unwrap();
+        sender.create_group_key(group_id, 1).unwrap();
+        let mut receiver = GroupCrypto::new().unwrap();
+        receiver.set_group_key(group_id, sender.export_group_key(&group_id).unwrap(), 1);
+
+        let sent: Vec<Vec<u8>> = (0u8..3)
+            .map(|i| {
+                sender
+                    .encrypt_sender_message(&group_id, &alice, &[i])
+                    .unwrap()
+            })
+            .collect();
+        for (index, message) in sent.iter().enumerate() {
+            assert_eq!(message[40..44], (index as u32).to_le_bytes());
+        }
+
+        // Out of order is fine; the same index twice is not.
+        for (index, replays) in [(2, false), (0, false), (2, true), (1, false), (0, true)] {
+            let opened = receiver.decrypt_sender_message(&group_id, &sent[index]);
+            if replays {
+                assert!(opened.is_err());
+            } else {
+                assert_eq!(opened.unwrap(), (alice, vec![index as u8]));
+            }
+        }
+
+        // The index is authenticated.
+        let mut reindexed = sender
+            .encrypt_sender_message(&group_id, &alice, b"x")
+            .unwrap();
+        reindexed[40] = 7;
+        assert!(receiver
+            .decrypt_sender_message(&group_id, &reindexed)
+            .is_err());
     }
 }
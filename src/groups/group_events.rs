@@ -23,6 +23,42 @@ pub enum GroupEventType {
     InvitationCreated,
     /// The group settings were changed.
     SettingsChanged,
+    /// The owner handed ownership to another member and became an
+    /// admin.
+    OwnershipTransferred,
+    /// A member opened a poll.
+    PollCreated,
+    /// A poll was closed before its deadline.
+    PollClosed,
+}
+
+impl GroupEventType {
+    /// Fixed byte for the event hash, so the chain doesn't depend on
+    /// bincode's enum encoding. Never renumber.
+    fn code(&self) -> u8 {
+        match self {
+            GroupEventType::GroupCreated => 0,
+            GroupEventType::MemberAdded => 1,
+            GroupEventType::MemberRemoved => 2,
+            GroupEventType::MemberLeft => 3,
+            GroupEventType::RoleChanged => 4,
+            GroupEventType::InvitationCreated => 5,
+            GroupEventType::SettingsChanged => 6,
+            GroupEventType::OwnershipTransferred => 7,
+            GroupEventType::PollCreated => 8,
+            GroupEventType::PollClosed => 9,
+        }
+    }
+}
+
+const GROUP_EVENT_HASH_CONTEXT: &str = "qubee group event hash v1";
+const GROUP_EVENT_GENESIS_CONTEXT: &str = "qubee group event genesis v1";
+const GROUP_EVENT_TAG: &[u8] = b"qubee_group_event_v1";
+
+/// `prev_hash` of a group's first event. Derived from the group id so
+/// one group's log can't be spliced onto another's.
+pub fn genesis_hash(group_id: &GroupId) -> [u8; 32] {
+    blake3::derive_key(GROUP_EVENT_GENESIS_CONTEXT, group_id.as_ref())
 }
 
 /// Records a single group event along with contextual metadata.
@@ -38,6 +74,73 @@ pub struct GroupEvent {
     pub description: String,
     /// Unix timestamp when the event occurred.
     pub timestamp: u64,
+    /// Position in the group's log, from 0. Orders events logged in
+    /// the same second.
+    pub sequence: u64,
+    /// [`hash`](Self::hash) of the previous event, or
+    /// [`genesis_hash`] for the first. Chaining the events this way
+    /// makes deleting, reordering or editing a stored event detectable.
+    pub prev_hash: [u8; 32],
+}
+
+impl GroupEvent {
+    /// Hash over every field, including `prev_hash`.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_derive_key(GROUP_EVENT_HASH_CONTEXT);
+        hasher.update(GROUP_EVENT_TAG);
+        hasher.update(&[0u8]);
+        hasher.update(self.group_id.as_ref());
+        hasher.update(self.actor_id.as_ref());
+        hasher.update(&[self.event_type.code()]);
+        hasher.update(&(self.description.len() as u64).to_le_bytes());
+        hasher.update(self.description.as_bytes());
+        hasher.update(&self.timestamp.to_le_bytes());
+        hasher.update(&self.sequence.to_le_bytes());
+        hasher.update(&self.prev_hash);
+        *hasher.finalize().as_bytes()
+    }
+}
+
+/// A [`GroupEvent`] as stored before the log was hash-chained: no
+/// `sequence` or `prev_hash`, keyed `group_event_{group_id}_{timestamp}`.
+/// Read only to migrate such entries into the chain.
+#[derive(Deserialize)]
+pub(crate) struct LegacyGroupEvent {
+    pub group_id: GroupId,
+    pub actor_id: IdentityId,
+    pub event_type: GroupEventType,
+    pub description: String,
+    pub timestamp: u64,
+}
+
+/// Index of the first tampered event in `events` (expected in sequence
+/// order), or `None` if the chain is intact. A deleted event shows up
+/// where the gap is. An edited event is caught by its successor's
+/// `prev_hash` but reported at its own index; when the broken link is
+/// the successor's own `prev_hash` field, the link after it breaks too
+/// and the successor is reported instead. Nothing links to the newest
+/// event, so an edit to it isn't caught, and truncating the newest
+/// events leaves a valid shorter chain; callers that care compare
+/// against a head they trust.
+pub fn verify_chain(group_id: &GroupId, events: &[GroupEvent]) -> Option<usize> {
+    let mut expected_prev = genesis_hash(group_id);
+    for (index, event) in events.iter().enumerate() {
+        if event.group_id != *group_id || event.sequence != index as u64 {
+            return Some(index);
+        }
+        if event.prev_hash != expected_prev {
+            let own_link_edited = events
+                .get(index + 1)
+                .is_some_and(|next| next.prev_hash != event.hash());
+            return Some(if index == 0 || own_link_edited {
+                index
+            } else {
+                index - 1
+            });
+        }
+        expected_prev = event.hash();
+    }
+    None
 }
 
 /// An append‑only log of group events. This structure holds events
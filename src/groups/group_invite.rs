@@ -1,6 +1,8 @@
 use anyhow::{anyhow, Context, Result};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use bincode::Options;
 use blake3::Hasher;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use crate::groups::group_manager::{GroupId, GroupInvitation, QUBEE_MAX_GROUP_MEMBERS};
@@ -14,6 +16,34 @@ pub const QUBEE_URI_SCHEME: &str = "qubee";
 /// Host used for invite links: `qubee://invite/<token>`.
 pub const QUBEE_INVITE_HOST: &str = "invite";
 
+/// Longest deep-link token any link decoder will look at. Links
+/// arrive from QR codes and NFC tags, i.e. from anyone; this is well
+/// above the largest link we emit (an identity link, carrying an
+/// ML-DSA key and signature) and keeps a hostile one from costing
+/// more than a few KiB of work.
+pub const MAX_DEEP_LINK_TOKEN_LEN: usize = 16 * 1024;
+
+/// Decode the base64url token of a deep link into its bincode payload.
+/// `kind` names the link in errors ("invite", "identity").
+///
+/// Every length prefix in the payload is checked against the bytes
+/// actually present, so a token claiming a multi-gigabyte string fails
+/// at the prefix instead of allocating for it, and trailing bytes are
+/// refused.
+pub(crate) fn decode_link_token<T: DeserializeOwned>(token: &str, kind: &str) -> Result<T> {
+    if token.len() > MAX_DEEP_LINK_TOKEN_LEN {
+        return Err(anyhow!("{kind} token is too long"));
+    }
+    let bytes = URL_SAFE_NO_PAD
+        .decode(token.as_bytes())
+        .with_context(|| format!("{kind} token is not valid base64url"))?;
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(bytes.len() as u64)
+        .deserialize(&bytes)
+        .with_context(|| format!("{kind} payload could not be decoded"))
+}
+
 /// Compact, signed-ish payload that can be embedded in an invite link or
 /// rendered as a QR code. The `fingerprint` is a short BLAKE3 tag over the
 /// other fields, primarily for tamper detection of the link itself — the
@@ -71,11 +101,7 @@ impl InvitePayload {
             .ok_or_else(|| anyhow!("not a qubee invite link"))?;
         // Tolerate optional trailing `?foo=bar` (deep-link routing tags).
         let token = token.split(['?', '#']).next().unwrap_or(token);
-        let bytes = URL_SAFE_NO_PAD
-            .decode(token.as_bytes())
-            .context("invite token is not valid base64url")?;
-        let payload: InvitePayload =
-            bincode::deserialize(&bytes).context("invite payload could not be decoded")?;
+        let payload: InvitePayload = decode_link_token(token, "invite")?;
         if payload.fingerprint != payload.compute_fingerprint() {
             return Err(anyhow!("invite link fingerprint mismatch (corrupt link?)"));
         }
@@ -147,6 +173,25 @@ mod tests {
         assert!(InvitePayload::from_invite_link(&tampered).is_err());
     }
 
+    #[test]
+    fn rejects_payload_claiming_a_huge_length() {
+        // group_id, then a group_name length prefix of 2^62 bytes
+        // backed by nothing.
+        let mut bytes = vec![7u8; 32];
+        bytes.extend_from_slice(&(1u64 << 62).to_le_bytes());
+        bytes.extend_from_slice(b"Test Group");
+        let link = format!("qubee://invite/{}", URL_SAFE_NO_PAD.encode(&bytes));
+        let err = InvitePayload::from_invite_link(&link).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("could not be decoded"),
+            "{err:#}"
+        );
+
+        let link = format!("qubee://invite/{}", "A".repeat(MAX_DEEP_LINK_TOKEN_LEN + 1));
+        let err = InvitePayload::from_invite_link(&link).unwrap_err();
+        assert!(err.to_string().contains("too long"), "{err:#}");
+    }
+
     #[test]
     fn rejects_non_qubee_scheme() {
         assert!(InvitePayload::from_invite_link("https://example.com/foo").is_err());
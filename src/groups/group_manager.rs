@@ -1,17 +1,37 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use bincode::Options;
 use blake3::Hasher;
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use crate::groups::group_crypto::GroupCrypto;
-use crate::groups::group_events::{GroupEvent, GroupEventType};
-use crate::groups::group_permissions::{GroupPermissions, Permission, Role};
-use crate::identity::identity_key::{IdentityId, IdentityKey};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::groups::group_crypto::{GroupCrypto, GroupKeyEpoch};
+use crate::groups::group_events::{self, GroupEvent, GroupEventType, LegacyGroupEvent};
+use crate::groups::group_message::GROUP_MESSAGE_MAX_AGE_SECS;
+use crate::groups::group_permissions::{
+    ContentRejected, GroupPermissions, Permission, PermissionContext, PermissionDenied, Role,
+};
+use crate::identity::identity_key::{HybridSignature, IdentityId, IdentityKey, IdentityKeyPair};
+use crate::storage::framed::{self, Versioned};
 use crate::storage::secure_keystore::{KeyMetadata, KeyType, KeyUsage, SecureKeystore};
 use std::collections::HashMap as StdHashMap;
 
+const GROUP_SENDER_MESSAGE_TAG: &[u8] = b"qubee_group_sender_message_v1";
+
+/// Bytes a sender-keys message signature covers: a domain tag, the
+/// group id and the full ciphertext (which starts with the sender id).
+fn canonical_sender_message(group_id: &GroupId, ciphertext: &[u8]) -> Vec<u8> {
+    let mut out =
+        Vec::with_capacity(GROUP_SENDER_MESSAGE_TAG.len() + 1 + 32 + 8 + ciphertext.len());
+    out.extend_from_slice(GROUP_SENDER_MESSAGE_TAG);
+    out.push(0u8);
+    out.extend_from_slice(group_id.as_ref());
+    out.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    out.extend_from_slice(ciphertext);
+    out
+}
+
 /// Hard cap on the number of members in a single Qubee group, including
 /// the creator. Enforced both in `create_group` (via the default settings)
 /// and in `add_member` regardless of any user-supplied override. This
@@ -19,12 +39,33 @@ use std::collections::HashMap as StdHashMap;
 /// enough for out-of-band identity verification.
 pub const QUBEE_MAX_GROUP_MEMBERS: usize = 16;
 
+/// Furthest one incoming message may move the local Lamport clock. The
+/// clock is peer-supplied, so without a bound a single signed message
+/// could push it to `u64::MAX` and leave the group unable to order
+/// anything after it. A message further ahead is still delivered; the
+/// clock just advances by this much.
+pub const MAX_LAMPORT_JUMP: u64 = 1 << 20;
+
 /// Comprehensive group management system
 pub struct GroupManager {
     groups: HashMap<GroupId, Group>,
     member_groups: HashMap<IdentityId, HashSet<GroupId>>,
     group_crypto: GroupCrypto,
     keystore: SecureKeystore,
+    /// Last accepted send per member, for slow mode. In memory only: a
+    /// restart gives everyone one free message, which is harmless.
+    last_sends: HashMap<(GroupId, IdentityId), u64>,
+    /// `(next sequence, hash of the last event)` per group's event log.
+    /// Filled from the keystore the first time a group logs an event.
+    event_heads: HashMap<GroupId, (u64, [u8; 32])>,
+    /// Lamport clock per group, stamped on outgoing group messages.
+    /// Behind a mutex because the message path only borrows the
+    /// manager immutably. In memory only: after a restart, or on a
+    /// newly joined device, the first message received seeds it.
+    lamport_clocks: std::sync::Mutex<HashMap<GroupId, u64>>,
+    /// Last time each member used a permission whose role has a
+    /// cooldown. In memory only, like `last_sends`.
+    permission_uses: std::sync::Mutex<HashMap<(GroupId, IdentityId, Permission), u64>>,
 }
 
 /// Group information and configuration
@@ -43,6 +84,127 @@ pub struct Group {
     pub version: u64,
 }
 
+impl Versioned for Group {
+    /// 2: `GroupPermissions::role_contexts`. Version 1 and unframed
+    /// records still load, through `Group::from_stored`.
+    const SCHEMA_VERSION: u16 = 2;
+}
+
+impl Group {
+    /// Decode a stored group record written by this or an older build.
+    /// Older layouts are decoded as they were written and migrated.
+    fn from_stored(data: &[u8]) -> Result<Group> {
+        match framed::schema_version(data) {
+            // Written before framing existed: no slow mode, no role
+            // contexts.
+            None => {
+                let stored: StoredGroup<LegacyGroupPermissions, LegacyGroupSettings> =
+                    bincode::DefaultOptions::new()
+                        .with_fixint_encoding()
+                        .reject_trailing_bytes()
+                        .deserialize(data)
+                        .context("decode unframed group record")?;
+                Ok(stored.migrate())
+            }
+            Some(1) => {
+                let stored: StoredGroup<LegacyGroupPermissions, GroupSettings> =
+                    framed::decode_legacy(data, 1)?;
+                Ok(stored.migrate())
+            }
+            Some(_) => framed::decode::<Group>(data),
+        }
+    }
+}
+
+/// A persisted [`Group`] with the parts whose layout has changed left
+/// generic, so an older record decodes in the shape it was written in.
+/// Field order must match `Group`: bincode is positional.
+#[derive(Deserialize)]
+struct StoredGroup<P, S> {
+    id: GroupId,
+    name: String,
+    description: String,
+    group_type: GroupType,
+    members: HashMap<IdentityId, GroupMember>,
+    permissions: P,
+    settings: S,
+    metadata: GroupMetadata,
+    created_at: u64,
+    last_updated: u64,
+    version: u64,
+}
+
+impl<P: Into<GroupPermissions>, S: Into<GroupSettings>> StoredGroup<P, S> {
+    fn migrate(self) -> Group {
+        Group {
+            id: self.id,
+            name: self.name,
+            description: self.description,
+            group_type: self.group_type,
+            members: self.members,
+            permissions: self.permissions.into(),
+            settings: self.settings.into(),
+            metadata: self.metadata,
+            created_at: self.created_at,
+            last_updated: self.last_updated,
+            version: self.version,
+        }
+    }
+}
+
+/// [`GroupPermissions`] as stored before role contexts (unframed and
+/// schema 1 records).
+#[derive(Deserialize)]
+struct LegacyGroupPermissions {
+    role_permissions: HashMap<Role, HashSet<Permission>>,
+    custom_overrides: HashMap<String, HashSet<Permission>>,
+}
+
+impl From<LegacyGroupPermissions> for GroupPermissions {
+    fn from(legacy: LegacyGroupPermissions) -> Self {
+        GroupPermissions {
+            role_permissions: legacy.role_permissions,
+            custom_overrides: legacy.custom_overrides,
+            role_contexts: HashMap::new(),
+        }
+    }
+}
+
+/// [`GroupSettings`] as stored before slow mode.
+#[derive(Deserialize)]
+struct LegacyGroupSettings {
+    max_members: Option<usize>,
+    message_history_retention: Option<u64>,
+    allow_member_invites: bool,
+    require_admin_approval: bool,
+    disappearing_messages: Option<u64>,
+    read_receipts_enabled: bool,
+    typing_indicators_enabled: bool,
+    file_sharing_enabled: bool,
+    voice_calls_enabled: bool,
+    video_calls_enabled: bool,
+    screen_sharing_enabled: bool,
+}
+
+impl From<LegacyGroupSettings> for GroupSettings {
+    fn from(legacy: LegacyGroupSettings) -> Self {
+        GroupSettings {
+            max_members: legacy.max_members,
+            message_history_retention: legacy.message_history_retention,
+            allow_member_invites: legacy.allow_member_invites,
+            require_admin_approval: legacy.require_admin_approval,
+            disappearing_messages: legacy.disappearing_messages,
+            read_receipts_enabled: legacy.read_receipts_enabled,
+            typing_indicators_enabled: legacy.typing_indicators_enabled,
+            file_sharing_enabled: legacy.file_sharing_enabled,
+            voice_calls_enabled: legacy.voice_calls_enabled,
+            video_calls_enabled: legacy.video_calls_enabled,
+            screen_sharing_enabled: legacy.screen_sharing_enabled,
+            slow_mode: None,
+        }
+    }
+}
+
 /// Unique identifier for a group
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct GroupId([u8; 32]);
@@ -115,6 +277,112 @@ pub struct GroupSettings {
     pub voice_calls_enabled: bool,
     pub video_calls_enabled: bool,
     pub screen_sharing_enabled: bool,
+    /// Added after the first stored layout; older records migrate
+    /// through `LegacyGroupSettings`.
+    pub slow_mode: Option<SlowMode>,
+}
+
+/// Minimum spacing between one member's messages. Owners and admins
+/// are exempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlowMode {
+    pub interval_secs: u64,
+}
+
+/// How long slow mode is holding a member back; carried by
+/// [`GroupError::RetryAfter`].
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("slow mode: retry in {}s", .0.as_secs())]
+pub struct RetryAfter(pub Duration);
+
+/// Typed failures from [`GroupManager`]. Membership, role, invitation,
+/// permission, slow-mode, content and poll operations return it
+/// directly; the rest of the manager still returns `anyhow::Error` with
+/// a `GroupError` inside, to downcast.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum GroupError {
+    #[error("Group not found")]
+    NotFound,
+    /// Neither the member's role nor their custom grants include it.
+    #[error("Permission denied: lacks {0}")]
+    PermissionDenied(Permission),
+    /// The role has the permission, but one of its time restrictions
+    /// refuses it right now.
+    #[error(transparent)]
+    Restricted(#[from] PermissionDenied),
+    /// Adding or re-inviting an identity under a ban that hasn't
+    /// expired.
+    #[error("Member is banned from this group: {reason}")]
+    Banned {
+        reason: String,
+        /// `None` for a permanent ban.
+        until: Option<u64>,
+    },
+    #[error("Member not found in group")]
+    MemberNotFound,
+    #[error("Member is not active")]
+    MemberInactive,
+    #[error("Member already in group")]
+    MemberExists,
+    #[error("Group member limit reached (max {max} members)")]
+    MemberLimitReached { max: usize },
+    #[error("Owner cannot leave group without transferring ownership")]
+    OwnerCannotLeave,
+    /// Promoting members and transferring ownership are owner-only.
+    #[error("Only the group owner can do this")]
+    NotOwner,
+    #[error("Cannot transfer ownership to yourself")]
+    TransferToSelf,
+    /// Removing, banning or demoting the owner.
+    #[error("The group owner cannot be removed, banned or demoted")]
+    OwnerProtected,
+    #[error("Invitation not found locally")]
+    InvitationNotFound,
+    #[error("Invitation has expired")]
+    InvitationExpired,
+    #[error("Invitation has reached maximum uses")]
+    InvitationExhausted,
+    #[error("File sharing is disabled in this group")]
+    FileSharingDisabled,
+    /// The sender's role's content limits refuse the message.
+    #[error(transparent)]
+    ContentRejected(#[from] ContentRejected),
+    /// Slow mode: the member sent too recently.
+    #[error(transparent)]
+    RetryAfter(#[from] RetryAfter),
+    #[error("Poll not found")]
+    PollNotFound,
+    #[error("Poll is closed")]
+    PollClosed,
+    #[error("A poll needs between 2 and {max} options")]
+    PollOptionCount { max: usize },
+    #[error("Poll closing time is in the past")]
+    PollClosesInPast,
+    #[error("Poll has no option {0}")]
+    NoSuchPollOption(usize),
+    /// Our own Lamport clock would overflow.
+    #[error("Lamport clock out of range (local {local}, message {seen})")]
+    LamportOutOfRange { local: u64, seen: u64 },
+    /// The keystore, clock or group crypto failed underneath the
+    /// operation.
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl From<anyhow::Error> for GroupError {
+    fn from(err: anyhow::Error) -> Self {
+        // Helpers still return anyhow; keep a GroupError they raised.
+        match err.downcast::<GroupError>() {
+            Ok(group_error) => group_error,
+            Err(err) => GroupError::Internal(format!("{err:#}")),
+        }
+    }
+}
+
+impl From<std::time::SystemTimeError> for GroupError {
+    fn from(err: std::time::SystemTimeError) -> Self {
+        GroupError::Internal(err.to_string())
+    }
 }
 
 /// Group metadata
@@ -154,6 +422,42 @@ pub struct GroupInvitation {
     pub created_at: u64,
 }
 
+/// Most options a poll may offer.
+pub const MAX_POLL_OPTIONS: usize = 12;
+
+/// Identifier of a poll, unique within its group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PollId(pub [u8; 16]);
+
+/// A group poll. `votes` maps each voter to the index of the option
+/// they picked; voting again before `closes_at` replaces the earlier
+/// choice.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Poll {
+    pub id: PollId,
+    pub group_id: GroupId,
+    pub creator_id: IdentityId,
+    pub question: String,
+    pub options: Vec<String>,
+    pub votes: HashMap<IdentityId, usize>,
+    pub created_at: u64,
+    /// Unix seconds; no votes are accepted from this moment on.
+    pub closes_at: u64,
+}
+
+impl Versioned for Poll {
+    const SCHEMA_VERSION: u16 = 1;
+}
+
+/// Result of [`GroupManager::tally_poll`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PollTally {
+    /// Votes per option, in the poll's option order.
+    pub counts: Vec<usize>,
+    pub total_votes: usize,
+    pub closed: bool,
+}
+
 /// Group join request
 #[derive(Clone, Serialize, Deserialize)]
 pub struct GroupJoinRequest {
@@ -174,6 +478,10 @@ impl GroupManager {
             member_groups: HashMap::new(),
             group_crypto,
             keystore,
+            last_sends: HashMap::new(),
+            event_heads: HashMap::new(),
+            lamport_clocks: std::sync::Mutex::new(HashMap::new()),
+            permission_uses: std::sync::Mutex::new(HashMap::new()),
         })
     }
 
@@ -232,7 +540,8 @@ impl GroupManager {
         };
 
         // Generate group key
-        self.group_crypto.create_group_key(group_id)?;
+        self.group_crypto
+            .create_group_key(group_id, group.version)?;
 
         // Store group
         self.groups.insert(group_id, group);
@@ -268,18 +577,30 @@ impl GroupManager {
         new_member_key: IdentityKey,
         display_name: String,
         role: Role,
-    ) -> Result<()> {
+    ) -> Result<(), GroupError> {
         // Check if admin has permission to add members
         self.check_permission(group_id, admin_id, Permission::AddMembers)?;
 
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
 
-        // Check if member already exists
-        if group.members.contains_key(&new_member_id) {
-            return Err(anyhow::anyhow!("Member already in group"));
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+        // An existing entry blocks the add, except a ban that has run
+        // out: that entry is replaced, which clears the ban.
+        let mut replaces_expired_ban = false;
+        if let Some(existing) = group.members.get(&new_member_id) {
+            match &existing.member_status {
+                MemberStatus::Banned { reason, until }
+                    if until.is_none_or(|until| until > current_time) =>
+                {
+                    return Err(GroupError::Banned {
+                        reason: reason.clone(),
+                        until: *until,
+                    });
+                }
+                MemberStatus::Banned { .. } => replaces_expired_ban = true,
+                _ => return Err(GroupError::MemberExists),
+            }
         }
 
         // Check member limit. The group's own setting may be lower, but the
@@ -289,15 +610,10 @@ impl GroupManager {
             .max_members
             .map(|n| n.min(QUBEE_MAX_GROUP_MEMBERS))
             .unwrap_or(QUBEE_MAX_GROUP_MEMBERS);
-        if group.members.len() >= effective_cap {
-            return Err(anyhow::anyhow!(
-                "Group member limit reached (max {} members)",
-                effective_cap
-            ));
+        if group.members.len() - usize::from(replaces_expired_ban) >= effective_cap {
+            return Err(GroupError::MemberLimitReached { max: effective_cap });
         }
 
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-
         let new_member = GroupMember {
             identity_id: new_member_id,
             identity_key: new_member_key,
@@ -338,6 +654,7 @@ impl GroupManager {
         )?;
 
         self.store_group_securely(&group_id)?;
+        self.record_permission_use(group_id, admin_id, Permission::AddMembers)?;
 
         Ok(())
     }
@@ -360,10 +677,7 @@ impl GroupManager {
         new_version: u64,
     ) -> Result<()> {
         let new_member_id = new_member.identity_id;
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
         if group.members.contains_key(&new_member_id) {
             // Idempotent: nothing to insert. Still adopt the inviter's
             // version in case a duplicate broadcast carries a newer
@@ -381,10 +695,7 @@ impl GroupManager {
             .map(|n| n.min(QUBEE_MAX_GROUP_MEMBERS))
             .unwrap_or(QUBEE_MAX_GROUP_MEMBERS);
         if group.members.len() >= effective_cap {
-            return Err(anyhow::anyhow!(
-                "Group member limit reached (max {} members)",
-                effective_cap,
-            ));
+            return Err(GroupError::MemberLimitReached { max: effective_cap }.into());
         }
         group.members.insert(new_member_id, new_member);
         group.last_updated = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
@@ -406,19 +717,16 @@ impl GroupManager {
         admin_id: IdentityId,
         member_id: IdentityId,
         reason: String,
-    ) -> Result<()> {
+    ) -> Result<(), GroupError> {
         // Check permissions
         self.check_permission(group_id, admin_id, Permission::RemoveMembers)?;
 
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
 
         // Cannot remove the owner
         if let Some(member) = group.members.get(&member_id) {
             if member.role == Role::Owner {
-                return Err(anyhow::anyhow!("Cannot remove group owner"));
+                return Err(GroupError::OwnerProtected);
             }
         }
 
@@ -439,7 +747,8 @@ impl GroupManager {
         group.version += 1;
 
         // Rotate group key for backward secrecy
-        self.group_crypto.rotate_group_key(group_id)?;
+        self.group_crypto
+            .rotate_group_key(group_id, group.version)?;
 
         // Log event
         self.log_group_event(
@@ -450,6 +759,58 @@ impl GroupManager {
         )?;
 
         self.store_group_securely(&group_id)?;
+        self.record_permission_use(group_id, admin_id, Permission::RemoveMembers)?;
+
+        Ok(())
+    }
+
+    /// Ban a member until `until` (Unix seconds), or for good with
+    /// `None`. Like [`remove_member`](Self::remove_member) it rotates
+    /// the group key; unlike removal, [`add_member`](Self::add_member)
+    /// and invitation joins are refused while the ban lasts.
+    pub fn ban_member(
+        &mut self,
+        group_id: GroupId,
+        admin_id: IdentityId,
+        member_id: IdentityId,
+        reason: String,
+        until: Option<u64>,
+    ) -> Result<(), GroupError> {
+        self.check_permission(group_id, admin_id, Permission::BanMembers)?;
+
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
+        let member = group
+            .members
+            .get_mut(&member_id)
+            .ok_or(GroupError::MemberNotFound)?;
+        if member.role == Role::Owner {
+            return Err(GroupError::OwnerProtected);
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        member.member_status = MemberStatus::Banned {
+            reason: reason.clone(),
+            until,
+        };
+        member.last_seen = now;
+        if let Some(member_groups) = self.member_groups.get_mut(&member_id) {
+            member_groups.remove(&group_id);
+        }
+        group.last_updated = now;
+        group.version += 1;
+
+        self.group_crypto
+            .rotate_group_key(group_id, group.version)?;
+
+        self.log_group_event(
+            group_id,
+            admin_id,
+            GroupEventType::MemberRemoved,
+            format!("Member {} banned: {}", member_id, reason),
+        )?;
+
+        self.store_group_securely(&group_id)?;
+        self.record_permission_use(group_id, admin_id, Permission::BanMembers)?;
 
         Ok(())
     }
@@ -465,7 +826,7 @@ impl GroupManager {
         promoter_id: IdentityId,
         member_id: IdentityId,
         new_role: Role,
-    ) -> Result<crate::groups::group_handshake::RoleChangeBody> {
+    ) -> Result<crate::groups::group_handshake::RoleChangeBody, GroupError> {
         // Strict owner-only gate: in this codebase only the Owner
         // hands out / takes back the Admin / Moderator roles. This
         // is stricter than `Permission::ManageRoles`, which Admins
@@ -478,14 +839,14 @@ impl GroupManager {
             .map(|m| m.role == Role::Owner)
             .unwrap_or(false);
         if !promoter_is_owner {
-            return Err(anyhow::anyhow!("only the group owner may promote members"));
+            return Err(GroupError::NotOwner);
         }
         // Re-uses the existing role mutation path (permission check,
         // version bump, log event, persist).
         self.update_member_role(group_id, promoter_id, member_id, new_role.clone())?;
         let new_version = self
             .get_group(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found after role update"))?
+            .ok_or(GroupError::NotFound)?
             .version;
         Ok(crate::groups::group_handshake::RoleChangeBody {
             group_id,
@@ -507,16 +868,13 @@ impl GroupManager {
         new_role: Role,
         new_version: u64,
     ) -> Result<()> {
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
         let member = group
             .members
             .get_mut(&member_id)
             .ok_or_else(|| anyhow::anyhow!("Role change target not in local view"))?;
         if member.role == Role::Owner {
-            return Err(anyhow::anyhow!("Cannot change owner role"));
+            return Err(GroupError::OwnerProtected.into());
         }
         member.role = new_role;
         if new_version > group.version {
@@ -547,28 +905,25 @@ impl GroupManager {
         group_id: GroupId,
         donor_id: IdentityId,
         new_owner_id: IdentityId,
-    ) -> Result<crate::groups::group_handshake::OwnershipTransferBody> {
+    ) -> Result<crate::groups::group_handshake::OwnershipTransferBody, GroupError> {
         if donor_id == new_owner_id {
-            return Err(anyhow::anyhow!("cannot transfer ownership to self"));
+            return Err(GroupError::TransferToSelf);
         }
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
 
         let donor = group
             .members
             .get(&donor_id)
-            .ok_or_else(|| anyhow::anyhow!("donor not in group"))?;
+            .ok_or(GroupError::MemberNotFound)?;
         if donor.role != Role::Owner {
-            return Err(anyhow::anyhow!("only the group owner may transfer ownership"));
+            return Err(GroupError::NotOwner);
         }
         let new_owner = group
             .members
             .get(&new_owner_id)
-            .ok_or_else(|| anyhow::anyhow!("new owner not in group"))?;
+            .ok_or(GroupError::MemberNotFound)?;
         if new_owner.member_status != MemberStatus::Active {
-            return Err(anyhow::anyhow!("new owner is not an active member"));
+            return Err(GroupError::MemberInactive);
         }
 
         // Atomic swap. We don't go through `update_member_role`
@@ -579,12 +934,12 @@ impl GroupManager {
         group
             .members
             .get_mut(&new_owner_id)
-            .ok_or_else(|| anyhow::anyhow!("new owner vanished mid-transfer"))?
+            .ok_or(GroupError::MemberNotFound)?
             .role = Role::Owner;
         group
             .members
             .get_mut(&donor_id)
-            .ok_or_else(|| anyhow::anyhow!("donor vanished mid-transfer"))?
+            .ok_or(GroupError::MemberNotFound)?
             .role = Role::Admin;
 
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
@@ -594,7 +949,7 @@ impl GroupManager {
         self.log_group_event(
             group_id,
             donor_id,
-            GroupEventType::RoleChanged,
+            GroupEventType::OwnershipTransferred,
             format!(
                 "Ownership transferred from {} to {}",
                 hex::encode(donor_id.as_ref() as &[u8]),
@@ -626,10 +981,7 @@ impl GroupManager {
         new_owner_id: IdentityId,
         new_version: u64,
     ) -> Result<()> {
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
 
         // Pre-conditions: donor must currently be Owner, new_owner
         // must be a known active member. Fail loudly so a forged
@@ -782,7 +1134,7 @@ impl GroupManager {
         admin_id: IdentityId,
         member_id: IdentityId,
         new_role: Role,
-    ) -> Result<()> {
+    ) -> Result<(), GroupError> {
         // Check permissions
         self.check_permission(group_id, admin_id, Permission::ManageRoles)?;
 
@@ -791,14 +1143,11 @@ impl GroupManager {
         // after the borrow is released. Capture the log message to fire
         // later — the actual side effect runs outside the scope.
         let log_msg = {
-            let group = self
-                .groups
-                .get_mut(&group_id)
-                .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+            let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
 
             if let Some(member) = group.members.get(&member_id) {
                 if member.role == Role::Owner {
-                    return Err(anyhow::anyhow!("Cannot change owner role"));
+                    return Err(GroupError::OwnerProtected);
                 }
             }
 
@@ -823,6 +1172,7 @@ impl GroupManager {
         }
 
         self.store_group_securely(&group_id)?;
+        self.record_permission_use(group_id, admin_id, Permission::ManageRoles)?;
 
         Ok(())
     }
@@ -834,19 +1184,16 @@ impl GroupManager {
         admin_id: IdentityId,
         expires_at: Option<u64>,
         max_uses: Option<u32>,
-    ) -> Result<GroupInvitation> {
+    ) -> Result<GroupInvitation, GroupError> {
         // Check permissions
         self.check_permission(group_id, admin_id, Permission::CreateInvites)?;
 
-        let group = self
-            .groups
-            .get(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        let group = self.groups.get(&group_id).ok_or(GroupError::NotFound)?;
 
         let admin = group
             .members
             .get(&admin_id)
-            .ok_or_else(|| anyhow::anyhow!("Admin not found in group"))?;
+            .ok_or(GroupError::MemberNotFound)?;
 
         let invitation_code = self.generate_invitation_code(group_id, admin_id)?;
 
@@ -864,7 +1211,7 @@ impl GroupManager {
 
         // Store invitation
         let invitation_key = format!("invitation_{}", invitation.invitation_code);
-        let serialized = bincode::serialize(&invitation)?;
+        let serialized = bincode::serialize(&invitation).context("invitation encode")?;
         let metadata = KeyMetadata {
             algorithm: "bincode".to_string(),
             key_size: serialized.len(),
@@ -889,6 +1236,7 @@ impl GroupManager {
                 invitation.invitation_code
             ),
         )?;
+        self.record_permission_use(group_id, admin_id, Permission::CreateInvites)?;
 
         Ok(invitation)
     }
@@ -904,14 +1252,11 @@ impl GroupManager {
         member_id: IdentityId,
         kyber_pub: Vec<u8>,
     ) -> Result<()> {
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
         let member = group
             .members
             .get_mut(&member_id)
-            .ok_or_else(|| anyhow::anyhow!("Member not in group"))?;
+            .ok_or(GroupError::MemberNotFound)?;
         member.kyber_pub = kyber_pub;
         group.last_updated = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
         group.version += 1;
@@ -974,13 +1319,10 @@ impl GroupManager {
         // Generate a fresh 32-byte key. We pass it through
         // `set_group_key` so the rotator's own GroupCrypto picks it
         // up immediately.
+        let group = self.groups.get(&group_id).ok_or(GroupError::NotFound)?;
         let new_key = crate::security::secure_rng::random::array::<32>()?;
-        self.group_crypto.set_group_key(group_id, new_key);
-
-        let group = self
-            .groups
-            .get(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        self.group_crypto
+            .set_group_key(group_id, new_key, group.version);
 
         // Build a (recipient_id, kyber_pub) plan first to avoid holding
         // the immutable borrow across WrappedGroupKey::wrap calls.
@@ -1014,23 +1356,24 @@ impl GroupManager {
         member_id: IdentityId,
         member_key: IdentityKey,
         display_name: String,
-    ) -> Result<GroupId> {
+    ) -> Result<GroupId, GroupError> {
         let invitation_key = format!("invitation_{}", invitation_code);
         let secret = self
             .keystore
             .retrieve_key(&invitation_key)?
-            .ok_or_else(|| anyhow::anyhow!("Invitation not found locally"))?;
-        let mut invitation: GroupInvitation = bincode::deserialize(secret.expose_secret())?;
+            .ok_or(GroupError::InvitationNotFound)?;
+        let mut invitation: GroupInvitation =
+            bincode::deserialize(secret.expose_secret()).context("invitation decode")?;
 
         let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
         if let Some(expires_at) = invitation.expires_at {
             if current_time > expires_at {
-                return Err(anyhow::anyhow!("Invitation has expired"));
+                return Err(GroupError::InvitationExpired);
             }
         }
         if let Some(max_uses) = invitation.max_uses {
             if invitation.current_uses >= max_uses {
-                return Err(anyhow::anyhow!("Invitation has reached maximum uses"));
+                return Err(GroupError::InvitationExhausted);
             }
         }
 
@@ -1044,7 +1387,7 @@ impl GroupManager {
         )?;
 
         invitation.current_uses += 1;
-        let serialized = bincode::serialize(&invitation)?;
+        let serialized = bincode::serialize(&invitation).context("invitation encode")?;
         let metadata = KeyMetadata {
             algorithm: "bincode".to_string(),
             key_size: serialized.len(),
@@ -1145,7 +1488,8 @@ impl GroupManager {
         // placeholder so the joiner can immediately decrypt subsequent
         // group messages. We copy the bytes into a Secret-wrapped
         // owned array so the caller can zeroise their stack copy.
-        self.group_crypto.set_group_key(group_id, *group_key);
+        self.group_crypto
+            .set_group_key(group_id, *group_key, snapshot_version);
         self.store_group_securely(&group_id)?;
         Ok(())
     }
@@ -1157,10 +1501,25 @@ impl GroupManager {
         self.group_crypto.export_group_key(group_id)
     }
 
+    /// Every key still retained for the group as `(generation, key)`,
+    /// newest first: the current key plus up to
+    /// [`GROUP_KEY_HISTORY`](crate::groups::group_crypto::GROUP_KEY_HISTORY)` - 1`
+    /// predecessors. `generation` is the group version the key was
+    /// installed at.
+    pub fn export_retained_group_keys(&self, group_id: &GroupId) -> Vec<(u64, [u8; 32])> {
+        self.group_crypto.export_retained_keys(group_id)
+    }
+
+    fn group_version(&self, group_id: &GroupId) -> u64 {
+        self.groups.get(group_id).map(|g| g.version).unwrap_or(0)
+    }
+
     /// Install a 32-byte symmetric group key. Used by the joiner side
     /// of a `KeyRotation` after it unwraps the new key from the wire.
     pub fn install_group_key(&mut self, group_id: GroupId, key_bytes: &[u8; 32]) -> Result<()> {
-        self.group_crypto.set_group_key(group_id, *key_bytes);
+        let generation = self.group_version(&group_id);
+        self.group_crypto
+            .set_group_key(group_id, *key_bytes, generation);
         self.store_group_securely(&group_id)?;
         Ok(())
     }
@@ -1173,7 +1532,8 @@ impl GroupManager {
         if self.group_crypto.export_group_key(&group_id).is_some() {
             return Ok(());
         }
-        self.group_crypto.create_group_key(group_id)
+        let generation = self.group_version(&group_id);
+        self.group_crypto.create_group_key(group_id, generation)
     }
 
     /// Record that the local user accepted an invite scanned from a
@@ -1234,18 +1594,17 @@ impl GroupManager {
     }
 
     /// Leave a group
-    pub fn leave_group(&mut self, group_id: GroupId, member_id: IdentityId) -> Result<()> {
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+    pub fn leave_group(
+        &mut self,
+        group_id: GroupId,
+        member_id: IdentityId,
+    ) -> Result<(), GroupError> {
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
 
         // Owner cannot leave (must transfer ownership first)
         if let Some(member) = group.members.get(&member_id) {
             if member.role == Role::Owner {
-                return Err(anyhow::anyhow!(
-                    "Owner cannot leave group without transferring ownership"
-                ));
+                return Err(GroupError::OwnerCannotLeave);
             }
         }
 
@@ -1264,7 +1623,8 @@ impl GroupManager {
         group.version += 1;
 
         // Rotate group key
-        self.group_crypto.rotate_group_key(group_id)?;
+        self.group_crypto
+            .rotate_group_key(group_id, group.version)?;
 
         // Log event
         self.log_group_event(
@@ -1289,10 +1649,7 @@ impl GroupManager {
         // Check permissions
         self.check_permission(group_id, admin_id, Permission::ManageSettings)?;
 
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
 
         group.settings = new_settings;
         group.last_updated = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
@@ -1307,91 +1664,456 @@ impl GroupManager {
         )?;
 
         self.store_group_securely(&group_id)?;
+        self.record_permission_use(group_id, admin_id, Permission::ManageSettings)?;
 
         Ok(())
     }
 
-    /// Get a group by ID
-    pub fn get_group(&self, group_id: &GroupId) -> Option<&Group> {
-        self.groups.get(group_id)
+    /// Turn slow mode on or off. Needs [`Permission::SetSlowMode`].
+    pub fn set_slow_mode(
+        &mut self,
+        group_id: GroupId,
+        admin_id: IdentityId,
+        slow_mode: Option<SlowMode>,
+    ) -> Result<(), GroupError> {
+        self.check_permission(group_id, admin_id, Permission::SetSlowMode)?;
+
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
+        group.settings.slow_mode = slow_mode;
+        group.last_updated = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        group.version += 1;
+
+        let description = match slow_mode {
+            Some(mode) => format!("Slow mode set to {}s", mode.interval_secs),
+            None => "Slow mode disabled".to_string(),
+        };
+        self.log_group_event(
+            group_id,
+            admin_id,
+            GroupEventType::SettingsChanged,
+            description,
+        )?;
+
+        self.store_group_securely(&group_id)?;
+        self.record_permission_use(group_id, admin_id, Permission::SetSlowMode)?;
+
+        Ok(())
     }
 
-    /// Get all groups for a member
-    pub fn get_member_groups(&self, member_id: &IdentityId) -> Vec<&Group> {
-        if let Some(group_ids) = self.member_groups.get(member_id) {
-            group_ids
-                .iter()
-                .filter_map(|id| self.groups.get(id))
-                .collect()
-        } else {
-            Vec::new()
-        }
+    /// Gate one outgoing message from `sender_id`. Records the send
+    /// when allowed; under slow mode a second send inside the interval
+    /// fails with [`GroupError::RetryAfter`].
+    pub fn check_send_allowed(
+        &mut self,
+        group_id: GroupId,
+        sender_id: IdentityId,
+    ) -> Result<(), GroupError> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.check_send_allowed_at(group_id, sender_id, now)
     }
 
-    /// Get active members of a group
-    pub fn get_active_members(&self, group_id: &GroupId) -> Vec<&GroupMember> {
-        if let Some(group) = self.groups.get(group_id) {
-            group
-                .members
-                .values()
-                .filter(|member| member.member_status == MemberStatus::Active)
-                .collect()
-        } else {
-            Vec::new()
+    /// [`check_send_allowed`](Self::check_send_allowed) with an explicit
+    /// clock, in Unix seconds.
+    pub fn check_send_allowed_at(
+        &mut self,
+        group_id: GroupId,
+        sender_id: IdentityId,
+        now: u64,
+    ) -> Result<(), GroupError> {
+        let member = self.active_member(&group_id, &sender_id)?;
+        let exempt = matches!(member.role, Role::Owner | Role::Admin);
+        let slow_mode = self.groups[&group_id].settings.slow_mode;
+
+        if let (Some(mode), false) = (slow_mode, exempt) {
+            if let Some(&last) = self.last_sends.get(&(group_id, sender_id)) {
+                let elapsed = now.saturating_sub(last);
+                if elapsed < mode.interval_secs {
+                    let wait = Duration::from_secs(mode.interval_secs - elapsed);
+                    return Err(RetryAfter(wait).into());
+                }
+            }
         }
+
+        self.last_sends.insert((group_id, sender_id), now);
+        Ok(())
     }
 
-    /// Check if a member has a specific permission
-    pub fn check_permission(
-        &self,
+    /// Attach conditions (content limits and the like) to a role, or
+    /// lift them with `None`. Needs [`Permission::ManageRoles`].
+    pub fn set_role_context(
+        &mut self,
         group_id: GroupId,
-        member_id: IdentityId,
-        permission: Permission,
-    ) -> Result<()> {
-        let group = self
-            .groups
-            .get(&group_id)
-            .ok_or_else(|| anyhow::anyhow!("Group not found"))?;
+        admin_id: IdentityId,
+        role: Role,
+        context: Option<PermissionContext>,
+    ) -> Result<(), GroupError> {
+        self.check_permission(group_id, admin_id, Permission::ManageRoles)?;
 
-        let member = group
-            .members
-            .get(&member_id)
-            .ok_or_else(|| anyhow::anyhow!("Member not found in group"))?;
+        let group = self.groups.get_mut(&group_id).ok_or(GroupError::NotFound)?;
+        let description = match context {
+            Some(_) => format!("Restrictions set for role {}", role),
+            None => format!("Restrictions lifted for role {}", role),
+        };
+        group.permissions.set_role_context(role, context);
+        group.last_updated = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        group.version += 1;
 
-        if member.member_status != MemberStatus::Active {
-            return Err(anyhow::anyhow!("Member is not active"));
-        }
+        self.log_group_event(
+            group_id,
+            admin_id,
+            GroupEventType::SettingsChanged,
+            description,
+        )?;
+        self.store_group_securely(&group_id)?;
+        self.record_permission_use(group_id, admin_id, Permission::ManageRoles)?;
+
+        Ok(())
+    }
 
-        // Check custom permissions first
-        if let Some(custom_permissions) = &member.custom_permissions {
-            if custom_permissions.contains(&permission) {
-                return Ok(());
+    /// Check an outgoing message against the group's rules before it is
+    /// encrypted. `content_type` and `size` describe an attached file
+    /// (`None` for plain text); `body_len` is the text length in bytes.
+    /// Files need file sharing enabled and [`Permission::SendFiles`];
+    /// both then go through the sender's role's [`ContentRestriction`],
+    /// whose violations come back as [`GroupError::ContentRejected`].
+    ///
+    /// [`ContentRestriction`]: crate::groups::group_permissions::ContentRestriction
+    pub fn validate_content(
+        &self,
+        group_id: GroupId,
+        sender_id: IdentityId,
+        content_type: Option<&str>,
+        size: u64,
+        body_len: usize,
+    ) -> Result<(), GroupError> {
+        let member = self.active_member(&group_id, &sender_id)?;
+        let group = &self.groups[&group_id];
+        if content_type.is_some() {
+            if !group.settings.file_sharing_enabled {
+                return Err(GroupError::FileSharingDisabled);
             }
+            self.check_permission(group_id, sender_id, Permission::SendFiles)?;
         }
-
-        // Check role-based permissions
-        if group
+        let restrictions = group
             .permissions
-            .role_has_permission(&member.role, &permission)
-        {
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Permission denied"))
+            .role_context(&member.role)
+            .and_then(|context| context.content_restrictions.as_ref());
+        if let Some(restrictions) = restrictions {
+            restrictions.check(content_type, size, body_len)?;
         }
+        Ok(())
     }
 
-    /// Generate a unique group ID
-    fn generate_group_id(&self, name: &str, creator_id: &IdentityId) -> Result<GroupId> {
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    /// Open a poll. Needs [`Permission::CreatePolls`]; takes two to
+    /// [`MAX_POLL_OPTIONS`] options and a closing time in the future.
+    pub fn create_poll(
+        &mut self,
+        group_id: GroupId,
+        creator_id: IdentityId,
+        question: String,
+        options: Vec<String>,
+        closes_at: u64,
+    ) -> Result<PollId, GroupError> {
+        self.check_permission(group_id, creator_id, Permission::CreatePolls)?;
+        if !(2..=MAX_POLL_OPTIONS).contains(&options.len()) {
+            return Err(GroupError::PollOptionCount {
+                max: MAX_POLL_OPTIONS,
+            });
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        if closes_at <= now {
+            return Err(GroupError::PollClosesInPast);
+        }
 
-        let mut hasher = Hasher::new();
-        hasher.update(name.as_bytes());
-        hasher.update(creator_id.as_ref());
-        hasher.update(&current_time.to_le_bytes());
-        hasher.update(b"qubee_group_id");
+        let poll = Poll {
+            id: PollId(crate::security::secure_rng::random::array::<16>()?),
+            group_id,
+            creator_id,
+            question,
+            options,
+            votes: HashMap::new(),
+            created_at: now,
+            closes_at,
+        };
+        self.store_poll(&poll)?;
+        self.log_group_event(
+            group_id,
+            creator_id,
+            GroupEventType::PollCreated,
+            format!("Poll created: {}", poll.question),
+        )?;
+        self.record_permission_use(group_id, creator_id, Permission::CreatePolls)?;
 
-        let hash = hasher.finalize();
-        Ok(GroupId(hash.as_bytes()[..32].try_into().unwrap()))
+        Ok(poll.id)
+    }
+
+    /// Vote for option `option` (an index into the poll's options).
+    /// Any member who may send messages can vote, once; a later vote
+    /// replaces the earlier one until the poll closes.
+    pub fn cast_vote(
+        &mut self,
+        group_id: GroupId,
+        poll_id: PollId,
+        voter_id: IdentityId,
+        option: usize,
+    ) -> Result<(), GroupError> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.cast_vote_at(group_id, poll_id, voter_id, option, now)
+    }
+
+    /// [`cast_vote`](Self::cast_vote) with an explicit clock, in Unix
+    /// seconds.
+    pub fn cast_vote_at(
+        &mut self,
+        group_id: GroupId,
+        poll_id: PollId,
+        voter_id: IdentityId,
+        option: usize,
+        now: u64,
+    ) -> Result<(), GroupError> {
+        self.check_permission(group_id, voter_id, Permission::SendMessages)?;
+        let mut poll = self.load_poll(&group_id, &poll_id)?;
+        if now >= poll.closes_at {
+            return Err(GroupError::PollClosed);
+        }
+        if option >= poll.options.len() {
+            return Err(GroupError::NoSuchPollOption(option));
+        }
+        poll.votes.insert(voter_id, option);
+        self.store_poll(&poll)?;
+        self.record_permission_use_at(group_id, voter_id, Permission::SendMessages, now);
+        Ok(())
+    }
+
+    /// Close a poll ahead of its deadline. Allowed for the poll's
+    /// creator and for members with [`Permission::ManagePolls`].
+    pub fn close_poll(
+        &mut self,
+        group_id: GroupId,
+        poll_id: PollId,
+        actor_id: IdentityId,
+    ) -> Result<(), GroupError> {
+        let mut poll = self.load_poll(&group_id, &poll_id)?;
+        if poll.creator_id != actor_id {
+            self.check_permission(group_id, actor_id, Permission::ManagePolls)?;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        if now >= poll.closes_at {
+            return Ok(());
+        }
+        poll.closes_at = now;
+        self.store_poll(&poll)?;
+        self.log_group_event(
+            group_id,
+            actor_id,
+            GroupEventType::PollClosed,
+            format!("Poll closed: {}", poll.question),
+        )?;
+        if poll.creator_id != actor_id {
+            self.record_permission_use(group_id, actor_id, Permission::ManagePolls)?;
+        }
+        Ok(())
+    }
+
+    /// Count the votes on a poll, open or closed.
+    pub fn tally_poll(
+        &mut self,
+        group_id: GroupId,
+        poll_id: PollId,
+    ) -> Result<PollTally, GroupError> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let poll = self.load_poll(&group_id, &poll_id)?;
+        let mut counts = vec![0; poll.options.len()];
+        for &option in poll.votes.values() {
+            if let Some(count) = counts.get_mut(option) {
+                *count += 1;
+            }
+        }
+        Ok(PollTally {
+            counts,
+            total_votes: poll.votes.len(),
+            closed: now >= poll.closes_at,
+        })
+    }
+
+    fn poll_key(group_id: &GroupId, poll_id: &PollId) -> String {
+        format!(
+            "poll_{}_{}",
+            hex::encode(group_id.as_ref()),
+            hex::encode(poll_id.0)
+        )
+    }
+
+    fn store_poll(&mut self, poll: &Poll) -> Result<()> {
+        let serialized = framed::encode(poll)?;
+        let metadata = KeyMetadata {
+            algorithm: "bincode-framed".to_string(),
+            key_size: serialized.len(),
+            usage: vec![KeyUsage::Encryption],
+            expiry: None,
+            tags: StdHashMap::new(),
+        };
+        self.keystore.store_key(
+            &Self::poll_key(&poll.group_id, &poll.id),
+            &serialized,
+            KeyType::MessageKey,
+            metadata,
+        )
+    }
+
+    fn load_poll(&mut self, group_id: &GroupId, poll_id: &PollId) -> Result<Poll> {
+        let secret = self
+            .keystore
+            .retrieve_key(&Self::poll_key(group_id, poll_id))?
+            .ok_or(GroupError::PollNotFound)?;
+        framed::decode(secret.expose_secret())
+    }
+
+    /// Get a group by ID
+    pub fn get_group(&self, group_id: &GroupId) -> Option<&Group> {
+        self.groups.get(group_id)
+    }
+
+    /// Get all groups for a member
+    pub fn get_member_groups(&self, member_id: &IdentityId) -> Vec<&Group> {
+        if let Some(group_ids) = self.member_groups.get(member_id) {
+            group_ids
+                .iter()
+                .filter_map(|id| self.groups.get(id))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Get active members of a group
+    pub fn get_active_members(&self, group_id: &GroupId) -> Vec<&GroupMember> {
+        if let Some(group) = self.groups.get(group_id) {
+            group
+                .members
+                .values()
+                .filter(|member| member.member_status == MemberStatus::Active)
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Check if a member has a specific permission, and that their
+    /// role's time restrictions allow using it now. Fails with
+    /// [`GroupError::PermissionDenied`] for a missing permission and
+    /// [`GroupError::Restricted`] for a time restriction. The check
+    /// itself doesn't start the role's cooldown; the action it guards
+    /// calls [`record_permission_use`](Self::record_permission_use)
+    /// once it has succeeded.
+    pub fn check_permission(
+        &self,
+        group_id: GroupId,
+        member_id: IdentityId,
+        permission: Permission,
+    ) -> Result<(), GroupError> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.check_permission_at(group_id, member_id, permission, now)
+    }
+
+    /// [`check_permission`](Self::check_permission) with an explicit
+    /// clock, in Unix seconds.
+    pub fn check_permission_at(
+        &self,
+        group_id: GroupId,
+        member_id: IdentityId,
+        permission: Permission,
+        now: u64,
+    ) -> Result<(), GroupError> {
+        let group = self.groups.get(&group_id).ok_or(GroupError::NotFound)?;
+
+        let member = group
+            .members
+            .get(&member_id)
+            .ok_or(GroupError::MemberNotFound)?;
+
+        if member.member_status != MemberStatus::Active {
+            return Err(GroupError::MemberInactive);
+        }
+
+        // Custom grants first, then the role's permission set
+        let granted = member
+            .custom_permissions
+            .as_ref()
+            .is_some_and(|custom| custom.contains(&permission))
+            || group
+                .permissions
+                .role_has_permission(&member.role, &permission);
+        if !granted {
+            return Err(GroupError::PermissionDenied(permission));
+        }
+
+        let restriction = group
+            .permissions
+            .role_context(&member.role)
+            .and_then(|context| context.time_restrictions.as_ref());
+        if let Some(restriction) = restriction {
+            let uses = self.permission_uses.lock().unwrap();
+            restriction.check(now, uses.get(&(group_id, member_id, permission)).copied())?;
+        }
+        Ok(())
+    }
+
+    /// Count a use of `permission` by `member_id` towards their role's
+    /// cooldown. Called after the guarded action succeeds, so a
+    /// read-only check or an action that fails part-way leaves the
+    /// cooldown untouched.
+    pub fn record_permission_use(
+        &self,
+        group_id: GroupId,
+        member_id: IdentityId,
+        permission: Permission,
+    ) -> Result<(), GroupError> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.record_permission_use_at(group_id, member_id, permission, now);
+        Ok(())
+    }
+
+    /// [`record_permission_use`](Self::record_permission_use) with an
+    /// explicit clock, in Unix seconds.
+    pub fn record_permission_use_at(
+        &self,
+        group_id: GroupId,
+        member_id: IdentityId,
+        permission: Permission,
+        now: u64,
+    ) {
+        let Some(group) = self.groups.get(&group_id) else {
+            return;
+        };
+        let Some(member) = group.members.get(&member_id) else {
+            return;
+        };
+        let has_cooldown = group
+            .permissions
+            .role_context(&member.role)
+            .and_then(|context| context.time_restrictions.as_ref())
+            .is_some_and(|restriction| restriction.cooldown_period.is_some());
+        if has_cooldown {
+            self.permission_uses
+                .lock()
+                .unwrap()
+                .insert((group_id, member_id, permission), now);
+        }
+    }
+
+    /// Generate a unique group ID
+    fn generate_group_id(&self, name: &str, creator_id: &IdentityId) -> Result<GroupId> {
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+        let mut hasher = Hasher::new();
+        hasher.update(name.as_bytes());
+        hasher.update(creator_id.as_ref());
+        hasher.update(&current_time.to_le_bytes());
+        hasher.update(b"qubee_group_id");
+
+        let hash = hasher.finalize();
+        Ok(GroupId(hash.as_bytes()[..32].try_into().unwrap()))
     }
 
     /// Generate an invitation code
@@ -1416,20 +2138,41 @@ impl GroupManager {
         event_type: GroupEventType,
         description: String,
     ) -> Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.append_group_event(group_id, actor_id, event_type, description, timestamp)
+    }
+
+    fn append_group_event(
+        &mut self,
+        group_id: GroupId,
+        actor_id: IdentityId,
+        event_type: GroupEventType,
+        description: String,
+        timestamp: u64,
+    ) -> Result<()> {
+        let (sequence, prev_hash) = match self.event_heads.get(&group_id) {
+            Some(&head) => head,
+            None => match self.get_group_events(&group_id)?.last() {
+                Some(last) => (last.sequence + 1, last.hash()),
+                None => (0, group_events::genesis_hash(&group_id)),
+            },
+        };
         let event = GroupEvent {
             group_id,
             actor_id,
             event_type,
             description,
-            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            timestamp,
+            sequence,
+            prev_hash,
         };
 
         // Store event in keystore. We classify group events as message keys
         // since they represent logged messages rather than cryptographic
         // material. The serialized event is stored under a key name that
-        // includes the group ID and timestamp. We include minimal metadata
-        // describing the format and size of the stored data.
-        let event_key = format!("group_event_{}_{}", group_id, event.timestamp);
+        // includes the group ID and the event's sequence number. We include
+        // minimal metadata describing the format and size of the stored data.
+        let event_key = format!("{}{:020}", Self::group_event_prefix(&group_id), sequence);
         let serialized = bincode::serialize(&event)?;
         let metadata = KeyMetadata {
             algorithm: "bincode".to_string(),
@@ -1440,17 +2183,70 @@ impl GroupManager {
         };
         self.keystore
             .store_key(&event_key, &serialized, KeyType::MessageKey, metadata)?;
+        self.event_heads
+            .insert(group_id, (sequence + 1, event.hash()));
+
+        Ok(())
+    }
+
+    fn group_event_prefix(group_id: &GroupId) -> String {
+        format!("group_event_{}_", hex::encode(group_id.as_ref()))
+    }
 
+    /// Move events stored before the log was hash-chained (keyed by the
+    /// short group id and timestamp, see [`LegacyGroupEvent`]) into the
+    /// chain, oldest first, keeping their timestamps. Runs before the
+    /// group's log is read or appended to, so they land at the start.
+    /// An entry that doesn't decode, or a log that already has chained
+    /// events, is an error rather than something to drop quietly.
+    fn migrate_legacy_events(&mut self, group_id: &GroupId) -> Result<()> {
+        let legacy_prefix = format!("group_event_{}_", group_id);
+        let key_ids = self
+            .keystore
+            .list_keys_with_prefix(&legacy_prefix, usize::MAX, None);
+        let mut legacy = Vec::with_capacity(key_ids.len());
+        for key_id in key_ids {
+            let Some(secret_data) = self.keystore.retrieve_key(&key_id)? else {
+                continue;
+            };
+            let event: LegacyGroupEvent = bincode::deserialize(secret_data.expose_secret())
+                .with_context(|| format!("legacy group event {key_id} doesn't decode"))?;
+            // The short id is only 8 bytes of the group id.
+            if event.group_id == *group_id {
+                legacy.push((key_id, event));
+            }
+        }
+        if legacy.is_empty() {
+            return Ok(());
+        }
+        if self.next_event_sequence(group_id) != 0 {
+            return Err(anyhow::anyhow!(
+                "group {group_id} has both legacy and hash-chained events"
+            ));
+        }
+        legacy.sort_by_key(|(_, event)| event.timestamp);
+        self.event_heads
+            .insert(*group_id, (0, group_events::genesis_hash(group_id)));
+        for (key_id, event) in legacy {
+            self.append_group_event(
+                event.group_id,
+                event.actor_id,
+                event.event_type,
+                event.description,
+                event.timestamp,
+            )?;
+            self.keystore.delete_key(&key_id)?;
+        }
         Ok(())
     }
 
     /// Store group securely
     fn store_group_securely(&mut self, group_id: &GroupId) -> Result<()> {
         if let Some(group) = self.groups.get(group_id) {
-            let serialized = bincode::serialize(group)?;
+            let serialized = framed::encode(group)?;
             let key_name = format!("group_{}", hex::encode(group_id.as_ref()));
             let metadata = KeyMetadata {
-                algorithm: "bincode".to_string(),
+                algorithm: "bincode-framed".to_string(),
                 key_size: serialized.len(),
                 usage: vec![KeyUsage::Encryption],
                 expiry: None,
@@ -1464,71 +2260,273 @@ impl GroupManager {
 
     /// Retrieve all events logged for the given group. Events are
     /// stored in the secure keystore with keys of the form
-    /// `group_event_{group_id_hex}_{timestamp}`. This method
-    /// iterates over all stored keys, deserializes the corresponding
-    /// events and returns them sorted by timestamp.
+    /// `group_event_{group_id_hex}_{sequence}`. This method
+    /// deserializes every stored event of the group and returns them in
+    /// log order; prefer [`get_group_events_paged`](Self::get_group_events_paged)
+    /// for display.
     pub fn get_group_events(&mut self, group_id: &GroupId) -> Result<Vec<GroupEvent>> {
-        let prefix = format!("group_event_{}", hex::encode(group_id.as_ref()));
-        let key_ids = self.keystore.list_keys();
-        let mut events = Vec::new();
+        self.migrate_legacy_events(group_id)?;
+        let prefix = Self::group_event_prefix(group_id);
+        let key_ids = self
+            .keystore
+            .list_keys_with_prefix(&prefix, usize::MAX, None);
+        let mut events = self.load_group_events(&key_ids)?;
+        events.sort_by_key(|e| e.sequence);
+        Ok(events)
+    }
+
+    /// One page of the group's event log, newest first: up to `limit`
+    /// events whose sequence is below `before_sequence`, or the newest
+    /// `limit` events when it is `None`. To fetch the next page pass the
+    /// `sequence` of the last event returned; paging is done once the
+    /// event with sequence 0 has come back.
+    ///
+    /// The cursor is the log sequence rather than the timestamp:
+    /// timestamps have one-second resolution, so a busy group logs
+    /// several events per second and a timestamp cursor would drop or
+    /// repeat them at page boundaries. Only the page's own entries are
+    /// decrypted.
+    pub fn get_group_events_paged(
+        &mut self,
+        group_id: &GroupId,
+        before_sequence: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<GroupEvent>> {
+        self.migrate_legacy_events(group_id)?;
+        let prefix = Self::group_event_prefix(group_id);
+        let end = match before_sequence {
+            Some(before) => before,
+            None => self.next_event_sequence(group_id),
+        };
+        let start = end.saturating_sub(limit as u64);
+        let after = start
+            .checked_sub(1)
+            .map(|sequence| format!("{}{:020}", prefix, sequence));
+        let key_ids =
+            self.keystore
+                .list_keys_with_prefix(&prefix, (end - start) as usize, after.as_deref());
+        let mut events = self.load_group_events(&key_ids)?;
+        // A gap in the log (a deleted event) would otherwise pull in
+        // events from the next window.
+        events.retain(|e| e.sequence >= start && e.sequence < end);
+        events.sort_by_key(|e| std::cmp::Reverse(e.sequence));
+        Ok(events)
+    }
+
+    /// Sequence the group's next event will get.
+    fn next_event_sequence(&self, group_id: &GroupId) -> u64 {
+        if let Some(&(next, _)) = self.event_heads.get(group_id) {
+            return next;
+        }
+        let prefix = Self::group_event_prefix(group_id);
+        self.keystore
+            .list_keys_with_prefix(&prefix, usize::MAX, None)
+            .last()
+            .and_then(|key_id| key_id[prefix.len()..].parse::<u64>().ok())
+            .map_or(0, |last| last + 1)
+    }
+
+    fn load_group_events(&mut self, key_ids: &[String]) -> Result<Vec<GroupEvent>> {
+        let mut events = Vec::with_capacity(key_ids.len());
         for key_id in key_ids {
-            if key_id.starts_with(&prefix) {
-                if let Some(secret_data) = self.keystore.retrieve_key(&key_id)? {
-                    let data = secret_data.expose_secret();
-                    if let Ok(event) = bincode::deserialize::<GroupEvent>(data) {
-                        events.push(event);
-                    }
-                }
+            if let Some(secret_data) = self.keystore.retrieve_key(key_id)? {
+                let event = bincode::deserialize::<GroupEvent>(secret_data.expose_secret())
+                    .with_context(|| format!("group event {key_id} doesn't decode"))?;
+                events.push(event);
             }
         }
-        events.sort_by_key(|e| e.timestamp);
         Ok(events)
     }
 
+    /// Walk the group's stored event log and return the index of the
+    /// first event whose hash-chain link is broken, or `None` if the
+    /// log is intact. See [`group_events::verify_chain`] for what a
+    /// break means.
+    ///
+    /// The newest event has no successor to check its hash, so an edit
+    /// to it goes unnoticed, as does dropping events off the end. Both
+    /// need a head hash kept somewhere the log's writer can't reach.
+    pub fn verify_event_log(&mut self, group_id: &GroupId) -> Result<Option<usize>> {
+        let events = self.get_group_events(group_id)?;
+        Ok(group_events::verify_chain(group_id, &events))
+    }
+
     /// Encrypt a plaintext message for delivery to the specified group.
     /// This method uses the `GroupCrypto` to derive a symmetric key
     /// associated with the group and returns the ciphertext with the
-    /// nonce prepended. The caller is responsible for publishing the
+    /// key epoch and nonce prepended. The caller is responsible for publishing the
     /// encrypted message via the network layer (e.g. gossipsub).
     pub fn encrypt_group_message(&self, group_id: &GroupId, plaintext: &[u8]) -> Result<Vec<u8>> {
         self.group_crypto.encrypt_message(group_id, plaintext)
     }
 
     /// Decrypt an incoming group message. The provided `data` should
-    /// contain the nonce prefix as produced by `encrypt_group_message`.
-    /// If decryption succeeds the plaintext is returned; otherwise
-    /// an error is propagated.
+    /// contain the epoch and nonce prefix as produced by
+    /// `encrypt_group_message`; messages under any of the last
+    /// [`GROUP_KEY_HISTORY`](crate::groups::group_crypto::GROUP_KEY_HISTORY)
+    /// keys still decrypt. If decryption succeeds the plaintext is
+    /// returned; otherwise an error is propagated.
     pub fn decrypt_group_message(&self, group_id: &GroupId, data: &[u8]) -> Result<Vec<u8>> {
         self.group_crypto.decrypt_message(group_id, data)
     }
 
-    /// Load groups from storage
+    /// Epoch of the group's current symmetric key, or `None` if no key
+    /// is installed.
+    pub fn current_epoch(&self, group_id: &GroupId) -> Option<GroupKeyEpoch> {
+        self.group_crypto.current_epoch(group_id)
+    }
+
+    /// Current Lamport clock for the group: the highest value this
+    /// device has sent or seen. 0 before any message.
+    pub fn current_lamport(&self, group_id: &GroupId) -> u64 {
+        let clocks = self.lamport_clocks.lock().unwrap();
+        clocks.get(group_id).copied().unwrap_or(0)
+    }
+
+    /// Advance the group's clock for an outgoing message and return
+    /// the value to stamp on it.
+    pub(crate) fn tick_lamport(&self, group_id: &GroupId) -> Result<u64, GroupError> {
+        let mut clocks = self.lamport_clocks.lock().unwrap();
+        let clock = clocks.entry(*group_id).or_insert(0);
+        *clock = clock.checked_add(1).ok_or(GroupError::LamportOutOfRange {
+            local: *clock,
+            seen: *clock,
+        })?;
+        Ok(*clock)
+    }
+
+    /// Merge the clock of a verified incoming message, so the next
+    /// message sent here orders after it. The first message seen for a
+    /// group since start-up sets the clock outright; after that a clock
+    /// more than [`MAX_LAMPORT_JUMP`] ahead of ours only advances ours
+    /// by [`MAX_LAMPORT_JUMP`].
+    pub(crate) fn observe_lamport(&self, group_id: &GroupId, seen: u64) {
+        let mut clocks = self.lamport_clocks.lock().unwrap();
+        let clock = match clocks.entry(*group_id) {
+            Entry::Vacant(entry) => {
+                entry.insert(seen);
+                return;
+            }
+            Entry::Occupied(entry) => entry.into_mut(),
+        };
+        let ceiling = clock.saturating_add(MAX_LAMPORT_JUMP);
+        if seen > ceiling {
+            tracing::warn!(group = %group_id, local = *clock, seen, "Lamport clock jump clamped");
+        }
+        *clock = (*clock).max(seen.min(ceiling));
+    }
+
+    /// Sender-keys mode: encrypt under the next key of `sender`'s chain
+    /// for this group and sign the ciphertext with their identity key,
+    /// so recipients learn *which* member wrote it rather than just
+    /// "some holder of the group key". The sender must be an active
+    /// member.
+    pub fn encrypt_group_message_authenticated(
+        &mut self,
+        group_id: &GroupId,
+        sender: &IdentityKeyPair,
+        plaintext: &[u8],
+    ) -> Result<(Vec<u8>, HybridSignature)> {
+        let sender_id = sender.identity_id();
+        self.active_member(group_id, &sender_id)?;
+        let ciphertext = self
+            .group_crypto
+            .encrypt_sender_message(group_id, &sender_id, plaintext)?;
+        let signature = sender.sign(&canonical_sender_message(group_id, &ciphertext))?;
+        Ok((ciphertext, signature))
+    }
+
+    /// Verify and decrypt a message from
+    /// [`encrypt_group_message_authenticated`](Self::encrypt_group_message_authenticated).
+    /// The signature is checked against the claimed sender's identity
+    /// key before anything is decrypted, and must be no older than
+    /// [`GROUP_MESSAGE_MAX_AGE_SECS`]; within that window the sender's
+    /// chain refuses an index it has already opened. On success returns
+    /// the verified sender id and the plaintext.
+    pub fn decrypt_group_message_authenticated(
+        &mut self,
+        group_id: &GroupId,
+        data: &[u8],
+        signature: &HybridSignature,
+    ) -> Result<(IdentityId, Vec<u8>)> {
+        let claimed: [u8; 32] = data
+            .get(..32)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| anyhow::anyhow!("Ciphertext too short"))?;
+        let claimed = IdentityId::from(claimed);
+        let sender = self.active_member(group_id, &claimed)?;
+        if !sender.identity_key.verify_with_max_age(
+            &canonical_sender_message(group_id, data),
+            signature,
+            GROUP_MESSAGE_MAX_AGE_SECS,
+        )? {
+            return Err(anyhow::anyhow!("Sender signature verification failed"));
+        }
+        let (sender_id, plaintext) = self.group_crypto.decrypt_sender_message(group_id, data)?;
+        Ok((sender_id, plaintext))
+    }
+
+    fn active_member(
+        &self,
+        group_id: &GroupId,
+        member_id: &IdentityId,
+    ) -> Result<&GroupMember, GroupError> {
+        let group = self.groups.get(group_id).ok_or(GroupError::NotFound)?;
+        group
+            .members
+            .get(member_id)
+            .filter(|m| m.member_status == MemberStatus::Active)
+            .ok_or(GroupError::MemberInactive)
+    }
+
+    /// Load groups from storage. Records written by older builds are
+    /// migrated to the current layout and get re-framed on their next
+    /// store. A record that can't be read (a newer schema's
+    /// [`UnsupportedVersion`](framed::UnsupportedVersion), or
+    /// corruption) doesn't stop the others loading; it is logged, and
+    /// the first such error is returned at the end so the caller knows
+    /// a group is missing.
     pub fn load_groups_from_storage(&mut self) -> Result<()> {
-        // List all keys and filter to those representing stored group objects
         let group_keys = self
             .keystore
             .list_keys()
             .into_iter()
-            .filter(|key| key.starts_with("group_"))
+            .filter(|key| Self::is_group_record_key(key))
             .collect::<Vec<_>>();
 
+        let mut first_error = None;
         for key_name in group_keys {
             if let Some(secret_data) = self.keystore.retrieve_key(&key_name)? {
-                let data = secret_data.expose_secret();
-                if let Ok(group) = bincode::deserialize::<Group>(data) {
-                    let group_id = group.id;
-                    // Update member groups mapping
-                    for member_id in group.members.keys() {
-                        self.member_groups
-                            .entry(*member_id)
-                            .or_insert_with(HashSet::new)
-                            .insert(group_id);
+                let group = match Group::from_stored(secret_data.expose_secret()) {
+                    Ok(group) => group,
+                    Err(e) => {
+                        tracing::warn!(key = %key_name, error = %format!("{e:#}"), "group record not loaded");
+                        first_error.get_or_insert(e.context(format!("load {}", key_name)));
+                        continue;
                     }
-                    self.groups.insert(group_id, group);
+                };
+                let group_id = group.id;
+                // Update member groups mapping
+                for member_id in group.members.keys() {
+                    self.member_groups
+                        .entry(*member_id)
+                        .or_insert_with(HashSet::new)
+                        .insert(group_id);
                 }
+                self.groups.insert(group_id, group);
             }
         }
-        Ok(())
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// `group_<64 hex>`, as written by `store_group_securely`. Event
+    /// records share the `group_` prefix.
+    fn is_group_record_key(key: &str) -> bool {
+        key.strip_prefix("group_")
+            .is_some_and(|id| id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit()))
     }
 }
 
@@ -1575,6 +2573,7 @@ impl Default for GroupSettings {
             voice_calls_enabled: true,
             video_calls_enabled: true,
             screen_sharing_enabled: false,
+            slow_mode: None,
         }
     }
 }
@@ -1594,6 +2593,7 @@ impl Default for GroupMetadata {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::errors::MessengerError;
     use crate::identity::identity_key::IdentityKeyPair;
     use tempfile::TempDir;
 
@@ -1701,45 +2701,169 @@ mod tests {
     }
 
     #[test]
-    fn test_group_invitations() {
-        // Create a temporary keystore for testing
+    fn authenticated_group_message_rejects_forged_sender() {
         let temp_dir = TempDir::new().expect("Failed to create temp dir");
         let keystore_path = temp_dir.path().join("group_keystore.db");
-        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase").expect("Should create keystore");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
         let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
 
-        let creator_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
-        let creator_key = creator_keypair.public_key();
-        let creator_id = creator_key.identity_id;
-
+        let alice = IdentityKeyPair::generate().unwrap();
+        let mallory = IdentityKeyPair::generate().unwrap();
         let group_id = group_manager
             .create_group(
-                creator_id,
-                creator_key,
+                alice.identity_id(),
+                alice.public_key(),
                 "Test Group".to_string(),
                 "A test group".to_string(),
                 GroupType::Private,
                 GroupSettings::default(),
             )
-            .expect("Should create group");
-
-        // Create invitation
-        let invitation = group_manager
-            .create_invitation(group_id, creator_id, None, Some(5))
-            .expect("Should create invitation");
-
-        assert_eq!(invitation.group_id, group_id);
-        assert_eq!(invitation.max_uses, Some(5));
-        assert_eq!(invitation.current_uses, 0);
+            .unwrap();
+        group_manager
+            .add_member(
+                group_id,
+                alice.identity_id(),
+                mallory.identity_id(),
+                mallory.public_key(),
+                "Mallory".to_string(),
+                Role::Member,
+            )
+            .unwrap();
+
+        let (ciphertext, signature) = group_manager
+            .encrypt_group_message_authenticated(&group_id, &alice, b"from alice")
+            .unwrap();
+        let (sender, plaintext) = group_manager
+            .decrypt_group_message_authenticated(&group_id, &ciphertext, &signature)
+            .unwrap();
+        assert_eq!(sender, alice.identity_id());
+        assert_eq!(plaintext, b"from alice");
+        assert!(group_manager
+            .decrypt_group_message_authenticated(&group_id, &ciphertext, &signature)
+            .is_err());
+
+        // Mallory knows the group key, so she can produce a ciphertext
+        // claiming to be from Alice — but can only sign it as herself.
+        let forged = group_manager
+            .group_crypto
+            .encrypt_sender_message(&group_id, &alice.identity_id(), b"from alice?")
+            .unwrap();
+        let forged_sig = mallory
+            .sign(&canonical_sender_message(&group_id, &forged))
+            .unwrap();
+        assert!(group_manager
+            .decrypt_group_message_authenticated(&group_id, &forged, &forged_sig)
+            .is_err());
+
+        // Re-attributing Mallory's own message to Alice fails too.
+        let (mut relabelled, sig) = group_manager
+            .encrypt_group_message_authenticated(&group_id, &mallory, b"hi")
+            .unwrap();
+        relabelled[..32].copy_from_slice(alice.identity_id().as_ref());
+        assert!(group_manager
+            .decrypt_group_message_authenticated(&group_id, &relabelled, &sig)
+            .is_err());
+    }
 
-        // Join with invitation
-        let joiner_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
-        let joiner_key = joiner_keypair.public_key();
-        let joiner_id = joiner_key.identity_id;
+    #[test]
+    fn transfer_ownership_rejects_self_and_non_members() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
 
-        let joined_group_id = group_manager
-            .join_group_with_invitation(
-                invitation.invitation_code,
+        let owner = IdentityKeyPair::generate().unwrap();
+        let member = IdentityKeyPair::generate().unwrap();
+        let outsider = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let member_id = member.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        group_manager
+            .add_member(
+                group_id,
+                owner_id,
+                member_id,
+                member.public_key(),
+                "Member".to_string(),
+                Role::Member,
+            )
+            .unwrap();
+        let version_before = group_manager.get_group(&group_id).unwrap().version;
+
+        assert!(group_manager
+            .transfer_ownership(group_id, owner_id, owner_id)
+            .is_err());
+        assert!(group_manager
+            .transfer_ownership(group_id, owner_id, outsider.identity_id())
+            .is_err());
+        assert!(group_manager
+            .transfer_ownership(group_id, member_id, owner_id)
+            .is_err());
+        assert_eq!(
+            group_manager.get_group(&group_id).unwrap().version,
+            version_before
+        );
+
+        group_manager
+            .transfer_ownership(group_id, owner_id, member_id)
+            .expect("owner can hand over to an active member");
+        let group = group_manager.get_group(&group_id).unwrap();
+        assert_eq!(group.members[&member_id].role, Role::Owner);
+        assert_eq!(group.members[&owner_id].role, Role::Admin);
+        assert_eq!(group.version, version_before + 1);
+    }
+
+    #[test]
+    fn test_group_invitations() {
+        // Create a temporary keystore for testing
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase").expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+
+        let creator_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
+        let creator_key = creator_keypair.public_key();
+        let creator_id = creator_key.identity_id;
+
+        let group_id = group_manager
+            .create_group(
+                creator_id,
+                creator_key,
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .expect("Should create group");
+
+        // Create invitation
+        let invitation = group_manager
+            .create_invitation(group_id, creator_id, None, Some(5))
+            .expect("Should create invitation");
+
+        assert_eq!(invitation.group_id, group_id);
+        assert_eq!(invitation.max_uses, Some(5));
+        assert_eq!(invitation.current_uses, 0);
+
+        // Join with invitation
+        let joiner_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
+        let joiner_key = joiner_keypair.public_key();
+        let joiner_id = joiner_key.identity_id;
+
+        let joined_group_id = group_manager
+            .join_group_with_invitation(
+                invitation.invitation_code,
                 joiner_id,
                 joiner_key,
                 "Joiner".to_string(),
@@ -1808,4 +2932,1005 @@ mod tests {
             "rehydrated group must include the creator",
         );
     }
+
+    /// A memberless group as bincoded by builds from before slow mode
+    /// and framing: id `[0x11; 32]`, name "Fixture", a temporary group
+    /// with `SendMessages` granted to the owner, version 7. Captured
+    /// from that code, not rebuilt from today's types.
+    const UNFRAMED_GROUP_FIXTURE: &str = "11111111111111111111111111111111111111111111111111111111111111110700000000000000466978747572650d0000000000000073746f726564206c61796f75740400000000f1536500000000000000000000000001000000000000000000000001000000000000000f000000000000000000000001100000000000000000010001100e000000000000010001010000000100000000000000010000000000000074010100000000000000630000000000000000010000000000000001000000000000006b01000000000000007600f153650000000064f15365000000000700000000000000";
+
+    /// The same group framed at schema 1 (slow mode, no role contexts)
+    /// with a 30-second slow mode, captured from that build.
+    const SCHEMA_1_GROUP_FIXTURE: &str = "51425243010011111111111111111111111111111111111111111111111111111111111111110700000000000000466978747572650d0000000000000073746f726564206c61796f75740400000000f1536500000000000000000000000001000000000000000000000001000000000000000f000000000000000000000001100000000000000000010001100e000000000000010001010000011e00000000000000000100000000000000010000000000000074010100000000000000630000000000000000010000000000000001000000000000006b01000000000000007600f153650000000064f15365000000000700000000000000";
+
+    /// Group records written by older builds migrate on load; a record
+    /// this build can't read is reported instead of silently dropped.
+    #[test]
+    fn load_migrates_legacy_records_and_reports_unreadable_ones() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(&keystore_path, b"test-keystore-passphrase")
+            .expect("keystore open");
+        let mut gm = GroupManager::new(keystore).expect("gm");
+        let group_id = GroupId::from_bytes([0x11; 32]);
+        let key_name = format!("group_{}", hex::encode(group_id.as_ref()));
+        let metadata = || KeyMetadata {
+            algorithm: "bincode".to_string(),
+            key_size: 0,
+            usage: vec![KeyUsage::Encryption],
+            expiry: None,
+            tags: StdHashMap::new(),
+        };
+
+        for (fixture, slow_mode) in [
+            (UNFRAMED_GROUP_FIXTURE, None),
+            (SCHEMA_1_GROUP_FIXTURE, Some(SlowMode { interval_secs: 30 })),
+        ] {
+            let bytes = hex::decode(fixture).unwrap();
+            gm.keystore
+                .store_key(&key_name, &bytes, KeyType::EncryptionKey, metadata())
+                .unwrap();
+            gm.groups.clear();
+            gm.load_groups_from_storage().expect("stored group loads");
+
+            let group = &gm.groups[&group_id];
+            assert_eq!(group.name, "Fixture");
+            assert_eq!(group.description, "stored layout");
+            assert!(
+                group.group_type
+                    == GroupType::Temporary {
+                        expires_at: 1_700_000_000
+                    }
+            );
+            assert!(group.members.is_empty());
+            assert!(group
+                .permissions
+                .role_has_permission(&Role::Owner, &Permission::SendMessages));
+            assert!(group.permissions.role_contexts.is_empty());
+            assert_eq!(group.settings.max_members, Some(16));
+            assert_eq!(group.settings.disappearing_messages, Some(3600));
+            assert!(!group.settings.screen_sharing_enabled);
+            assert_eq!(group.settings.slow_mode, slow_mode);
+            assert_eq!(group.metadata.tags, vec!["t".to_string()]);
+            assert_eq!(group.last_updated, 1_700_000_100);
+            assert_eq!(group.version, 7);
+
+            // The next store writes the current schema.
+            gm.store_group_securely(&group_id).unwrap();
+            let stored = gm.keystore.retrieve_key(&key_name).unwrap().unwrap();
+            assert_eq!(
+                framed::schema_version(stored.expose_secret()),
+                Some(Group::SCHEMA_VERSION)
+            );
+        }
+
+        let other_key = format!("group_{}", hex::encode([7u8; 32]));
+        let mut future = framed::encode(&gm.groups[&group_id]).unwrap();
+        future[framed::MAGIC.len()] = 0xFF;
+        gm.keystore
+            .store_key(&other_key, &future, KeyType::EncryptionKey, metadata())
+            .unwrap();
+        gm.groups.clear();
+        let err = gm.load_groups_from_storage().unwrap_err();
+        assert!(err.downcast_ref::<framed::UnsupportedVersion>().is_some());
+        assert!(gm.groups.contains_key(&group_id));
+
+        gm.keystore
+            .store_key(
+                &other_key,
+                b"not a group",
+                KeyType::EncryptionKey,
+                metadata(),
+            )
+            .unwrap();
+        gm.groups.clear();
+        let err = gm.load_groups_from_storage().unwrap_err();
+        assert!(format!("{err:#}").contains(&other_key), "{err:#}");
+        assert!(gm.groups.contains_key(&group_id));
+    }
+
+    #[test]
+    fn time_restrictions_gate_hours_and_cooldown() {
+        use crate::groups::group_permissions::TimeRestriction;
+
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+
+        let owner = IdentityKeyPair::generate().unwrap();
+        let member = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let member_id = member.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        group_manager
+            .add_member(
+                group_id,
+                owner_id,
+                member_id,
+                member.public_key(),
+                "Member".to_string(),
+                Role::Member,
+            )
+            .unwrap();
+        let office_hours = TimeRestriction {
+            allowed_hours: Some((9..=17).collect()),
+            allowed_days: None,
+            cooldown_period: Some(60),
+        };
+        group_manager
+            .set_role_context(
+                group_id,
+                owner_id,
+                Role::Member,
+                Some(PermissionContext {
+                    time_restrictions: Some(office_hours.clone()),
+                    ..PermissionContext::default()
+                }),
+            )
+            .unwrap();
+
+        // Midnight UTC on a Saturday.
+        let midnight = 19_000 * 86_400;
+        let denied = |result: Result<(), GroupError>| match result.unwrap_err() {
+            GroupError::Restricted(denied) => denied,
+            other => panic!("expected a time restriction, got {other:?}"),
+        };
+        let err = denied(group_manager.check_permission_at(
+            group_id,
+            member_id,
+            Permission::CreatePolls,
+            midnight + 3 * 3600,
+        ));
+        assert!(err.reason.contains("03:00"), "{}", err.reason);
+
+        let ten = midnight + 10 * 3600;
+        // Checking alone doesn't start the cooldown; only a recorded use
+        // does.
+        for _ in 0..2 {
+            group_manager
+                .check_permission_at(group_id, member_id, Permission::CreatePolls, ten)
+                .unwrap();
+        }
+        group_manager.record_permission_use_at(group_id, member_id, Permission::CreatePolls, ten);
+        let err = denied(group_manager.check_permission_at(
+            group_id,
+            member_id,
+            Permission::CreatePolls,
+            ten + 30,
+        ));
+        assert!(err.reason.contains("cooldown"), "{}", err.reason);
+        // The cooldown is per permission, and expires.
+        group_manager
+            .check_permission_at(group_id, member_id, Permission::SendMessages, ten + 30)
+            .unwrap();
+        group_manager
+            .check_permission_at(group_id, member_id, Permission::CreatePolls, ten + 60)
+            .unwrap();
+        // The owner's role is unrestricted.
+        group_manager
+            .check_permission_at(group_id, owner_id, Permission::CreatePolls, midnight)
+            .unwrap();
+
+        let weekdays = TimeRestriction {
+            allowed_days: Some((1..=5).collect()),
+            ..office_hours
+        };
+        assert!(weekdays.check(ten, None).is_err());
+        assert!(weekdays.check(ten + 2 * 86_400, None).is_ok());
+    }
+
+    #[test]
+    fn only_successful_actions_start_the_cooldown() {
+        use crate::groups::group_permissions::TimeRestriction;
+
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = SecureKeystore::new(
+            temp_dir.path().join("group_keystore.db"),
+            b"test-keystore-passphrase",
+        )
+        .unwrap();
+        let mut group_manager = GroupManager::new(keystore).unwrap();
+
+        let owner = IdentityKeyPair::generate().unwrap();
+        let member = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let member_id = member.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings {
+                    file_sharing_enabled: true,
+                    ..GroupSettings::default()
+                },
+            )
+            .unwrap();
+        group_manager
+            .add_member(
+                group_id,
+                owner_id,
+                member_id,
+                member.public_key(),
+                "Member".to_string(),
+                Role::Member,
+            )
+            .unwrap();
+        group_manager
+            .set_role_context(
+                group_id,
+                owner_id,
+                Role::Member,
+                Some(PermissionContext {
+                    time_restrictions: Some(TimeRestriction {
+                        allowed_hours: None,
+                        allowed_days: None,
+                        cooldown_period: Some(3600),
+                    }),
+                    ..PermissionContext::default()
+                }),
+            )
+            .unwrap();
+
+        // Read-only checks don't count as uses.
+        for _ in 0..2 {
+            group_manager
+                .validate_content(group_id, member_id, Some("image/png"), 10, 0)
+                .unwrap();
+        }
+
+        let closes_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600;
+        let poll = |gm: &mut GroupManager, options: usize| {
+            gm.create_poll(
+                group_id,
+                member_id,
+                "Lunch?".to_string(),
+                (0..options).map(|i| i.to_string()).collect(),
+                closes_at,
+            )
+        };
+        assert!(matches!(
+            poll(&mut group_manager, 1),
+            Err(GroupError::PollOptionCount { .. })
+        ));
+        poll(&mut group_manager, 2).unwrap();
+        assert!(matches!(
+            poll(&mut group_manager, 2),
+            Err(GroupError::Restricted(_))
+        ));
+    }
+
+    #[test]
+    fn content_restrictions_name_the_limit_exceeded() {
+        use crate::groups::group_permissions::{ContentRejected, ContentRestriction};
+
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+
+        let owner = IdentityKeyPair::generate().unwrap();
+        let member = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let member_id = member.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        group_manager
+            .add_member(
+                group_id,
+                owner_id,
+                member_id,
+                member.public_key(),
+                "Member".to_string(),
+                Role::Member,
+            )
+            .unwrap();
+
+        let context = PermissionContext {
+            content_restrictions: Some(ContentRestriction {
+                max_file_size: Some(1024),
+                allowed_file_types: Some(vec![
+                    "image/*".to_string(),
+                    "application/pdf".to_string(),
+                ]),
+                max_message_length: Some(64),
+            }),
+            ..PermissionContext::default()
+        };
+        assert!(group_manager
+            .set_role_context(group_id, member_id, Role::Member, Some(context.clone()))
+            .is_err());
+        group_manager
+            .set_role_context(group_id, owner_id, Role::Member, Some(context))
+            .unwrap();
+
+        let rejected = |result: Result<(), GroupError>| match result {
+            Err(GroupError::ContentRejected(rejected)) => rejected,
+            other => panic!("expected ContentRejected, got {other:?}"),
+        };
+        assert_eq!(
+            rejected(group_manager.validate_content(
+                group_id,
+                member_id,
+                Some("image/png"),
+                4096,
+                0
+            )),
+            ContentRejected::FileTooLarge {
+                size: 4096,
+                limit: 1024
+            }
+        );
+        assert_eq!(
+            rejected(group_manager.validate_content(group_id, member_id, Some("video/mp4"), 10, 0)),
+            ContentRejected::FileTypeNotAllowed {
+                content_type: "video/mp4".to_string()
+            }
+        );
+        assert_eq!(
+            rejected(group_manager.validate_content(group_id, member_id, None, 0, 65)),
+            ContentRejected::MessageTooLong { len: 65, limit: 64 }
+        );
+        group_manager
+            .validate_content(group_id, member_id, Some("IMAGE/JPEG"), 1024, 64)
+            .unwrap();
+        group_manager
+            .validate_content(group_id, member_id, Some("application/pdf"), 10, 0)
+            .unwrap();
+        // The owner's role carries no restrictions.
+        group_manager
+            .validate_content(group_id, owner_id, Some("video/mp4"), 4096, 0)
+            .unwrap();
+    }
+
+    #[test]
+    fn poll_create_vote_tally_and_close() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+
+        let owner = IdentityKeyPair::generate().unwrap();
+        let member = IdentityKeyPair::generate().unwrap();
+        let observer = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        for (kp, role) in [(&member, Role::Member), (&observer, Role::Observer)] {
+            group_manager
+                .add_member(
+                    group_id,
+                    owner_id,
+                    kp.identity_id(),
+                    kp.public_key(),
+                    "Member".to_string(),
+                    role,
+                )
+                .unwrap();
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let closes_at = now + 3600;
+        let options = vec!["Tea".to_string(), "Coffee".to_string()];
+        assert!(group_manager
+            .create_poll(
+                group_id,
+                observer.identity_id(),
+                "Drink?".into(),
+                options.clone(),
+                closes_at
+            )
+            .is_err());
+        let poll_id = group_manager
+            .create_poll(
+                group_id,
+                member.identity_id(),
+                "Drink?".into(),
+                options,
+                closes_at,
+            )
+            .unwrap();
+
+        group_manager
+            .cast_vote(group_id, poll_id, owner_id, 0)
+            .unwrap();
+        group_manager
+            .cast_vote(group_id, poll_id, member.identity_id(), 0)
+            .unwrap();
+        // Changing a vote overwrites it rather than counting twice.
+        group_manager
+            .cast_vote(group_id, poll_id, member.identity_id(), 1)
+            .unwrap();
+        assert!(group_manager
+            .cast_vote(group_id, poll_id, observer.identity_id(), 0)
+            .is_err());
+        assert!(group_manager
+            .cast_vote(group_id, poll_id, owner_id, 2)
+            .is_err());
+        assert_eq!(
+            group_manager.tally_poll(group_id, poll_id).unwrap(),
+            PollTally {
+                counts: vec![1, 1],
+                total_votes: 2,
+                closed: false,
+            }
+        );
+
+        let err = group_manager
+            .cast_vote_at(group_id, poll_id, owner_id, 1, closes_at)
+            .unwrap_err();
+        assert!(err.to_string().contains("closed"));
+
+        // Only the creator or a poll manager may close early.
+        assert!(group_manager
+            .close_poll(group_id, poll_id, observer.identity_id())
+            .is_err());
+        group_manager
+            .close_poll(group_id, poll_id, owner_id)
+            .unwrap();
+        assert!(group_manager
+            .cast_vote(group_id, poll_id, owner_id, 1)
+            .is_err());
+        let tally = group_manager.tally_poll(group_id, poll_id).unwrap();
+        assert!(tally.closed);
+        assert_eq!(tally.counts, vec![1, 1]);
+    }
+
+    #[test]
+    fn slow_mode_throttles_members_but_not_admins() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+
+        let owner = IdentityKeyPair::generate().unwrap();
+        let admin = IdentityKeyPair::generate().unwrap();
+        let member = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        for (kp, role) in [(&admin, Role::Admin), (&member, Role::Member)] {
+            group_manager
+                .add_member(
+                    group_id,
+                    owner_id,
+                    kp.identity_id(),
+                    kp.public_key(),
+                    "Member".to_string(),
+                    role,
+                )
+                .unwrap();
+        }
+        assert!(group_manager
+            .set_slow_mode(
+                group_id,
+                member.identity_id(),
+                Some(SlowMode { interval_secs: 1 })
+            )
+            .is_err());
+        group_manager
+            .set_slow_mode(group_id, owner_id, Some(SlowMode { interval_secs: 30 }))
+            .unwrap();
+
+        let t = 1_000;
+        group_manager
+            .check_send_allowed_at(group_id, member.identity_id(), t)
+            .unwrap();
+        assert_eq!(
+            group_manager.check_send_allowed_at(group_id, member.identity_id(), t + 10),
+            Err(GroupError::RetryAfter(RetryAfter(Duration::from_secs(20))))
+        );
+
+        for offset in [0, 1, 2] {
+            group_manager
+                .check_send_allowed_at(group_id, admin.identity_id(), t + offset)
+                .expect("admins are exempt");
+        }
+
+        // The rejected attempt didn't restart the window.
+        group_manager
+            .check_send_allowed_at(group_id, member.identity_id(), t + 30)
+            .expect("window has passed");
+        assert!(group_manager
+            .check_send_allowed_at(group_id, member.identity_id(), t + 31)
+            .is_err());
+    }
+
+    #[test]
+    fn banned_member_cannot_rejoin_until_ban_expires() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+
+        let owner = IdentityKeyPair::generate().unwrap();
+        let troll = IdentityKeyPair::generate().unwrap();
+        let visitor = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        for kp in [&troll, &visitor] {
+            group_manager
+                .add_member(
+                    group_id,
+                    owner_id,
+                    kp.identity_id(),
+                    kp.public_key(),
+                    "Member".to_string(),
+                    Role::Member,
+                )
+                .unwrap();
+        }
+
+        // Permanent ban: direct add and invitation join are both refused.
+        group_manager
+            .ban_member(group_id, owner_id, troll.identity_id(), "spam".into(), None)
+            .unwrap();
+        let err = group_manager
+            .add_member(
+                group_id,
+                owner_id,
+                troll.identity_id(),
+                troll.public_key(),
+                "Troll".to_string(),
+                Role::Member,
+            )
+            .unwrap_err();
+        assert_eq!(
+            err,
+            GroupError::Banned {
+                reason: "spam".into(),
+                until: None
+            }
+        );
+        let invitation = group_manager
+            .create_invitation(group_id, owner_id, None, Some(5))
+            .unwrap();
+        assert!(matches!(
+            group_manager.join_group_with_invitation(
+                invitation.invitation_code.clone(),
+                troll.identity_id(),
+                troll.public_key(),
+                "Troll".to_string(),
+            ),
+            Err(GroupError::Banned { .. })
+        ));
+        assert_eq!(
+            group_manager
+                .get_invitation(&invitation.invitation_code)
+                .unwrap()
+                .unwrap()
+                .current_uses,
+            0
+        );
+
+        // A ban that has already run out is cleared by re-joining.
+        group_manager
+            .ban_member(
+                group_id,
+                owner_id,
+                visitor.identity_id(),
+                "cool off".into(),
+                Some(1),
+            )
+            .unwrap();
+        group_manager
+            .join_group_with_invitation(
+                invitation.invitation_code,
+                visitor.identity_id(),
+                visitor.public_key(),
+                "Visitor".to_string(),
+            )
+            .expect("expired ban allows re-join");
+        let group = group_manager.get_group(&group_id).unwrap();
+        assert_eq!(
+            group.members[&visitor.identity_id()].member_status,
+            MemberStatus::Active
+        );
+        assert_eq!(group.members.len(), 3);
+    }
+
+    #[test]
+    fn event_log_hash_chain_detects_tampering() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+
+        let owner = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        for _ in 0..3 {
+            let member = IdentityKeyPair::generate().unwrap();
+            group_manager
+                .add_member(
+                    group_id,
+                    owner_id,
+                    member.identity_id(),
+                    member.public_key(),
+                    "Member".to_string(),
+                    Role::Member,
+                )
+                .unwrap();
+        }
+
+        // All four events land in the same second; sequence keeps them
+        // apart and in order.
+        let events = group_manager.get_group_events(&group_id).unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].event_type, GroupEventType::GroupCreated);
+        assert_eq!(events[0].prev_hash, group_events::genesis_hash(&group_id));
+        assert_eq!(group_manager.verify_event_log(&group_id).unwrap(), None);
+
+        // Rewriting the second event's description is reported there,
+        // not at the third event whose link catches it.
+        let mut edited = events[1].clone();
+        edited.description = "Nothing to see here".to_string();
+        let key = |sequence: u64| {
+            format!(
+                "{}{:020}",
+                GroupManager::group_event_prefix(&group_id),
+                sequence
+            )
+        };
+        store_raw(
+            &mut group_manager,
+            &key(1),
+            &bincode::serialize(&edited).unwrap(),
+        );
+        assert_eq!(group_manager.verify_event_log(&group_id).unwrap(), Some(1));
+
+        // Rewriting an event's own link is reported at that event.
+        store_raw(
+            &mut group_manager,
+            &key(1),
+            &bincode::serialize(&events[1]).unwrap(),
+        );
+        let mut relinked = events[2].clone();
+        relinked.prev_hash = [0u8; 32];
+        store_raw(
+            &mut group_manager,
+            &key(2),
+            &bincode::serialize(&relinked).unwrap(),
+        );
+        assert_eq!(group_manager.verify_event_log(&group_id).unwrap(), Some(2));
+
+        // Deleting an event breaks the chain where the gap is.
+        group_manager.keystore.delete_key(&key(1)).unwrap();
+        assert_eq!(group_manager.verify_event_log(&group_id).unwrap(), Some(1));
+    }
+
+    fn store_raw(group_manager: &mut GroupManager, key: &str, data: &[u8]) {
+        group_manager
+            .keystore
+            .store_key(
+                key,
+                data,
+                KeyType::MessageKey,
+                KeyMetadata {
+                    algorithm: "bincode".to_string(),
+                    key_size: data.len(),
+                    usage: vec![KeyUsage::Encryption],
+                    expiry: None,
+                    tags: StdHashMap::new(),
+                },
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn legacy_events_migrate_into_the_chain() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+        let group_id = GroupId::from_bytes([7u8; 32]);
+        let actor_id = IdentityId::from([8u8; 32]);
+
+        // Bytes as the pre-chain `GroupEvent` serialized them: group_id,
+        // actor_id, variant index (u32 LE), description (u64 LE length),
+        // timestamp (u64 LE); keyed by the short group id and timestamp.
+        let legacy = |variant: u32, description: &str, timestamp: u64| {
+            let mut bytes = [7u8; 32].to_vec();
+            bytes.extend_from_slice(&[8u8; 32]);
+            bytes.extend_from_slice(&variant.to_le_bytes());
+            bytes.extend_from_slice(&(description.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(description.as_bytes());
+            bytes.extend_from_slice(&timestamp.to_le_bytes());
+            (format!("group_event_{group_id}_{timestamp}"), bytes)
+        };
+        for (variant, description, timestamp) in [(1, "added", 200), (0, "created", 100)] {
+            let (key, bytes) = legacy(variant, description, timestamp);
+            store_raw(&mut group_manager, &key, &bytes);
+        }
+
+        let events = group_manager.get_group_events(&group_id).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event_type, GroupEventType::GroupCreated);
+        assert_eq!(events[0].actor_id, actor_id);
+        assert_eq!((events[0].sequence, events[0].timestamp), (0, 100));
+        assert_eq!(events[1].description, "added");
+        assert_eq!((events[1].sequence, events[1].timestamp), (1, 200));
+        assert_eq!(group_manager.verify_event_log(&group_id).unwrap(), None);
+        let legacy_prefix = format!("group_event_{group_id}_");
+        assert!(group_manager
+            .keystore
+            .list_keys_with_prefix(&legacy_prefix, usize::MAX, None)
+            .is_empty());
+
+        // A legacy entry turning up next to a chained log, or an entry
+        // that doesn't decode, is an error rather than a silent gap.
+        let (key, bytes) = legacy(2, "late", 300);
+        store_raw(&mut group_manager, &key, &bytes);
+        assert!(group_manager.get_group_events(&group_id).is_err());
+        group_manager.keystore.delete_key(&key).unwrap();
+        let chained = format!("{}{:020}", GroupManager::group_event_prefix(&group_id), 2);
+        store_raw(&mut group_manager, &chained, b"garbage");
+        assert!(group_manager.get_group_events(&group_id).is_err());
+    }
+
+    #[test]
+    fn failures_downcast_to_group_error() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path, b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+
+        let owner = IdentityKeyPair::generate().unwrap();
+        let member = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let member_id = member.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        let add = |gm: &mut GroupManager| {
+            gm.add_member(
+                group_id,
+                owner_id,
+                member_id,
+                member.public_key(),
+                "Member".to_string(),
+                Role::Member,
+            )
+        };
+        add(&mut group_manager).unwrap();
+        assert_eq!(add(&mut group_manager), Err(GroupError::MemberExists));
+        assert_eq!(
+            group_manager.leave_group(group_id, owner_id),
+            Err(GroupError::OwnerCannotLeave)
+        );
+        assert_eq!(
+            group_manager.leave_group(GroupId::from_bytes([9; 32]), member_id),
+            Err(GroupError::NotFound)
+        );
+        assert_eq!(
+            group_manager.remove_member(group_id, owner_id, owner_id, "test".to_string()),
+            Err(GroupError::OwnerProtected)
+        );
+        assert_eq!(
+            group_manager.remove_member(group_id, member_id, owner_id, "test".to_string()),
+            Err(GroupError::PermissionDenied(Permission::RemoveMembers))
+        );
+        assert_eq!(
+            group_manager.create_poll(
+                group_id,
+                owner_id,
+                "Lunch?".to_string(),
+                vec!["Yes".to_string()],
+                u64::MAX,
+            ),
+            Err(GroupError::PollOptionCount {
+                max: MAX_POLL_OPTIONS
+            })
+        );
+
+        assert_eq!(
+            group_manager.update_member_role(group_id, owner_id, owner_id, Role::Member),
+            Err(GroupError::OwnerProtected)
+        );
+        assert!(matches!(
+            group_manager.promote_member(group_id, member_id, member_id, Role::Admin),
+            Err(GroupError::NotOwner)
+        ));
+        assert!(matches!(
+            group_manager.transfer_ownership(group_id, owner_id, owner_id),
+            Err(GroupError::TransferToSelf)
+        ));
+        assert!(matches!(
+            group_manager.create_invitation(group_id, member_id, None, None),
+            Err(GroupError::PermissionDenied(Permission::CreateInvites))
+        ));
+
+        // Methods still on anyhow carry the same variants.
+        let err = group_manager
+            .apply_role_change(group_id, owner_id, Role::Member, 0)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<GroupError>(),
+            Some(&GroupError::OwnerProtected)
+        );
+        assert!(matches!(
+            MessengerError::from(GroupError::NotFound),
+            MessengerError::Group(GroupError::NotFound)
+        ));
+        // Display strings are unchanged for callers that only log them.
+        assert_eq!(GroupError::NotFound.to_string(), "Group not found");
+    }
+
+    #[test]
+    fn event_log_pages_newest_first_in_contiguous_windows() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let keystore_path = temp_dir.path().join("group_keystore.db");
+        let keystore = SecureKeystore::new(keystore_path.clone(), b"test-keystore-passphrase")
+            .expect("Should create keystore");
+        let mut group_manager = GroupManager::new(keystore).expect("Should create group manager");
+
+        let owner = IdentityKeyPair::generate().unwrap();
+        let owner_id = owner.identity_id();
+        let group_id = group_manager
+            .create_group(
+                owner_id,
+                owner.public_key(),
+                "Test Group".to_string(),
+                "A test group".to_string(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        for i in 1..50 {
+            group_manager
+                .log_group_event(
+                    group_id,
+                    owner_id,
+                    GroupEventType::SettingsChanged,
+                    format!("change {}", i),
+                )
+                .unwrap();
+        }
+        let all = group_manager.get_group_events(&group_id).unwrap();
+        assert_eq!(all.len(), 50);
+
+        let mut paged = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = group_manager
+                .get_group_events_paged(&group_id, cursor, 20)
+                .unwrap();
+            assert!(page.len() <= 20);
+            let Some(last) = page.last() else { break };
+            cursor = Some(last.sequence);
+            paged.extend(page);
+        }
+        let sequences: Vec<u64> = paged.iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, (0..50).rev().collect::<Vec<u64>>());
+        assert!(paged.windows(2).all(|w| w[0].timestamp >= w[1].timestamp));
+        assert!(paged
+            .iter()
+            .rev()
+            .zip(&all)
+            .all(|(a, b)| a.hash() == b.hash()));
+
+        // A manager that hasn't logged anything yet finds the head from
+        // storage.
+        let mut reopened = GroupManager::new(
+            SecureKeystore::new(keystore_path, b"test-keystore-passphrase").unwrap(),
+        )
+        .unwrap();
+        let newest = reopened.get_group_events_paged(&group_id, None, 5).unwrap();
+        assert_eq!(newest.first().map(|e| e.sequence), Some(49));
+        assert_eq!(newest.len(), 5);
+        let middle = reopened
+            .get_group_events_paged(&group_id, Some(30), 5)
+            .unwrap();
+        assert_eq!(middle.first().map(|e| e.sequence), Some(29));
+        assert_eq!(middle.last().map(|e| e.sequence), Some(25));
+    }
+
+    #[test]
+    fn lamport_clock_seeds_then_clamps_jumps_and_rejects_overflow() {
+        let temp_dir = TempDir::new().unwrap();
+        let keystore = SecureKeystore::new(
+            temp_dir.path().join("group_keystore.db"),
+            b"test-keystore-passphrase",
+        )
+        .unwrap();
+        let manager = GroupManager::new(keystore).unwrap();
+        let group_id = GroupId::from_bytes([3u8; 32]);
+
+        manager.observe_lamport(&group_id, 5 * MAX_LAMPORT_JUMP);
+        assert_eq!(manager.current_lamport(&group_id), 5 * MAX_LAMPORT_JUMP);
+
+        manager.observe_lamport(&group_id, 7 * MAX_LAMPORT_JUMP);
+        assert_eq!(manager.current_lamport(&group_id), 6 * MAX_LAMPORT_JUMP);
+        manager.observe_lamport(&group_id, u64::MAX);
+        assert_eq!(manager.current_lamport(&group_id), 7 * MAX_LAMPORT_JUMP);
+        manager.observe_lamport(&group_id, 1);
+        assert_eq!(manager.current_lamport(&group_id), 7 * MAX_LAMPORT_JUMP);
+
+        manager
+            .lamport_clocks
+            .lock()
+            .unwrap()
+            .insert(group_id, u64::MAX);
+        assert!(matches!(
+            manager.tick_lamport(&group_id),
+            Err(GroupError::LamportOutOfRange { .. })
+        ));
+        assert_eq!(manager.current_lamport(&group_id), u64::MAX);
+    }
 }
@@ -8,7 +8,7 @@
 //! ```text
 //! MAGIC_GROUP_MESSAGE || bincode({
 //!   body: GroupMessageBody { group_id, sender_id, generation,
-//!                            aead_payload, timestamp },
+//!                            lamport, aead_payload, timestamp },
 //!   signature: HybridSignature(over canonical_group_message(body)),
 //! })
 //! ```
@@ -17,12 +17,20 @@
 //! who knew the group key wrote this") plus the sender's hybrid
 //! signature (gives you "this specific member wrote this", so members
 //! can't impersonate each other inside the group).
+//!
+//! Gossipsub doesn't preserve order, so each body also carries the
+//! sender's per-group Lamport clock (covered by the signature).
+//! Receivers that sort by [`DecryptedGroupMessage::order_key`] agree
+//! on one timeline, and a reply always sorts after what it answers.
 
 use anyhow::{anyhow, Context, Result};
+use bincode::Options;
 use serde::{Deserialize, Serialize};
 
 use crate::groups::group_manager::{GroupId, GroupManager};
+use crate::groups::group_permissions::Permission;
 use crate::identity::identity_key::{HybridSignature, IdentityId, IdentityKey, IdentityKeyPair};
+use crate::network::fragmentation;
 use chacha20poly1305::{
     aead::{Aead, KeyInit},
     ChaCha20Poly1305, Nonce,
@@ -56,18 +64,42 @@ const OUTER_ENVELOPE_KDF_CONTEXT: &str = "qubee outer envelope v1";
 /// for a captured frame. 5 minutes matches the rest of the protocol.
 pub const GROUP_MESSAGE_MAX_AGE_SECS: u64 = 5 * 60;
 
-const GROUP_MESSAGE_TAG: &[u8] = b"qubee_group_message_v1";
+const GROUP_MESSAGE_TAG: &[u8] = b"qubee_group_message_v3";
+
+/// Largest sealed frame accepted, checked before any crypto runs. Set
+/// to what the fragment [`Reassembler`] can rebuild at most under its
+/// default [`ReassemblyConfig`], so every frame that can legitimately
+/// arrive fits.
+///
+/// [`Reassembler`]: crate::network::fragmentation::Reassembler
+/// [`ReassemblyConfig`]: crate::network::fragmentation::ReassemblyConfig
+pub const MAX_GROUP_MESSAGE_LEN: usize = fragmentation::DEFAULT_MAX_BUFFERED_BYTES;
+
+/// Shortest possible inner AEAD payload: `epoch(8) || nonce(12)` plus
+/// the Poly1305 tag of an empty message.
+const MIN_AEAD_PAYLOAD_LEN: usize = 8 + 12 + 16;
+
+/// A frame that fails structural validation: oversized, truncated,
+/// trailing garbage, or a field out of bounds. Raised before signature
+/// or AEAD work on the offending part. Downcast the `anyhow::Error` to
+/// tell a malformed frame from one that was well-formed but not ours.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("malformed group message: {0}")]
+pub struct MalformedFrame(pub &'static str);
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GroupMessageBody {
     pub group_id: GroupId,
     pub sender_id: IdentityId,
     /// Snapshot of the group's `version` counter at send time.
-    /// Lets receivers detect "this was encrypted under an older key
-    /// I no longer have", though for now we simply attempt decryption
-    /// against the current key and fall through to logging.
+    /// Receivers accept it only if it is no newer than their own
+    /// version and no older than the retained key the frame opened
+    /// under; see [`decrypt_group_message`].
     pub generation: u64,
-    /// `[nonce(12) || ciphertext]` from
+    /// Sender's Lamport clock for this group at send time; see
+    /// [`GroupManager::current_lamport`].
+    pub lamport: u64,
+    /// `[epoch(8) || nonce(12) || ciphertext]` from
     /// [`GroupCrypto::encrypt_message`].
     pub aead_payload: Vec<u8>,
     pub timestamp: u64,
@@ -90,8 +122,20 @@ impl GroupMessageEnvelope {
         bincode::serialize(self).context("group message serialize")
     }
 
+    /// Decode an inner envelope. Stricter than plain
+    /// `bincode::deserialize`: length prefixes can't claim more than
+    /// [`MAX_GROUP_MESSAGE_LEN`] in total and trailing bytes are
+    /// refused.
     pub fn from_inner_bincode(bytes: &[u8]) -> Result<Self> {
-        bincode::deserialize(bytes).context("group message deserialize")
+        if bytes.len() > MAX_GROUP_MESSAGE_LEN {
+            return Err(MalformedFrame("inner envelope too large").into());
+        }
+        let envelope: Self = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .with_limit(MAX_GROUP_MESSAGE_LEN as u64)
+            .deserialize(bytes)
+            .map_err(|_| MalformedFrame("inner envelope does not decode"))?;
+        Ok(envelope)
     }
 }
 
@@ -152,19 +196,39 @@ pub fn seal_outer_envelope(
 /// on success.
 ///
 /// `group_key_lookup` is a closure that takes the parsed `group_id`
-/// and returns the current 32-byte group key for it, or `None` if the
+/// and returns the candidate 32-byte group keys for it — the current
+/// key, or every retained key newest first — and nothing if the
 /// receiver isn't a member. Two-stage because the wire carries
 /// `group_id` in the clear (it's identical to the gossipsub topic
 /// name), but the inner ciphertext can only be opened with the right
 /// group key — so the parser first reads `group_id`, asks the caller
-/// which key to use, then attempts AEAD.
-pub fn open_outer_envelope(
+/// which keys to try, then attempts AEAD with each in turn.
+pub fn open_outer_envelope<K>(
+    wire: &[u8],
+    group_key_lookup: impl FnOnce(&GroupId) -> K,
+) -> Result<(GroupId, Vec<u8>)>
+where
+    K: IntoIterator<Item = [u8; 32]>,
+{
+    let (group_id, _, inner) = open_outer_envelope_indexed(wire, group_key_lookup)?;
+    Ok((group_id, inner))
+}
+
+/// [`open_outer_envelope`], also returning the position of the key
+/// that opened the frame among those the lookup produced.
+fn open_outer_envelope_indexed<K>(
     wire: &[u8],
-    group_key_lookup: impl FnOnce(&GroupId) -> Option<[u8; 32]>,
-) -> Result<(GroupId, Vec<u8>)> {
+    group_key_lookup: impl FnOnce(&GroupId) -> K,
+) -> Result<(GroupId, usize, Vec<u8>)>
+where
+    K: IntoIterator<Item = [u8; 32]>,
+{
     if wire.len() < MAGIC_GROUP_MESSAGE.len() + 32 + 12 {
         return Err(anyhow!("outer envelope too short"));
     }
+    if wire.len() > MAX_GROUP_MESSAGE_LEN {
+        return Err(MalformedFrame("frame too large").into());
+    }
     if &wire[..MAGIC_GROUP_MESSAGE.len()] != MAGIC_GROUP_MESSAGE {
         return Err(anyhow!("not a sealed group-message frame"));
     }
@@ -179,21 +243,28 @@ pub fn open_outer_envelope(
     offset += 12;
     let ciphertext = &wire[offset..];
 
-    let group_key = group_key_lookup(&group_id)
-        .ok_or_else(|| anyhow!("outer envelope: unknown group / not a member"))?;
-    let outer_key = derive_outer_envelope_key(&group_key);
-    let cipher = ChaCha20Poly1305::new_from_slice(&outer_key)
-        .map_err(|_| anyhow!("invalid outer key length"))?;
-    let inner = cipher
-        .decrypt(
+    let mut tried_any = false;
+    for (index, group_key) in group_key_lookup(&group_id).into_iter().enumerate() {
+        tried_any = true;
+        let outer_key = derive_outer_envelope_key(&group_key);
+        let cipher = ChaCha20Poly1305::new_from_slice(&outer_key)
+            .map_err(|_| anyhow!("invalid outer key length"))?;
+        if let Ok(inner) = cipher.decrypt(
             nonce,
             chacha20poly1305::aead::Payload {
                 msg: ciphertext,
                 aad: group_id.as_ref(),
             },
-        )
-        .map_err(|e| anyhow!("outer envelope open: {e:?}"))?;
-    Ok((group_id, inner))
+        ) {
+            return Ok((group_id, index, inner));
+        }
+    }
+    if !tried_any {
+        return Err(anyhow!("outer envelope: unknown group / not a member"));
+    }
+    Err(anyhow!(
+        "outer envelope open: no retained group key opens the frame"
+    ))
 }
 
 /// Canonical bytes the sender's [`HybridSignature`] covers. Built by
@@ -209,6 +280,8 @@ pub fn canonical_group_message(body: &GroupMessageBody) -> Vec<u8> {
     out.push(0u8);
     out.extend_from_slice(&body.generation.to_le_bytes());
     out.push(0u8);
+    out.extend_from_slice(&body.lamport.to_le_bytes());
+    out.push(0u8);
     out.extend_from_slice(&body.timestamp.to_le_bytes());
     out.push(0u8);
     out.extend_from_slice(&(body.aead_payload.len() as u32).to_le_bytes());
@@ -249,7 +322,7 @@ pub fn group_message_id(body: &GroupMessageBody) -> [u8; 16] {
 /// aren't a valid sealed frame for a group the receiver is a member
 /// of.
 pub fn extract_message_id(gm: &GroupManager, wire: &[u8]) -> Option<[u8; 16]> {
-    let (_, inner) = open_outer_envelope(wire, |gid| gm.export_group_key(gid)).ok()?;
+    let (_, inner) = open_outer_envelope(wire, |gid| retained_keys(gm, gid)).ok()?;
     let envelope = GroupMessageEnvelope::from_inner_bincode(&inner).ok()?;
     Some(group_message_id(&envelope.body))
 }
@@ -261,10 +334,20 @@ pub struct DecryptedGroupMessage {
     pub group_id: GroupId,
     pub sender_id: IdentityId,
     pub generation: u64,
+    pub lamport: u64,
     pub plaintext: Vec<u8>,
     pub timestamp: u64,
 }
 
+impl DecryptedGroupMessage {
+    /// Sort key for rendering a group's timeline. Every replica orders
+    /// the same set of messages identically: by Lamport clock, with
+    /// concurrent sends (equal clocks) broken by sender id.
+    pub fn order_key(&self) -> (u64, IdentityId) {
+        (self.lamport, self.sender_id)
+    }
+}
+
 /// Encrypt a plaintext message for the named group, sign the envelope
 /// with the sender's identity keypair, and return the wire-ready
 /// bytes (with [`MAGIC_GROUP_MESSAGE`] prefix).
@@ -272,6 +355,7 @@ pub struct DecryptedGroupMessage {
 /// The group must already have a key installed in `gm`'s GroupCrypto;
 /// callers can assume that's the case after a successful join or
 /// `plan_key_rotation`.
+#[tracing::instrument(skip_all, err, fields(group = %group_id, len = plaintext.len(), generation))]
 pub fn encrypt_group_message(
     gm: &GroupManager,
     sender_identity: &IdentityKeyPair,
@@ -281,11 +365,13 @@ pub fn encrypt_group_message(
     let group = gm
         .get_group(&group_id)
         .ok_or_else(|| anyhow!("encrypt: unknown group"))?;
+    tracing::Span::current().record("generation", group.version);
     let aead_payload = gm.encrypt_group_message(&group_id, plaintext)?;
     let body = GroupMessageBody {
         group_id,
         sender_id: sender_identity.identity_id(),
         generation: group.version,
+        lamport: gm.tick_lamport(&group_id)?,
         aead_payload,
         timestamp: now_secs(),
     };
@@ -303,7 +389,37 @@ pub fn encrypt_group_message(
     let group_key = gm
         .export_group_key(&group_id)
         .ok_or_else(|| anyhow!("encrypt: no group key installed"))?;
-    seal_outer_envelope(&group_id, &group_key, &inner_bincoded)
+    let wire = seal_outer_envelope(&group_id, &group_key, &inner_bincoded)?;
+    if wire.len() > MAX_GROUP_MESSAGE_LEN {
+        return Err(anyhow!("encrypt: message too large"));
+    }
+    Ok(wire)
+}
+
+/// [`encrypt_group_message`] for a file attachment. Checks the bytes
+/// against the group's content rules for the sender
+/// ([`GroupManager::validate_content`]) before encrypting anything.
+pub fn encrypt_group_file(
+    gm: &GroupManager,
+    sender_identity: &IdentityKeyPair,
+    group_id: GroupId,
+    content_type: &str,
+    bytes: &[u8],
+) -> Result<Vec<u8>> {
+    gm.validate_content(
+        group_id,
+        sender_identity.identity_id(),
+        Some(content_type),
+        bytes.len() as u64,
+        0,
+    )?;
+    let wire = encrypt_group_message(gm, sender_identity, group_id, bytes)?;
+    gm.record_permission_use(
+        group_id,
+        sender_identity.identity_id(),
+        Permission::SendFiles,
+    )?;
+    Ok(wire)
 }
 
 /// Validate + decrypt a wire-format group-message frame.
@@ -314,13 +430,20 @@ pub fn encrypt_group_message(
 ///      (so a former member's captured key can't be replayed once
 ///      they're rotated out).
 ///   3. Verify the sender's signature against the canonical payload.
-///   4. Decrypt the AEAD payload with the current group key.
-///   5. Return the plaintext + sender id + timestamp.
+///   4. Decrypt the AEAD payload with whichever retained group key
+///      opens it (current first, then up to
+///      [`GROUP_KEY_HISTORY`](crate::groups::group_crypto::GROUP_KEY_HISTORY)` - 1`
+///      predecessors), so frames sent just before a rotation still
+///      land.
+///   5. Advance the local Lamport clock past the frame's, by at most
+///      `MAX_LAMPORT_JUMP` once the clock has been seeded.
+///   6. Return the plaintext + sender id + timestamp.
 ///
 /// Step 2 is the linchpin of "removed members can't keep talking" —
 /// `process_key_rotation` flips the kicked member's status, so any
 /// later GroupMessage from them is rejected here on purely local
 /// state.
+#[tracing::instrument(skip_all, err, fields(len = wire.len(), group, generation))]
 pub fn decrypt_group_message(
     gm: &GroupManager,
     wire: &[u8],
@@ -328,23 +451,36 @@ pub fn decrypt_group_message(
     // Strip the outer-envelope layer first. Failure here (wrong magic,
     // wrong group, outer AEAD reject) means the frame either isn't ours
     // or has been tampered with — bounce it before any signature work.
-    let (_outer_group_id, inner) = open_outer_envelope(wire, |gid| gm.export_group_key(gid))?;
+    let mut key_generations = Vec::new();
+    let (_outer_group_id, key_index, inner) = open_outer_envelope_indexed(wire, |gid| {
+        let retained = gm.export_retained_group_keys(gid);
+        key_generations = retained.iter().map(|(generation, _)| *generation).collect();
+        retained.into_iter().map(|(_, key)| key)
+    })?;
     let envelope = GroupMessageEnvelope::from_inner_bincode(&inner)?;
     let body = &envelope.body;
+    if body.aead_payload.len() < MIN_AEAD_PAYLOAD_LEN {
+        return Err(MalformedFrame("AEAD payload truncated").into());
+    }
+    tracing::Span::current()
+        .record("group", tracing::field::display(body.group_id))
+        .record("generation", body.generation);
 
     let group = gm
         .get_group(&body.group_id)
         .ok_or_else(|| anyhow!("decrypt: unknown group"))?;
 
-    // Generation gate: closes the small race where a kicked-then-
-    // rotated member's already-in-flight message lands after the
-    // local rotation but before the gossipsub mesh has fully
-    // settled. `body.generation` is the sender's snapshot of
-    // `group.version` at send time; we accept only equal-version
-    // frames. Strict policy because the alternative — buffer
-    // future-generation frames until the matching KeyRotation
-    // arrives — needs reorder-safe state we don't yet have.
-    if body.generation != group.version {
+    // Generation gate. `body.generation` is the sender's snapshot of
+    // `group.version` at send time. Frames from the future are
+    // refused: buffering them until the matching KeyRotation arrives
+    // needs reorder-safe state we don't yet have. Older frames are
+    // fine as long as the key that opened them was installed at or
+    // before the claimed generation — anything older than the
+    // retained history already failed the outer AEAD. A kicked
+    // member's in-flight message is still stopped by the
+    // active-member check below.
+    let key_generation = key_generations[key_index];
+    if body.generation > group.version || body.generation < key_generation {
         return Err(anyhow!(
             "decrypt: generation mismatch (frame={}, local={})",
             body.generation,
@@ -372,16 +508,24 @@ pub fn decrypt_group_message(
     let plaintext = gm
         .decrypt_group_message(&body.group_id, &body.aead_payload)
         .context("AEAD decrypt")?;
+    gm.observe_lamport(&body.group_id, body.lamport);
 
     Ok(DecryptedGroupMessage {
         group_id: body.group_id,
         sender_id: body.sender_id,
         generation: body.generation,
+        lamport: body.lamport,
         plaintext,
         timestamp: body.timestamp,
     })
 }
 
+fn retained_keys(gm: &GroupManager, group_id: &GroupId) -> impl Iterator<Item = [u8; 32]> {
+    gm.export_retained_group_keys(group_id)
+        .into_iter()
+        .map(|(_, key)| key)
+}
+
 fn now_secs() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
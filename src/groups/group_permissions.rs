@@ -8,6 +8,10 @@ pub struct GroupPermissions {
     pub role_permissions: HashMap<Role, HashSet<Permission>>,
     /// Custom permission overrides
     pub custom_overrides: HashMap<String, HashSet<Permission>>,
+    /// Conditions attached to a role, on top of its permission set.
+    /// Roles without an entry are unrestricted. Stored groups from
+    /// before this field migrate with it empty.
+    pub role_contexts: HashMap<Role, PermissionContext>,
 }
 
 /// Roles within a group
@@ -133,6 +137,43 @@ pub struct TimeRestriction {
     pub cooldown_period: Option<u64>,
 }
 
+impl TimeRestriction {
+    /// Check an action at `now` (Unix seconds; hours and days are
+    /// UTC). `last_used` is when the member last used the same
+    /// permission, for the cooldown.
+    pub fn check(&self, now: u64, last_used: Option<u64>) -> Result<(), PermissionDenied> {
+        let hour = ((now / 3600) % 24) as u8;
+        // 1970-01-01 was a Thursday.
+        let day = ((now / 86_400 + 4) % 7) as u8;
+        if let Some(hours) = &self.allowed_hours {
+            if !hours.contains(&hour) {
+                return Err(PermissionDenied::new(format!(
+                    "not allowed at {:02}:00 UTC",
+                    hour
+                )));
+            }
+        }
+        if let Some(days) = &self.allowed_days {
+            if !days.contains(&day) {
+                return Err(PermissionDenied::new(format!(
+                    "not allowed on day {} of the week",
+                    day
+                )));
+            }
+        }
+        if let (Some(cooldown), Some(last)) = (self.cooldown_period, last_used) {
+            let elapsed = now.saturating_sub(last);
+            if elapsed < cooldown {
+                return Err(PermissionDenied::new(format!(
+                    "cooldown: retry in {}s",
+                    cooldown - elapsed
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Member count-based restrictions
 #[derive(Clone, Serialize, Deserialize)]
 pub struct MemberCountRestriction {
@@ -147,12 +188,87 @@ pub struct MemberCountRestriction {
 pub struct ContentRestriction {
     /// Maximum file size (bytes)
     pub max_file_size: Option<u64>,
-    /// Allowed file types
+    /// Allowed file types: MIME types such as `image/png`, or a
+    /// top-level wildcard such as `image/*`
     pub allowed_file_types: Option<Vec<String>>,
-    /// Maximum message length
+    /// Maximum message length (bytes)
     pub max_message_length: Option<usize>,
 }
 
+/// A [`TimeRestriction`] on the member's role refused a permission the
+/// role otherwise has; `reason` says which rule. `GroupManager`
+/// reports it as
+/// [`GroupError::Restricted`](crate::groups::group_manager::GroupError::Restricted).
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("permission denied: {reason}")]
+pub struct PermissionDenied {
+    pub reason: String,
+}
+
+impl PermissionDenied {
+    pub fn new(reason: impl Into<String>) -> Self {
+        PermissionDenied {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Which [`ContentRestriction`] limit a message or file broke.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ContentRejected {
+    #[error("file is {size} bytes, over the group's {limit}-byte limit")]
+    FileTooLarge { size: u64, limit: u64 },
+    #[error("file type {content_type} is not allowed in this group")]
+    FileTypeNotAllowed { content_type: String },
+    #[error("message is {len} bytes, over the group's {limit}-byte limit")]
+    MessageTooLong { len: usize, limit: usize },
+}
+
+impl ContentRestriction {
+    /// Check one outgoing message. `content_type` and `size` describe
+    /// an attached file, if any; `body_len` is the text length.
+    pub fn check(
+        &self,
+        content_type: Option<&str>,
+        size: u64,
+        body_len: usize,
+    ) -> Result<(), ContentRejected> {
+        if let Some(limit) = self.max_message_length {
+            if body_len > limit {
+                return Err(ContentRejected::MessageTooLong {
+                    len: body_len,
+                    limit,
+                });
+            }
+        }
+        let Some(content_type) = content_type else {
+            return Ok(());
+        };
+        if let Some(limit) = self.max_file_size {
+            if size > limit {
+                return Err(ContentRejected::FileTooLarge { size, limit });
+            }
+        }
+        if let Some(allowed) = &self.allowed_file_types {
+            if !allowed.iter().any(|a| mime_matches(a, content_type)) {
+                return Err(ContentRejected::FileTypeNotAllowed {
+                    content_type: content_type.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn mime_matches(pattern: &str, content_type: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(top) => content_type
+            .split_once('/')
+            .is_some_and(|(t, _)| t.eq_ignore_ascii_case(top)),
+        None => pattern.eq_ignore_ascii_case(content_type),
+    }
+}
+
 impl GroupPermissions {
     /// Create default permissions for a standard group
     pub fn default() -> Self {
@@ -227,6 +343,7 @@ impl GroupPermissions {
         GroupPermissions {
             role_permissions,
             custom_overrides: HashMap::new(),
+            role_contexts: HashMap::new(),
         }
     }
 
@@ -305,6 +422,19 @@ impl GroupPermissions {
         self.role_permissions.insert(custom_role, permissions);
     }
 
+    /// Conditions attached to `role`, if any.
+    pub fn role_context(&self, role: &Role) -> Option<&PermissionContext> {
+        self.role_contexts.get(role)
+    }
+
+    /// Attach conditions to `role`, or lift them with `None`.
+    pub fn set_role_context(&mut self, role: Role, context: Option<PermissionContext>) {
+        match context {
+            Some(context) => self.role_contexts.insert(role, context),
+            None => self.role_contexts.remove(&role),
+        };
+    }
+
     /// Get all permissions for a role
     pub fn get_role_permissions(&self, role: &Role) -> HashSet<Permission> {
         self.role_permissions.get(role).cloned().unwrap_or_default()
@@ -59,6 +59,7 @@ pub enum HandshakeOutcome {
 /// must be the keypair that originally minted the invitation. The
 /// function does no I/O — it just transforms state and returns the
 /// outcome.
+#[tracing::instrument(skip_all, err, fields(group = %body.group_id))]
 pub fn process_request_join(
     gm: &mut GroupManager,
     inviter_identity: &IdentityKeyPair,
@@ -201,6 +202,7 @@ pub fn process_request_join(
 /// `expected_inviter_id` comes from the joiner's local receipt of the
 /// original invite — passing it explicitly keeps this handler
 /// independent of where the receipt is stored.
+#[tracing::instrument(skip_all, err, fields(group = %body.group_id, members = body.members.len()))]
 pub fn process_join_accepted(
     gm: &mut GroupManager,
     expected_inviter_id: IdentityId,
@@ -616,6 +618,7 @@ fn now_secs() -> u64 {
 /// group key so the caller can install it locally before publishing.
 /// Side effects on `gm`: the member is removed (if `removed_member`
 /// is `Some`) and the new key is installed in `group_crypto`.
+#[tracing::instrument(skip_all, err, fields(group = %group_id, removed = ?removed_member))]
 pub fn plan_key_rotation(
     gm: &mut GroupManager,
     rotator_identity: &IdentityKeyPair,
@@ -675,6 +678,7 @@ pub fn plan_key_rotation(
 ///
 /// `local_id` is the IdentityId of the device running this handler
 /// (so we can pick our own delivery out of the broadcast).
+#[tracing::instrument(skip_all, err, fields(group = %body.group_id, generation = body.generation))]
 pub fn process_key_rotation(
     gm: &mut GroupManager,
     local_id: IdentityId,
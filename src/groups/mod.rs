@@ -1,11 +1,14 @@
 pub mod group_crypto;
 pub mod group_events;
 pub mod group_handshake;
+pub mod group_inbound;
 pub mod group_invite;
 pub mod group_manager;
 pub mod group_message;
 pub mod group_permissions;
 pub mod handshake_handlers;
+pub mod session_messenger;
+pub mod signed_invite;
 
 pub use group_crypto::{GroupCrypto, GroupKey, GroupKeyRotation};
 pub use group_events::{GroupEvent, GroupEventLog, GroupEventType};
@@ -13,10 +16,18 @@ pub use group_handshake::{
     GroupHandshake, GroupMemberSummary, JoinAcceptedBody, JoinRejectedBody, KeyRotationBody,
     MemberKeyDelivery, RequestJoinBody,
 };
+pub use group_inbound::{GroupInbound, GroupMessageEvent};
 pub use group_invite::{InvitePayload, QUBEE_INVITE_HOST, QUBEE_URI_SCHEME};
-pub use group_manager::{Group, GroupId, GroupManager, GroupMember, QUBEE_MAX_GROUP_MEMBERS};
+pub use group_manager::{
+    Group, GroupError, GroupId, GroupManager, GroupMember, Poll, PollId, PollTally, RetryAfter,
+    SlowMode, MAX_POLL_OPTIONS, QUBEE_MAX_GROUP_MEMBERS,
+};
 pub use group_message::{
     decrypt_group_message, encrypt_group_message, DecryptedGroupMessage, GroupMessageBody,
     GroupMessageEnvelope, GROUP_MESSAGE_MAX_AGE_SECS, MAGIC_GROUP_MESSAGE,
 };
-pub use group_permissions::{GroupPermissions, Permission, Role};
+pub use group_permissions::{
+    ContentRejected, GroupPermissions, Permission, PermissionContext, PermissionDenied, Role,
+};
+pub use session_messenger::SessionMessenger;
+pub use signed_invite::{parse_invite_link, SignedInvite, SignedInviteBody, QUBEE_JOIN_HOST};
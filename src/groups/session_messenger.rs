@@ -0,0 +1,89 @@
+//! A messenger bound to one session, for callers that hold a handle
+//! instead of passing the session id on every call.
+//!
+//! Sessions are groups (a 2-member group for 1:1), so this is a thin
+//! layer over [`encrypt_group_message`] / [`decrypt_group_message`].
+//! What it adds is the binding: a frame that opens fine but belongs to
+//! a different group is rejected, where the bare decrypt would hand it
+//! back as if it were this session's.
+
+use anyhow::{anyhow, Result};
+
+use crate::groups::group_manager::{GroupId, GroupManager};
+use crate::groups::group_message::{decrypt_group_message, encrypt_group_message};
+use crate::identity::identity_key::IdentityKeyPair;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionMessenger {
+    session: GroupId,
+}
+
+impl SessionMessenger {
+    pub fn new(session: GroupId) -> Self {
+        SessionMessenger { session }
+    }
+
+    pub fn session(&self) -> GroupId {
+        self.session
+    }
+
+    /// Seal `plaintext` for this session; returns the wire envelope.
+    pub fn encrypt_message(
+        &self,
+        gm: &GroupManager,
+        sender: &IdentityKeyPair,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>> {
+        encrypt_group_message(gm, sender, self.session, plaintext)
+    }
+
+    /// Open a wire envelope sent to this session.
+    pub fn decrypt_message(&self, gm: &GroupManager, wire: &[u8]) -> Result<Vec<u8>> {
+        let decrypted = decrypt_group_message(gm, wire)?;
+        if decrypted.group_id != self.session {
+            return Err(anyhow!("decrypt: frame belongs to another session"));
+        }
+        Ok(decrypted.plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::group_manager::{GroupSettings, GroupType};
+    use crate::storage::secure_keystore::SecureKeystore;
+    use tempfile::TempDir;
+
+    fn create_group(gm: &mut GroupManager, kp: &IdentityKeyPair, name: &str) -> GroupId {
+        let gid = gm
+            .create_group(
+                kp.identity_id(),
+                kp.public_key(),
+                name.to_string(),
+                String::new(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        gm.ensure_group_key(gid).unwrap();
+        gid
+    }
+
+    #[test]
+    fn messenger_round_trips_only_its_own_session() {
+        let dir = TempDir::new().unwrap();
+        let ks =
+            SecureKeystore::new(dir.path().join("gm.db"), b"test-keystore-passphrase").unwrap();
+        let mut gm = GroupManager::new(ks).unwrap();
+        let kp = IdentityKeyPair::generate().unwrap();
+        let messenger = SessionMessenger::new(create_group(&mut gm, &kp, "session"));
+        let other = SessionMessenger::new(create_group(&mut gm, &kp, "other"));
+
+        let plaintext = [0u8, 0xFF, 0x80, 0x7F, b'h', b'i'];
+        let wire = messenger.encrypt_message(&gm, &kp, &plaintext).unwrap();
+        assert_eq!(messenger.decrypt_message(&gm, &wire).unwrap(), plaintext);
+
+        let err = other.decrypt_message(&gm, &wire).unwrap_err();
+        assert!(err.to_string().contains("another session"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Self-contained, inviter-signed group invite links.
+//!
+//! [`InvitePayload`](crate::groups::group_invite::InvitePayload) links
+//! only carry a BLAKE3 fingerprint, so the joiner can't tell a genuine
+//! invite from one minted by anyone who knows the group id. A
+//! `qubee://join/<token>` link embeds the inviter's full
+//! [`IdentityKey`] and a [`HybridSignature`] over the invite fields, so
+//! the joiner can check who issued it and that it hasn't expired
+//! without reaching any server or the inviter's peer.
+//!
+//! The `invitation_code` inside is the same code
+//! [`GroupManager::create_invitation`](crate::groups::GroupManager::create_invitation)
+//! stores in the inviter's keystore, so redemption still goes through
+//! the normal `RequestJoin` handshake and the inviter's use-count /
+//! expiry checks. The link just lets the joiner decide, offline, that
+//! the invite is worth acting on.
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::groups::group_invite::{decode_link_token, QUBEE_URI_SCHEME};
+use crate::groups::group_manager::{GroupId, GroupInvitation};
+use crate::identity::identity_key::{HybridSignature, IdentityKey, IdentityKeyPair};
+
+/// Host used for signed invite links: `qubee://join/<token>`.
+pub const QUBEE_JOIN_HOST: &str = "join";
+
+/// Domain separator for the bytes the invite signature covers.
+const SIGNED_INVITE_TAG: &[u8] = b"qubee_signed_invite_v1";
+
+/// Upper bound on a signed invite's lifetime. A link can't be revoked
+/// once it's out, so unbounded expiries aren't accepted on either the
+/// issuing or the parsing side.
+pub const SIGNED_INVITE_MAX_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Fields covered by the inviter's signature.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedInviteBody {
+    pub group_id: GroupId,
+    pub group_name: String,
+    pub inviter_key: IdentityKey,
+    pub inviter_name: String,
+    pub invitation_code: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedInvite {
+    pub body: SignedInviteBody,
+    pub signature: HybridSignature,
+}
+
+impl SignedInvite {
+    /// Sign `invitation` with the inviter's keypair. The invitation must
+    /// have been issued by this keypair's identity and must carry an
+    /// expiry no more than [`SIGNED_INVITE_MAX_TTL_SECS`] out.
+    pub fn issue(keypair: &IdentityKeyPair, invitation: &GroupInvitation) -> Result<Self> {
+        if invitation.inviter_id != keypair.identity_id() {
+            return Err(anyhow!("invitation was issued by a different identity"));
+        }
+        let expires_at = invitation
+            .expires_at
+            .ok_or_else(|| anyhow!("signed invite links require an expiry"))?;
+        let issued_at = now_secs()?;
+        if expires_at <= issued_at {
+            return Err(anyhow!("invitation has already expired"));
+        }
+        if expires_at - issued_at > SIGNED_INVITE_MAX_TTL_SECS {
+            return Err(anyhow!(
+                "signed invite lifetime exceeds {} seconds",
+                SIGNED_INVITE_MAX_TTL_SECS
+            ));
+        }
+
+        let body = SignedInviteBody {
+            group_id: invitation.group_id,
+            group_name: invitation.group_name.clone(),
+            inviter_key: keypair.public_key(),
+            inviter_name: invitation.inviter_name.clone(),
+            invitation_code: invitation.invitation_code.clone(),
+            issued_at,
+            expires_at,
+        };
+        let payload = canonical_signed_invite(&body)?;
+        let signature = keypair.sign(&payload).context("invite sign failed")?;
+        Ok(SignedInvite { body, signature })
+    }
+
+    /// Encode as a `qubee://join/<base64url>` deep link.
+    pub fn to_invite_link(&self) -> Result<String> {
+        let bytes = bincode::serialize(self).context("signed invite serialize failed")?;
+        let token = URL_SAFE_NO_PAD.encode(bytes);
+        Ok(format!(
+            "{}://{}/{}",
+            QUBEE_URI_SCHEME, QUBEE_JOIN_HOST, token
+        ))
+    }
+
+    /// Check the inviter's signature, that the embedded key really owns
+    /// the identity id it claims, and that the invite is still live.
+    pub fn verify(&self) -> Result<()> {
+        let body = &self.body;
+        if !body.inviter_key.has_consistent_identity_id() {
+            return Err(anyhow!("invite inviter key does not match its identity id"));
+        }
+        if body.expires_at <= body.issued_at
+            || body.expires_at - body.issued_at > SIGNED_INVITE_MAX_TTL_SECS
+        {
+            return Err(anyhow!("invite has an invalid lifetime"));
+        }
+        if now_secs()? >= body.expires_at {
+            return Err(anyhow!("invite has expired"));
+        }
+        // The signature timestamp is set at signing time, so allowing it
+        // to be as old as the invite's full lifetime is exactly "not yet
+        // expired" on the signature side too.
+        let max_age = body.expires_at.saturating_sub(self.signature.timestamp);
+        let payload = canonical_signed_invite(body)?;
+        match body
+            .inviter_key
+            .verify_with_max_age(&payload, &self.signature, max_age)
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(anyhow!(
+                "invite signature is invalid (bad signature, wrong signer, or expired)"
+            )),
+            Err(e) => Err(anyhow!("invite signature could not be verified: {e}")),
+        }
+    }
+}
+
+/// Parse a `qubee://join/<token>` link and verify it locally. Returns
+/// the invite only if the signature, identity binding and expiry all
+/// check out.
+pub fn parse_invite_link(link: &str) -> Result<SignedInvite> {
+    let prefix = format!("{}://{}/", QUBEE_URI_SCHEME, QUBEE_JOIN_HOST);
+    let token = link
+        .strip_prefix(&prefix)
+        .ok_or_else(|| anyhow!("not a qubee join link"))?;
+    let token = token.split(['?', '#']).next().unwrap_or(token);
+    let invite: SignedInvite = decode_link_token(token, "join")?;
+    invite.verify()?;
+    Ok(invite)
+}
+
+pub fn canonical_signed_invite(body: &SignedInviteBody) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(2048);
+    out.extend_from_slice(SIGNED_INVITE_TAG);
+    out.push(0u8);
+    out.extend_from_slice(body.group_id.as_ref());
+    out.push(0u8);
+    out.extend_from_slice(&(body.group_name.len() as u32).to_le_bytes());
+    out.extend_from_slice(body.group_name.as_bytes());
+    out.extend_from_slice(&bincode::serialize(&body.inviter_key)?);
+    out.push(0u8);
+    out.extend_from_slice(&(body.inviter_name.len() as u32).to_le_bytes());
+    out.extend_from_slice(body.inviter_name.as_bytes());
+    out.extend_from_slice(&(body.invitation_code.len() as u32).to_le_bytes());
+    out.extend_from_slice(body.invitation_code.as_bytes());
+    out.extend_from_slice(&body.issued_at.to_le_bytes());
+    out.extend_from_slice(&body.expires_at.to_le_bytes());
+    Ok(out)
+}
+
+fn now_secs() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invitation_for(kp: &IdentityKeyPair, expires_at: Option<u64>) -> GroupInvitation {
+        GroupInvitation {
+            group_id: GroupId::from_bytes([7u8; 32]),
+            group_name: "Test Group".to_string(),
+            inviter_id: kp.identity_id(),
+            inviter_name: "Alice".to_string(),
+            invitation_code: "abc123".to_string(),
+            expires_at,
+            max_uses: Some(5),
+            current_uses: 0,
+            created_at: now_secs().unwrap(),
+        }
+    }
+
+    #[test]
+    fn roundtrip_signed_link() {
+        let kp = IdentityKeyPair::generate().unwrap();
+        let inv = invitation_for(&kp, Some(now_secs().unwrap() + 3600));
+        let link = SignedInvite::issue(&kp, &inv)
+            .unwrap()
+            .to_invite_link()
+            .unwrap();
+        assert!(link.starts_with("qubee://join/"));
+
+        let parsed = parse_invite_link(&link).unwrap();
+        assert_eq!(parsed.body.group_id, inv.group_id);
+        assert_eq!(parsed.body.invitation_code, "abc123");
+        assert_eq!(parsed.body.inviter_key.identity_id, kp.identity_id());
+    }
+
+    #[test]
+    fn rejects_swapped_inviter_key() {
+        let kp = IdentityKeyPair::generate().unwrap();
+        let other = IdentityKeyPair::generate().unwrap();
+        let inv = invitation_for(&kp, Some(now_secs().unwrap() + 3600));
+        let mut invite = SignedInvite::issue(&kp, &inv).unwrap();
+        invite.body.inviter_key = other.public_key();
+        let link = invite.to_invite_link().unwrap();
+        assert!(parse_invite_link(&link).is_err());
+    }
+
+    #[test]
+    fn rejects_expired_and_unbounded_invites() {
+        let kp = IdentityKeyPair::generate().unwrap();
+        assert!(SignedInvite::issue(&kp, &invitation_for(&kp, None)).is_err());
+        let too_long = now_secs().unwrap() + SIGNED_INVITE_MAX_TTL_SECS + 60;
+        assert!(SignedInvite::issue(&kp, &invitation_for(&kp, Some(too_long))).is_err());
+
+        // Re-dating a valid invite breaks the signature.
+        let inv = invitation_for(&kp, Some(now_secs().unwrap() + 3600));
+        let mut invite = SignedInvite::issue(&kp, &inv).unwrap();
+        invite.body.expires_at += 3600;
+        assert!(invite.verify().is_err());
+    }
+}
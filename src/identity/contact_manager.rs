@@ -4,14 +4,15 @@ use std::sync::Arc;
 
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::{mpsc, Mutex, RwLock};
 
 use crate::identity::identity_key::{IdentityId, IdentityKey};
 use crate::storage::secure_keystore::{KeyMetadata, KeyType, KeyUsage, SecureKeystore};
 
 /// Represents the verification status of a contact. Applications may
-/// choose different trust models (e.g. TOFU, cross‑signature). For
-/// now we distinguish between verified and unverified contacts only.
+/// choose different trust models (e.g. TOFU, cross‑signature). A
+/// verified contact whose key later changes is kept apart from one
+/// that was never verified so the UI can warn about it.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum ContactVerificationStatus {
     /// The contact's identity has been verified via an out‑of‑band
@@ -21,11 +22,92 @@ pub enum ContactVerificationStatus {
     /// caution. In a real system users could be prompted to verify
     /// keys before exchanging sensitive information.
     Unverified,
+    /// The contact was verified, but has since presented a different
+    /// identity key. Treat as unverified and warn the user until they
+    /// verify the new key.
+    VerifiedButChanged,
     /// The contact is blocked and should not be able to initiate
     /// communication.
     Blocked,
 }
 
+/// How a contact's key was verified.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum VerificationMethod {
+    /// Short authentication string compared in person or over a call.
+    Sas,
+    /// Safety-number / fingerprint comparison.
+    Fingerprint,
+    /// Scanned the contact's QR code.
+    QrCode,
+}
+
+/// What was verified and when. `key_fingerprint` is the BLAKE3 hash of
+/// the identity key that was shown at the time, so a later key change
+/// can be told apart from the verified one.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct VerificationRecord {
+    pub method: VerificationMethod,
+    pub verified_at: u64,
+    pub key_fingerprint: [u8; 32],
+}
+
+fn key_fingerprint(key: &IdentityKey) -> [u8; 32] {
+    *blake3::hash(&key.to_bytes()).as_bytes()
+}
+
+/// Signal-style safety number for a conversation between `local` and
+/// `remote`: 60 digits in groups of five. The two identity
+/// fingerprints are sorted before hashing, so both parties compute the
+/// same number and can read it to each other. It changes whenever
+/// either side's identity key does.
+pub fn safety_number(local: &IdentityKey, remote: &IdentityKey) -> String {
+    let ours = local.fingerprint_bytes();
+    let theirs = remote.fingerprint_bytes();
+    let (first, second) = if ours <= theirs {
+        (ours, theirs)
+    } else {
+        (theirs, ours)
+    };
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"qubee safety number v1");
+    hasher.update(&first);
+    hasher.update(&second);
+    let mut digest = [0u8; 60];
+    hasher.finalize_xof().fill(&mut digest);
+    // 40 bits per group keeps the `% 100_000` bias negligible.
+    digest
+        .chunks_exact(5)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+            format!("{:05}", value % 100_000)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Events published to [`ContactManager::subscribe`] receivers.
+#[derive(Clone, Debug)]
+pub enum ContactEvent {
+    /// A known contact presented a different identity key, so every
+    /// safety number involving them has changed. `was_verified` is set
+    /// when the old key had been verified; the UI should prompt the
+    /// user to verify again.
+    SafetyNumberChanged {
+        contact_id: IdentityId,
+        previous_key: IdentityKey,
+        new_key: IdentityKey,
+        was_verified: bool,
+    },
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 /// Stores metadata about a user contact. Each contact is keyed by
 /// their `IdentityId`. The `IdentityKey` is the public key used for
 /// authentication and encryption; it may be rotated by the contact
@@ -38,6 +120,9 @@ pub struct Contact {
     pub display_name: String,
     pub verification_status: ContactVerificationStatus,
     pub added_at: u64,
+    /// Set by [`ContactManager::mark_verified`]; kept across key changes
+    /// so the UI can show what was verified before.
+    pub verification: Option<VerificationRecord>,
 }
 
 /// Manages a collection of contacts. Contacts are stored in an
@@ -52,6 +137,7 @@ pub struct ContactManager {
     /// contact is stored under a key named `contact_{identity_id}`
     /// where `identity_id` is hex‑encoded.
     keystore: Option<Arc<Mutex<SecureKeystore>>>,
+    subscribers: std::sync::Mutex<Vec<mpsc::UnboundedSender<ContactEvent>>>,
 }
 
 impl ContactManager {
@@ -60,6 +146,7 @@ impl ContactManager {
         ContactManager {
             contacts: RwLock::new(HashMap::new()),
             keystore: None,
+            subscribers: std::sync::Mutex::new(Vec::new()),
         }
     }
 
@@ -73,6 +160,7 @@ impl ContactManager {
         ContactManager {
             contacts: RwLock::new(HashMap::new()),
             keystore: Some(Arc::new(Mutex::new(keystore))),
+            subscribers: std::sync::Mutex::new(Vec::new()),
         }
     }
 
@@ -85,9 +173,93 @@ impl ContactManager {
             let mut map = self.contacts.write().await;
             map.insert(contact.identity_id, contact.clone());
         }
-        // Persist to keystore if configured
+        self.persist(&contact).await
+    }
+
+    /// Record that the user verified `contact_id`'s current key by
+    /// `method`. Blocked contacts stay blocked.
+    pub async fn mark_verified(
+        &self,
+        contact_id: &IdentityId,
+        method: VerificationMethod,
+    ) -> anyhow::Result<()> {
+        let contact = {
+            let mut map = self.contacts.write().await;
+            let contact = map
+                .get_mut(contact_id)
+                .ok_or_else(|| anyhow::anyhow!("Unknown contact"))?;
+            if contact.verification_status == ContactVerificationStatus::Blocked {
+                return Err(anyhow::anyhow!("Cannot verify a blocked contact"));
+            }
+            contact.verification_status = ContactVerificationStatus::Verified;
+            contact.verification = Some(VerificationRecord {
+                method,
+                verified_at: now_secs(),
+                key_fingerprint: key_fingerprint(&contact.identity_key),
+            });
+            contact.clone()
+        };
+        self.persist(&contact).await
+    }
+
+    /// Receive a [`ContactEvent`] for every subsequent contact change.
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<ContactEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn publish(&self, event: ContactEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Replace `contact_id`'s identity key with `new_key`. Returns
+    /// `true` when this demotes a verified contact to
+    /// [`ContactVerificationStatus::VerifiedButChanged`], i.e. when the
+    /// caller should warn the user. Re-announcing the same key is a
+    /// no-op; any other key publishes
+    /// [`ContactEvent::SafetyNumberChanged`].
+    pub async fn record_key_change(
+        &self,
+        contact_id: &IdentityId,
+        new_key: IdentityKey,
+    ) -> anyhow::Result<bool> {
+        let (contact, previous_key, warn) = {
+            let mut map = self.contacts.write().await;
+            let contact = map
+                .get_mut(contact_id)
+                .ok_or_else(|| anyhow::anyhow!("Unknown contact"))?;
+            if new_key.fingerprint_matches(&contact.identity_key) {
+                return Ok(false);
+            }
+            let previous_key = std::mem::replace(&mut contact.identity_key, new_key);
+            let warn = match contact.verification_status {
+                ContactVerificationStatus::Verified => {
+                    contact.verification_status = ContactVerificationStatus::VerifiedButChanged;
+                    true
+                }
+                ContactVerificationStatus::VerifiedButChanged => true,
+                ContactVerificationStatus::Unverified | ContactVerificationStatus::Blocked => false,
+            };
+            (contact.clone(), previous_key, warn)
+        };
+        self.persist(&contact).await?;
+        self.publish(ContactEvent::SafetyNumberChanged {
+            contact_id: *contact_id,
+            previous_key,
+            new_key: contact.identity_key,
+            was_verified: warn,
+        });
+        Ok(warn)
+    }
+
+    async fn persist(&self, contact: &Contact) -> anyhow::Result<()> {
         if let Some(ref ks_arc) = self.keystore {
-            let serialized = bincode::serialize(&contact)?;
+            let serialized = bincode::serialize(contact)?;
             let key_name = format!("contact_{}", hex::encode(contact.identity_id.as_ref()));
             let metadata = KeyMetadata {
                 algorithm: "bincode".to_string(),
@@ -153,3 +325,124 @@ impl ContactManager {
         Ok(count)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::identity_key::IdentityKeyPair;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn verified_contact_drops_to_changed_on_key_rotation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("contacts.db");
+        let ks = SecureKeystore::new(&path, b"test-keystore-passphrase").unwrap();
+        let manager = ContactManager::new_with_keystore(ks);
+
+        let original = IdentityKeyPair::generate().unwrap().public_key();
+        let id = original.identity_id;
+        manager
+            .add_contact(Contact {
+                identity_id: id,
+                identity_key: original.clone(),
+                display_name: "Bob".to_string(),
+                verification_status: ContactVerificationStatus::Unverified,
+                added_at: now_secs(),
+                verification: None,
+            })
+            .await
+            .unwrap();
+
+        manager
+            .mark_verified(&id, VerificationMethod::Sas)
+            .await
+            .unwrap();
+        assert!(!manager
+            .record_key_change(&id, original.clone())
+            .await
+            .unwrap());
+        let contact = manager.get_contact(&id).await.unwrap();
+        assert_eq!(
+            contact.verification_status,
+            ContactVerificationStatus::Verified
+        );
+
+        let rotated = IdentityKeyPair::generate().unwrap().public_key();
+        assert!(manager.record_key_change(&id, rotated).await.unwrap());
+
+        // The demotion and the original verification survive a reload.
+        drop(manager);
+        let ks = SecureKeystore::new(&path, b"test-keystore-passphrase").unwrap();
+        let reloaded = ContactManager::new_with_keystore(ks);
+        assert_eq!(reloaded.load_from_storage().await.unwrap(), 1);
+        let contact = reloaded.get_contact(&id).await.unwrap();
+        assert_eq!(
+            contact.verification_status,
+            ContactVerificationStatus::VerifiedButChanged
+        );
+        let record = contact.verification.unwrap();
+        assert_eq!(record.method, VerificationMethod::Sas);
+        assert_eq!(record.key_fingerprint, key_fingerprint(&original));
+    }
+
+    #[test]
+    fn safety_number_is_symmetric() {
+        let alice = IdentityKeyPair::generate().unwrap().public_key();
+        let bob = IdentityKeyPair::generate().unwrap().public_key();
+        let number = safety_number(&alice, &bob);
+        assert_eq!(number, safety_number(&bob, &alice));
+        assert_eq!(number.len(), 12 * 5 + 11);
+        assert!(number.split(' ').all(|g| g.len() == 5));
+
+        let carol = IdentityKeyPair::generate().unwrap().public_key();
+        assert_ne!(number, safety_number(&alice, &carol));
+    }
+
+    #[tokio::test]
+    async fn key_change_publishes_new_safety_number() {
+        let local = IdentityKeyPair::generate().unwrap().public_key();
+        let original = IdentityKeyPair::generate().unwrap().public_key();
+        let id = original.identity_id;
+        let manager = ContactManager::new();
+        manager
+            .add_contact(Contact {
+                identity_id: id,
+                identity_key: original.clone(),
+                display_name: "Bob".to_string(),
+                verification_status: ContactVerificationStatus::Unverified,
+                added_at: now_secs(),
+                verification: None,
+            })
+            .await
+            .unwrap();
+        let mut events = manager.subscribe();
+
+        // Same key material with a different timestamp is not a change.
+        let mut reannounced = original.clone();
+        reannounced.created_at += 60;
+        manager.record_key_change(&id, reannounced).await.unwrap();
+        assert!(events.try_recv().is_err());
+
+        let rotated = IdentityKeyPair::generate().unwrap().public_key();
+        assert!(!manager
+            .record_key_change(&id, rotated.clone())
+            .await
+            .unwrap());
+        let ContactEvent::SafetyNumberChanged {
+            contact_id,
+            previous_key,
+            new_key,
+            was_verified,
+        } = events.try_recv().unwrap();
+        assert_eq!(contact_id, id);
+        assert!(!was_verified);
+        assert_ne!(
+            safety_number(&local, &previous_key),
+            safety_number(&local, &new_key)
+        );
+        assert_eq!(
+            safety_number(&local, &new_key),
+            safety_number(&local, &rotated)
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Stable identifier for a 1:1 conversation between two devices.
+//!
+//! Both peers must land on the same id without talking to each other,
+//! so the derivation is symmetric: the two `(identity, device)`
+//! endpoints are put into a canonical order before hashing. Device ids
+//! are part of the input, so Alice's phone ↔ Bob's laptop is a
+//! different conversation from Alice's laptop ↔ Bob's laptop — each
+//! device pair gets its own session state once the ratchet lands.
+//!
+//! The hash input is fixed-width (32-byte identity id, 16-byte device
+//! id per endpoint) and prefixed by a BLAKE3 `derive_key` context, so
+//! there is no concatenation ambiguity and no overlap with any other
+//! derivation in the protocol.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::identity::identity_key::{DeviceId, IdentityId};
+
+/// BLAKE3 `derive_key` context for conversation ids. Bump the version
+/// suffix if the input layout below ever changes — existing ids are
+/// persisted as keystore key names.
+const CONVERSATION_ID_KDF_CONTEXT: &str = "qubee conversation id v1";
+
+/// Identifier shared by both ends of a device-to-device conversation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConversationId([u8; 32]);
+
+impl ConversationId {
+    /// Derive the conversation id for `local` ↔ `remote`. Swapping the
+    /// local and remote arguments yields the same id.
+    pub fn derive(
+        local_identity: &IdentityId,
+        local_device: &DeviceId,
+        remote_identity: &IdentityId,
+        remote_device: &DeviceId,
+    ) -> Self {
+        let local = endpoint_bytes(local_identity, local_device);
+        let remote = endpoint_bytes(remote_identity, remote_device);
+        let (first, second) = if local <= remote {
+            (local, remote)
+        } else {
+            (remote, local)
+        };
+
+        let mut hasher = blake3::Hasher::new_derive_key(CONVERSATION_ID_KDF_CONTEXT);
+        hasher.update(&first);
+        hasher.update(&second);
+        ConversationId(*hasher.finalize().as_bytes())
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        ConversationId(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// `identity_id(32) || device_id(16)`. Both halves are fixed-width so
+/// the concatenation is unambiguous without length prefixes.
+fn endpoint_bytes(identity: &IdentityId, device: &DeviceId) -> [u8; 48] {
+    let mut out = [0u8; 48];
+    out[..32].copy_from_slice(&identity.0);
+    out[32..].copy_from_slice(&device.0);
+    out
+}
+
+impl fmt::Display for ConversationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(&self.0[..8]))
+    }
+}
+
+impl fmt::Debug for ConversationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ConversationId({})", hex::encode(&self.0[..8]))
+    }
+}
+
+impl AsRef<[u8]> for ConversationId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(b: u8) -> IdentityId {
+        IdentityId([b; 32])
+    }
+
+    fn device(b: u8) -> DeviceId {
+        DeviceId([b; 16])
+    }
+
+    #[test]
+    fn both_peers_derive_the_same_id() {
+        let (alice, alice_phone) = (identity(0xA1), device(0x01));
+        let (bob, bob_laptop) = (identity(0xB0), device(0x02));
+
+        let on_alice = ConversationId::derive(&alice, &alice_phone, &bob, &bob_laptop);
+        let on_bob = ConversationId::derive(&bob, &bob_laptop, &alice, &alice_phone);
+        assert_eq!(on_alice, on_bob);
+    }
+
+    #[test]
+    fn distinct_device_pairs_do_not_collide() {
+        let (alice, bob) = (identity(0xA1), identity(0xB0));
+        let ids = [
+            ConversationId::derive(&alice, &device(1), &bob, &device(2)),
+            ConversationId::derive(&alice, &device(3), &bob, &device(2)),
+            ConversationId::derive(&alice, &device(1), &bob, &device(4)),
+            // Same device bytes, endpoints' identities swapped.
+            ConversationId::derive(&alice, &device(2), &bob, &device(1)),
+            // Two devices of the same identity (note-to-self sync).
+            ConversationId::derive(&alice, &device(1), &alice, &device(3)),
+        ];
+        for i in 0..ids.len() {
+            for j in (i + 1)..ids.len() {
+                assert_ne!(ids[i], ids[j], "pair {i} collided with pair {j}");
+            }
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Fan-out of one user's messages to their other devices.
+//!
+//! A message sent or received on one device should show up on every
+//! device of the same identity. [`DeviceSyncManager::sync_message`]
+//! seals it once per sibling device under a hybrid KEM:
+//!
+//! ```text
+//! x25519_ss = X25519(ephemeral, device.x25519_public)
+//! (kem_ct, kem_ss) = ML-KEM-768.Encaps(device.kyber_public)
+//! key  = BLAKE3-derive("qubee device sync v1",
+//!                      x25519_ss || kem_ss || ephemeral_pub || kem_ct)
+//! body = ChaCha20-Poly1305(key, nonce, message, aad = routing header)
+//! ```
+//!
+//! and signs the whole envelope with the identity key, so a device only
+//! accepts sync traffic its own identity produced. Breaking either
+//! X25519 or ML-KEM alone doesn't recover the key. Revoked devices are
+//! skipped: they get no envelope, and any other device's envelope is
+//! useless to them.
+
+use anyhow::{anyhow, Result};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use zeroize::Zeroize;
+
+use crate::identity::identity_key::{
+    verify_revocation, DeviceId, DeviceKey, DevicePublicKey, DeviceRevocation, HybridSignature,
+    IdentityId, IdentityKeyPair,
+};
+use crate::security::secure_rng;
+
+const DEVICE_SYNC_TAG: &[u8] = b"qubee device sync v1";
+
+/// One device's copy of a synced message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceSyncEnvelope {
+    pub identity_id: IdentityId,
+    pub sender_device: DeviceId,
+    pub recipient_device: DeviceId,
+    pub ephemeral_public: [u8; 32],
+    pub kem_ciphertext: Vec<u8>,
+    pub nonce: [u8; 12],
+    pub ciphertext: Vec<u8>,
+    /// Identity signature over every other field.
+    pub signature: HybridSignature,
+}
+
+impl DeviceSyncEnvelope {
+    fn header_bytes(&self) -> Vec<u8> {
+        header_bytes(
+            &self.identity_id,
+            &self.sender_device,
+            &self.recipient_device,
+        )
+    }
+
+    fn signing_bytes(&self) -> Vec<u8> {
+        signing_bytes(
+            &self.header_bytes(),
+            &self.ephemeral_public,
+            &self.kem_ciphertext,
+            &self.nonce,
+            &self.ciphertext,
+        )
+    }
+}
+
+/// Routing fields, bound into the AEAD so an envelope can't be
+/// re-addressed.
+fn header_bytes(identity_id: &IdentityId, sender: &DeviceId, recipient: &DeviceId) -> Vec<u8> {
+    let mut out = Vec::with_capacity(DEVICE_SYNC_TAG.len() + 1 + 32 + 16 + 16);
+    out.extend_from_slice(DEVICE_SYNC_TAG);
+    out.push(0u8);
+    out.extend_from_slice(identity_id.as_ref());
+    out.extend_from_slice(sender.as_ref());
+    out.extend_from_slice(recipient.as_ref());
+    out
+}
+
+/// Everything in the envelope but the signature.
+fn signing_bytes(
+    header: &[u8],
+    ephemeral_public: &[u8; 32],
+    kem_ciphertext: &[u8],
+    nonce: &[u8; 12],
+    ciphertext: &[u8],
+) -> Vec<u8> {
+    let mut out = header.to_vec();
+    out.extend_from_slice(ephemeral_public);
+    out.extend_from_slice(&(kem_ciphertext.len() as u32).to_le_bytes());
+    out.extend_from_slice(kem_ciphertext);
+    out.extend_from_slice(nonce);
+    out.extend_from_slice(&(ciphertext.len() as u32).to_le_bytes());
+    out.extend_from_slice(ciphertext);
+    out
+}
+
+fn derive_sync_key(
+    x25519_ss: &[u8; 32],
+    kem_ss: &[u8; 32],
+    ephemeral_public: &[u8; 32],
+    kem_ciphertext: &[u8],
+) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key("qubee device sync v1");
+    hasher.update(x25519_ss);
+    hasher.update(kem_ss);
+    hasher.update(ephemeral_public);
+    hasher.update(kem_ciphertext);
+    *hasher.finalize().as_bytes()
+}
+
+/// Seals messages for, and opens messages from, the other devices of
+/// this device's identity.
+pub struct DeviceSyncManager<'a> {
+    identity: &'a IdentityKeyPair,
+    device: &'a DeviceKey,
+    revoked: HashSet<DeviceId>,
+}
+
+impl<'a> DeviceSyncManager<'a> {
+    /// `device` is this device's key; it must belong to `identity`.
+    pub fn new(identity: &'a IdentityKeyPair, device: &'a DeviceKey) -> Result<Self> {
+        if device.identity_id() != identity.identity_id() {
+            return Err(anyhow!("device key belongs to another identity"));
+        }
+        Ok(DeviceSyncManager {
+            identity,
+            device,
+            revoked: HashSet::new(),
+        })
+    }
+
+    /// Stop syncing to `device_id`, and stop accepting sync traffic it
+    /// sends.
+    pub fn revoke_device(&mut self, device_id: DeviceId) {
+        self.revoked.insert(device_id);
+    }
+
+    /// [`revoke_device`](Self::revoke_device) from a signed record,
+    /// e.g. one another of the user's devices issued.
+    pub fn apply_revocation(&mut self, revocation: &DeviceRevocation) -> Result<()> {
+        verify_revocation(revocation, &self.identity.public_key())?;
+        self.revoke_device(revocation.device_id);
+        Ok(())
+    }
+
+    pub fn is_revoked(&self, device_id: &DeviceId) -> bool {
+        self.revoked.contains(device_id)
+    }
+
+    /// Seal `message` once for each of `own_devices`, skipping this
+    /// device and revoked ones. Errors if any listed device belongs to
+    /// another identity: sync traffic never leaves the user's devices.
+    pub fn sync_message(
+        &self,
+        message: &[u8],
+        own_devices: &[DevicePublicKey],
+    ) -> Result<Vec<DeviceSyncEnvelope>> {
+        let identity_id = self.identity.identity_id();
+        if let Some(foreign) = own_devices.iter().find(|d| d.identity_id != identity_id) {
+            return Err(anyhow!(
+                "device {} belongs to another identity",
+                foreign.device_id
+            ));
+        }
+        own_devices
+            .iter()
+            .filter(|d| d.device_id != self.device.device_id() && !self.is_revoked(&d.device_id))
+            .map(|d| self.seal_for(message, d))
+            .collect()
+    }
+
+    fn seal_for(&self, message: &[u8], device: &DevicePublicKey) -> Result<DeviceSyncEnvelope> {
+        use pqcrypto_traits::kem::{Ciphertext as _, SharedSecret as _};
+
+        let ephemeral = x25519_dalek::StaticSecret::from(secure_rng::random::array::<32>()?);
+        let ephemeral_public = x25519_dalek::PublicKey::from(&ephemeral).to_bytes();
+        let mut x25519_ss = ephemeral.diffie_hellman(&device.x25519_public).to_bytes();
+        let (kem_ss, kem_ct) = pqcrypto_mlkem::mlkem768::encapsulate(&device.kyber_public);
+        let mut kem_ss_bytes = [0u8; 32];
+        kem_ss_bytes.copy_from_slice(&kem_ss.as_bytes()[..32]);
+        let kem_ciphertext = kem_ct.as_bytes().to_vec();
+        let mut key = derive_sync_key(
+            &x25519_ss,
+            &kem_ss_bytes,
+            &ephemeral_public,
+            &kem_ciphertext,
+        );
+        x25519_ss.zeroize();
+        kem_ss_bytes.zeroize();
+
+        let identity_id = self.identity.identity_id();
+        let sender_device = self.device.device_id();
+        let header = header_bytes(&identity_id, &sender_device, &device.device_id);
+        let nonce = secure_rng::random::array::<12>()?;
+        let sealed = ChaCha20Poly1305::new((&key).into()).encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: message,
+                aad: &header,
+            },
+        );
+        key.zeroize();
+        let ciphertext = sealed.map_err(|_| anyhow!("device sync encryption failed"))?;
+        let signature = self.identity.sign(&signing_bytes(
+            &header,
+            &ephemeral_public,
+            &kem_ciphertext,
+            &nonce,
+            &ciphertext,
+        ))?;
+        Ok(DeviceSyncEnvelope {
+            identity_id,
+            sender_device,
+            recipient_device: device.device_id,
+            ephemeral_public,
+            kem_ciphertext,
+            nonce,
+            ciphertext,
+            signature,
+        })
+    }
+
+    /// Open an envelope addressed to this device. Rejects envelopes for
+    /// another device, from another identity, from a revoked device, or
+    /// with a bad signature or ciphertext.
+    pub fn open_synced(&self, envelope: &DeviceSyncEnvelope) -> Result<Vec<u8>> {
+        if envelope.recipient_device != self.device.device_id() {
+            return Err(anyhow!("sync envelope is for another device"));
+        }
+        if envelope.identity_id != self.identity.identity_id() {
+            return Err(anyhow!("sync envelope is from another identity"));
+        }
+        if self.is_revoked(&envelope.sender_device) {
+            return Err(anyhow!("sync envelope is from a revoked device"));
+        }
+        if !self
+            .identity
+            .public_key()
+            .verify(&envelope.signing_bytes(), &envelope.signature)?
+        {
+            return Err(anyhow!("sync envelope signature failed"));
+        }
+
+        let ephemeral = x25519_dalek::PublicKey::from(envelope.ephemeral_public);
+        let mut x25519_ss = self.device.x25519_agree(&ephemeral);
+        let mut kem_ss = self.device.kyber_decapsulate(&envelope.kem_ciphertext)?;
+        let mut key = derive_sync_key(
+            &x25519_ss,
+            &kem_ss,
+            &envelope.ephemeral_public,
+            &envelope.kem_ciphertext,
+        );
+        x25519_ss.zeroize();
+        kem_ss.zeroize();
+        let opened = ChaCha20Poly1305::new((&key).into()).decrypt(
+            Nonce::from_slice(&envelope.nonce),
+            Payload {
+                msg: &envelope.ciphertext,
+                aad: &envelope.header_bytes(),
+            },
+        );
+        key.zeroize();
+        opened.map_err(|_| anyhow!("sync envelope failed to decrypt"))
+    }
+}
+
+/// The user's device keys as published on a key server: one verified
+/// pre-key bundle per device listed by
+/// [`list_devices`](crate::identity::signal_protocol::KeyDistributionServer::list_devices).
+pub fn fetch_own_devices(
+    server: &impl crate::identity::signal_protocol::KeyDistributionServer,
+    identity_id: &IdentityId,
+) -> Result<Vec<DevicePublicKey>> {
+    use crate::identity::signal_protocol::verify_prekey_bundle;
+
+    server
+        .list_devices(identity_id)?
+        .iter()
+        .map(|device_id| {
+            let bundle = server.get_prekey_bundle(identity_id, device_id)?;
+            verify_prekey_bundle(&bundle)?;
+            Ok(bundle.signed_prekey.device_public_key)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synced_message_opens_on_siblings_but_not_revoked_devices() {
+        let identity = IdentityKeyPair::generate().unwrap();
+        let phone = identity.derive_device_key(b"phone").unwrap();
+        let laptop = identity.derive_device_key(b"laptop").unwrap();
+        let lost = identity.derive_device_key(b"lost tablet").unwrap();
+        let devices = [phone.public_key(), laptop.public_key(), lost.public_key()];
+
+        let mut sender = DeviceSyncManager::new(&identity, &phone).unwrap();
+        let revocation = identity.revoke_device(lost.device_id()).unwrap();
+        sender.apply_revocation(&revocation).unwrap();
+        let envelopes = sender
+            .sync_message(b"hello from the phone", &devices)
+            .unwrap();
+        assert_eq!(envelopes.len(), 1);
+        assert_eq!(envelopes[0].recipient_device, laptop.device_id());
+
+        let laptop_sync = DeviceSyncManager::new(&identity, &laptop).unwrap();
+        assert_eq!(
+            laptop_sync.open_synced(&envelopes[0]).unwrap(),
+            b"hello from the phone"
+        );
+
+        // The revoked device holds the identity key too, but its own
+        // device key can't open the laptop's copy, even re-addressed.
+        let lost_sync = DeviceSyncManager::new(&identity, &lost).unwrap();
+        assert!(lost_sync.open_synced(&envelopes[0]).is_err());
+        let mut readdressed = envelopes[0].clone();
+        readdressed.recipient_device = lost.device_id();
+        assert!(lost_sync.open_synced(&readdressed).is_err());
+
+        let stranger = IdentityKeyPair::generate().unwrap();
+        let stranger_device = stranger.derive_device_key(b"phone").unwrap();
+        assert!(sender
+            .sync_message(b"x", &[stranger_device.public_key()])
+            .is_err());
+    }
+}
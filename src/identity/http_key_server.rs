@@ -0,0 +1,466 @@
+//! [`KeyDistributionServer`] over HTTPS.
+//!
+//! The crate deliberately has no HTTP/TLS stack of its own — on Android
+//! the app already ships one (OkHttp) with the platform trust store and
+//! certificate pinning, and pulling a second TLS implementation into
+//! the `.so` for one request type isn't worth it. So `HttpKeyServer`
+//! owns the REST contract (paths, encodings, auth header, status
+//! handling) and delegates the actual round-trip to an [`HttpTransport`]
+//! the embedding app provides.
+//!
+//! # API
+//!
+//! All paths are relative to the server's base URL, which must be
+//! `https://`. Every request carries `Authorization: Bearer <token>`.
+//! Ids in paths are lowercase hex.
+//!
+//! | Method   | Path                            | Body                          | Success |
+//! |----------|---------------------------------|-------------------------------|---------|
+//! | `POST`   | `/bundle`                       | bincode(`PreKeyBundle`)       | 204     |
+//! | `GET`    | `/bundle/{identity}/{device}`   | —                             | 200, bincode(`PreKeyBundle`) |
+//! | `DELETE` | `/otk`                          | JSON `{identity, device, prekey_id}` | 204 |
+//! | `GET`    | `/devices/{identity}`           | —                             | 200, JSON `["<device hex>", …]` |
+//! | `POST`   | `/revocation`                   | bincode(`DeviceRevocation`)   | 204     |
+//!
+//! `404` on the two `GET`s maps to "not found"; anything else outside
+//! 2xx is an error carrying the status code.
+//!
+//! After accepting a revocation the server drops the device's bundle,
+//! stops listing it and refuses new uploads for it, as
+//! [`InMemoryKeyServer`](crate::identity::signal_protocol::InMemoryKeyServer)
+//! does.
+//!
+//! The server is untrusted for integrity. A fetched bundle must match
+//! the identity/device that was asked for and pass
+//! [`verify_prekey_bundle`] before `get_prekey_bundle` returns it, so
+//! a tampered or stale bundle never leaves this module.
+//! [`SignalProtocol::initiate_key_exchange`](crate::identity::signal_protocol::SignalProtocol::initiate_key_exchange)
+//! checks it again, as it does for a bundle from any other source.
+//! Uploads are validated with the same function first.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::identity::identity_key::{DeviceId, DeviceRevocation, IdentityId};
+use crate::identity::signal_protocol::{verify_prekey_bundle, KeyDistributionServer, PreKeyBundle};
+
+/// HTTP methods used by the key-server API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Delete,
+}
+
+/// One request as handed to the transport.
+#[derive(Clone, Debug)]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    /// Absolute `https://` URL.
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// Blocking HTTPS round-trip supplied by the embedding app. The
+/// implementation is responsible for TLS, certificate validation and
+/// timeouts; it must refuse to send anything over plain HTTP.
+pub trait HttpTransport {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse>;
+}
+
+#[derive(Serialize, Deserialize)]
+struct RemoveOneTimePrekeyRequest {
+    identity: String,
+    device: String,
+    prekey_id: u32,
+}
+
+/// Remote prekey directory speaking the API documented at the top of
+/// this module.
+pub struct HttpKeyServer<T: HttpTransport> {
+    base_url: String,
+    auth_token: String,
+    transport: T,
+}
+
+impl<T: HttpTransport> HttpKeyServer<T> {
+    /// `base_url` must be `https://…`; a trailing slash is tolerated.
+    pub fn new(
+        base_url: impl Into<String>,
+        auth_token: impl Into<String>,
+        transport: T,
+    ) -> Result<Self> {
+        let base_url = base_url.into();
+        if !base_url.starts_with("https://") {
+            return Err(anyhow!("key server URL must use https"));
+        }
+        Ok(HttpKeyServer {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            auth_token: auth_token.into(),
+            transport,
+        })
+    }
+
+    fn request(
+        &self,
+        method: HttpMethod,
+        path: &str,
+        content_type: Option<&str>,
+        body: Vec<u8>,
+    ) -> Result<HttpResponse> {
+        let mut headers = vec![(
+            "Authorization".to_string(),
+            format!("Bearer {}", self.auth_token),
+        )];
+        if let Some(ct) = content_type {
+            headers.push(("Content-Type".to_string(), ct.to_string()));
+        }
+        self.transport
+            .send(HttpRequest {
+                method,
+                url: format!("{}{}", self.base_url, path),
+                headers,
+                body,
+            })
+            .with_context(|| format!("key server {method:?} {path}"))
+    }
+}
+
+fn expect_success(response: &HttpResponse, what: &str) -> Result<()> {
+    if (200..300).contains(&response.status) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "key server {what} failed with status {}",
+            response.status
+        ))
+    }
+}
+
+impl<T: HttpTransport> KeyDistributionServer for HttpKeyServer<T> {
+    fn upload_prekey_bundle(&mut self, bundle: &PreKeyBundle) -> Result<()> {
+        // Don't publish something every fetcher would reject anyway.
+        verify_prekey_bundle(bundle).context("refusing to upload invalid pre-key bundle")?;
+        let body = bincode::serialize(bundle).context("prekey bundle serialize")?;
+        let response = self.request(
+            HttpMethod::Post,
+            "/bundle",
+            Some("application/octet-stream"),
+            body,
+        )?;
+        expect_success(&response, "bundle upload")
+    }
+
+    fn get_prekey_bundle(
+        &self,
+        identity_id: &IdentityId,
+        device_id: &DeviceId,
+    ) -> Result<PreKeyBundle> {
+        let path = format!(
+            "/bundle/{}/{}",
+            hex::encode(identity_id),
+            hex::encode(device_id)
+        );
+        let response = self.request(HttpMethod::Get, &path, None, Vec::new())?;
+        if response.status == 404 {
+            return Err(anyhow!("Pre-key bundle not found"));
+        }
+        expect_success(&response, "bundle fetch")?;
+        let bundle: PreKeyBundle =
+            bincode::deserialize(&response.body).context("prekey bundle deserialize")?;
+        // A server answering with someone else's bundle is the cheapest
+        // substitution it could try; the signature check downstream
+        // would pass for that bundle, so catch it here.
+        if bundle.identity_key.identity_id != *identity_id || bundle.device_id != *device_id {
+            return Err(anyhow!(
+                "key server returned a bundle for a different device"
+            ));
+        }
+        verify_prekey_bundle(&bundle).context("key server returned an invalid pre-key bundle")?;
+        Ok(bundle)
+    }
+
+    fn remove_one_time_prekey(
+        &mut self,
+        identity_id: &IdentityId,
+        device_id: &DeviceId,
+        prekey_id: u32,
+    ) -> Result<()> {
+        let body = serde_json::to_vec(&RemoveOneTimePrekeyRequest {
+            identity: hex::encode(identity_id),
+            device: hex::encode(device_id),
+            prekey_id,
+        })?;
+        let response = self.request(HttpMethod::Delete, "/otk", Some("application/json"), body)?;
+        expect_success(&response, "one-time prekey removal")
+    }
+
+    fn revoke_device(&mut self, revocation: &DeviceRevocation) -> Result<()> {
+        // The server verifies it against the identity key it holds; the
+        // record is self-authenticating, so the bearer token only rate-
+        // limits who can submit one.
+        let body = bincode::serialize(revocation).context("device revocation serialize")?;
+        let response = self.request(
+            HttpMethod::Post,
+            "/revocation",
+            Some("application/octet-stream"),
+            body,
+        )?;
+        expect_success(&response, "device revocation")
+    }
+
+    fn list_devices(&self, identity_id: &IdentityId) -> Result<Vec<DeviceId>> {
+        let path = format!("/devices/{}", hex::encode(identity_id));
+        let response = self.request(HttpMethod::Get, &path, None, Vec::new())?;
+        if response.status == 404 {
+            return Ok(Vec::new());
+        }
+        expect_success(&response, "device list")?;
+        let hex_ids: Vec<String> =
+            serde_json::from_slice(&response.body).context("device list is not a JSON array")?;
+        hex_ids
+            .iter()
+            .map(|h| {
+                let bytes: [u8; 16] = hex::decode(h)
+                    .ok()
+                    .and_then(|b| b.try_into().ok())
+                    .ok_or_else(|| anyhow!("invalid device id {h:?}"))?;
+                Ok(DeviceId::from(bytes))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::identity_key::IdentityKeyPair;
+    use crate::identity::signal_protocol::{InMemoryKeyServer, SignalProtocol};
+    use std::cell::RefCell;
+
+    /// Transport that records requests and replays a canned response.
+    struct RecordingTransport {
+        requests: RefCell<Vec<HttpRequest>>,
+        response: HttpResponse,
+    }
+
+    impl HttpTransport for &RecordingTransport {
+        fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+            self.requests.borrow_mut().push(request);
+            Ok(self.response.clone())
+        }
+    }
+
+    fn transport(status: u16, body: Vec<u8>) -> RecordingTransport {
+        RecordingTransport {
+            requests: RefCell::new(Vec::new()),
+            response: HttpResponse { status, body },
+        }
+    }
+
+    #[test]
+    fn rejects_plain_http_base_url() {
+        let t = transport(200, Vec::new());
+        assert!(HttpKeyServer::new("http://keys.example", "tok", &t).is_err());
+    }
+
+    #[test]
+    fn list_devices_decodes_hex_and_sends_auth() {
+        let body = serde_json::to_vec(&vec![hex::encode([7u8; 16])]).unwrap();
+        let t = transport(200, body);
+        let server = HttpKeyServer::new("https://keys.example/", "tok", &t).unwrap();
+
+        let devices = server.list_devices(&IdentityId::from([1u8; 32])).unwrap();
+        assert_eq!(devices, vec![DeviceId::from([7u8; 16])]);
+
+        let sent = t.requests.borrow();
+        assert_eq!(sent[0].method, HttpMethod::Get);
+        assert_eq!(
+            sent[0].url,
+            format!("https://keys.example/devices/{}", hex::encode([1u8; 32]))
+        );
+        assert!(sent[0]
+            .headers
+            .contains(&("Authorization".to_string(), "Bearer tok".to_string())));
+    }
+
+    #[test]
+    fn missing_bundle_maps_to_not_found() {
+        let t = transport(404, Vec::new());
+        let server = HttpKeyServer::new("https://keys.example", "tok", &t).unwrap();
+        let err = server
+            .get_prekey_bundle(&IdentityId::from([1u8; 32]), &DeviceId::from([2u8; 16]))
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[test]
+    fn tampered_or_substituted_bundles_are_rejected() {
+        let mut alice =
+            SignalProtocol::new(IdentityKeyPair::generate().unwrap(), b"phone").unwrap();
+        alice.generate_signed_prekey().unwrap();
+        alice.generate_one_time_prekeys(1).unwrap();
+        let bundle = alice.create_prekey_bundle().unwrap();
+        let identity = bundle.identity_key.identity_id;
+
+        // One-time prekey swapped for the server's own.
+        let mut tampered = bundle.clone();
+        tampered.one_time_prekey.as_mut().unwrap().kyber_public =
+            pqcrypto_mlkem::mlkem768::keypair().0;
+        let t = transport(200, bincode::serialize(&tampered).unwrap());
+        let server = HttpKeyServer::new("https://keys.example", "tok", &t).unwrap();
+        let err = server
+            .get_prekey_bundle(&identity, &bundle.device_id)
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("invalid pre-key bundle"));
+
+        // A valid bundle, but not for the device that was asked for.
+        let t = transport(200, bincode::serialize(&bundle).unwrap());
+        let server = HttpKeyServer::new("https://keys.example", "tok", &t).unwrap();
+        let err = server
+            .get_prekey_bundle(&identity, &DeviceId::from([9u8; 16]))
+            .unwrap_err();
+        assert!(err.to_string().contains("different device"));
+    }
+
+    /// Transport that answers the documented API from an
+    /// [`InMemoryKeyServer`], standing in for the real directory.
+    struct MockDirectory {
+        inner: RefCell<InMemoryKeyServer>,
+    }
+
+    impl HttpTransport for &MockDirectory {
+        fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+            let path = request
+                .url
+                .strip_prefix("https://keys.example")
+                .expect("request went to the configured base URL");
+            let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+            let reply = |status| HttpResponse {
+                status,
+                body: Vec::new(),
+            };
+            let mut inner = self.inner.borrow_mut();
+            Ok(match (request.method, parts.as_slice()) {
+                (HttpMethod::Post, ["bundle"]) => {
+                    let bundle: PreKeyBundle = bincode::deserialize(&request.body)?;
+                    match inner.upload_prekey_bundle(&bundle) {
+                        Ok(()) => reply(204),
+                        Err(_) => reply(400),
+                    }
+                }
+                (HttpMethod::Get, ["bundle", identity, device]) => {
+                    let identity: [u8; 32] = hex::decode(identity)?.try_into().unwrap();
+                    let device: [u8; 16] = hex::decode(device)?.try_into().unwrap();
+                    match inner.get_prekey_bundle(&identity.into(), &device.into()) {
+                        Ok(bundle) => HttpResponse {
+                            status: 200,
+                            body: bincode::serialize(&bundle)?,
+                        },
+                        Err(_) => reply(404),
+                    }
+                }
+                (HttpMethod::Get, ["devices", identity]) => {
+                    let identity: [u8; 32] = hex::decode(identity)?.try_into().unwrap();
+                    let devices: Vec<String> = inner
+                        .list_devices(&identity.into())?
+                        .iter()
+                        .map(hex::encode)
+                        .collect();
+                    HttpResponse {
+                        status: 200,
+                        body: serde_json::to_vec(&devices)?,
+                    }
+                }
+                (HttpMethod::Post, ["revocation"]) => {
+                    let revocation: DeviceRevocation = bincode::deserialize(&request.body)?;
+                    match inner.revoke_device(&revocation) {
+                        Ok(()) => reply(204),
+                        Err(_) => reply(400),
+                    }
+                }
+                (HttpMethod::Delete, ["otk"]) => {
+                    let req: RemoveOneTimePrekeyRequest = serde_json::from_slice(&request.body)?;
+                    let identity: [u8; 32] = hex::decode(&req.identity)?.try_into().unwrap();
+                    let device: [u8; 16] = hex::decode(&req.device)?.try_into().unwrap();
+                    inner.remove_one_time_prekey(
+                        &identity.into(),
+                        &device.into(),
+                        req.prekey_id,
+                    )?;
+                    reply(204)
+                }
+                _ => reply(404),
+            })
+        }
+    }
+
+    #[test]
+    fn upload_fetch_and_consume_one_time_prekey() {
+        let mut alice =
+            SignalProtocol::new(IdentityKeyPair::generate().unwrap(), b"phone").unwrap();
+        alice.generate_signed_prekey().unwrap();
+        alice.generate_one_time_prekeys(1).unwrap();
+        let bundle = alice.create_prekey_bundle().unwrap();
+        let identity = bundle.identity_key.identity_id;
+        let otk_id = bundle.one_time_prekey.as_ref().unwrap().id;
+
+        let directory = MockDirectory {
+            inner: RefCell::new(InMemoryKeyServer::new()),
+        };
+        let mut server = HttpKeyServer::new("https://keys.example", "tok", &directory).unwrap();
+        server.upload_prekey_bundle(&bundle).unwrap();
+
+        let fetched = server
+            .get_prekey_bundle(&identity, &bundle.device_id)
+            .unwrap();
+        assert_eq!(fetched.one_time_prekey.as_ref().unwrap().id, otk_id);
+
+        server
+            .remove_one_time_prekey(&identity, &bundle.device_id, otk_id)
+            .unwrap();
+        let fetched = server
+            .get_prekey_bundle(&identity, &bundle.device_id)
+            .unwrap();
+        assert!(fetched.one_time_prekey.is_none());
+    }
+
+    #[test]
+    fn revoked_device_is_delisted_and_cannot_upload() {
+        let identity_keypair = IdentityKeyPair::generate().unwrap();
+        let forger = IdentityKeyPair::generate().unwrap();
+        let mut phone = SignalProtocol::new(identity_keypair, b"phone").unwrap();
+        phone.generate_signed_prekey().unwrap();
+        let bundle = phone.create_prekey_bundle().unwrap();
+        let identity = bundle.identity_key.identity_id;
+
+        let directory = MockDirectory {
+            inner: RefCell::new(InMemoryKeyServer::new()),
+        };
+        let mut server = HttpKeyServer::new("https://keys.example", "tok", &directory).unwrap();
+        server.upload_prekey_bundle(&bundle).unwrap();
+
+        let mut forged = forger.revoke_device(bundle.device_id).unwrap();
+        forged.identity_id = identity;
+        assert!(server.revoke_device(&forged).is_err());
+        assert_eq!(server.list_devices(&identity).unwrap().len(), 1);
+
+        let revocation = phone
+            .identity_keypair()
+            .revoke_device(bundle.device_id)
+            .unwrap();
+        server.revoke_device(&revocation).unwrap();
+        assert!(server.list_devices(&identity).unwrap().is_empty());
+        assert!(server
+            .get_prekey_bundle(&identity, &bundle.device_id)
+            .is_err());
+        assert!(server.upload_prekey_bundle(&bundle).is_err());
+    }
+}
@@ -15,10 +15,13 @@
 use anyhow::{anyhow, Context, Result};
 use blake3::Hasher;
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use hkdf::Hkdf;
 use pqcrypto_mldsa::mldsa44::{self};
 use pqcrypto_traits::sign::{DetachedSignature as _, PublicKey as _, SecretKey as _};
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use std::fmt;
+use subtle::{Choice, ConstantTimeEq};
 use zeroize::Zeroize;
 
 use crate::security::secure_rng;
@@ -49,7 +52,7 @@ impl fmt::Debug for IdentityKey {
 }
 
 /// Unique identifier for an identity derived from its public keys.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct IdentityId(pub(crate) [u8; 32]);
 
 /// Public portion of a device key (one device per identity).
@@ -270,11 +273,43 @@ impl IdentityKeyPair {
         })
     }
 
+    /// Sign a record revoking `device_id` (a lost or retired device).
+    /// Contacts and key servers that check it with
+    /// [`verify_revocation`] stop encrypting to that device.
+    pub fn revoke_device(&self, device_id: DeviceId) -> Result<DeviceRevocation> {
+        let revoked_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let bytes = DeviceRevocation::signing_bytes(&self.identity_id, &device_id, revoked_at);
+        Ok(DeviceRevocation {
+            identity_id: self.identity_id,
+            device_id,
+            revoked_at,
+            signature: self.sign(&bytes)?,
+        })
+    }
+
     /// Stable identifier for this identity.
     pub fn identity_id(&self) -> IdentityId {
         self.identity_id
     }
 
+    /// Derive a 32-byte local-only subkey for `info` (e.g. a storage
+    /// feature's key) via HKDF-SHA256 over the private halves, salted
+    /// with the identity id. Distinct `info` strings give independent
+    /// keys; none of them reveal anything about the signing keys.
+    pub fn derive_subkey(&self, info: &[u8]) -> Result<[u8; 32]> {
+        let mut ikm = Vec::with_capacity(32 + self.pq_private_bytes.len());
+        ikm.extend_from_slice(&self.classical_private_bytes);
+        ikm.extend_from_slice(&self.pq_private_bytes);
+        let hk = Hkdf::<Sha256>::new(Some(&self.identity_id.0), &ikm);
+        ikm.zeroize();
+        let mut out = [0u8; 32];
+        hk.expand(info, &mut out)
+            .map_err(|e| anyhow!("HKDF expand: {e}"))?;
+        Ok(out)
+    }
+
     /// Serialise the full keypair to a byte buffer suitable for the
     /// encrypted [`crate::storage::secure_keystore::SecureKeyStore`].
     /// Bytes contain raw private material — the keystore must wrap
@@ -328,35 +363,53 @@ impl IdentityKey {
     }
 
     /// Verify a hybrid signature with a caller-supplied freshness window.
+    ///
+    /// Every check — signer id, freshness, Ed25519, ML-DSA-44 — runs on
+    /// every call and the results are folded with a constant-time `&`,
+    /// so how long this takes doesn't reveal which check failed. The
+    /// two signature verifies are only as constant-time as
+    /// `ed25519-dalek` and `pqcrypto` make them; both operate on public
+    /// inputs, so what we control here is the control flow around them.
     pub fn verify_with_max_age(
         &self,
         data: &[u8],
         signature: &HybridSignature,
         max_age_secs: u64,
     ) -> Result<bool> {
-        if signature.signer_identity != self.identity_id {
-            return Ok(false);
-        }
         let current_time = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)?
             .as_secs();
-        if current_time.saturating_sub(signature.timestamp) > max_age_secs {
-            return Ok(false);
-        }
+
+        let signer_matches = signature.signer_identity.0.ct_eq(&self.identity_id.0);
+        let fresh =
+            Choice::from((current_time.saturating_sub(signature.timestamp) <= max_age_secs) as u8);
 
         let mut message = Vec::with_capacity(data.len() + 8 + 32);
         message.extend_from_slice(data);
         message.extend_from_slice(&signature.timestamp.to_le_bytes());
         message.extend_from_slice(&self.identity_id.0);
 
-        let classical_valid = self
-            .classical_public
-            .verify(&message, &signature.classical_signature)
-            .is_ok();
-        let pq_valid =
+        let classical_valid = Choice::from(
+            self.classical_public
+                .verify(&message, &signature.classical_signature)
+                .is_ok() as u8,
+        );
+        let pq_valid = Choice::from(
             mldsa44::verify_detached_signature(&signature.pq_signature, &message, &self.pq_public)
-                .is_ok();
-        Ok(classical_valid && pq_valid)
+                .is_ok() as u8,
+        );
+        Ok(bool::from(
+            signer_matches & fresh & classical_valid & pq_valid,
+        ))
+    }
+
+    /// `true` if `identity_id` is the one derived from the two public
+    /// halves. The wire decoder trusts the id it's given, so anything
+    /// that accepts an `IdentityKey` from an untrusted peer without a
+    /// stored copy to compare against must check this first.
+    pub fn has_consistent_identity_id(&self) -> bool {
+        IdentityKeyPair::derive_identity_id(&self.classical_public, &self.pq_public)
+            == self.identity_id
     }
 
     /// Serialize to bytes for storage / transmission. Uses bincode
@@ -374,22 +427,43 @@ impl IdentityKey {
         Self::try_from(wire)
     }
 
-    /// 8-byte fingerprint suitable for human-readable display.
-    pub fn fingerprint(&self) -> String {
+    /// Full 32-byte fingerprint over both public halves. Covers only
+    /// the key material — not `created_at` or the (untrusted) wire
+    /// `identity_id` — so the same key always yields the same value
+    /// however it was serialized.
+    pub fn fingerprint_bytes(&self) -> [u8; 32] {
         let mut hasher = Hasher::new();
+        hasher.update(b"qubee identity fingerprint v1");
         hasher.update(self.classical_public.as_bytes());
+        hasher.update(&(self.pq_public.as_bytes().len() as u32).to_le_bytes());
         hasher.update(self.pq_public.as_bytes());
-        let hash = hasher.finalize();
-        let f = &hash.as_bytes()[..8];
+        *hasher.finalize().as_bytes()
+    }
+
+    /// 8-byte fingerprint suitable for human-readable display: the
+    /// leading bytes of [`fingerprint_bytes`](Self::fingerprint_bytes)
+    /// as grouped hex.
+    pub fn fingerprint(&self) -> String {
+        let hash = self.fingerprint_bytes();
+        let f = &hash[..8];
         format!(
             "{:02X}{:02X} {:02X}{:02X} {:02X}{:02X} {:02X}{:02X}",
             f[0], f[1], f[2], f[3], f[4], f[5], f[6], f[7],
         )
     }
+
+    /// Constant-time comparison of the full fingerprints, for
+    /// trust-on-first-use checks against a stored contact key.
+    pub fn fingerprint_matches(&self, other: &IdentityKey) -> bool {
+        self.fingerprint_bytes()
+            .ct_eq(&other.fingerprint_bytes())
+            .into()
+    }
 }
 
 // ---------------------------------------------------------------------------
-// Wire serde helpers (used by IdentityKey + HybridSignature)
+// Wire serde helpers (used by IdentityKey, HybridSignature and
+// DevicePublicKey)
 // ---------------------------------------------------------------------------
 
 #[derive(Serialize, Deserialize)]
@@ -486,6 +560,45 @@ impl<'de> Deserialize<'de> for HybridSignature {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+struct WireDevicePublicKey {
+    x25519_public: [u8; 32],
+    kyber_public: Vec<u8>,
+    device_id: DeviceId,
+    identity_id: IdentityId,
+    created_at: u64,
+}
+
+impl Serialize for DevicePublicKey {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use pqcrypto_traits::kem::PublicKey as _;
+        WireDevicePublicKey {
+            x25519_public: self.x25519_public.to_bytes(),
+            kyber_public: self.kyber_public.as_bytes().to_vec(),
+            device_id: self.device_id,
+            identity_id: self.identity_id,
+            created_at: self.created_at,
+        }
+        .serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for DevicePublicKey {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use pqcrypto_traits::kem::PublicKey as _;
+        let wire = WireDevicePublicKey::deserialize(d)?;
+        let kyber_public = pqcrypto_mlkem::mlkem768::PublicKey::from_bytes(&wire.kyber_public)
+            .map_err(serde::de::Error::custom)?;
+        Ok(DevicePublicKey {
+            x25519_public: x25519_dalek::PublicKey::from(wire.x25519_public),
+            kyber_public,
+            device_id: wire.device_id,
+            identity_id: wire.identity_id,
+            created_at: wire.created_at,
+        })
+    }
+}
+
 // ---------------------------------------------------------------------------
 // DeviceKey
 // ---------------------------------------------------------------------------
@@ -555,6 +668,53 @@ impl DeviceKey {
     }
 }
 
+// ---------------------------------------------------------------------------
+// DeviceRevocation
+// ---------------------------------------------------------------------------
+
+const DEVICE_REVOCATION_TAG: &[u8] = b"qubee device revocation v1";
+
+/// An identity's signed statement that one of its devices is no longer
+/// trusted. Permanent: there is no un-revoke, a recovered device gets a
+/// fresh device key instead.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceRevocation {
+    pub identity_id: IdentityId,
+    pub device_id: DeviceId,
+    pub revoked_at: u64,
+    pub signature: HybridSignature,
+}
+
+impl DeviceRevocation {
+    fn signing_bytes(identity_id: &IdentityId, device_id: &DeviceId, revoked_at: u64) -> Vec<u8> {
+        let mut out = Vec::with_capacity(DEVICE_REVOCATION_TAG.len() + 1 + 32 + 16 + 8);
+        out.extend_from_slice(DEVICE_REVOCATION_TAG);
+        out.push(0u8);
+        out.extend_from_slice(&identity_id.0);
+        out.extend_from_slice(&device_id.0);
+        out.extend_from_slice(&revoked_at.to_le_bytes());
+        out
+    }
+}
+
+/// Check `revocation` was signed by `identity_key` for one of its own
+/// devices. No freshness window: a revocation stays valid however long
+/// it takes to reach a contact.
+pub fn verify_revocation(revocation: &DeviceRevocation, identity_key: &IdentityKey) -> Result<()> {
+    if revocation.identity_id != identity_key.identity_id {
+        return Err(anyhow!("revocation is for another identity"));
+    }
+    let bytes = DeviceRevocation::signing_bytes(
+        &revocation.identity_id,
+        &revocation.device_id,
+        revocation.revoked_at,
+    );
+    if !identity_key.verify_with_max_age(&bytes, &revocation.signature, u64::MAX)? {
+        return Err(anyhow!("revocation signature failed"));
+    }
+    Ok(())
+}
+
 // ---------------------------------------------------------------------------
 // Display / conversions
 // ---------------------------------------------------------------------------
@@ -641,4 +801,55 @@ mod tests {
         let fp = kp.public_key().fingerprint();
         assert_eq!(fp.len(), 19); // "XXXX XXXX XXXX XXXX"
     }
+
+    #[test]
+    fn fingerprint_is_stable_across_serializations() {
+        let kp = IdentityKeyPair::generate().unwrap();
+        let pk = kp.public_key();
+        let other = IdentityKeyPair::generate().unwrap().public_key();
+        assert_ne!(pk.fingerprint(), other.fingerprint());
+        assert!(!pk.fingerprint_matches(&other));
+
+        let via_bytes = IdentityKey::from_bytes(&pk.to_bytes()).unwrap();
+        let via_json: IdentityKey =
+            serde_json::from_str(&serde_json::to_string(&pk).unwrap()).unwrap();
+        for decoded in [via_bytes, via_json] {
+            assert_eq!(decoded.fingerprint(), pk.fingerprint());
+            assert!(decoded.fingerprint_matches(&pk));
+        }
+    }
+
+    #[test]
+    fn device_revocation_verifies_only_for_its_identity() {
+        let kp = IdentityKeyPair::generate().unwrap();
+        let device = kp.derive_device_key(b"phone").unwrap();
+        let mut revocation = kp.revoke_device(device.device_id()).unwrap();
+        verify_revocation(&revocation, &kp.public_key()).unwrap();
+
+        let other = IdentityKeyPair::generate().unwrap();
+        assert!(verify_revocation(&revocation, &other.public_key()).is_err());
+
+        // A forger can't move a revocation to another device, nor sign
+        // one for an identity they don't hold.
+        revocation.device_id = DeviceId([0xAB; 16]);
+        assert!(verify_revocation(&revocation, &kp.public_key()).is_err());
+        let mut forged = other.revoke_device(device.device_id()).unwrap();
+        forged.identity_id = kp.identity_id();
+        assert!(verify_revocation(&forged, &kp.public_key()).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_each_failure_cause() {
+        let kp = IdentityKeyPair::generate().unwrap();
+        let other = IdentityKeyPair::generate().unwrap();
+        let sig = kp.sign(b"payload").unwrap();
+
+        assert!(kp.public_key().verify(b"payload", &sig).unwrap());
+        assert!(!kp.public_key().verify(b"tampered", &sig).unwrap());
+        assert!(!other.public_key().verify(b"payload", &sig).unwrap());
+
+        let mut stale = sig.clone();
+        stale.timestamp -= 3600;
+        assert!(!kp.public_key().verify(b"payload", &stale).unwrap());
+    }
 }
@@ -0,0 +1,317 @@
+//! Key-transparency log primitives: an append-only Merkle tree over
+//! published identity keys, inclusion proofs, and signed tree heads.
+//!
+//! A key server can hand two clients different keys for the same
+//! identity and neither would notice. With a transparency log the
+//! server also has to commit every key it serves to a Merkle tree whose
+//! root it signs (the *signed tree head*, STH). Clients verify an
+//! inclusion proof for the key they were given against an STH, and
+//! gossip STHs with each other; a server that shows different people
+//! different trees is caught holding two STHs for the same size with
+//! different roots, both under its own signature
+//! ([`is_equivocation`]).
+//!
+//! Tree hashing follows RFC 9162 (Certificate Transparency v2): leaf
+//! hashes are `H(0x00 || data)`, interior nodes `H(0x01 || left ||
+//! right)`, and the tree shape is the RFC's left-balanced split, so
+//! the proof-verification algorithm below is the standard one. `H` is
+//! BLAKE3 in derive-key mode with a Qubee-specific context so these
+//! hashes can't be confused with any other BLAKE3 output in the
+//! protocol.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::identity::identity_key::{DeviceId, HybridSignature, IdentityKey, IdentityKeyPair};
+
+const KT_HASH_CONTEXT: &str = "qubee key transparency v1";
+const KT_LEAF_TAG: &[u8] = b"qubee_kt_leaf_v1";
+const KT_TREE_HEAD_TAG: &[u8] = b"qubee_kt_tree_head_v1";
+
+fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key(KT_HASH_CONTEXT);
+    hasher.update(&[0x00]);
+    hasher.update(data);
+    *hasher.finalize().as_bytes()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key(KT_HASH_CONTEXT);
+    hasher.update(&[0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+/// Leaf committed to the log for one device of one identity. Binding
+/// the device id means a server can't reuse an honest leaf to vouch
+/// for a device the identity never published.
+pub fn identity_leaf(identity_key: &IdentityKey, device_id: &DeviceId) -> [u8; 32] {
+    let mut data = Vec::with_capacity(2048);
+    data.extend_from_slice(KT_LEAF_TAG);
+    data.push(0u8);
+    data.extend_from_slice(identity_key.identity_id.as_ref());
+    data.extend_from_slice(device_id.as_ref());
+    data.extend_from_slice(&identity_key.to_bytes());
+    leaf_hash(&data)
+}
+
+/// Proof that a leaf sits at `leaf_index` in a tree of `tree_size`
+/// leaves. `audit_path` is ordered leaf-to-root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub audit_path: Vec<[u8; 32]>,
+}
+
+impl InclusionProof {
+    /// RFC 9162 §2.1.3.2. Returns `true` only if `leaf` hashes up to
+    /// `root` along `audit_path`.
+    pub fn verify(&self, leaf: &[u8; 32], root: &[u8; 32]) -> bool {
+        if self.leaf_index >= self.tree_size {
+            return false;
+        }
+        let mut fnode = self.leaf_index;
+        let mut snode = self.tree_size - 1;
+        let mut r = *leaf;
+        for p in &self.audit_path {
+            if snode == 0 {
+                return false;
+            }
+            if fnode & 1 == 1 || fnode == snode {
+                r = node_hash(p, &r);
+                while fnode & 1 == 0 && fnode != 0 {
+                    fnode >>= 1;
+                    snode >>= 1;
+                }
+            } else {
+                r = node_hash(&r, p);
+            }
+            fnode >>= 1;
+            snode >>= 1;
+        }
+        snode == 0 && r == *root
+    }
+}
+
+/// Append-only Merkle tree of leaf hashes. Held by the log operator
+/// (and by [`InMemoryKeyServer`](crate::identity::signal_protocol::InMemoryKeyServer)
+/// in tests); clients never need the full tree.
+#[derive(Clone, Debug, Default)]
+pub struct MerkleLog {
+    leaves: Vec<[u8; 32]>,
+}
+
+impl MerkleLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a leaf and return its index.
+    pub fn append(&mut self, leaf: [u8; 32]) -> u64 {
+        self.leaves.push(leaf);
+        (self.leaves.len() - 1) as u64
+    }
+
+    pub fn size(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        subtree_root(&self.leaves)
+    }
+
+    /// Inclusion proof for `leaf_index` against the current root.
+    pub fn inclusion_proof(&self, leaf_index: u64) -> Result<InclusionProof> {
+        if leaf_index >= self.size() {
+            return Err(anyhow!(
+                "leaf {leaf_index} not in log of size {}",
+                self.size()
+            ));
+        }
+        let mut audit_path = Vec::new();
+        audit_path_for(leaf_index as usize, &self.leaves, &mut audit_path);
+        Ok(InclusionProof {
+            leaf_index,
+            tree_size: self.size(),
+            audit_path,
+        })
+    }
+}
+
+/// Largest power of two strictly less than `n` (`n >= 2`).
+fn split_point(n: usize) -> usize {
+    let mut k = 1;
+    while k << 1 < n {
+        k <<= 1;
+    }
+    k
+}
+
+fn subtree_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    match leaves.len() {
+        0 => *blake3::Hasher::new_derive_key(KT_HASH_CONTEXT)
+            .finalize()
+            .as_bytes(),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&subtree_root(&leaves[..k]), &subtree_root(&leaves[k..]))
+        }
+    }
+}
+
+fn audit_path_for(index: usize, leaves: &[[u8; 32]], out: &mut Vec<[u8; 32]>) {
+    if leaves.len() <= 1 {
+        return;
+    }
+    let k = split_point(leaves.len());
+    if index < k {
+        audit_path_for(index, &leaves[..k], out);
+        out.push(subtree_root(&leaves[k..]));
+    } else {
+        audit_path_for(index - k, &leaves[k..], out);
+        out.push(subtree_root(&leaves[..k]));
+    }
+}
+
+/// Log operator's signed commitment to a tree state. This is what
+/// clients gossip to each other.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedTreeHead {
+    pub tree_size: u64,
+    pub root_hash: [u8; 32],
+    pub timestamp: u64,
+    pub signature: HybridSignature,
+}
+
+fn canonical_tree_head(tree_size: u64, root_hash: &[u8; 32], timestamp: u64) -> Vec<u8> {
+    let mut out = Vec::with_capacity(KT_TREE_HEAD_TAG.len() + 1 + 8 + 32 + 8);
+    out.extend_from_slice(KT_TREE_HEAD_TAG);
+    out.push(0u8);
+    out.extend_from_slice(&tree_size.to_le_bytes());
+    out.extend_from_slice(root_hash);
+    out.extend_from_slice(&timestamp.to_le_bytes());
+    out
+}
+
+impl SignedTreeHead {
+    /// Sign the current state of `log` with the operator's key.
+    pub fn sign(log: &MerkleLog, log_keypair: &IdentityKeyPair) -> Result<Self> {
+        let tree_size = log.size();
+        let root_hash = log.root();
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let signature = log_keypair.sign(&canonical_tree_head(tree_size, &root_hash, timestamp))?;
+        Ok(SignedTreeHead {
+            tree_size,
+            root_hash,
+            timestamp,
+            signature,
+        })
+    }
+
+    /// Check the operator's signature. `max_age_secs` bounds how stale
+    /// a tree head the caller is willing to trust.
+    pub fn verify(&self, log_key: &IdentityKey, max_age_secs: u64) -> Result<bool> {
+        let payload = canonical_tree_head(self.tree_size, &self.root_hash, self.timestamp);
+        log_key.verify_with_max_age(&payload, &self.signature, max_age_secs)
+    }
+}
+
+/// `true` if two tree heads (already signature-checked against the
+/// same log key) commit to different trees of the same size — proof
+/// that the log showed different views to different clients.
+pub fn is_equivocation(a: &SignedTreeHead, b: &SignedTreeHead) -> bool {
+    a.tree_size == b.tree_size && a.root_hash != b.root_hash
+}
+
+/// Verify that `identity_key`/`device_id` is in the log committed to by
+/// `tree_head`, and that `tree_head` is signed by `log_key`.
+pub fn verify_key_inclusion(
+    identity_key: &IdentityKey,
+    device_id: &DeviceId,
+    proof: &InclusionProof,
+    tree_head: &SignedTreeHead,
+    log_key: &IdentityKey,
+    max_tree_head_age_secs: u64,
+) -> Result<()> {
+    if !tree_head.verify(log_key, max_tree_head_age_secs)? {
+        return Err(anyhow!("tree head signature invalid or stale"));
+    }
+    if proof.tree_size != tree_head.tree_size {
+        return Err(anyhow!(
+            "inclusion proof is for tree size {}, tree head is {}",
+            proof.tree_size,
+            tree_head.tree_size
+        ));
+    }
+    if !proof.verify(
+        &identity_leaf(identity_key, device_id),
+        &tree_head.root_hash,
+    ) {
+        return Err(anyhow!("identity key is not included in the log"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(i: u8) -> [u8; 32] {
+        leaf_hash(&[i])
+    }
+
+    #[test]
+    fn every_leaf_proves_against_root_for_all_small_sizes() {
+        for size in 1..=17u8 {
+            let mut log = MerkleLog::new();
+            for i in 0..size {
+                log.append(leaf(i));
+            }
+            let root = log.root();
+            for i in 0..size {
+                let proof = log.inclusion_proof(i as u64).unwrap();
+                assert!(proof.verify(&leaf(i), &root), "size {size} leaf {i}");
+                assert!(!proof.verify(&leaf(i.wrapping_add(100)), &root));
+            }
+        }
+    }
+
+    #[test]
+    fn key_inclusion_against_signed_tree_head() {
+        let operator = IdentityKeyPair::generate().unwrap();
+        let alice = IdentityKeyPair::generate().unwrap().public_key();
+        let mallory = IdentityKeyPair::generate().unwrap().public_key();
+        let device = DeviceId::from([5u8; 16]);
+
+        let mut log = MerkleLog::new();
+        log.append(leaf(0));
+        let idx = log.append(identity_leaf(&alice, &device));
+        log.append(leaf(2));
+
+        let sth = SignedTreeHead::sign(&log, &operator).unwrap();
+        let proof = log.inclusion_proof(idx).unwrap();
+        let op_key = operator.public_key();
+
+        verify_key_inclusion(&alice, &device, &proof, &sth, &op_key, 3600).unwrap();
+        // Server substituting a different key for the same proof.
+        assert!(verify_key_inclusion(&mallory, &device, &proof, &sth, &op_key, 3600).is_err());
+    }
+
+    #[test]
+    fn diverging_views_are_equivocation() {
+        let operator = IdentityKeyPair::generate().unwrap();
+        let mut view_a = MerkleLog::new();
+        let mut view_b = MerkleLog::new();
+        view_a.append(leaf(1));
+        view_b.append(leaf(2));
+        let a = SignedTreeHead::sign(&view_a, &operator).unwrap();
+        let b = SignedTreeHead::sign(&view_b, &operator).unwrap();
+        assert!(is_equivocation(&a, &b));
+        assert!(!is_equivocation(&a, &a.clone()));
+    }
+}
@@ -1,14 +1,21 @@
 pub mod contact_manager;
+pub mod conversation_id;
+pub mod device_sync;
+pub mod http_key_server;
 pub mod identity_key;
-
-// Signal-protocol prototype. Lives behind the `legacy` feature
-// because it derives serde over `DevicePublicKey` (which contains
-// pqcrypto types that don't impl serde) and wraps non-Zeroize types
-// in `Secret`. See docs/build-status.md for the migration list.
-#[cfg(feature = "legacy")]
+pub mod key_transparency;
+pub mod sealed_sender;
 pub mod signal_protocol;
 
-pub use contact_manager::{Contact, ContactManager, ContactVerificationStatus};
-pub use identity_key::{DeviceKey, HybridSignature, IdentityKey, IdentityKeyPair};
-#[cfg(feature = "legacy")]
-pub use signal_protocol::{PreKeyBundle, SignalProtocol, SignedPreKey};
+pub use contact_manager::{
+    safety_number, Contact, ContactEvent, ContactManager, ContactVerificationStatus,
+    VerificationMethod, VerificationRecord,
+};
+pub use conversation_id::ConversationId;
+pub use device_sync::{DeviceSyncEnvelope, DeviceSyncManager};
+pub use http_key_server::{HttpKeyServer, HttpTransport};
+pub use identity_key::{
+    DeviceKey, DeviceRevocation, HybridSignature, IdentityKey, IdentityKeyPair,
+};
+pub use sealed_sender::SealedEnvelope;
+pub use signal_protocol::{verify_prekey_bundle, PreKeyBundle, SignalProtocol, SignedPreKey};
@@ -0,0 +1,312 @@
+//! Sealed-sender envelopes: the sender's identity travels inside the
+//! encryption, so a relay (or anyone watching the recipient's inbox)
+//! sees who a message is for but not who it is from.
+//!
+//! ```text
+//! x25519_ss = X25519(ephemeral, device.x25519_public)
+//! (kem_ct, kem_ss) = ML-KEM-768.Encaps(device.kyber_public)
+//! key   = BLAKE3-derive("qubee sealed sender v1",
+//!                       x25519_ss || kem_ss || ephemeral_pub || kem_ct)
+//! inner = bincode({ sender_key, signature, message })
+//! body  = ChaCha20-Poly1305(key, nonce, inner, aad = recipient device)
+//! ```
+//!
+//! `signature` is the sender's identity signature over the recipient
+//! device, the ephemeral key and the message. Unsealing checks it, so
+//! the revealed sender is the one who wrote this message to this
+//! device: nobody can attach a sender whose key they don't hold, and a
+//! recipient can't re-seal a message it received to someone else under
+//! the original sender's name.
+
+use anyhow::{anyhow, Context, Result};
+use bincode::Options;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::identity::identity_key::{
+    DeviceId, DeviceKey, DevicePublicKey, HybridSignature, IdentityId, IdentityKey, IdentityKeyPair,
+};
+use crate::security::secure_rng;
+
+const SEALED_SENDER_TAG: &[u8] = b"qubee sealed sender v1";
+
+/// Largest inner payload accepted when unsealing; bounds the length
+/// prefixes bincode will honour.
+const MAX_SEALED_LEN: u64 = 16 * 1024 * 1024;
+
+/// A message sealed to one device. Only the recipient device is
+/// visible; the sender is inside `ciphertext`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SealedEnvelope {
+    pub recipient_device: DeviceId,
+    pub ephemeral_public: [u8; 32],
+    pub kem_ciphertext: Vec<u8>,
+    pub nonce: [u8; 12],
+    pub ciphertext: Vec<u8>,
+}
+
+/// What `ciphertext` decrypts to.
+#[derive(Serialize, Deserialize)]
+struct SealedContent {
+    sender_key: IdentityKey,
+    signature: HybridSignature,
+    message: Vec<u8>,
+}
+
+impl SealedEnvelope {
+    /// Seal `message` (typically already end-to-end encrypted) from
+    /// `sender` to `recipient`.
+    pub fn seal(
+        sender: &IdentityKeyPair,
+        recipient: &DevicePublicKey,
+        message: &[u8],
+    ) -> Result<Self> {
+        Self::seal_with(recipient, |ephemeral_public| {
+            let signature = sender.sign(&signing_bytes(
+                &recipient.identity_id,
+                &recipient.device_id,
+                ephemeral_public,
+                message,
+            ))?;
+            encode_content(sender.public_key(), signature, message)
+        })
+    }
+
+    /// Encrypt the content `inner` builds for this envelope's ephemeral
+    /// key to `recipient`.
+    fn seal_with(
+        recipient: &DevicePublicKey,
+        inner: impl FnOnce(&[u8; 32]) -> Result<Vec<u8>>,
+    ) -> Result<Self> {
+        use pqcrypto_traits::kem::{Ciphertext as _, SharedSecret as _};
+
+        let ephemeral = x25519_dalek::StaticSecret::from(secure_rng::random::array::<32>()?);
+        let ephemeral_public = x25519_dalek::PublicKey::from(&ephemeral).to_bytes();
+        let mut inner = inner(&ephemeral_public)?;
+
+        let mut x25519_ss = ephemeral
+            .diffie_hellman(&recipient.x25519_public)
+            .to_bytes();
+        let (kem_ss, kem_ct) = pqcrypto_mlkem::mlkem768::encapsulate(&recipient.kyber_public);
+        let mut kem_ss_bytes = [0u8; 32];
+        kem_ss_bytes.copy_from_slice(&kem_ss.as_bytes()[..32]);
+        let kem_ciphertext = kem_ct.as_bytes().to_vec();
+        let mut key = derive_seal_key(
+            &x25519_ss,
+            &kem_ss_bytes,
+            &ephemeral_public,
+            &kem_ciphertext,
+        );
+        x25519_ss.zeroize();
+        kem_ss_bytes.zeroize();
+
+        let nonce = secure_rng::random::array::<12>()?;
+        let sealed = ChaCha20Poly1305::new((&key).into()).encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &inner,
+                aad: recipient.device_id.as_ref(),
+            },
+        );
+        key.zeroize();
+        inner.zeroize();
+        let ciphertext = sealed.map_err(|_| anyhow!("sealed sender encryption failed"))?;
+        Ok(SealedEnvelope {
+            recipient_device: recipient.device_id,
+            ephemeral_public,
+            kem_ciphertext,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Open an envelope addressed to `recipient` and return the sender
+    /// with the message. Fails unless the sender's signature covers
+    /// this device and this message.
+    pub fn unseal(&self, recipient: &DeviceKey) -> Result<(IdentityKey, Vec<u8>)> {
+        if self.recipient_device != recipient.device_id() {
+            return Err(anyhow!("sealed envelope is for another device"));
+        }
+
+        let ephemeral = x25519_dalek::PublicKey::from(self.ephemeral_public);
+        let mut x25519_ss = recipient.x25519_agree(&ephemeral);
+        let mut kem_ss = recipient.kyber_decapsulate(&self.kem_ciphertext)?;
+        let mut key = derive_seal_key(
+            &x25519_ss,
+            &kem_ss,
+            &self.ephemeral_public,
+            &self.kem_ciphertext,
+        );
+        x25519_ss.zeroize();
+        kem_ss.zeroize();
+        let opened = ChaCha20Poly1305::new((&key).into()).decrypt(
+            Nonce::from_slice(&self.nonce),
+            Payload {
+                msg: &self.ciphertext,
+                aad: self.recipient_device.as_ref(),
+            },
+        );
+        key.zeroize();
+        let mut inner = opened.map_err(|_| anyhow!("sealed envelope failed to decrypt"))?;
+
+        let content: Result<SealedContent> = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .with_limit(MAX_SEALED_LEN)
+            .reject_trailing_bytes()
+            .deserialize(&inner)
+            .context("sealed sender deserialize");
+        inner.zeroize();
+        let content = content?;
+
+        if !content.sender_key.has_consistent_identity_id() {
+            return Err(anyhow!("sealed sender key doesn't match its identity id"));
+        }
+        let signed = signing_bytes(
+            &recipient.identity_id(),
+            &self.recipient_device,
+            &self.ephemeral_public,
+            &content.message,
+        );
+        if !content.sender_key.verify(&signed, &content.signature)? {
+            return Err(anyhow!("sealed sender signature failed"));
+        }
+        Ok((content.sender_key, content.message))
+    }
+}
+
+fn encode_content(
+    sender_key: IdentityKey,
+    signature: HybridSignature,
+    message: &[u8],
+) -> Result<Vec<u8>> {
+    bincode::serialize(&SealedContent {
+        sender_key,
+        signature,
+        message: message.to_vec(),
+    })
+    .context("sealed sender serialize")
+}
+
+/// What the sender signs: the recipient, this envelope's ephemeral key
+/// and the message.
+fn signing_bytes(
+    recipient: &IdentityId,
+    recipient_device: &DeviceId,
+    ephemeral_public: &[u8; 32],
+    message: &[u8],
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(SEALED_SENDER_TAG.len() + 1 + 32 + 16 + 32 + message.len());
+    out.extend_from_slice(SEALED_SENDER_TAG);
+    out.push(0u8);
+    out.extend_from_slice(recipient.as_ref());
+    out.extend_from_slice(recipient_device.as_ref());
+    out.extend_from_slice(ephemeral_public);
+    out.extend_from_slice(message);
+    out
+}
+
+fn derive_seal_key(
+    x25519_ss: &[u8; 32],
+    kem_ss: &[u8; 32],
+    ephemeral_public: &[u8; 32],
+    kem_ciphertext: &[u8],
+) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key("qubee sealed sender v1");
+    hasher.update(x25519_ss);
+    hasher.update(kem_ss);
+    hasher.update(ephemeral_public);
+    hasher.update(kem_ciphertext);
+    *hasher.finalize().as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_bytes_hide_the_sender_and_unsealing_reveals_it() {
+        let alice = IdentityKeyPair::generate().unwrap();
+        let bob = IdentityKeyPair::generate().unwrap();
+        let bob_phone = bob.derive_device_key(b"phone").unwrap();
+
+        let envelope =
+            SealedEnvelope::seal(&alice, &bob_phone.public_key(), b"ratchet ciphertext").unwrap();
+        let wire = bincode::serialize(&envelope).unwrap();
+        let alice_id = alice.identity_id();
+        assert!(!wire.windows(32).any(|w| w == alice_id.as_ref()));
+        let alice_key = alice.public_key().classical_public.to_bytes();
+        assert!(!wire.windows(32).any(|w| w == alice_key));
+
+        let (sender, message) = envelope.unseal(&bob_phone).unwrap();
+        assert_eq!(sender.identity_id, alice_id);
+        assert!(sender.fingerprint_matches(&alice.public_key()));
+        assert_eq!(message, b"ratchet ciphertext");
+
+        // Another device, even re-addressed, can't open it.
+        let bob_laptop = bob.derive_device_key(b"laptop").unwrap();
+        assert!(envelope.unseal(&bob_laptop).is_err());
+        let mut readdressed = envelope.clone();
+        readdressed.recipient_device = bob_laptop.device_id();
+        assert!(readdressed.unseal(&bob_laptop).is_err());
+    }
+
+    #[test]
+    fn sender_must_have_signed_for_this_recipient() {
+        let alice = IdentityKeyPair::generate().unwrap();
+        let bob = IdentityKeyPair::generate().unwrap();
+        let bob_phone = bob.derive_device_key(b"phone").unwrap();
+        let carol = IdentityKeyPair::generate().unwrap();
+        let carol_phone = carol.derive_device_key(b"phone").unwrap();
+        let mallory = IdentityKeyPair::generate().unwrap();
+
+        // Mallory names Alice as the sender but can only sign as herself.
+        let impersonation = SealedEnvelope::seal_with(&carol_phone.public_key(), |ephemeral| {
+            let signature = mallory.sign(&signing_bytes(
+                &carol.identity_id(),
+                &carol_phone.device_id(),
+                ephemeral,
+                b"hi carol",
+            ))?;
+            encode_content(alice.public_key(), signature, b"hi carol")
+        })
+        .unwrap();
+        assert!(impersonation.unseal(&carol_phone).is_err());
+
+        // Bob passes on what Alice signed for him, re-sealed to Carol.
+        let original = SealedEnvelope::seal(&alice, &bob_phone.public_key(), b"for bob").unwrap();
+        let resealed = SealedEnvelope::seal_with(&carol_phone.public_key(), |_| {
+            let signature = alice.sign(&signing_bytes(
+                &bob.identity_id(),
+                &bob_phone.device_id(),
+                &original.ephemeral_public,
+                b"for bob",
+            ))?;
+            encode_content(alice.public_key(), signature, b"for bob")
+        })
+        .unwrap();
+        assert!(resealed.unseal(&carol_phone).is_err());
+    }
+
+    #[test]
+    fn signing_bytes_are_pinned() {
+        // tag || 0 || recipient || recipient_device || ephemeral || message
+        let mut expected = b"qubee sealed sender v1\x00".to_vec();
+        expected.extend_from_slice(&[0x11; 32]);
+        expected.extend_from_slice(&[0x22; 16]);
+        expected.extend_from_slice(&[0x33; 32]);
+        expected.extend_from_slice(b"msg");
+        assert_eq!(
+            signing_bytes(
+                &IdentityId::from([0x11; 32]),
+                &DeviceId::from([0x22; 16]),
+                &[0x33; 32],
+                b"msg",
+            ),
+            expected
+        );
+    }
+}
@@ -1,14 +1,17 @@
 use anyhow::{Context, Result};
 use blake3::Hasher;
-use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
 
-use crate::crypto::enhanced_ratchet::EnhancedHybridRatchet;
 use crate::identity::identity_key::{
-    DeviceId, DeviceKey, DevicePublicKey, HybridSignature, IdentityId, IdentityKey, IdentityKeyPair,
+    verify_revocation, DeviceId, DeviceKey, DevicePublicKey, DeviceRevocation, HybridSignature,
+    IdentityId, IdentityKey, IdentityKeyPair,
 };
+use crate::identity::key_transparency::{identity_leaf, InclusionProof, MerkleLog, SignedTreeHead};
 use crate::security::secure_rng;
 
 /// Signal Protocol-inspired key distribution system
@@ -17,11 +20,15 @@ pub struct SignalProtocol {
     device_key: DeviceKey,
     signed_prekeys: HashMap<u32, SignedPreKey>,
     one_time_prekeys: HashMap<u32, OneTimePreKey>,
+    one_time_prekey_secrets: HashMap<u32, OneTimePreKeySecret>,
     next_prekey_id: u32,
+    low_prekey_threshold: usize,
+    /// Set once a verified revocation names this device.
+    revoked: bool,
 }
 
 /// Signed pre-key for key exchange initialization
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SignedPreKey {
     pub id: u32,
     pub device_public_key: DevicePublicKey,
@@ -30,16 +37,83 @@ pub struct SignedPreKey {
 }
 
 /// One-time pre-key for perfect forward secrecy
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone)]
 pub struct OneTimePreKey {
     pub id: u32,
     pub x25519_public: x25519_dalek::PublicKey,
-    pub kyber_public: pqcrypto_kyber::kyber768::PublicKey,
+    pub kyber_public: pqcrypto_mlkem::mlkem768::PublicKey,
     pub created_at: u64,
+    /// Identity signature over [`one_time_prekey_signing_bytes`], so a
+    /// key server can't substitute a one-time pre-key of its own.
+    pub signature: HybridSignature,
+}
+
+impl fmt::Debug for OneTimePreKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OneTimePreKey")
+            .field("id", &self.id)
+            .field("created_at", &self.created_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Serde shadow for [`OneTimePreKey`]: `mlkem768::PublicKey` has no
+/// serde impl, so it goes over the wire as bytes.
+#[derive(Serialize, Deserialize)]
+struct WireOneTimePreKey {
+    id: u32,
+    x25519_public: [u8; 32],
+    kyber_public: Vec<u8>,
+    created_at: u64,
+    signature: HybridSignature,
+}
+
+impl Serialize for OneTimePreKey {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use pqcrypto_traits::kem::PublicKey as _;
+        WireOneTimePreKey {
+            id: self.id,
+            x25519_public: self.x25519_public.to_bytes(),
+            kyber_public: self.kyber_public.as_bytes().to_vec(),
+            created_at: self.created_at,
+            signature: self.signature.clone(),
+        }
+        .serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for OneTimePreKey {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use pqcrypto_traits::kem::PublicKey as _;
+        let wire = WireOneTimePreKey::deserialize(d)?;
+        let kyber_public = pqcrypto_mlkem::mlkem768::PublicKey::from_bytes(&wire.kyber_public)
+            .map_err(serde::de::Error::custom)?;
+        Ok(OneTimePreKey {
+            id: wire.id,
+            x25519_public: x25519_dalek::PublicKey::from(wire.x25519_public),
+            kyber_public,
+            created_at: wire.created_at,
+            signature: wire.signature,
+        })
+    }
+}
+
+/// Private half of a [`OneTimePreKey`]. Kept only by the owner, until the
+/// pre-key is consumed by an incoming key exchange.
+struct OneTimePreKeySecret {
+    x25519_private_bytes: [u8; 32],
+    kyber_private_bytes: Vec<u8>,
+}
+
+impl Drop for OneTimePreKeySecret {
+    fn drop(&mut self) {
+        self.x25519_private_bytes.zeroize();
+        self.kyber_private_bytes.zeroize();
+    }
 }
 
 /// Bundle of keys for initiating communication
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PreKeyBundle {
     pub identity_key: IdentityKey,
     pub device_id: DeviceId,
@@ -48,11 +122,16 @@ pub struct PreKeyBundle {
     pub bundle_timestamp: u64,
 }
 
-/// Result of key exchange initialization
+/// Result of key exchange initialization. `shared_secret` is the root
+/// both sides seed their session ratchet with.
 pub struct KeyExchangeResult {
     pub shared_secret: [u8; 64], // Combined classical + post-quantum secret
-    pub ratchet: EnhancedHybridRatchet,
     pub used_one_time_key: Option<u32>,
+    /// ML-KEM ciphertexts the responder needs to decapsulate, in order
+    /// DH1, DH2 and (if a one-time pre-key was used) DH3. Sent to the
+    /// responder alongside `used_one_time_key`; empty on the responder
+    /// side.
+    pub pq_ciphertexts: Vec<Vec<u8>>,
 }
 
 /// Key distribution server interface
@@ -70,11 +149,43 @@ pub trait KeyDistributionServer {
         prekey_id: u32,
     ) -> Result<()>;
     fn list_devices(&self, identity_id: &IdentityId) -> Result<Vec<DeviceId>>;
+    /// Drop every bundle for the revoked device and refuse new uploads
+    /// for it. Servers must check the revocation against the identity
+    /// key they already hold; an unverified revocation would let anyone
+    /// delist anyone.
+    fn revoke_device(&mut self, revocation: &DeviceRevocation) -> Result<()>;
+
+    /// Fetch a bundle together with a key-transparency inclusion proof
+    /// for its identity key (see [`crate::identity::key_transparency`]).
+    /// Servers without a transparency log keep this default and return
+    /// no proof; callers decide whether that's acceptable.
+    fn get_prekey_bundle_with_proof(
+        &self,
+        identity_id: &IdentityId,
+        device_id: &DeviceId,
+    ) -> Result<(PreKeyBundle, Option<InclusionProof>)> {
+        Ok((self.get_prekey_bundle(identity_id, device_id)?, None))
+    }
+
+    /// The log's latest signed tree head, if this server publishes one.
+    fn signed_tree_head(&self) -> Result<Option<SignedTreeHead>> {
+        Ok(None)
+    }
 }
 
 /// In-memory key distribution server for testing
 pub struct InMemoryKeyServer {
     bundles: HashMap<(IdentityId, DeviceId), PreKeyBundle>,
+    revoked: HashSet<(IdentityId, DeviceId)>,
+    /// Optional transparency log; `None` behaves like a plain directory.
+    transparency: Option<TransparencyLog>,
+}
+
+/// Log state for [`InMemoryKeyServer::with_transparency_log`].
+struct TransparencyLog {
+    log: MerkleLog,
+    leaf_index: HashMap<(IdentityId, DeviceId), u64>,
+    operator: IdentityKeyPair,
 }
 
 impl SignalProtocol {
@@ -87,7 +198,10 @@ impl SignalProtocol {
             device_key,
             signed_prekeys: HashMap::new(),
             one_time_prekeys: HashMap::new(),
+            one_time_prekey_secrets: HashMap::new(),
             next_prekey_id: 1,
+            low_prekey_threshold: DEFAULT_LOW_PREKEY_THRESHOLD,
+            revoked: false,
         })
     }
 
@@ -125,31 +239,92 @@ impl SignalProtocol {
             self.next_prekey_id += 1;
 
             // Generate ephemeral X25519 key pair
-            let x25519_private = x25519_dalek::StaticSecret::new(&mut rand::thread_rng());
+            let x25519_private =
+                x25519_dalek::StaticSecret::from(secure_rng::random::array::<32>()?);
             let x25519_public = x25519_dalek::PublicKey::from(&x25519_private);
 
             // Generate ephemeral Kyber key pair
-            let (kyber_public, _kyber_private) = pqcrypto_kyber::kyber768::keypair();
+            use pqcrypto_traits::kem::SecretKey as _;
+            let (kyber_public, kyber_private) = pqcrypto_mlkem::mlkem768::keypair();
 
             let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
 
+            let signature = self.identity_keypair.sign(&one_time_prekey_signing_bytes(
+                prekey_id,
+                &x25519_public,
+                &kyber_public,
+                created_at,
+                &self.device_key.public_key(),
+            ))?;
             let one_time_prekey = OneTimePreKey {
                 id: prekey_id,
                 x25519_public,
                 kyber_public,
                 created_at,
+                signature,
             };
 
             self.one_time_prekeys
                 .insert(prekey_id, one_time_prekey.clone());
+            self.one_time_prekey_secrets.insert(
+                prekey_id,
+                OneTimePreKeySecret {
+                    x25519_private_bytes: x25519_private.to_bytes(),
+                    kyber_private_bytes: kyber_private.as_bytes().to_vec(),
+                },
+            );
             prekeys.push(one_time_prekey);
         }
 
         Ok(prekeys)
     }
 
-    /// Create a pre-key bundle for upload to the server
+    /// One-time pre-keys generated and not yet consumed by an incoming
+    /// key exchange.
+    pub fn available_one_time_prekey_count(&self) -> usize {
+        self.one_time_prekeys.len()
+    }
+
+    /// Top the unused one-time pre-keys back up to `target`, returning
+    /// only the newly generated ones (the caller uploads those). Does
+    /// nothing if `target` are already available.
+    pub fn replenish_one_time_prekeys(&mut self, target: usize) -> Result<Vec<OneTimePreKey>> {
+        let missing = target.saturating_sub(self.available_one_time_prekey_count());
+        self.generate_one_time_prekeys(missing)
+    }
+
+    /// Below this many unused one-time pre-keys,
+    /// [`needs_prekey_replenishment`](Self::needs_prekey_replenishment)
+    /// reports `true`. Defaults to [`DEFAULT_LOW_PREKEY_THRESHOLD`].
+    pub fn set_low_prekey_threshold(&mut self, threshold: usize) {
+        self.low_prekey_threshold = threshold;
+    }
+
+    /// `true` once the supply of one-time pre-keys has run low. Check
+    /// after responding to key exchanges or before uploading a bundle
+    /// from [`create_prekey_bundle`](Self::create_prekey_bundle); once
+    /// they run out, new sessions fall back to the signed pre-key only
+    /// and lose the one-time key's forward secrecy.
+    pub fn needs_prekey_replenishment(&self) -> bool {
+        self.available_one_time_prekey_count() < self.low_prekey_threshold
+    }
+
+    /// Apply a revocation issued by this identity. If it names this
+    /// device, no further pre-key bundles are built for it.
+    pub fn apply_revocation(&mut self, revocation: &DeviceRevocation) -> Result<()> {
+        verify_revocation(revocation, &self.identity_keypair.public_key())?;
+        if revocation.device_id == self.device_key.device_id() {
+            self.revoked = true;
+        }
+        Ok(())
+    }
+
+    /// Create a pre-key bundle for upload to the server. Fails once
+    /// this device has been revoked.
     pub fn create_prekey_bundle(&self) -> Result<PreKeyBundle> {
+        if self.revoked {
+            return Err(anyhow::anyhow!("Device has been revoked"));
+        }
         // Get the most recent signed pre-key
         let signed_prekey = self
             .signed_prekeys
@@ -183,76 +358,52 @@ impl SignalProtocol {
         // Combine classical and post-quantum shared secrets
         let combined_secret = self.combine_shared_secrets(&shared_secrets)?;
 
-        // Initialize the double ratchet
-        let mut ratchet = EnhancedHybridRatchet::new()?;
-        ratchet.initialize_sender(&combined_secret, &self.device_key.public_key())?;
+        let mut pq_ciphertexts = vec![shared_secrets.dh1_pq_ct, shared_secrets.dh2_pq_ct];
+        pq_ciphertexts.extend(shared_secrets.dh3_pq_ct);
 
         Ok(KeyExchangeResult {
             shared_secret: combined_secret,
-            ratchet,
             used_one_time_key: remote_bundle.one_time_prekey.as_ref().map(|otk| otk.id),
+            pq_ciphertexts,
         })
     }
 
-    /// Respond to key exchange initiation
+    /// Respond to key exchange initiation. `used_one_time_key` and
+    /// `pq_ciphertexts` are the initiator's
+    /// [`KeyExchangeResult`] fields of the same name.
     pub fn respond_to_key_exchange(
-        &self,
+        &mut self,
         initiator_identity: &IdentityKey,
         initiator_device: &DevicePublicKey,
         used_one_time_key: Option<u32>,
+        pq_ciphertexts: &[Vec<u8>],
     ) -> Result<KeyExchangeResult> {
         // Verify initiator's identity and device key
         self.verify_device_key(initiator_identity, initiator_device)?;
 
         // Reconstruct the key exchange
         let shared_secrets =
-            self.reconstruct_3dh_key_exchange(initiator_device, used_one_time_key)?;
+            self.reconstruct_3dh_key_exchange(initiator_device, used_one_time_key, pq_ciphertexts)?;
 
         // Combine shared secrets
         let combined_secret = self.combine_shared_secrets(&shared_secrets)?;
 
-        // Initialize the double ratchet as receiver
-        let mut ratchet = EnhancedHybridRatchet::new()?;
-        ratchet.initialize_receiver(&combined_secret, initiator_device)?;
-
-        // Remove used one-time pre-key
+        // Remove used one-time pre-key, private half included
         if let Some(otk_id) = used_one_time_key {
             self.one_time_prekeys.remove(&otk_id);
+            self.one_time_prekey_secrets.remove(&otk_id);
         }
 
         Ok(KeyExchangeResult {
             shared_secret: combined_secret,
-            ratchet,
             used_one_time_key,
+            pq_ciphertexts: Vec::new(),
         })
     }
 
     /// Verify a pre-key bundle's authenticity
     fn verify_prekey_bundle(&self, bundle: &PreKeyBundle) -> Result<()> {
-        // Verify signed pre-key signature
-        let signature_data =
-            self.serialize_device_key_for_signing(&bundle.signed_prekey.device_public_key)?;
-
-        let signature_valid = bundle
-            .identity_key
-            .verify(&signature_data, &bundle.signed_prekey.signature)?;
-        if !signature_valid {
-            return Err(anyhow::anyhow!("Invalid signed pre-key signature"));
-        }
-
-        // Verify device key belongs to the identity
-        if bundle.signed_prekey.device_public_key.identity_id != bundle.identity_key.identity_id {
-            return Err(anyhow::anyhow!("Device key identity mismatch"));
-        }
-
-        // Check bundle freshness (within 7 days)
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-
-        if current_time.saturating_sub(bundle.bundle_timestamp) > 7 * 24 * 3600 {
-            return Err(anyhow::anyhow!("Pre-key bundle is too old"));
-        }
-
-        Ok(())
+        verify_prekey_bundle(bundle)
     }
 
     /// Verify a device key belongs to an identity
@@ -316,36 +467,43 @@ impl SignalProtocol {
         &self,
         initiator_device: &DevicePublicKey,
         used_one_time_key: Option<u32>,
+        pq_ciphertexts: &[Vec<u8>],
     ) -> Result<SharedSecrets> {
+        let expected = if used_one_time_key.is_some() { 3 } else { 2 };
+        if pq_ciphertexts.len() != expected {
+            return Err(anyhow::anyhow!(
+                "Expected {} PQ ciphertexts, got {}",
+                expected,
+                pq_ciphertexts.len()
+            ));
+        }
+
         // DH1: Their identity key with our signed pre-key
         let dh1_classical = self
             .device_key
             .x25519_agree(&initiator_device.x25519_public);
-        // Note: For PQ, we need the ciphertext from the initiator to decapsulate
-        // This is simplified - in practice, the ciphertext would be transmitted
-        let dh1_pq_ss = [0u8; 32]; // Placeholder
-        let dh1_pq_ct = Vec::new(); // Placeholder
+        let dh1_pq_ct = pq_ciphertexts[0].clone();
+        let dh1_pq_ss = self.device_key.kyber_decapsulate(&dh1_pq_ct)?;
 
         // DH2: Their ephemeral key with our identity key
         let dh2_classical = self
             .device_key
             .x25519_agree(&initiator_device.x25519_public);
-        let dh2_pq_ss = [0u8; 32]; // Placeholder
-        let dh2_pq_ct = Vec::new(); // Placeholder
+        let dh2_pq_ct = pq_ciphertexts[1].clone();
+        let dh2_pq_ss = self.device_key.kyber_decapsulate(&dh2_pq_ct)?;
 
         // DH3: Their ephemeral key with our one-time pre-key
         let (dh3_classical, dh3_pq_ss, dh3_pq_ct) = if let Some(otk_id) = used_one_time_key {
-            if let Some(_otk) = self.one_time_prekeys.get(&otk_id) {
-                // In practice, we would use the one-time pre-key private key
-                let dh3_classical = self
-                    .device_key
-                    .x25519_agree(&initiator_device.x25519_public);
-                let dh3_pq_ss = [0u8; 32]; // Placeholder
-                let dh3_pq_ct = Vec::new(); // Placeholder
-                (Some(dh3_classical), Some(dh3_pq_ss), Some(dh3_pq_ct))
-            } else {
-                return Err(anyhow::anyhow!("One-time pre-key not found"));
-            }
+            let otk_secret = self
+                .one_time_prekey_secrets
+                .get(&otk_id)
+                .ok_or_else(|| anyhow::anyhow!("One-time pre-key not found"))?;
+            let dh3_classical = x25519_dalek::StaticSecret::from(otk_secret.x25519_private_bytes)
+                .diffie_hellman(&initiator_device.x25519_public)
+                .to_bytes();
+            let dh3_pq_ct = pq_ciphertexts[2].clone();
+            let dh3_pq_ss = one_time_kyber_decapsulate(otk_secret, &dh3_pq_ct)?;
+            (Some(dh3_classical), Some(dh3_pq_ss), Some(dh3_pq_ct))
         } else {
             (None, None, None)
         };
@@ -392,7 +550,7 @@ impl SignalProtocol {
 
         // Second round for remaining bytes
         let mut hasher2 = Hasher::new();
-        hasher2.update(&hash.as_bytes());
+        hasher2.update(hash.as_bytes());
         hasher2.update(&[0x01]);
         let hash2 = hasher2.finalize();
         output[32..].copy_from_slice(&hash2.as_bytes()[..32]);
@@ -402,13 +560,7 @@ impl SignalProtocol {
 
     /// Serialize device key for signing
     fn serialize_device_key_for_signing(&self, device_key: &DevicePublicKey) -> Result<Vec<u8>> {
-        let mut data = Vec::new();
-        data.extend_from_slice(device_key.x25519_public.as_bytes());
-        data.extend_from_slice(device_key.kyber_public.as_bytes());
-        data.extend_from_slice(device_key.device_id.as_ref());
-        data.extend_from_slice(device_key.identity_id.as_ref());
-        data.extend_from_slice(&device_key.created_at.to_le_bytes());
-        Ok(data)
+        Ok(signed_prekey_signing_bytes(device_key))
     }
 
     /// Get identity key pair
@@ -432,6 +584,144 @@ impl SignalProtocol {
     }
 }
 
+/// Default for [`SignalProtocol::set_low_prekey_threshold`].
+pub const DEFAULT_LOW_PREKEY_THRESHOLD: usize = 10;
+
+/// Oldest pre-key bundle (and signed-prekey signature) accepted.
+pub const PREKEY_BUNDLE_MAX_AGE_SECS: u64 = 7 * 24 * 3600;
+
+fn signed_prekey_signing_bytes(device_key: &DevicePublicKey) -> Vec<u8> {
+    use pqcrypto_traits::kem::PublicKey as _;
+    let mut data = Vec::new();
+    data.extend_from_slice(device_key.x25519_public.as_bytes());
+    data.extend_from_slice(device_key.kyber_public.as_bytes());
+    data.extend_from_slice(device_key.device_id.as_ref());
+    data.extend_from_slice(device_key.identity_id.as_ref());
+    data.extend_from_slice(&device_key.created_at.to_le_bytes());
+    data
+}
+
+/// What the identity key signs for a one-time pre-key: the key itself
+/// and the device it was issued for, so it can't be moved to another
+/// device's bundle either.
+fn one_time_prekey_signing_bytes(
+    id: u32,
+    x25519_public: &x25519_dalek::PublicKey,
+    kyber_public: &pqcrypto_mlkem::mlkem768::PublicKey,
+    created_at: u64,
+    device_key: &DevicePublicKey,
+) -> Vec<u8> {
+    use pqcrypto_traits::kem::PublicKey as _;
+    let mut data = Vec::new();
+    data.extend_from_slice(b"qubee one-time prekey v1");
+    data.push(0u8);
+    data.extend_from_slice(&id.to_le_bytes());
+    data.extend_from_slice(x25519_public.as_bytes());
+    data.extend_from_slice(kyber_public.as_bytes());
+    data.extend_from_slice(&created_at.to_le_bytes());
+    data.extend_from_slice(device_key.device_id.as_ref());
+    data.extend_from_slice(device_key.identity_id.as_ref());
+    data
+}
+
+/// Validate a pre-key bundle from any source — this crate's
+/// [`SignalProtocol`], another implementation, or a key server — before
+/// using it or accepting it for upload. Checks:
+///
+/// * the identity key's id is the one derived from its public halves
+///   (the wire decoder doesn't check this);
+/// * the signed prekey is signed by that identity key, within
+///   [`PREKEY_BUNDLE_MAX_AGE_SECS`];
+/// * the signed prekey belongs to the bundle's identity *and* device;
+/// * the one-time prekey, if any, is signed by the identity key for
+///   this device (no age limit: unused one-time keys may sit on the
+///   server for a long time);
+/// * the bundle itself is no older than [`PREKEY_BUNDLE_MAX_AGE_SECS`].
+///
+/// All checks run unconditionally and are combined with a
+/// constant-time `&` before any branch, so a forged bundle takes the
+/// same path whichever check it fails. The error afterwards can still
+/// say which one.
+pub fn verify_prekey_bundle(bundle: &PreKeyBundle) -> Result<()> {
+    let consistent_identity = Choice::from(bundle.identity_key.has_consistent_identity_id() as u8);
+
+    let signature_data = signed_prekey_signing_bytes(&bundle.signed_prekey.device_public_key);
+    let signature_valid = Choice::from(bundle.identity_key.verify_with_max_age(
+        &signature_data,
+        &bundle.signed_prekey.signature,
+        PREKEY_BUNDLE_MAX_AGE_SECS,
+    )? as u8);
+
+    let device_key = &bundle.signed_prekey.device_public_key;
+    let identity_matches = device_key
+        .identity_id
+        .as_ref()
+        .ct_eq(bundle.identity_key.identity_id.as_ref());
+    let device_matches = device_key
+        .device_id
+        .as_ref()
+        .ct_eq(bundle.device_id.as_ref());
+
+    let one_time_prekey_valid = match &bundle.one_time_prekey {
+        Some(otk) => Choice::from(bundle.identity_key.verify_with_max_age(
+            &one_time_prekey_signing_bytes(
+                otk.id,
+                &otk.x25519_public,
+                &otk.kyber_public,
+                otk.created_at,
+                device_key,
+            ),
+            &otk.signature,
+            u64::MAX,
+        )? as u8),
+        None => Choice::from(1u8),
+    };
+
+    let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let fresh = Choice::from(
+        (current_time.saturating_sub(bundle.bundle_timestamp) <= PREKEY_BUNDLE_MAX_AGE_SECS) as u8,
+    );
+
+    if bool::from(
+        consistent_identity
+            & signature_valid
+            & identity_matches
+            & device_matches
+            & one_time_prekey_valid
+            & fresh,
+    ) {
+        return Ok(());
+    }
+    if !bool::from(consistent_identity) {
+        Err(anyhow::anyhow!(
+            "Identity key does not match its identity id"
+        ))
+    } else if !bool::from(signature_valid) {
+        Err(anyhow::anyhow!("Invalid signed pre-key signature"))
+    } else if !bool::from(identity_matches) {
+        Err(anyhow::anyhow!("Device key identity mismatch"))
+    } else if !bool::from(device_matches) {
+        Err(anyhow::anyhow!("Device key does not match bundle device"))
+    } else if !bool::from(one_time_prekey_valid) {
+        Err(anyhow::anyhow!("Invalid one-time pre-key signature"))
+    } else {
+        Err(anyhow::anyhow!("Pre-key bundle is too old"))
+    }
+}
+
+/// ML-KEM decapsulation under a one-time pre-key's private half.
+fn one_time_kyber_decapsulate(secret: &OneTimePreKeySecret, ciphertext: &[u8]) -> Result<[u8; 32]> {
+    use pqcrypto_traits::kem::{Ciphertext as _, SecretKey as _, SharedSecret as _};
+    let ct = pqcrypto_mlkem::mlkem768::Ciphertext::from_bytes(ciphertext)
+        .map_err(|e| anyhow::anyhow!("Invalid Kyber ciphertext: {e}"))?;
+    let sk = pqcrypto_mlkem::mlkem768::SecretKey::from_bytes(&secret.kyber_private_bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid one-time pre-key Kyber sk: {e}"))?;
+    let shared = pqcrypto_mlkem::mlkem768::decapsulate(&ct, &sk);
+    let mut ss = [0u8; 32];
+    ss.copy_from_slice(&shared.as_bytes()[..32]);
+    Ok(ss)
+}
+
 /// Shared secrets from key exchange
 struct SharedSecrets {
     dh1_classical: [u8; 32],
@@ -450,13 +740,55 @@ impl InMemoryKeyServer {
     pub fn new() -> Self {
         InMemoryKeyServer {
             bundles: HashMap::new(),
+            revoked: HashSet::new(),
+            transparency: None,
+        }
+    }
+
+    /// Key server that also logs every uploaded identity key in a
+    /// Merkle tree signed by `operator`, so tests can exercise the
+    /// inclusion-proof path end to end.
+    pub fn with_transparency_log(operator: IdentityKeyPair) -> Self {
+        InMemoryKeyServer {
+            bundles: HashMap::new(),
+            revoked: HashSet::new(),
+            transparency: Some(TransparencyLog {
+                log: MerkleLog::new(),
+                leaf_index: HashMap::new(),
+                operator,
+            }),
         }
     }
 }
 
+impl Default for InMemoryKeyServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl KeyDistributionServer for InMemoryKeyServer {
     fn upload_prekey_bundle(&mut self, bundle: &PreKeyBundle) -> Result<()> {
+        verify_prekey_bundle(bundle).context("rejected pre-key bundle upload")?;
         let key = (bundle.identity_key.identity_id, bundle.device_id);
+        if self.revoked.contains(&key) {
+            return Err(anyhow::anyhow!(
+                "rejected pre-key bundle upload: device revoked"
+            ));
+        }
+        if let Some(kt) = self.transparency.as_mut() {
+            // Re-uploads (fresh prekeys, same identity key) don't add a
+            // leaf; only a changed identity key does.
+            let leaf = identity_leaf(&bundle.identity_key, &bundle.device_id);
+            let already_logged = self
+                .bundles
+                .get(&key)
+                .is_some_and(|old| identity_leaf(&old.identity_key, &old.device_id) == leaf);
+            if !already_logged {
+                let index = kt.log.append(leaf);
+                kt.leaf_index.insert(key, index);
+            }
+        }
         self.bundles.insert(key, bundle.clone());
         Ok(())
     }
@@ -500,6 +832,48 @@ impl KeyDistributionServer for InMemoryKeyServer {
 
         Ok(devices)
     }
+
+    fn revoke_device(&mut self, revocation: &DeviceRevocation) -> Result<()> {
+        let key = (revocation.identity_id, revocation.device_id);
+        // Any bundle of the identity carries its key; the revoked
+        // device's own may already be gone.
+        let identity_key = self
+            .bundles
+            .iter()
+            .find(|((id, _), _)| *id == revocation.identity_id)
+            .map(|(_, bundle)| bundle.identity_key.clone())
+            .ok_or_else(|| anyhow::anyhow!("Unknown identity"))?;
+        verify_revocation(revocation, &identity_key).context("rejected device revocation")?;
+        self.bundles.remove(&key);
+        self.revoked.insert(key);
+        Ok(())
+    }
+
+    fn get_prekey_bundle_with_proof(
+        &self,
+        identity_id: &IdentityId,
+        device_id: &DeviceId,
+    ) -> Result<(PreKeyBundle, Option<InclusionProof>)> {
+        let bundle = self.get_prekey_bundle(identity_id, device_id)?;
+        let proof = match self.transparency.as_ref() {
+            Some(kt) => {
+                let index = kt
+                    .leaf_index
+                    .get(&(*identity_id, *device_id))
+                    .ok_or_else(|| anyhow::anyhow!("bundle missing from transparency log"))?;
+                Some(kt.log.inclusion_proof(*index)?)
+            }
+            None => None,
+        };
+        Ok((bundle, proof))
+    }
+
+    fn signed_tree_head(&self) -> Result<Option<SignedTreeHead>> {
+        self.transparency
+            .as_ref()
+            .map(|kt| SignedTreeHead::sign(&kt.log, &kt.operator))
+            .transpose()
+    }
 }
 
 #[cfg(test)]
@@ -560,6 +934,74 @@ mod tests {
             .expect("Should verify pre-key bundle");
     }
 
+    #[test]
+    fn test_standalone_bundle_verification() {
+        let identity_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
+        let mut signal_protocol = SignalProtocol::new(identity_keypair, b"test_device")
+            .expect("Should create Signal protocol");
+        signal_protocol
+            .generate_signed_prekey()
+            .expect("Should generate signed pre-key");
+        let bundle = signal_protocol
+            .create_prekey_bundle()
+            .expect("Should create pre-key bundle");
+
+        verify_prekey_bundle(&bundle).expect("Fresh bundle should verify");
+
+        // Re-labelled for another device of the same identity.
+        let mut relabelled = bundle.clone();
+        relabelled.device_id = DeviceId::from([0xEE; 16]);
+        assert!(verify_prekey_bundle(&relabelled).is_err());
+
+        // Someone else's identity key claiming this signed prekey.
+        let mut swapped = bundle.clone();
+        swapped.identity_key = IdentityKeyPair::generate().unwrap().public_key();
+        assert!(verify_prekey_bundle(&swapped).is_err());
+
+        let mut stale = bundle;
+        stale.bundle_timestamp -= PREKEY_BUNDLE_MAX_AGE_SECS + 1;
+        assert!(verify_prekey_bundle(&stale).is_err());
+        assert!(InMemoryKeyServer::new()
+            .upload_prekey_bundle(&stale)
+            .is_err());
+    }
+
+    #[test]
+    fn test_swapped_one_time_prekey_fails_verification() {
+        let identity_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
+        let same_identity = IdentityKeyPair::deserialize_from_keystore(
+            &identity_keypair.serialize_for_keystore().unwrap(),
+        )
+        .unwrap();
+        let mut signal_protocol = SignalProtocol::new(identity_keypair, b"test_device")
+            .expect("Should create Signal protocol");
+        signal_protocol.generate_signed_prekey().unwrap();
+        signal_protocol.generate_one_time_prekeys(1).unwrap();
+        let bundle = signal_protocol.create_prekey_bundle().unwrap();
+        verify_prekey_bundle(&bundle).expect("Signed one-time pre-key should verify");
+
+        // The signature still covers the key after a trip over the wire.
+        let wire = bincode::serialize(&bundle).unwrap();
+        let decoded: PreKeyBundle = bincode::deserialize(&wire).unwrap();
+        verify_prekey_bundle(&decoded).expect("Decoded one-time pre-key should verify");
+
+        // A server swapping in its own key pair, keeping the signature.
+        let mut swapped = bundle.clone();
+        let otk = swapped.one_time_prekey.as_mut().unwrap();
+        otk.x25519_public =
+            x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from([7u8; 32]));
+        otk.kyber_public = pqcrypto_mlkem::mlkem768::keypair().0;
+        let err = verify_prekey_bundle(&swapped).unwrap_err();
+        assert!(err.to_string().contains("one-time pre-key"));
+
+        // Or replaying one signed for another device of the identity.
+        let mut other_device = SignalProtocol::new(same_identity, b"other_device").unwrap();
+        other_device.generate_one_time_prekeys(1).unwrap();
+        let mut moved = bundle;
+        moved.one_time_prekey = other_device.one_time_prekeys().values().next().cloned();
+        assert!(verify_prekey_bundle(&moved).is_err());
+    }
+
     #[test]
     fn test_key_distribution_server() {
         let identity_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
@@ -604,6 +1046,89 @@ mod tests {
         assert_eq!(devices[0], bundle.device_id);
     }
 
+    #[test]
+    fn revoked_device_loses_its_bundles() {
+        let identity_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
+        let identity_key = identity_keypair.public_key();
+        let mut phone = SignalProtocol::new(identity_keypair, b"phone").unwrap();
+        phone.generate_signed_prekey().unwrap();
+        let bundle = phone.create_prekey_bundle().unwrap();
+
+        let mut server = InMemoryKeyServer::new();
+        server.upload_prekey_bundle(&bundle).unwrap();
+        let revocation = phone
+            .identity_keypair()
+            .revoke_device(bundle.device_id)
+            .unwrap();
+
+        let mut forged = IdentityKeyPair::generate()
+            .unwrap()
+            .revoke_device(bundle.device_id)
+            .unwrap();
+        forged.identity_id = identity_key.identity_id;
+        assert!(server.revoke_device(&forged).is_err());
+        assert!(server
+            .get_prekey_bundle(&identity_key.identity_id, &bundle.device_id)
+            .is_ok());
+
+        server.revoke_device(&revocation).unwrap();
+        assert!(server
+            .list_devices(&identity_key.identity_id)
+            .unwrap()
+            .is_empty());
+        assert!(server.upload_prekey_bundle(&bundle).is_err());
+
+        phone.apply_revocation(&revocation).unwrap();
+        assert!(phone.create_prekey_bundle().is_err());
+    }
+
+    #[test]
+    fn test_key_server_transparency_proof() {
+        let identity_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
+        let mut signal_protocol = SignalProtocol::new(identity_keypair, b"test_device")
+            .expect("Should create Signal protocol");
+        signal_protocol
+            .generate_signed_prekey()
+            .expect("Should generate signed pre-key");
+        let bundle = signal_protocol
+            .create_prekey_bundle()
+            .expect("Should create pre-key bundle");
+
+        let operator = IdentityKeyPair::generate().expect("Should generate operator keypair");
+        let operator_key = operator.public_key();
+        let mut server = InMemoryKeyServer::with_transparency_log(operator);
+        server
+            .upload_prekey_bundle(&bundle)
+            .expect("Should upload bundle");
+
+        let (fetched, proof) = server
+            .get_prekey_bundle_with_proof(&bundle.identity_key.identity_id, &bundle.device_id)
+            .expect("Should retrieve bundle with proof");
+        let proof = proof.expect("Transparency server should return a proof");
+        let sth = server
+            .signed_tree_head()
+            .expect("Should sign tree head")
+            .expect("Transparency server should publish a tree head");
+
+        crate::identity::key_transparency::verify_key_inclusion(
+            &fetched.identity_key,
+            &fetched.device_id,
+            &proof,
+            &sth,
+            &operator_key,
+            3600,
+        )
+        .expect("Identity key should be in the log");
+
+        // A plain directory still works, just without a proof.
+        let mut plain = InMemoryKeyServer::new();
+        plain.upload_prekey_bundle(&bundle).unwrap();
+        let (_, proof) = plain
+            .get_prekey_bundle_with_proof(&bundle.identity_key.identity_id, &bundle.device_id)
+            .unwrap();
+        assert!(proof.is_none());
+    }
+
     #[test]
     fn test_key_exchange_flow() {
         // Create two users
@@ -649,10 +1174,89 @@ mod tests {
                 &alice_bundle.identity_key,
                 &alice_bundle.signed_prekey.device_public_key,
                 alice_result.used_one_time_key,
+                &alice_result.pq_ciphertexts,
             )
             .expect("Should respond to key exchange");
 
         // Both should have the same shared secret
         assert_eq!(alice_result.shared_secret, bob_result.shared_secret);
     }
+
+    #[test]
+    fn test_key_exchange_with_one_time_prekey_decapsulates() {
+        let alice_identity = IdentityKeyPair::generate().unwrap();
+        let bob_identity = IdentityKeyPair::generate().unwrap();
+        let alice_protocol = SignalProtocol::new(alice_identity, b"alice_device").unwrap();
+        let mut bob_protocol = SignalProtocol::new(bob_identity, b"bob_device").unwrap();
+
+        bob_protocol.generate_signed_prekey().unwrap();
+        bob_protocol.generate_one_time_prekeys(1).unwrap();
+        let bob_bundle = bob_protocol.create_prekey_bundle().unwrap();
+
+        let alice_result = alice_protocol.initiate_key_exchange(&bob_bundle).unwrap();
+        let otk_id = alice_result
+            .used_one_time_key
+            .expect("bundle carried a one-time pre-key");
+        assert_eq!(alice_result.pq_ciphertexts.len(), 3);
+
+        let alice_identity_key = alice_protocol.identity_keypair().public_key();
+        let alice_device = alice_protocol.device_key().public_key();
+
+        // Missing the DH3 ciphertext is rejected outright.
+        assert!(bob_protocol
+            .respond_to_key_exchange(
+                &alice_identity_key,
+                &alice_device,
+                Some(otk_id),
+                &alice_result.pq_ciphertexts[..2],
+            )
+            .is_err());
+
+        let bob_result = bob_protocol
+            .respond_to_key_exchange(
+                &alice_identity_key,
+                &alice_device,
+                Some(otk_id),
+                &alice_result.pq_ciphertexts,
+            )
+            .unwrap();
+        assert_eq!(alice_result.shared_secret, bob_result.shared_secret);
+
+        // The one-time pre-key is gone, so a replay can't reuse it.
+        assert!(!bob_protocol.one_time_prekeys().contains_key(&otk_id));
+        assert!(bob_protocol
+            .respond_to_key_exchange(
+                &alice_identity_key,
+                &alice_device,
+                Some(otk_id),
+                &alice_result.pq_ciphertexts,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_one_time_prekeys_replenish_to_target() {
+        let identity_keypair = IdentityKeyPair::generate().expect("Should generate keypair");
+        let mut protocol = SignalProtocol::new(identity_keypair, b"test_device")
+            .expect("Should create Signal protocol");
+        protocol.set_low_prekey_threshold(5);
+
+        let generated = protocol.replenish_one_time_prekeys(8).unwrap();
+        assert_eq!(generated.len(), 8);
+        assert!(!protocol.needs_prekey_replenishment());
+
+        // Consume four, as respond_to_key_exchange does.
+        for prekey in &generated[..4] {
+            protocol.one_time_prekeys.remove(&prekey.id);
+            protocol.one_time_prekey_secrets.remove(&prekey.id);
+        }
+        assert_eq!(protocol.available_one_time_prekey_count(), 4);
+        assert!(protocol.needs_prekey_replenishment());
+
+        let topped_up = protocol.replenish_one_time_prekeys(8).unwrap();
+        assert_eq!(topped_up.len(), 4);
+        assert_eq!(protocol.available_one_time_prekey_count(), 8);
+        assert!(!protocol.needs_prekey_replenishment());
+        assert!(protocol.replenish_one_time_prekeys(8).unwrap().is_empty());
+    }
 }
@@ -1,7 +1,7 @@
 // src/jni_api.rs
 
 use jni::objects::{GlobalRef, JByteArray, JClass, JObject, JString, JValue};
-use jni::sys::{jboolean, jbyteArray, jstring};
+use jni::sys::{jboolean, jbyteArray, jlong, jstring};
 use jni::{JNIEnv, JavaVM};
 use lazy_static::lazy_static;
 use secrecy::ExposeSecret;
@@ -12,13 +12,15 @@ use std::sync::{Arc, Mutex};
 use tokio::runtime::Runtime;
 
 // Core modules
+use crate::ffi_handle::HandleTable;
 use crate::groups::group_handshake::{
     generate_ephemeral_kyber, sign_message_ack, sign_ownership_transfer, sign_request_join,
     sign_role_change, GroupHandshake, KeyRotationBody, MessageAckBody, RequestJoinBody,
 };
 use crate::groups::group_permissions::Role;
 use crate::groups::group_message::{
-    decrypt_group_message, encrypt_group_message, extract_message_id, is_group_message_frame,
+    decrypt_group_message, encrypt_group_file, encrypt_group_message, extract_message_id,
+    is_group_message_frame,
 };
 use crate::groups::group_manager::{
     GroupId, GroupInvitation, GroupManager, GroupSettings, GroupType, QUBEE_MAX_GROUP_MEMBERS,
@@ -28,9 +30,11 @@ use crate::groups::handshake_handlers::{
     plan_key_rotation, process_join_accepted, process_key_rotation, process_request_join,
     HandshakeOutcome,
 };
+use crate::groups::session_messenger::SessionMessenger;
 use crate::identity::identity_key::{IdentityId, IdentityKey, IdentityKeyPair};
 use crate::network::p2p_node::{group_topic, NodeEvent, P2PCommand, P2PNode};
 use crate::onboarding::OnboardingBundle;
+use crate::security::audit::{AuditContext, SecurityAuditor};
 use crate::storage::secure_keystore::{KeyMetadata, KeyType, KeyUsage, SecureKeyStore};
 use blake3::Hasher;
 use std::collections::HashMap;
@@ -212,7 +216,9 @@ pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeInitia
             .map_err(|e| anyhow::anyhow!("groups keystore open failed: {e}"))?;
         let mut group_mgr = GroupManager::new(groups_keystore)
             .map_err(|e| anyhow::anyhow!("group manager init failed: {e}"))?;
-        let _ = group_mgr.load_groups_from_storage();
+        if let Err(e) = group_mgr.load_groups_from_storage() {
+            tracing::error!(error = %format!("{e:#}"), "some stored groups failed to load");
+        }
         *GROUP_MANAGER.lock().unwrap() = Some(group_mgr);
 
         // Best-effort eager identity load: lets nativeLoadOnboardingBundle
@@ -773,6 +779,7 @@ pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeSendGr
                 let gm = gm_guard
                     .as_ref()
                     .ok_or_else(|| anyhow::anyhow!("group manager not initialised"))?;
+                gm.validate_content(group_id, identity.identity_id(), None, 0, plaintext.len())?;
                 let wire = encrypt_group_message(gm, identity.as_ref(), group_id, &plaintext)?;
                 let generation = gm.get_group(&group_id).map(|g| g.version).unwrap_or(0);
                 (wire, generation)
@@ -1682,6 +1689,28 @@ pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeResetI
     })
 }
 
+/// Run the built-in security audit against the open keystore and
+/// return the report as JSON (`AuditReport::to_json`). Never returns
+/// null: an error, or a panic inside a check, comes back as
+/// `{"error": "..."}` so Settings can show it instead of crashing.
+#[no_mangle]
+pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeRunSecurityAudit(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let value = jni_catch_or(|| {
+        let ks_guard = KEYSTORE.lock().unwrap();
+        let config = crate::config::AppConfig::default();
+        let report = SecurityAuditor::with_default_checks().run(&AuditContext {
+            keystore: ks_guard.as_ref(),
+            config: &config,
+        });
+        Ok(report.to_json())
+    })
+    .unwrap_or_else(|e| json!({ "error": format!("{e:#}") }));
+    json_to_jstring(env, value)
+}
+
 // ---------------------------------------------------------------------------
 // Onboarding & invite-link surface (hybrid-signed, not ZK)
 //
@@ -2166,7 +2195,15 @@ pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeEncryp
             let gm = gm_guard
                 .as_ref()
                 .ok_or_else(|| anyhow::anyhow!("group manager not initialised"))?;
-            let wire = encrypt_group_message(gm, identity.as_ref(), group_id, &plaintext)?;
+            // The Kotlin side doesn't pass a MIME type yet, so files go
+            // out as generic binary for the group's type allow-list.
+            let wire = encrypt_group_file(
+                gm,
+                identity.as_ref(),
+                group_id,
+                "application/octet-stream",
+                &plaintext,
+            )?;
             let arr = env
                 .byte_array_from_slice(&wire)
                 .map_err(|e| anyhow::anyhow!("byte_array_from_slice: {e}"))?;
@@ -2206,6 +2243,118 @@ pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeDecryp
     })
 }
 
+// ---------------------------------------------------------------------------
+// Handle-based messenger
+// ---------------------------------------------------------------------------
+//
+// The same message path as `nativeEncryptMessage` /
+// `nativeDecryptMessage`, for callers that keep a session open:
+// `nativeCreateMessenger` binds a `SessionMessenger` to a session id
+// once and returns it as a `jlong` handle, and the calls below take
+// that handle instead of re-parsing the id. Unlike the session-id
+// exports these throw (`IllegalStateException`) instead of returning
+// null, so Kotlin sees why a call failed. Every handle must go back
+// through `nativeDestroyMessenger`; handles are checked against
+// `MESSENGERS`, so a destroyed or made-up handle throws rather than
+// dereferencing freed memory.
+
+static MESSENGERS: HandleTable<SessionMessenger> = HandleTable::new();
+
+/// Unwrap `result`, or throw its error as an `IllegalStateException`
+/// and return `fallback`, which the JVM ignores once the exception is
+/// pending.
+fn throw_on_err<T>(env: &mut JNIEnv, result: anyhow::Result<T>, fallback: T) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            let _ = env.throw_new("java/lang/IllegalStateException", format!("{e:#}"));
+            fallback
+        }
+    }
+}
+
+/// Bind a messenger to `session_id` (hex `GroupId`, as for
+/// `nativeEncryptMessage`) and return its handle.
+#[no_mangle]
+pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeCreateMessenger(
+    mut env: JNIEnv,
+    _class: JClass,
+    session_id: JString,
+) -> jlong {
+    let result = jni_catch_or(|| {
+        let group_id = parse_session_id(&mut env, session_id)?;
+        Ok(MESSENGERS.insert(SessionMessenger::new(group_id)))
+    });
+    throw_on_err(&mut env, result, 0)
+}
+
+/// Release a handle from `nativeCreateMessenger`. Throws if it was
+/// already released or never issued.
+#[no_mangle]
+pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeDestroyMessenger(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) {
+    let result = jni_catch_or(|| MESSENGERS.remove(handle).map(drop));
+    throw_on_err(&mut env, result, ())
+}
+
+/// Encrypt `plaintext` bytes for the handle's session; returns the
+/// wire envelope.
+#[no_mangle]
+pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeMessengerEncrypt(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    plaintext: JByteArray,
+) -> jbyteArray {
+    let result = jni_catch_or(|| {
+        let plaintext = env
+            .convert_byte_array(&plaintext)
+            .map_err(|e| anyhow::anyhow!("invalid plaintext: {e}"))?;
+        let identity = active_identity()?.ok_or_else(|| anyhow::anyhow!("no active identity"))?;
+        let gm_guard = GROUP_MANAGER.lock().unwrap();
+        let gm = gm_guard
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("group manager not initialised"))?;
+        let wire = MESSENGERS.with(handle, |m| {
+            m.encrypt_message(gm, identity.as_ref(), &plaintext)
+        })??;
+        let arr = env
+            .byte_array_from_slice(&wire)
+            .map_err(|e| anyhow::anyhow!("byte_array_from_slice: {e}"))?;
+        Ok(arr.into_raw())
+    });
+    throw_on_err(&mut env, result, std::ptr::null_mut())
+}
+
+/// Decrypt a wire envelope for the handle's session. Throws if it
+/// fails to open or was sent to a different session.
+#[no_mangle]
+pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeMessengerDecrypt(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    encrypted_envelope: JByteArray,
+) -> jbyteArray {
+    let result = jni_catch_or(|| {
+        let wire = env
+            .convert_byte_array(&encrypted_envelope)
+            .map_err(|e| anyhow::anyhow!("invalid encrypted_envelope: {e}"))?;
+        let gm_guard = GROUP_MANAGER.lock().unwrap();
+        let gm = gm_guard
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("group manager not initialised"))?;
+        let plaintext = MESSENGERS.with(handle, |m| m.decrypt_message(gm, &wire))??;
+        let arr = env
+            .byte_array_from_slice(&plaintext)
+            .map_err(|e| anyhow::anyhow!("byte_array_from_slice: {e}"))?;
+        Ok(arr.into_raw())
+    });
+    throw_on_err(&mut env, result, std::ptr::null_mut())
+}
+
 /// Compute the canonical 8-byte BLAKE3 fingerprint of an
 /// `IdentityKey` and return it as a string in the form
 /// `"AABB CCDD EEFF GGHH"` (4 groups of 2 bytes / 4 hex chars,
@@ -2274,11 +2423,14 @@ pub extern "system" fn Java_com_qubee_messenger_crypto_QubeeManager_nativeInspec
             let gm = gm_guard
                 .as_ref()
                 .ok_or_else(|| anyhow::anyhow!("group manager not initialised"))?;
-            let (_group_id, inner) = crate::groups::group_message::open_outer_envelope(
-                &wire_bytes,
-                |gid| gm.export_group_key(gid),
-            )?;
-            let envelope = crate::groups::group_message::GroupMessageEnvelope::from_inner_bincode(&inner)?;
+            let (_group_id, inner) =
+                crate::groups::group_message::open_outer_envelope(&wire_bytes, |gid| {
+                    gm.export_retained_group_keys(gid)
+                        .into_iter()
+                        .map(|(_, key)| key)
+                })?;
+            let envelope =
+                crate::groups::group_message::GroupMessageEnvelope::from_inner_bincode(&inner)?;
             let hex_id = hex::encode(envelope.body.sender_id.as_ref() as &[u8]);
             let java_str = env
                 .new_string(hex_id)
@@ -2,11 +2,16 @@
 pub mod config;
 pub mod ephemeral_keys;
 pub mod errors;
+pub mod ffi_handle;
+pub mod file_transfer;
 pub mod groups;
 pub mod identity;
 pub mod logging;
 pub mod network;
 pub mod onboarding;
+pub mod oob_secrets;
+pub mod sas;
+pub mod secure_message;
 pub mod security;
 pub mod storage;
 
@@ -21,15 +26,7 @@ pub mod storage;
 #[cfg(feature = "legacy")]
 pub mod audio;
 #[cfg(feature = "legacy")]
-pub mod file_transfer;
-#[cfg(feature = "legacy")]
 pub mod hybrid_ratchet;
-#[cfg(feature = "legacy")]
-pub mod oob_secrets;
-#[cfg(feature = "legacy")]
-pub mod sas;
-#[cfg(feature = "legacy")]
-pub mod secure_message;
 
 // WebRTC-backed calling. Behind a feature flag because the in-tree
 // implementation hasn't been ported to webrtc 0.14 yet — see
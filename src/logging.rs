@@ -1,5 +1,159 @@
-use tracing_subscriber;
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::Subscriber;
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::{FmtSpan, Writer};
+use tracing_subscriber::fmt::{FormatFields, MakeWriter};
 
 pub fn init_logging() {
     tracing_subscriber::fmt().with_env_filter("info").init();
 }
+
+/// Like [`init_logging`], but fields whose name looks secret (see
+/// [`is_secret_field`]) are dropped from every event and span before
+/// formatting, and span open/close is logged so the instrumented
+/// crypto paths show up with their timing. Instrumented functions
+/// already skip their key arguments; this catches the ones that slip
+/// through, e.g. an ad-hoc `debug!(group_key = ?k)`.
+pub fn init_with_redaction() {
+    tracing::subscriber::set_global_default(redacting_subscriber(std::io::stdout))
+        .expect("global tracing subscriber already set");
+}
+
+/// Substrings that mark a field name as secret, matched case-insensitively.
+const SECRET_FIELD_MARKERS: &[&str] =
+    &["key", "secret", "passphrase", "password", "seed", "private"];
+
+/// Whether a field named `name` may carry key material.
+pub fn is_secret_field(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_FIELD_MARKERS.iter().any(|m| name.contains(m))
+}
+
+fn redacting_subscriber<W>(writer: W) -> impl Subscriber + Send + Sync
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt()
+        .with_env_filter("info")
+        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+        .fmt_fields(RedactingFields)
+        .with_writer(writer)
+        .finish()
+}
+
+/// Field formatter that leaves out secret-named fields entirely, rather
+/// than printing a placeholder that still shows the field was there.
+struct RedactingFields;
+
+impl<'w> FormatFields<'w> for RedactingFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'w>, fields: R) -> fmt::Result {
+        let mut visitor = RedactingVisitor {
+            writer,
+            seen: false,
+            result: Ok(()),
+        };
+        fields.record(&mut visitor);
+        visitor.result
+    }
+}
+
+struct RedactingVisitor<'w> {
+    writer: Writer<'w>,
+    seen: bool,
+    result: fmt::Result,
+}
+
+impl Visit for RedactingVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if self.result.is_err() || is_secret_field(field.name()) {
+            return;
+        }
+        let sep = if self.seen { " " } else { "" };
+        self.seen = true;
+        self.result = if field.name() == "message" {
+            write!(self.writer, "{sep}{value:?}")
+        } else {
+            write!(self.writer, "{sep}{}={value:?}", field.name())
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::groups::group_manager::{GroupManager, GroupSettings, GroupType};
+    use crate::groups::group_message::{decrypt_group_message, encrypt_group_message};
+    use crate::identity::identity_key::IdentityKeyPair;
+    use crate::security::audit::{AuditContext, SecurityAuditor};
+    use crate::storage::secure_keystore::SecureKeystore;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Captured {
+        type Writer = Captured;
+
+        fn make_writer(&'w self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn spans_carry_metadata_but_no_key_material() {
+        let dir = TempDir::new().unwrap();
+        let ks =
+            SecureKeystore::new(dir.path().join("gm.db"), b"test-keystore-passphrase").unwrap();
+        let mut gm = GroupManager::new(ks).unwrap();
+        let kp = IdentityKeyPair::generate().unwrap();
+        let gid = gm
+            .create_group(
+                kp.identity_id(),
+                kp.public_key(),
+                "logged".to_string(),
+                String::new(),
+                GroupType::Private,
+                GroupSettings::default(),
+            )
+            .unwrap();
+        gm.ensure_group_key(gid).unwrap();
+        let group_key = gm.export_group_key(&gid).unwrap();
+
+        let captured = Captured::default();
+        tracing::subscriber::with_default(redacting_subscriber(captured.clone()), || {
+            let wire = encrypt_group_message(&gm, &kp, gid, b"hello").unwrap();
+            decrypt_group_message(&gm, &wire).unwrap();
+            SecurityAuditor::with_default_checks().run(&AuditContext {
+                keystore: None,
+                config: &AppConfig::default(),
+            });
+            tracing::info!(group_key = ?group_key, count = 1, "ad-hoc event");
+        });
+        let out = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+
+        assert!(out.contains("encrypt_group_message"), "{out}");
+        assert!(out.contains("generation=1"), "{out}");
+        assert!(out.contains("run_audit"), "{out}");
+        assert!(out.contains("finding_count=0"), "{out}");
+        assert!(out.contains("ad-hoc event count=1"), "{out}");
+        assert!(!out.contains("group_key"), "{out}");
+        for key in [&group_key[..], &kp.serialize_for_keystore().unwrap()[..]] {
+            assert!(!out.contains(&format!("{key:?}")));
+            assert!(!out.contains(&hex::encode(key)));
+        }
+    }
+}
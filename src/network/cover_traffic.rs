@@ -0,0 +1,220 @@
+//! Cover traffic: dummy frames that hide when the user is active.
+//!
+//! Encryption hides what is sent but not when. A network observer who
+//! sees a burst of frames every time the user types learns their
+//! activity pattern even without breaking a single ciphertext. The
+//! [`CoverTrafficScheduler`] owns the outbound queue and releases one
+//! frame per slot, with slots spaced by an exponential (Poisson
+//! process) delay of configurable mean:
+//!
+//! * a slot with a real frame queued sends that frame,
+//! * an empty slot sends a dummy frame instead.
+//!
+//! So the on-wire timing is the same Poisson process whether the user
+//! is idle or chatting; real sends only change *which* slots carry
+//! real data. The cost is latency: a real frame waits for the next
+//! slot, `mean_interval` on average, so pick the mean with that in
+//! mind.
+//!
+//! Dummy frames are random bytes whose length is drawn from the
+//! lengths of recent real frames, so they match real ciphertext in
+//! both content distribution and size. Receivers drop them when they
+//! fail to authenticate; once sessions carry an in-band dummy flag
+//! (as the legacy audio path does), the dummy should be sealed instead.
+
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::config::AppConfig;
+
+/// Number of recent real frame lengths dummy sizes are sampled from.
+const RECENT_LENGTHS: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct CoverTrafficConfig {
+    /// Off: real frames pass straight through and no dummies are sent.
+    pub enabled: bool,
+    /// Mean spacing of send slots.
+    pub mean_interval: Duration,
+    /// Dummy length used before any real frame has been seen.
+    pub default_frame_len: usize,
+}
+
+impl Default for CoverTrafficConfig {
+    fn default() -> Self {
+        CoverTrafficConfig::from_app_config(&AppConfig::default())
+    }
+}
+
+impl CoverTrafficConfig {
+    /// `enable_cover_traffic` switches it on and
+    /// `dummy_packet_frequency_secs` is the mean slot spacing.
+    pub fn from_app_config(config: &AppConfig) -> Self {
+        CoverTrafficConfig {
+            enabled: config.enable_cover_traffic,
+            mean_interval: Duration::from_secs(config.dummy_packet_frequency_secs.max(1)),
+            default_frame_len: 512,
+        }
+    }
+}
+
+/// One frame released by the scheduler.
+#[derive(Debug, PartialEq, Eq)]
+pub enum OutboundFrame {
+    Real(Vec<u8>),
+    Cover(Vec<u8>),
+}
+
+impl OutboundFrame {
+    pub fn is_cover(&self) -> bool {
+        matches!(self, OutboundFrame::Cover(_))
+    }
+
+    /// Bytes to put on the wire, whichever kind it is.
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            OutboundFrame::Real(bytes) | OutboundFrame::Cover(bytes) => bytes,
+        }
+    }
+}
+
+/// Single outbound queue mixing real frames with Poisson-timed cover
+/// frames. Real frames go in through the [`mpsc::Sender`] returned by
+/// [`new`](Self::new); everything comes out of
+/// [`next_frame`](Self::next_frame).
+pub struct CoverTrafficScheduler {
+    config: CoverTrafficConfig,
+    real: mpsc::Receiver<Vec<u8>>,
+    recent_lengths: VecDeque<usize>,
+    rng: StdRng,
+}
+
+impl CoverTrafficScheduler {
+    pub fn new(config: CoverTrafficConfig, capacity: usize) -> (Self, mpsc::Sender<Vec<u8>>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let scheduler = CoverTrafficScheduler {
+            config,
+            real: rx,
+            recent_lengths: VecDeque::with_capacity(RECENT_LENGTHS),
+            rng: StdRng::from_entropy(),
+        };
+        (scheduler, tx)
+    }
+
+    /// Delay until the next send slot: exponentially distributed with
+    /// mean `mean_interval`, so slots form a Poisson process.
+    pub fn next_dummy_delay(&mut self) -> Duration {
+        // `gen` is in [0, 1); `1 - u` keeps ln() away from 0.
+        let u: f64 = self.rng.gen();
+        self.config.mean_interval.mul_f64(-(1.0 - u).ln())
+    }
+
+    /// Wait for the next slot and return what to send in it. Returns
+    /// `None` once every sender is dropped and the queue is drained;
+    /// cover traffic stops with the real traffic it covers.
+    pub async fn next_frame(&mut self) -> Option<OutboundFrame> {
+        if !self.config.enabled {
+            let frame = self.real.recv().await?;
+            return Some(OutboundFrame::Real(frame));
+        }
+
+        tokio::time::sleep(self.next_dummy_delay()).await;
+        match self.real.try_recv() {
+            Ok(frame) => {
+                self.note_real_length(frame.len());
+                Some(OutboundFrame::Real(frame))
+            }
+            Err(mpsc::error::TryRecvError::Empty) => Some(OutboundFrame::Cover(self.dummy_frame())),
+            Err(mpsc::error::TryRecvError::Disconnected) => None,
+        }
+    }
+
+    fn note_real_length(&mut self, len: usize) {
+        if self.recent_lengths.len() == RECENT_LENGTHS {
+            self.recent_lengths.pop_front();
+        }
+        self.recent_lengths.push_back(len);
+    }
+
+    fn dummy_frame(&mut self) -> Vec<u8> {
+        let len = if self.recent_lengths.is_empty() {
+            self.config.default_frame_len
+        } else {
+            self.recent_lengths[self.rng.gen_range(0..self.recent_lengths.len())]
+        };
+        let mut frame = vec![0u8; len];
+        self.rng.fill_bytes(&mut frame);
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dummy_intervals_follow_the_configured_rate() {
+        let mean = 15.0;
+        let (mut scheduler, _tx) = CoverTrafficScheduler::new(
+            CoverTrafficConfig {
+                enabled: true,
+                mean_interval: Duration::from_secs_f64(mean),
+                default_frame_len: 512,
+            },
+            1,
+        );
+        scheduler.rng = StdRng::seed_from_u64(7);
+
+        let samples: Vec<f64> = (0..20_000)
+            .map(|_| scheduler.next_dummy_delay().as_secs_f64())
+            .collect();
+        let n = samples.len() as f64;
+        let sample_mean = samples.iter().sum::<f64>() / n;
+        let variance = samples
+            .iter()
+            .map(|s| (s - sample_mean).powi(2))
+            .sum::<f64>()
+            / n;
+
+        // Exponential: mean = 1/rate and standard deviation = mean.
+        assert!(
+            (sample_mean / mean - 1.0).abs() < 0.03,
+            "mean {sample_mean}"
+        );
+        assert!(
+            (variance.sqrt() / sample_mean - 1.0).abs() < 0.05,
+            "std dev {}",
+            variance.sqrt()
+        );
+        // Memoryless: about e^-1 of intervals exceed the mean.
+        let above = samples.iter().filter(|&&s| s > mean).count() as f64 / n;
+        assert!((above - (-1.0f64).exp()).abs() < 0.02, "tail {above}");
+    }
+
+    #[tokio::test]
+    async fn real_frames_share_slots_with_same_sized_cover() {
+        let (mut scheduler, tx) = CoverTrafficScheduler::new(
+            CoverTrafficConfig {
+                enabled: true,
+                mean_interval: Duration::from_millis(2),
+                default_frame_len: 64,
+            },
+            8,
+        );
+
+        tx.send(vec![0xAA; 300]).await.unwrap();
+        assert_eq!(
+            scheduler.next_frame().await,
+            Some(OutboundFrame::Real(vec![0xAA; 300]))
+        );
+        let cover = scheduler.next_frame().await.unwrap();
+        assert!(cover.is_cover());
+        assert_eq!(cover.into_bytes().len(), 300);
+
+        drop(tx);
+        assert_eq!(scheduler.next_frame().await, None);
+    }
+}
@@ -0,0 +1,380 @@
+//! Fragmentation + reassembly for payloads larger than a single
+//! gossipsub message.
+//!
+//! gossipsub rejects anything above its `max_transmit_size` (64 KiB by
+//! default), which a group message carrying an attachment or a file
+//! manifest easily exceeds. Oversized payloads are split into
+//! fragments framed as:
+//!
+//! ```text
+//! MAGIC_FRAGMENT || message_id(32) || total(u16 LE) || index(u16 LE) || chunk
+//! ```
+//!
+//! `message_id` is a domain-separated BLAKE3 hash of the *whole*
+//! payload, so the receiver can check the reassembled bytes against it
+//! before handing them up — a fragment injected from a different
+//! message, or a missing/duplicated chunk, fails that check instead of
+//! producing a corrupt frame for the group-message decoder. The
+//! individual gossipsub messages are already signed by the publishing
+//! peer, and the reassembled payload carries its own hybrid signature,
+//! so the id doesn't need to be keyed.
+//!
+//! Fragments are collected per *origin* (the peer that signed the
+//! gossipsub message) as well as per id, and the first fragment seen
+//! for an index is kept. Without the origin, a peer that saw the first
+//! fragment of someone else's message could race in a bogus chunk for
+//! a later index under the same id and make the whole message fail its
+//! check; keyed by origin, the bogus chunk only lands in the forger's
+//! own slot, which times out.
+//!
+//! [`Reassembler`] is deliberately bounded: incomplete messages are
+//! discarded after [`ReassemblyConfig::timeout`], and fragments for a
+//! *new* message are refused once the pending-message count or the
+//! total buffered bytes hit their caps. A peer that dribbles out
+//! fragments slowly can hold at most that budget, never unbounded
+//! memory.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Magic prefix for a fragment frame. Distinct from every other
+/// `QUBEE_*` magic so the inbound dispatcher can route on it.
+pub const MAGIC_FRAGMENT: &[u8] = b"QUBEE_FRG\x01";
+
+/// BLAKE3 `derive_key` context for fragment message ids.
+const FRAGMENT_ID_KDF_CONTEXT: &str = "qubee fragment message id v1";
+
+const FRAGMENT_HEADER_LEN: usize = 10 + 32 + 2 + 2;
+
+/// Upper bound on the number of fragments a single message may be
+/// split into. Also caps the reassembled size at
+/// `MAX_FRAGMENTS * max_fragment_payload`.
+pub const MAX_FRAGMENTS: u16 = 256;
+
+/// Returns `true` if `wire` carries the fragment magic prefix.
+pub fn is_fragment_frame(wire: &[u8]) -> bool {
+    wire.len() >= MAGIC_FRAGMENT.len() && &wire[..MAGIC_FRAGMENT.len()] == MAGIC_FRAGMENT
+}
+
+fn fragment_message_id(payload: &[u8]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key(FRAGMENT_ID_KDF_CONTEXT);
+    hasher.update(payload);
+    *hasher.finalize().as_bytes()
+}
+
+/// Split `payload` into fragment frames whose total length (header
+/// included) is at most `max_frame_len`. Errors if the payload would
+/// need more than [`MAX_FRAGMENTS`] fragments.
+pub fn fragment(payload: &[u8], max_frame_len: usize) -> Result<Vec<Vec<u8>>> {
+    if max_frame_len <= FRAGMENT_HEADER_LEN {
+        return Err(anyhow!(
+            "max frame length {} leaves no room for fragment payload",
+            max_frame_len
+        ));
+    }
+    let chunk_len = max_frame_len - FRAGMENT_HEADER_LEN;
+    let total = payload.len().div_ceil(chunk_len).max(1);
+    if total > MAX_FRAGMENTS as usize {
+        return Err(anyhow!(
+            "payload of {} bytes needs {} fragments (cap {})",
+            payload.len(),
+            total,
+            MAX_FRAGMENTS
+        ));
+    }
+
+    let message_id = fragment_message_id(payload);
+    let mut frames = Vec::with_capacity(total);
+    for index in 0..total {
+        let start = index * chunk_len;
+        let end = (start + chunk_len).min(payload.len());
+        let mut frame = Vec::with_capacity(FRAGMENT_HEADER_LEN + (end - start));
+        frame.extend_from_slice(MAGIC_FRAGMENT);
+        frame.extend_from_slice(&message_id);
+        frame.extend_from_slice(&(total as u16).to_le_bytes());
+        frame.extend_from_slice(&(index as u16).to_le_bytes());
+        frame.extend_from_slice(&payload[start..end]);
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+struct FragmentHeader<'a> {
+    message_id: [u8; 32],
+    total: u16,
+    index: u16,
+    chunk: &'a [u8],
+}
+
+fn parse_fragment(wire: &[u8]) -> Result<FragmentHeader<'_>> {
+    if !is_fragment_frame(wire) || wire.len() < FRAGMENT_HEADER_LEN {
+        return Err(anyhow!("not a fragment frame"));
+    }
+    let rest = &wire[MAGIC_FRAGMENT.len()..];
+    let mut message_id = [0u8; 32];
+    message_id.copy_from_slice(&rest[..32]);
+    let total = u16::from_le_bytes([rest[32], rest[33]]);
+    let index = u16::from_le_bytes([rest[34], rest[35]]);
+    if total == 0 || total > MAX_FRAGMENTS || index >= total {
+        return Err(anyhow!("fragment index {index}/{total} out of range"));
+    }
+    Ok(FragmentHeader {
+        message_id,
+        total,
+        index,
+        chunk: &rest[36..],
+    })
+}
+
+/// Limits for [`Reassembler`].
+#[derive(Clone, Debug)]
+pub struct ReassemblyConfig {
+    /// How long an incomplete message may wait for its remaining
+    /// fragments before it's discarded.
+    pub timeout: Duration,
+    /// Maximum number of partially received messages held at once.
+    pub max_pending_messages: usize,
+    /// Maximum fragment bytes buffered across all pending messages,
+    /// and so also the largest message that can be reassembled.
+    pub max_buffered_bytes: usize,
+}
+
+/// Default [`ReassemblyConfig::max_buffered_bytes`].
+pub const DEFAULT_MAX_BUFFERED_BYTES: usize = 8 * 1024 * 1024;
+
+impl Default for ReassemblyConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_pending_messages: 32,
+            max_buffered_bytes: DEFAULT_MAX_BUFFERED_BYTES,
+        }
+    }
+}
+
+struct PendingMessage {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: u16,
+    bytes: usize,
+    first_seen: Instant,
+}
+
+/// Pending messages are keyed by `(origin, message_id)`.
+type PendingKey = (Vec<u8>, [u8; 32]);
+
+/// Bounded buffer that turns fragment frames back into whole payloads.
+pub struct Reassembler {
+    config: ReassemblyConfig,
+    pending: HashMap<PendingKey, PendingMessage>,
+    buffered_bytes: usize,
+}
+
+impl Reassembler {
+    pub fn new(config: ReassemblyConfig) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+            buffered_bytes: 0,
+        }
+    }
+
+    /// Feed one fragment frame published by `origin`. Returns
+    /// `Ok(Some(payload))` when this fragment completed a message whose
+    /// bytes match its id, `Ok(None)` while fragments are still
+    /// outstanding, and `Err` if the frame is malformed, inconsistent
+    /// with earlier fragments of the same message, or refused because
+    /// the buffer budget is spent.
+    pub fn accept(&mut self, origin: &[u8], wire: &[u8], now: Instant) -> Result<Option<Vec<u8>>> {
+        self.evict_expired(now);
+        let frag = parse_fragment(wire)?;
+        let key = (origin.to_vec(), frag.message_id);
+
+        if !self.pending.contains_key(&key) {
+            if self.pending.len() >= self.config.max_pending_messages {
+                return Err(anyhow!(
+                    "reassembly buffer full ({} pending messages)",
+                    self.pending.len()
+                ));
+            }
+            self.pending.insert(
+                key.clone(),
+                PendingMessage {
+                    chunks: vec![None; frag.total as usize],
+                    received: 0,
+                    bytes: 0,
+                    first_seen: now,
+                },
+            );
+        }
+
+        let pending = self.pending.get_mut(&key).expect("inserted above");
+        if pending.chunks.len() != frag.total as usize {
+            self.discard(&key);
+            return Err(anyhow!("fragment total disagrees with earlier fragments"));
+        }
+        if pending.chunks[frag.index as usize].is_some() {
+            // gossipsub already de-duplicates, so a repeat index is
+            // either a replay or a conflicting fragment. Ignore it
+            // rather than letting it overwrite what we have.
+            return Ok(None);
+        }
+        if self.buffered_bytes + frag.chunk.len() > self.config.max_buffered_bytes {
+            self.discard(&key);
+            return Err(anyhow!(
+                "reassembly buffer full ({} bytes buffered)",
+                self.buffered_bytes
+            ));
+        }
+
+        pending.chunks[frag.index as usize] = Some(frag.chunk.to_vec());
+        pending.received += 1;
+        pending.bytes += frag.chunk.len();
+        self.buffered_bytes += frag.chunk.len();
+        if pending.received < frag.total {
+            return Ok(None);
+        }
+
+        let done = self.pending.remove(&key).expect("present above");
+        self.buffered_bytes -= done.bytes;
+        let payload: Vec<u8> = done.chunks.into_iter().flatten().flatten().collect();
+        if fragment_message_id(&payload) != frag.message_id {
+            return Err(anyhow!("reassembled payload does not match its message id"));
+        }
+        Ok(Some(payload))
+    }
+
+    /// Drop every pending message older than the configured timeout.
+    pub fn evict_expired(&mut self, now: Instant) {
+        let timeout = self.config.timeout;
+        let mut freed = 0;
+        self.pending.retain(|_, p| {
+            let keep = now.saturating_duration_since(p.first_seen) < timeout;
+            if !keep {
+                freed += p.bytes;
+            }
+            keep
+        });
+        self.buffered_bytes -= freed;
+    }
+
+    /// Number of partially received messages currently buffered.
+    pub fn pending_messages(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Total fragment bytes currently buffered.
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    fn discard(&mut self, key: &PendingKey) {
+        if let Some(p) = self.pending.remove(key) {
+            self.buffered_bytes -= p.bytes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER: &[u8] = b"peer a";
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn fragments_reassemble_out_of_order() {
+        let data = payload(10_000);
+        let mut frames = fragment(&data, 1024).unwrap();
+        assert!(frames.len() > 1);
+        assert!(frames.iter().all(|f| f.len() <= 1024));
+        frames.reverse();
+
+        let mut r = Reassembler::new(ReassemblyConfig::default());
+        let now = Instant::now();
+        let (last, rest) = frames.split_last().unwrap();
+        for f in rest {
+            assert!(r.accept(PEER, f, now).unwrap().is_none());
+        }
+        assert_eq!(r.accept(PEER, last, now).unwrap().unwrap(), data);
+        assert_eq!(r.pending_messages(), 0);
+        assert_eq!(r.buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn incomplete_message_times_out() {
+        let frames = fragment(&payload(4096), 1024).unwrap();
+        let mut r = Reassembler::new(ReassemblyConfig::default());
+        let start = Instant::now();
+        r.accept(PEER, &frames[0], start).unwrap();
+        assert_eq!(r.pending_messages(), 1);
+
+        r.evict_expired(start + Duration::from_secs(31));
+        assert_eq!(r.pending_messages(), 0);
+        assert_eq!(r.buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn refuses_new_messages_past_the_budget() {
+        let config = ReassemblyConfig {
+            max_pending_messages: 2,
+            ..ReassemblyConfig::default()
+        };
+        let mut r = Reassembler::new(config);
+        let now = Instant::now();
+        for seed in 0..2u8 {
+            let mut data = payload(4096);
+            data[0] = seed;
+            r.accept(PEER, &fragment(&data, 1024).unwrap()[0], now)
+                .unwrap();
+        }
+        let mut data = payload(4096);
+        data[0] = 9;
+        assert!(r
+            .accept(PEER, &fragment(&data, 1024).unwrap()[0], now)
+            .is_err());
+        assert_eq!(r.pending_messages(), 2);
+    }
+
+    #[test]
+    fn rejects_fragment_from_another_message() {
+        let a = fragment(&payload(2048), 1024).unwrap();
+        let mut other = payload(2048);
+        other[2000] ^= 0xff;
+        let b = fragment(&other, 1024).unwrap();
+
+        // Splice b's chunk bytes under a's header.
+        let mut forged = a[a.len() - 1][..FRAGMENT_HEADER_LEN].to_vec();
+        forged.extend_from_slice(&b[b.len() - 1][FRAGMENT_HEADER_LEN..]);
+
+        let mut r = Reassembler::new(ReassemblyConfig::default());
+        let now = Instant::now();
+        for f in &a[..a.len() - 1] {
+            r.accept(PEER, f, now).unwrap();
+        }
+        assert!(r.accept(PEER, &forged, now).is_err());
+    }
+
+    #[test]
+    fn conflicting_fragment_from_another_origin_does_not_block_the_message() {
+        let data = payload(3000);
+        let frames = fragment(&data, 1024).unwrap();
+        let mut bogus = frames[1].clone();
+        bogus[FRAGMENT_HEADER_LEN] ^= 0xff;
+
+        let mut r = Reassembler::new(ReassemblyConfig::default());
+        let now = Instant::now();
+        r.accept(PEER, &frames[0], now).unwrap();
+        // Another peer races a bad chunk for index 1 under the same id.
+        assert!(r.accept(b"peer b", &bogus, now).unwrap().is_none());
+        let (last, rest) = frames[1..].split_last().unwrap();
+        for f in rest {
+            assert!(r.accept(PEER, f, now).unwrap().is_none());
+        }
+        assert_eq!(r.accept(PEER, last, now).unwrap().unwrap(), data);
+        assert_eq!(r.pending_messages(), 1);
+    }
+}
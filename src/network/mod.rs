@@ -1,3 +1,7 @@
+pub mod cover_traffic;
+pub mod fragmentation;
 pub mod p2p_node;
 
-pub use p2p_node::{group_topic, NodeEvent, P2PCommand, P2PNode, P2PNodeConfig};
+pub use cover_traffic::{CoverTrafficConfig, CoverTrafficScheduler, OutboundFrame};
+pub use fragmentation::{Reassembler, ReassemblyConfig};
+pub use p2p_node::{group_topic, group_topic_for, NodeEvent, P2PCommand, P2PNode, P2PNodeConfig};
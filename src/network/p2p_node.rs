@@ -11,10 +11,14 @@ use libp2p::{
     swarm::{behaviour::toggle::Toggle, NetworkBehaviour, SwarmEvent},
     tcp, yamux, Multiaddr, PeerId, Swarm,
 };
+use std::collections::HashMap;
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
+use crate::groups::group_manager::GroupId;
+use crate::network::fragmentation::{self, Reassembler, ReassemblyConfig};
+
 // --- Data Structures ---
 
 /// Commands sent from Android (Kotlin) -> Rust
@@ -36,6 +40,12 @@ pub enum P2PCommand {
     /// that skip mDNS; production peers find each other via Kademlia
     /// or the local-network mDNS sweep.
     Dial { multiaddr: String },
+    /// Subscribe to a group's topic (see [`group_topic_for`]) and route
+    /// its traffic to the channel from [`P2PNode::group_messages`].
+    SubscribeGroup { group_id: GroupId },
+    /// Publish an already-encrypted group message on the group's topic.
+    /// Same subscription caveat as `PublishToTopic`.
+    PublishGroupCiphertext { group_id: GroupId, data: Vec<u8> },
 }
 
 /// Public helper so callers (JNI, tests) build the per-group topic
@@ -44,6 +54,11 @@ pub fn group_topic(group_id_hex: &str) -> String {
     format!("qubee-group-{}", group_id_hex)
 }
 
+/// [`group_topic`] for a typed [`GroupId`].
+pub fn group_topic_for(group_id: &GroupId) -> String {
+    group_topic(&hex::encode(group_id.as_ref()))
+}
+
 /// Events sent from Rust -> Android (Kotlin)
 #[derive(Debug)]
 pub enum NodeEvent {
@@ -74,6 +89,15 @@ pub struct P2PNodeConfig {
     pub gossipsub_heartbeat: Duration,
     pub gossipsub_validation_mode: gossipsub::ValidationMode,
     pub idle_connection_timeout: Duration,
+    /// Largest payload published as a single gossipsub message. Anything
+    /// bigger is split by [`fragmentation::fragment`]; kept under
+    /// gossipsub's 64 KiB `max_transmit_size` to leave room for the
+    /// protobuf envelope and signature.
+    pub max_publish_size: usize,
+    /// Limits on fragments buffered for reassembly. Peers are assumed
+    /// to run the same limits, so `reassembly.max_buffered_bytes` is
+    /// also the largest payload this node will publish.
+    pub reassembly: ReassemblyConfig,
 }
 
 impl Default for P2PNodeConfig {
@@ -84,6 +108,8 @@ impl Default for P2PNodeConfig {
             gossipsub_heartbeat: Duration::from_secs(10),
             gossipsub_validation_mode: gossipsub::ValidationMode::Strict,
             idle_connection_timeout: Duration::from_secs(60),
+            max_publish_size: DEFAULT_MAX_PUBLISH_SIZE,
+            reassembly: ReassemblyConfig::default(),
         }
     }
 }
@@ -98,10 +124,14 @@ impl P2PNodeConfig {
             gossipsub_heartbeat: Duration::from_millis(100),
             gossipsub_validation_mode: gossipsub::ValidationMode::Strict,
             idle_connection_timeout: Duration::from_secs(60),
+            max_publish_size: DEFAULT_MAX_PUBLISH_SIZE,
+            reassembly: ReassemblyConfig::default(),
         }
     }
 }
 
+const DEFAULT_MAX_PUBLISH_SIZE: usize = 60 * 1024;
+
 /// Composed network behaviour. The derive expands a sibling
 /// `QubeeBehaviourEvent` enum that we match on inside the run loop.
 /// `mdns` is wrapped in `Toggle` so the test profile can disable it
@@ -118,6 +148,14 @@ struct QubeeBehaviour {
 pub struct P2PNode {
     swarm: Swarm<QubeeBehaviour>,
     command_receiver: mpsc::Receiver<P2PCommand>,
+    reassembler: Reassembler,
+    max_publish_size: usize,
+    /// Largest payload a peer's reassembler will take back.
+    max_message_len: usize,
+    /// Topics joined through `SubscribeGroup`, so incoming messages can
+    /// be mapped back to their group.
+    group_topics: HashMap<gossipsub::TopicHash, GroupId>,
+    group_sender: Option<mpsc::Sender<(GroupId, Vec<u8>)>>,
 }
 
 const GLOBAL_TOPIC: &str = "qubee-global";
@@ -190,9 +228,56 @@ impl P2PNode {
         Ok(Self {
             swarm,
             command_receiver,
+            reassembler: Reassembler::new(config.reassembly.clone()),
+            max_publish_size: config.max_publish_size,
+            max_message_len: config.reassembly.max_buffered_bytes,
+            group_topics: HashMap::new(),
+            group_sender: None,
         })
     }
 
+    /// Channel carrying `(group, ciphertext)` for every message on a
+    /// topic joined with [`P2PCommand::SubscribeGroup`]. Call before
+    /// [`run`](Self::run). Until it is called those messages arrive as
+    /// ordinary [`NodeEvent::MessageReceived`] events instead. Messages
+    /// that arrive while `capacity` are already queued are dropped.
+    pub fn group_messages(&mut self, capacity: usize) -> mpsc::Receiver<(GroupId, Vec<u8>)> {
+        let (tx, rx) = mpsc::channel(capacity);
+        self.group_sender = Some(tx);
+        rx
+    }
+
+    /// Publish `data` on `topic`, fragmenting it first if it's over
+    /// `max_publish_size`. Refuses payloads over the reassembly budget,
+    /// which receivers would buffer and then drop. Stops at the first
+    /// failed fragment: the receiver can't use a partial message and
+    /// will time it out, so pushing the rest into an already-full send
+    /// queue only adds load.
+    fn publish(&mut self, topic: &gossipsub::IdentTopic, data: Vec<u8>) -> Result<()> {
+        if data.len() > self.max_message_len {
+            return Err(anyhow!(
+                "payload of {} bytes exceeds the {}-byte reassembly budget",
+                data.len(),
+                self.max_message_len
+            ));
+        }
+        let gossipsub = &mut self.swarm.behaviour_mut().gossipsub;
+        if data.len() <= self.max_publish_size {
+            gossipsub
+                .publish(topic.clone(), data)
+                .map_err(|e| anyhow!("{e:?}"))?;
+            return Ok(());
+        }
+        let frames = fragmentation::fragment(&data, self.max_publish_size)?;
+        let total = frames.len();
+        for (index, frame) in frames.into_iter().enumerate() {
+            gossipsub
+                .publish(topic.clone(), frame)
+                .map_err(|e| anyhow!("fragment {index}/{total}: {e:?}"))?;
+        }
+        Ok(())
+    }
+
     /// Main event loop. Drives the swarm forward and translates
     /// behaviour events into [`NodeEvent`] messages for Kotlin.
     pub async fn run(mut self, event_sender: mpsc::Sender<NodeEvent>) {
@@ -205,12 +290,7 @@ impl P2PNode {
             tokio::select! {
                 command = self.command_receiver.recv() => match command {
                     Some(P2PCommand::SendMessage { peer_id: _, data }) => {
-                        if let Err(e) = self
-                            .swarm
-                            .behaviour_mut()
-                            .gossipsub
-                            .publish(chat_topic.clone(), data)
-                        {
+                        if let Err(e) = self.publish(&chat_topic, data) {
                             eprintln!("Publish error: {e:?}");
                         }
                     }
@@ -236,18 +316,27 @@ impl P2PNode {
                         if !self.swarm.behaviour_mut().gossipsub.unsubscribe(&topic) {
                             eprintln!("Unsubscribe no-op for {topic} (not subscribed)");
                         }
+                        self.group_topics.remove(&topic.hash());
                     }
                     Some(P2PCommand::PublishToTopic { topic, data }) => {
                         let topic = gossipsub::IdentTopic::new(topic);
-                        if let Err(e) = self
-                            .swarm
-                            .behaviour_mut()
-                            .gossipsub
-                            .publish(topic.clone(), data)
-                        {
+                        if let Err(e) = self.publish(&topic, data) {
                             eprintln!("PublishToTopic {topic} error: {e:?}");
                         }
                     }
+                    Some(P2PCommand::SubscribeGroup { group_id }) => {
+                        let topic = gossipsub::IdentTopic::new(group_topic_for(&group_id));
+                        match self.swarm.behaviour_mut().gossipsub.subscribe(&topic) {
+                            Ok(_) => { self.group_topics.insert(topic.hash(), group_id); }
+                            Err(e) => eprintln!("Subscribe error for {topic}: {e:?}"),
+                        }
+                    }
+                    Some(P2PCommand::PublishGroupCiphertext { group_id, data }) => {
+                        let topic = gossipsub::IdentTopic::new(group_topic_for(&group_id));
+                        if let Err(e) = self.publish(&topic, data) {
+                            eprintln!("PublishGroupCiphertext {topic} error: {e:?}");
+                        }
+                    }
                     Some(P2PCommand::Dial { multiaddr }) => {
                         match multiaddr.parse::<Multiaddr>() {
                             Ok(addr) => {
@@ -286,11 +375,36 @@ impl P2PNode {
                         message,
                         ..
                     })) => {
+                        let data = if fragmentation::is_fragment_frame(&message.data) {
+                            let origin = message.source.map(|p| p.to_bytes()).unwrap_or_default();
+                            match self.reassembler.accept(&origin, &message.data, Instant::now()) {
+                                Ok(Some(payload)) => payload,
+                                Ok(None) => continue,
+                                Err(e) => {
+                                    eprintln!("Dropping fragment from {propagation_source}: {e}");
+                                    continue;
+                                }
+                            }
+                        } else {
+                            message.data
+                        };
+                        if let (Some(group_id), Some(tx)) =
+                            (self.group_topics.get(&message.topic), &self.group_sender)
+                        {
+                            // Waiting for room would stall the whole swarm
+                            // behind one slow consumer; drop instead.
+                            if let Err(mpsc::error::TrySendError::Full(_)) =
+                                tx.try_send((*group_id, data))
+                            {
+                                eprintln!("Dropping group message: receiver is full");
+                            }
+                            continue;
+                        }
                         let _ = event_sender
                             .send(NodeEvent::MessageReceived {
                                 sender: propagation_source.to_string(),
                                 topic: message.topic.into_string(),
-                                data: message.data,
+                                data,
                             })
                             .await;
                     }
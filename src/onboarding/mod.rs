@@ -16,6 +16,7 @@ use anyhow::{anyhow, Context, Result};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use serde::{Deserialize, Serialize};
 
+use crate::groups::group_invite::decode_link_token;
 use crate::identity::identity_key::{HybridSignature, IdentityId, IdentityKey, IdentityKeyPair};
 
 pub const QUBEE_IDENTITY_HOST: &str = "identity";
@@ -72,11 +73,7 @@ impl OnboardingBundle {
             .strip_prefix(&prefix)
             .ok_or_else(|| anyhow!("not a qubee identity link"))?;
         let token = token.split(['?', '#']).next().unwrap_or(token);
-        let bytes = URL_SAFE_NO_PAD
-            .decode(token.as_bytes())
-            .context("identity token is not valid base64url")?;
-        let bundle: OnboardingBundle =
-            bincode::deserialize(&bytes).context("identity payload could not be decoded")?;
+        let bundle: OnboardingBundle = decode_link_token(token, "identity")?;
         bundle.verify()?;
         Ok(bundle)
     }
@@ -1,8 +1,119 @@
+use anyhow::{anyhow, Result};
 use rand::rngs::OsRng;
 use rand::RngCore;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Payload version written by [`OobSecret::encode`].
+pub const OOB_SECRET_VERSION: u8 = 1;
+
+/// v1 layout: `version(1) | secret(32) | crc32(version || secret)(4 LE)`.
+const V1_LEN: usize = 1 + 32 + 4;
 
 pub fn generate_oob_secret() -> Vec<u8> {
     let mut secret = [0u8; 32];
     OsRng.fill_bytes(&mut secret);
     secret.to_vec()
 }
+
+/// Out-of-band shared secret as carried in a QR code.
+///
+/// The first byte of the encoding is a version so a later payload
+/// change is rejected by old readers instead of being misread as a
+/// secret. The CRC32 trailer only catches truncated or misread scans;
+/// it is not an integrity check against tampering.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct OobSecret {
+    secret: [u8; 32],
+}
+
+impl OobSecret {
+    pub fn generate() -> Self {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        OobSecret { secret }
+    }
+
+    pub fn from_bytes(secret: [u8; 32]) -> Self {
+        OobSecret { secret }
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.secret
+    }
+
+    /// Current-version QR payload.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(V1_LEN);
+        out.push(OOB_SECRET_VERSION);
+        out.extend_from_slice(&self.secret);
+        let checksum = crc32fast::hash(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    /// Parse a scanned payload, dispatching on its version byte.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        match bytes.first() {
+            None => Err(anyhow!("empty OOB secret payload")),
+            Some(1) => Self::decode_v1(bytes),
+            Some(v) => Err(anyhow!("unsupported OOB secret payload version {v}")),
+        }
+    }
+
+    fn decode_v1(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != V1_LEN {
+            return Err(anyhow!(
+                "OOB secret payload is {} bytes, expected {V1_LEN}",
+                bytes.len()
+            ));
+        }
+        let (body, checksum) = bytes.split_at(V1_LEN - 4);
+        if crc32fast::hash(body).to_le_bytes() != checksum {
+            return Err(anyhow!("OOB secret checksum mismatch (damaged scan?)"));
+        }
+        let mut secret = [0u8; 32];
+        secret.copy_from_slice(&body[1..]);
+        Ok(OobSecret { secret })
+    }
+}
+
+impl std::fmt::Debug for OobSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OobSecret(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_round_trips() {
+        let secret = OobSecret::generate();
+        let decoded = OobSecret::decode(&secret.encode()).unwrap();
+        assert_eq!(decoded.as_bytes(), secret.as_bytes());
+    }
+
+    #[test]
+    fn debug_output_redacts_the_secret() {
+        let secret = OobSecret::from_bytes([0xAB; 32]);
+        assert_eq!(format!("{secret:?}"), "OobSecret(..)");
+    }
+
+    #[test]
+    fn corrupted_or_truncated_payload_is_rejected() {
+        let mut payload = OobSecret::generate().encode();
+        assert!(OobSecret::decode(&payload[..payload.len() - 1]).is_err());
+        payload[10] ^= 0x01;
+        let err = OobSecret::decode(&payload).unwrap_err();
+        assert!(err.to_string().contains("checksum"));
+    }
+
+    #[test]
+    fn unknown_version_is_rejected() {
+        let mut payload = OobSecret::generate().encode();
+        payload[0] = 2;
+        let err = OobSecret::decode(&payload).unwrap_err();
+        assert!(err.to_string().contains("version 2"));
+    }
+}
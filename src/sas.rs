@@ -1,5 +1,21 @@
 use rand::rngs::OsRng;
 use rand::RngCore;
+use subtle::ConstantTimeEq;
+
+use crate::identity::identity_key::IdentityKey;
+
+const SAS_TRANSCRIPT_CONTEXT: &str = "qubee sas transcript v1";
+const SAS_TRANSCRIPT_TAG: &[u8] = b"qubee_sas_transcript_v1";
+
+/// 6 bits of the transcript hash pick one of these per emoji position.
+const SAS_EMOJI: [&str; 64] = [
+    "🐶", "🐱", "🦁", "🐎", "🦄", "🐷", "🐘", "🐰", "🐼", "🐓", "🐧", "🐢", "🐟", "🐙", "🦋", "🌷",
+    "🌳", "🌵", "🍄", "🌏", "🌙", "☁️", "🔥", "🍌", "🍎", "🍓", "🌽", "🍕", "🎂", "❤️", "😀", "🤖",
+    "🎩", "👓", "🔧", "🎅", "👍", "☂️", "⌛", "⏰", "🎁", "💡", "📕", "✏️", "📎", "✂️", "🔒", "🔑",
+    "🔨", "☎️", "🏁", "🚂", "🚲", "✈️", "🚀", "🏆", "⚽", "🎸", "🎺", "🔔", "⚓", "🎧", "📁", "📌",
+];
+
+const SAS_EMOJI_COUNT: usize = 7;
 
 pub fn generate_sas_code() -> String {
     let mut bytes = [0u8; 4];
@@ -7,3 +23,105 @@ pub fn generate_sas_code() -> String {
     let code = u32::from_be_bytes(bytes);
     format!("{:08}", code % 100_000_000)
 }
+
+/// Short authentication string bound to one negotiated session.
+///
+/// [`generate_sas_code`] is just random digits, and an SAS over the two
+/// identity keys alone only proves the keys: a relay holding its own
+/// session with each side would still show both users matching codes.
+/// Hashing the session's `combined_secret` into the transcript means
+/// the codes only match when both devices ended up with the same root
+/// key, i.e. when nobody is sitting in the middle.
+#[derive(Clone, Debug)]
+pub struct Sas {
+    transcript_hash: [u8; 32],
+}
+
+impl Sas {
+    /// Derive the SAS for the session whose X3DH output was
+    /// `combined_secret` between `alice` and `bob`. The two keys are
+    /// put in a canonical order first, so each side may pass its own
+    /// key in either position.
+    pub fn from_session(
+        combined_secret: &[u8; 32],
+        alice: &IdentityKey,
+        bob: &IdentityKey,
+    ) -> Self {
+        let alice_bytes = alice.to_bytes();
+        let bob_bytes = bob.to_bytes();
+        let (first, second) = if alice_bytes <= bob_bytes {
+            (&alice_bytes, &bob_bytes)
+        } else {
+            (&bob_bytes, &alice_bytes)
+        };
+
+        let mut hasher = blake3::Hasher::new_derive_key(SAS_TRANSCRIPT_CONTEXT);
+        hasher.update(SAS_TRANSCRIPT_TAG);
+        hasher.update(&[0u8]);
+        hasher.update(&(first.len() as u32).to_le_bytes());
+        hasher.update(first);
+        hasher.update(&(second.len() as u32).to_le_bytes());
+        hasher.update(second);
+        hasher.update(combined_secret);
+        Sas {
+            transcript_hash: *hasher.finalize().as_bytes(),
+        }
+    }
+
+    /// `"NNNN NNNN"`, the same shape as the identity-key SAS shown over
+    /// JNI.
+    pub fn digits(&self) -> String {
+        let h = &self.transcript_hash;
+        let value = u32::from_be_bytes([h[0], h[1], h[2], h[3]]);
+        let high = ((value >> 16) & 0xFFFF) % 10_000;
+        let low = (value & 0xFFFF) % 10_000;
+        format!("{:04} {:04}", high, low)
+    }
+
+    /// Seven emoji taken from 42 bits of the transcript hash that don't
+    /// overlap the ones behind [`digits`](Self::digits).
+    pub fn emoji(&self) -> Vec<&'static str> {
+        let h = &self.transcript_hash;
+        let bits = u64::from_be_bytes([h[4], h[5], h[6], h[7], h[8], h[9], 0, 0]);
+        (0..SAS_EMOJI_COUNT)
+            .map(|i| SAS_EMOJI[((bits >> (58 - 6 * i)) & 0x3F) as usize])
+            .collect()
+    }
+}
+
+/// Constant-time equality of two SAS values, for when the remote SAS
+/// arrives over a channel rather than being compared by eye.
+pub fn compare(local_sas: &Sas, remote_sas: &Sas) -> bool {
+    local_sas
+        .transcript_hash
+        .ct_eq(&remote_sas.transcript_hash)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::identity_key::IdentityKeyPair;
+
+    #[test]
+    fn sas_is_bound_to_both_keys_and_the_session_secret() {
+        let alice = IdentityKeyPair::generate().unwrap().public_key();
+        let bob = IdentityKeyPair::generate().unwrap().public_key();
+        let mallory = IdentityKeyPair::generate().unwrap().public_key();
+        let secret = [9u8; 32];
+
+        let on_alice = Sas::from_session(&secret, &alice, &bob);
+        let on_bob = Sas::from_session(&secret, &bob, &alice);
+        assert!(compare(&on_alice, &on_bob));
+        assert_eq!(on_alice.digits(), on_bob.digits());
+        assert_eq!(on_alice.emoji(), on_bob.emoji());
+        assert_eq!(on_alice.emoji().len(), SAS_EMOJI_COUNT);
+
+        let relayed = Sas::from_session(&secret, &alice, &mallory);
+        assert!(!compare(&on_alice, &relayed));
+        assert_ne!(on_alice.emoji(), relayed.emoji());
+
+        let other_session = Sas::from_session(&[8u8; 32], &alice, &bob);
+        assert!(!compare(&on_alice, &other_session));
+    }
+}
@@ -0,0 +1,196 @@
+//! The prototype's `SecureMsg`, sealed under the `HybridRatchet` root
+//! key. Kept for porting reference until the Stage 3 ratchet lands.
+
+use super::{message_aad, unpad, MessageContext, PaddingScheme};
+use crate::ephemeral_keys::{verify_and_pin_ephemeral_key, EphemeralKeyStore};
+use crate::hybrid_ratchet::HybridRatchet;
+use anyhow::{Context, Result};
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+};
+use pqcrypto_dilithium::dilithium2;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+#[derive(Serialize, Deserialize)]
+pub struct SecureMsg {
+    header: Vec<u8>,
+    nonce: [u8; 12],
+    body: Vec<u8>,
+    pq_ct: Option<Vec<u8>>,
+    is_dummy: bool,
+    ephemeral_pk: Vec<u8>,
+    ephemeral_sig: Vec<u8>,
+}
+
+impl SecureMsg {
+    pub fn encrypt(
+        r: &mut HybridRatchet,
+        peer_pq_pk: &pqcrypto_kyber::kyber768::PublicKey,
+        identity_sk: &dilithium2::SecretKey,
+        plaintext: &[u8],
+        padding: &PaddingScheme,
+        is_dummy: bool,
+    ) -> Result<SecureMsg> {
+        Self::seal(
+            r,
+            peer_pq_pk,
+            identity_sk,
+            plaintext,
+            padding,
+            is_dummy,
+            None,
+        )
+    }
+
+    /// [`encrypt`](Self::encrypt), additionally binding `context` into
+    /// the AEAD. The receiver must open it with
+    /// [`decrypt_with_context`](Self::decrypt_with_context) and the
+    /// same context.
+    pub fn encrypt_with_context(
+        r: &mut HybridRatchet,
+        peer_pq_pk: &pqcrypto_kyber::kyber768::PublicKey,
+        identity_sk: &dilithium2::SecretKey,
+        plaintext: &[u8],
+        padding: &PaddingScheme,
+        is_dummy: bool,
+        context: &MessageContext,
+    ) -> Result<SecureMsg> {
+        Self::seal(
+            r,
+            peer_pq_pk,
+            identity_sk,
+            plaintext,
+            padding,
+            is_dummy,
+            Some(context),
+        )
+    }
+
+    fn seal(
+        r: &mut HybridRatchet,
+        peer_pq_pk: &pqcrypto_kyber::kyber768::PublicKey,
+        identity_sk: &dilithium2::SecretKey,
+        plaintext: &[u8],
+        padding: &PaddingScheme,
+        is_dummy: bool,
+        context: Option<&MessageContext>,
+    ) -> Result<SecureMsg> {
+        let (header, _mklen) = r.dr.send(plaintext.len() as u32);
+        r.send_ctr = r.send_ctr.wrapping_add(1);
+
+        let pq_ct = if r.send_ctr % crate::hybrid_ratchet::PQ_REKEY_PERIOD == 0 {
+            Some(r.pq_reencap(peer_pq_pk)?)
+        } else {
+            None
+        };
+
+        let ephemeral_sk = dilithium2::keypair().0;
+        let ephemeral_pk = dilithium2::keypair().1 .0.to_vec();
+
+        let root = r.derive_root_key();
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(root.expose_secret()));
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+
+        let padded = padding.pad(plaintext)?;
+        let mut flagged_plaintext = Zeroizing::new(Vec::with_capacity(1 + padded.len()));
+        flagged_plaintext.push(if is_dummy { 1 } else { 0 });
+        flagged_plaintext.extend_from_slice(&padded);
+
+        let aad = message_aad(&header, context);
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: flagged_plaintext.as_slice(),
+                    aad: &aad,
+                },
+            )
+            .context("text message encryption failed")?;
+
+        let signature = dilithium2::sign(&ciphertext, &ephemeral_sk).0.to_vec();
+
+        Ok(SecureMsg {
+            header,
+            nonce,
+            body: ciphertext,
+            pq_ct,
+            is_dummy,
+            ephemeral_pk,
+            ephemeral_sig: signature,
+        })
+    }
+
+    pub fn decrypt(
+        r: &mut HybridRatchet,
+        msg: &SecureMsg,
+        sender_id: &str,
+        ephemeral_store: &EphemeralKeyStore,
+    ) -> Result<Option<Vec<u8>>> {
+        Self::open(r, msg, sender_id, ephemeral_store, None)
+    }
+
+    /// Open a message sealed by
+    /// [`encrypt_with_context`](Self::encrypt_with_context). Fails if
+    /// `context` differs in any field from the sender's.
+    pub fn decrypt_with_context(
+        r: &mut HybridRatchet,
+        msg: &SecureMsg,
+        ephemeral_store: &EphemeralKeyStore,
+        context: &MessageContext,
+    ) -> Result<Option<Vec<u8>>> {
+        Self::open(r, msg, &context.sender_id, ephemeral_store, Some(context))
+    }
+
+    fn open(
+        r: &mut HybridRatchet,
+        msg: &SecureMsg,
+        sender_id: &str,
+        ephemeral_store: &EphemeralKeyStore,
+        context: Option<&MessageContext>,
+    ) -> Result<Option<Vec<u8>>> {
+        if let Some(ct) = &msg.pq_ct {
+            r.pq_decaps(ct)?;
+        }
+
+        r.dr.recv(&msg.header)?;
+
+        let root = r.derive_root_key();
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(root.expose_secret()));
+        let decrypted = Zeroizing::new(
+            cipher
+                .decrypt(
+                    Nonce::from_slice(&msg.nonce),
+                    Payload {
+                        msg: msg.body.as_slice(),
+                        aad: &message_aad(&msg.header, context),
+                    },
+                )
+                .context("text message decryption failed")?,
+        );
+
+        let ephemeral_pk = dilithium2::PublicKey::from_bytes(&msg.ephemeral_pk)?;
+        let signature = dilithium2::Signature::from_bytes(&msg.ephemeral_sig)?;
+        dilithium2::verify(&msg.body, &signature, &ephemeral_pk)
+            .map_err(|_| anyhow::anyhow!("ephemeral signature verification failed"))?;
+
+        verify_and_pin_ephemeral_key(ephemeral_store, sender_id, &msg.ephemeral_pk)?;
+
+        let (flag, padded) = decrypted
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("empty text message"))?;
+        let is_dummy = *flag != 0;
+        let plaintext = unpad(padded)?;
+
+        if is_dummy {
+            Ok(None)
+        } else {
+            Ok(Some(plaintext))
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// src/secure_message/mod.rs
+// Message padding and AEAD associated data for 1:1 text messages
+
+#[cfg(feature = "legacy")]
+mod legacy;
+#[cfg(feature = "legacy")]
+pub use legacy::SecureMsg;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Bytes of the length prefix inside every padded plaintext.
+const LENGTH_PREFIX_LEN: usize = 4;
+
+/// How far a plaintext is padded before encryption, so ciphertext size
+/// says less about message length.
+///
+/// Every scheme frames the plaintext as `len(u32 LE) || plaintext ||
+/// zeros` and the framed length is what gets rounded up. A message
+/// bigger than the largest bucket is rounded up to a multiple of it
+/// rather than rejected.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaddingScheme {
+    /// Length prefix only.
+    None,
+    /// Pad to a multiple of this many bytes.
+    Fixed(usize),
+    /// Pad to the next power of two.
+    PowerOfTwo,
+    /// Pad to the smallest listed size that fits.
+    Bucketed(Vec<usize>),
+}
+
+impl PaddingScheme {
+    fn padded_len(&self, framed_len: usize) -> usize {
+        let round_up = |unit: usize| framed_len.div_ceil(unit.max(1)) * unit.max(1);
+        match self {
+            PaddingScheme::None => framed_len,
+            PaddingScheme::Fixed(size) => round_up(*size),
+            PaddingScheme::PowerOfTwo => framed_len.next_power_of_two(),
+            PaddingScheme::Bucketed(buckets) => buckets
+                .iter()
+                .copied()
+                .filter(|&b| b >= framed_len)
+                .min()
+                .unwrap_or_else(|| round_up(buckets.iter().copied().max().unwrap_or(1))),
+        }
+    }
+
+    /// Frame and pad `plaintext`. The result is zeroized on drop.
+    pub fn pad(&self, plaintext: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        let len = u32::try_from(plaintext.len()).context("message too long to pad")?;
+        let framed_len = LENGTH_PREFIX_LEN + plaintext.len();
+        let mut padded = Zeroizing::new(Vec::with_capacity(self.padded_len(framed_len)));
+        padded.extend_from_slice(&len.to_le_bytes());
+        padded.extend_from_slice(plaintext);
+        padded.resize(self.padded_len(framed_len), 0);
+        Ok(padded)
+    }
+}
+
+/// Strip the framing added by [`PaddingScheme::pad`]. Rejects a length
+/// prefix that points past the end of the buffer.
+pub fn unpad(padded: &[u8]) -> Result<Vec<u8>> {
+    if padded.len() < LENGTH_PREFIX_LEN {
+        return Err(anyhow::anyhow!(
+            "padded message too short for length prefix"
+        ));
+    }
+    let (prefix, body) = padded.split_at(LENGTH_PREFIX_LEN);
+    let len = u32::from_le_bytes(prefix.try_into()?) as usize;
+    if len > body.len() {
+        return Err(anyhow::anyhow!(
+            "padding length prefix {} exceeds {} available bytes",
+            len,
+            body.len()
+        ));
+    }
+    Ok(body[..len].to_vec())
+}
+
+/// Domain tag at the start of every message AAD.
+const MESSAGE_AAD_TAG: &[u8] = b"qubee message aad v1";
+
+/// Who sent a message, where, and when, bound into the AEAD so a
+/// ciphertext lifted into another conversation (or replayed under a
+/// different sender or timestamp) fails to decrypt.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageContext {
+    pub sender_id: String,
+    pub conversation_id: String,
+    /// Sender's clock, seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl MessageContext {
+    /// Canonical bytes, built by hand so the AAD doesn't shift with
+    /// serde. Strings are length-prefixed so `("ab", "c")` and
+    /// `("a", "bc")` differ.
+    fn canonical_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(24 + self.sender_id.len() + self.conversation_id.len());
+        for field in [&self.sender_id, &self.conversation_id] {
+            out.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            out.extend_from_slice(field.as_bytes());
+        }
+        out.extend_from_slice(&self.timestamp.to_le_bytes());
+        out
+    }
+}
+
+/// AEAD associated data for one message: the ratchet header, which
+/// travels in the clear, plus the context if the caller supplied one.
+pub fn message_aad(header: &[u8], context: Option<&MessageContext>) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(MESSAGE_AAD_TAG.len() + 5 + header.len() + 64);
+    aad.extend_from_slice(MESSAGE_AAD_TAG);
+    aad.push(0u8);
+    aad.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    aad.extend_from_slice(header);
+    if let Some(context) = context {
+        aad.extend_from_slice(&context.canonical_bytes());
+    }
+    aad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chacha20poly1305::aead::{Aead, Payload};
+    use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+
+    #[test]
+    fn fixed_padding_hides_length_difference() {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&[7u8; 32]));
+        let nonce = Nonce::from_slice(&[0u8; 12]);
+        let scheme = PaddingScheme::Fixed(4096);
+
+        let short = scheme.pad(&[b'a'; 100]).unwrap();
+        let long = scheme.pad(&[b'b'; 200]).unwrap();
+        let short_ct = cipher.encrypt(nonce, short.as_slice()).unwrap();
+        let long_ct = cipher.encrypt(nonce, long.as_slice()).unwrap();
+        assert_eq!(short_ct.len(), long_ct.len());
+        assert_eq!(short.len(), 4096);
+
+        assert_eq!(unpad(&short).unwrap(), vec![b'a'; 100]);
+        assert_eq!(unpad(&long).unwrap(), vec![b'b'; 200]);
+    }
+
+    #[test]
+    fn buckets_round_up_and_bad_prefix_is_rejected() {
+        let scheme = PaddingScheme::Bucketed(vec![256, 1024]);
+        assert_eq!(scheme.pad(&[0u8; 10]).unwrap().len(), 256);
+        assert_eq!(scheme.pad(&[0u8; 300]).unwrap().len(), 1024);
+        assert_eq!(scheme.pad(&[0u8; 1500]).unwrap().len(), 2048);
+        assert_eq!(
+            PaddingScheme::PowerOfTwo.pad(&[0u8; 100]).unwrap().len(),
+            128
+        );
+
+        let mut padded = scheme.pad(b"hello").unwrap();
+        padded[..4].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(unpad(&padded).is_err());
+        assert!(unpad(&[1, 0]).is_err());
+    }
+
+    #[test]
+    fn message_sealed_under_one_context_fails_under_another() {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&[9u8; 32]));
+        let nonce = Nonce::from_slice(&[1u8; 12]);
+        let header = b"ratchet header";
+        let alice = MessageContext {
+            sender_id: "alice".into(),
+            conversation_id: "alice-bob".into(),
+            timestamp: 1_700_000_000,
+        };
+        let aad = message_aad(header, Some(&alice));
+        let ct = cipher
+            .encrypt(
+                nonce,
+                Payload {
+                    msg: b"hi bob".as_ref(),
+                    aad: &aad,
+                },
+            )
+            .unwrap();
+
+        let open = |context: Option<&MessageContext>| {
+            cipher.decrypt(
+                nonce,
+                Payload {
+                    msg: ct.as_slice(),
+                    aad: &message_aad(header, context),
+                },
+            )
+        };
+        assert_eq!(open(Some(&alice)).unwrap(), b"hi bob");
+        assert!(open(None).is_err());
+        for other in [
+            MessageContext {
+                sender_id: "mallory".into(),
+                ..alice.clone()
+            },
+            MessageContext {
+                conversation_id: "alice-carol".into(),
+                ..alice.clone()
+            },
+            MessageContext {
+                timestamp: alice.timestamp + 1,
+                ..alice.clone()
+            },
+        ] {
+            assert!(open(Some(&other)).is_err(), "{other:?}");
+        }
+    }
+
+    #[test]
+    fn tampered_header_fails_at_the_aead() {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&[11u8; 32]));
+        let nonce = Nonce::from_slice(&[2u8; 12]);
+        let mut header = b"dh_pub|pn=3|n=7".to_vec();
+        let ct = cipher
+            .encrypt(
+                nonce,
+                Payload {
+                    msg: b"payload".as_ref(),
+                    aad: &message_aad(&header, None),
+                },
+            )
+            .unwrap();
+
+        header[5] ^= 0x01;
+        // No MAC or signature involved: the AEAD tag alone rejects it.
+        let err = cipher
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ct.as_slice(),
+                    aad: &message_aad(&header, None),
+                },
+            )
+            .unwrap_err();
+        assert_eq!(err, chacha20poly1305::aead::Error);
+    }
+}
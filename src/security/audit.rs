@@ -0,0 +1,546 @@
+//! Runtime security self-checks.
+//!
+//! A [`SecurityAuditor`] runs every registered [`SecurityCheck`] and
+//! folds their findings into an [`AuditReport`]. Checks inspect real
+//! state (files on disk, live configuration) rather than restating
+//! advice, so an empty report means the checks found nothing, not that
+//! nothing was checked.
+
+use anyhow::Result;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+
+use crate::config::AppConfig;
+use crate::groups::group_manager::GroupId;
+use crate::groups::group_message::{
+    canonical_group_message, open_outer_envelope, seal_outer_envelope, GroupMessageBody,
+    GroupMessageEnvelope, MAGIC_GROUP_MESSAGE, MAX_GROUP_MESSAGE_LEN,
+};
+use crate::identity::identity_key::IdentityKeyPair;
+use crate::storage::secure_keystore::SecureKeyStore;
+
+/// How bad a finding is. Ordered, so `Critical > High`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Points taken off the 100-point [`AuditReport::overall_score`].
+    fn penalty(self) -> u32 {
+        match self {
+            Severity::Info => 0,
+            Severity::Low => 5,
+            Severity::Medium => 10,
+            Severity::High => 25,
+            Severity::Critical => 50,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SecurityFinding {
+    /// [`SecurityCheck::name`] of the check that produced it.
+    pub check: String,
+    pub severity: Severity,
+    pub description: String,
+}
+
+/// The live state an audit looks at. Checks read what they need from
+/// here instead of holding their own handles, so one registered set of
+/// checks can be rerun as the state changes.
+pub struct AuditContext<'a> {
+    /// The keystore, if one is open. Checks that need it report
+    /// nothing without it.
+    pub keystore: Option<&'a SecureKeyStore>,
+    pub config: &'a AppConfig,
+}
+
+/// One self-check run by the [`SecurityAuditor`].
+pub trait SecurityCheck: Send + Sync {
+    fn name(&self) -> &str;
+    fn execute(&self, ctx: &AuditContext<'_>) -> Result<Vec<SecurityFinding>>;
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+    pub findings: Vec<SecurityFinding>,
+    /// 100 minus a per-severity penalty for each finding, floored at 0.
+    pub overall_score: u32,
+}
+
+impl AuditReport {
+    pub fn has_severity(&self, severity: Severity) -> bool {
+        self.findings.iter().any(|f| f.severity == severity)
+    }
+
+    /// `{"overall_score": .., "findings": [{"check", "severity",
+    /// "description"}]}`, severities lowercase. The shape the Android
+    /// settings screen reads.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("audit report serializes")
+    }
+}
+
+#[derive(Default)]
+pub struct SecurityAuditor {
+    checks: Vec<Box<dyn SecurityCheck>>,
+}
+
+impl SecurityAuditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every built-in check: keystore at-rest encryption, config, and
+    /// wire-parser input validation.
+    pub fn with_default_checks() -> Self {
+        let mut auditor = Self::new();
+        auditor.register_check(Box::new(KeystoreEncryptionCheck));
+        auditor.register_check(Box::new(ConfigCheck));
+        auditor.register_check(Box::new(InputValidationCheck::default()));
+        auditor
+    }
+
+    pub fn register_check(&mut self, check: Box<dyn SecurityCheck>) {
+        self.checks.push(check);
+    }
+
+    /// Run every registered check against `ctx`. A check that fails to
+    /// run is itself reported as a `High` finding instead of aborting
+    /// the audit.
+    #[tracing::instrument(
+        name = "run_audit",
+        skip_all,
+        fields(check_count = self.checks.len(), finding_count, overall_score)
+    )]
+    pub fn run(&self, ctx: &AuditContext<'_>) -> AuditReport {
+        let mut findings = Vec::new();
+        for check in &self.checks {
+            match check.execute(ctx) {
+                Ok(found) => findings.extend(found),
+                Err(e) => findings.push(SecurityFinding {
+                    check: check.name().to_string(),
+                    severity: Severity::High,
+                    description: format!("check failed to run: {e:#}"),
+                }),
+            }
+        }
+        let penalty: u32 = findings.iter().map(|f| f.severity.penalty()).sum();
+        let overall_score = 100u32.saturating_sub(penalty);
+        tracing::Span::current()
+            .record("finding_count", findings.len())
+            .record("overall_score", overall_score);
+        AuditReport {
+            findings,
+            overall_score,
+        }
+    }
+}
+
+/// Sealed values are ChaCha20-Poly1305 output, so always carry a
+/// 16-byte tag. Anything shorter can't be ciphertext.
+const AEAD_TAG_LEN: usize = 16;
+
+/// Share of printable ASCII above which a value reads as text.
+const PRINTABLE_RATIO_LIMIT: f64 = 0.9;
+
+/// Fraction of the best entropy achievable at a value's length below
+/// which it's too regular to be ciphertext. Random bytes sit near 0.9+
+/// even for short values.
+const ENTROPY_RATIO_LIMIT: f64 = 0.75;
+
+/// Reads the context's keystore file straight from disk and checks
+/// every stored value looks like AEAD ciphertext. Flags `Critical` for
+/// any entry that is too short to hold a tag, is mostly printable text,
+/// or has too little byte entropy to be ciphertext.
+pub struct KeystoreEncryptionCheck;
+
+impl KeystoreEncryptionCheck {
+    fn plaintext_reason(value: &[u8]) -> Option<&'static str> {
+        if value.len() < AEAD_TAG_LEN {
+            return Some("too short to carry an AEAD tag");
+        }
+        let printable = value
+            .iter()
+            .filter(|b| b.is_ascii_graphic() || **b == b' ')
+            .count();
+        if printable as f64 / value.len() as f64 >= PRINTABLE_RATIO_LIMIT {
+            return Some("mostly printable text");
+        }
+        let max_entropy = (value.len().min(256) as f64).log2();
+        if shannon_entropy(value) < ENTROPY_RATIO_LIMIT * max_entropy {
+            return Some("byte entropy too low for ciphertext");
+        }
+        None
+    }
+}
+
+impl SecurityCheck for KeystoreEncryptionCheck {
+    fn name(&self) -> &str {
+        "keystore_encryption"
+    }
+
+    fn execute(&self, ctx: &AuditContext<'_>) -> Result<Vec<SecurityFinding>> {
+        let Some(keystore) = ctx.keystore else {
+            return Ok(Vec::new());
+        };
+        let mut findings: Vec<SecurityFinding> = keystore
+            .read_stored_entries()?
+            .into_iter()
+            .filter_map(|(id, value)| {
+                Self::plaintext_reason(&value).map(|reason| SecurityFinding {
+                    check: self.name().to_string(),
+                    severity: Severity::Critical,
+                    description: format!(
+                        "keystore entry {id:?} appears to be stored unencrypted ({reason})"
+                    ),
+                })
+            })
+            .collect();
+        findings.sort_by(|a, b| a.description.cmp(&b.description));
+        Ok(findings)
+    }
+}
+
+/// Flags configuration that weakens metadata protection.
+pub struct ConfigCheck;
+
+impl SecurityCheck for ConfigCheck {
+    fn name(&self) -> &str {
+        "config"
+    }
+
+    fn execute(&self, ctx: &AuditContext<'_>) -> Result<Vec<SecurityFinding>> {
+        let mut findings = Vec::new();
+        if !ctx.config.enable_cover_traffic {
+            findings.push(SecurityFinding {
+                check: self.name().to_string(),
+                severity: Severity::Medium,
+                description: "cover traffic is disabled; message timing and volume are visible \
+                              to network observers"
+                    .to_string(),
+            });
+        }
+        Ok(findings)
+    }
+}
+
+/// Parses a sealed group-message frame down to its envelope.
+type FrameParser = fn(&[u8]) -> Result<GroupMessageEnvelope>;
+
+/// Group key the validation corpus is sealed under. Any fixed key
+/// will do: the point is to get malformed inner bytes past the outer
+/// AEAD so the inner decoder sees them too.
+const VALIDATION_GROUP_KEY: [u8; 32] = [0xA5; 32];
+
+/// Seed for the corpus's random inputs, fixed so a finding reproduces
+/// on every run.
+const VALIDATION_SEED: u64 = 0x5155_4245_4549_4e50;
+
+/// Random inputs generated per corpus section.
+const RANDOM_CASES: usize = 16;
+
+/// Feeds malformed group-message frames to the wire parsers and
+/// checks each is rejected with an error. A frame that parses is
+/// flagged `High`; one that panics the parser is `Critical`, since a
+/// panic on peer input takes the node down.
+///
+/// The corpus covers both layers: raw frames (truncated, wrong magic,
+/// oversized, random) for the outer envelope, and malformed inner
+/// bincode (truncations of a real envelope, trailing bytes, a length
+/// prefix claiming exabytes, random) sealed under a fixed key so it
+/// reaches the inner decoder.
+pub struct InputValidationCheck {
+    parse: FrameParser,
+}
+
+impl Default for InputValidationCheck {
+    fn default() -> Self {
+        InputValidationCheck {
+            parse: Self::parse_frame,
+        }
+    }
+}
+
+impl InputValidationCheck {
+    fn parse_frame(wire: &[u8]) -> Result<GroupMessageEnvelope> {
+        let (_, inner) = open_outer_envelope(wire, |_| Some(VALIDATION_GROUP_KEY))?;
+        GroupMessageEnvelope::from_inner_bincode(&inner)
+    }
+
+    /// A well-formed frame, sealed under [`VALIDATION_GROUP_KEY`], and
+    /// its inner bincode. The check fails to run if this doesn't
+    /// parse, so an overly strict parser can't pass by rejecting
+    /// everything.
+    fn valid_frame() -> Result<(GroupId, Vec<u8>, Vec<u8>)> {
+        let kp = IdentityKeyPair::generate()?;
+        let body = GroupMessageBody {
+            group_id: GroupId::from_bytes([0x42; 32]),
+            sender_id: kp.identity_id(),
+            generation: 1,
+            lamport: 1,
+            aead_payload: vec![0; 64],
+            timestamp: 0,
+        };
+        let signature = kp.sign(&canonical_group_message(&body))?;
+        let group_id = body.group_id;
+        let inner = GroupMessageEnvelope { body, signature }.to_inner_bincode()?;
+        let wire = seal_outer_envelope(&group_id, &VALIDATION_GROUP_KEY, &inner)?;
+        Ok((group_id, inner, wire))
+    }
+
+    fn corpus(group_id: &GroupId, inner: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut rng = ChaCha20Rng::seed_from_u64(VALIDATION_SEED);
+        let mut random = |len: usize| {
+            let mut bytes = vec![0u8; len];
+            rng.fill_bytes(&mut bytes);
+            bytes
+        };
+        let with_magic = |rest: &[u8]| [MAGIC_GROUP_MESSAGE, rest].concat();
+        let seal = |inner: &[u8]| seal_outer_envelope(group_id, &VALIDATION_GROUP_KEY, inner);
+
+        let mut cases = vec![
+            ("empty frame".to_string(), Vec::new()),
+            ("magic only".to_string(), MAGIC_GROUP_MESSAGE.to_vec()),
+            (
+                "header without ciphertext".to_string(),
+                with_magic(&[0u8; 32 + 12]),
+            ),
+            (
+                "superseded magic".to_string(),
+                [b"QUBEE_GMS\x01".as_slice(), &[0u8; 64]].concat(),
+            ),
+            (
+                "oversized frame".to_string(),
+                with_magic(&vec![0u8; MAX_GROUP_MESSAGE_LEN]),
+            ),
+        ];
+        for i in 0..RANDOM_CASES {
+            let len = 1 + (i * 37) % 512;
+            cases.push((format!("random frame #{i}"), random(len)));
+            cases.push((
+                format!("magic + random #{i}"),
+                with_magic(&random(len + 44)),
+            ));
+        }
+
+        cases.push(("empty inner envelope".to_string(), seal(&[])?));
+        for k in 1..8 {
+            let cut = inner.len() * k / 8;
+            cases.push((
+                format!("inner truncated to {cut} bytes"),
+                seal(&inner[..cut])?,
+            ));
+        }
+        cases.push((
+            "inner with trailing bytes".to_string(),
+            seal(&[inner, &[0u8]].concat())?,
+        ));
+        // group_id, sender_id, generation, then an aead_payload length
+        // prefix no frame could back.
+        let mut huge = inner[..32 + 32 + 8].to_vec();
+        huge.extend_from_slice(&u64::MAX.to_le_bytes());
+        huge.extend_from_slice(&random(64));
+        cases.push(("exabyte length prefix".to_string(), seal(&huge)?));
+        // Short blobs up to around a real envelope's size.
+        for i in 0..RANDOM_CASES {
+            let len = i * 256 + (i * 13) % 64;
+            cases.push((format!("random inner #{i}"), seal(&random(len))?));
+        }
+        Ok(cases)
+    }
+}
+
+impl SecurityCheck for InputValidationCheck {
+    fn name(&self) -> &str {
+        "input_validation"
+    }
+
+    fn execute(&self, _ctx: &AuditContext<'_>) -> Result<Vec<SecurityFinding>> {
+        let (group_id, inner, wire) = Self::valid_frame()?;
+        (self.parse)(&wire).map_err(|e| anyhow::anyhow!("parser rejects a valid frame: {e:#}"))?;
+
+        let mut findings = Vec::new();
+        for (label, frame) in Self::corpus(&group_id, &inner)? {
+            let outcome = std::panic::catch_unwind(|| (self.parse)(&frame));
+            let (severity, what) = match outcome {
+                Ok(Err(_)) => continue,
+                Ok(Ok(_)) => (Severity::High, "accepted"),
+                Err(_) => (Severity::Critical, "panicked on"),
+            };
+            findings.push(SecurityFinding {
+                check: self.name().to_string(),
+                severity,
+                description: format!("group-message parser {what} malformed input: {label}"),
+            });
+        }
+        Ok(findings)
+    }
+}
+
+/// Shannon entropy of `data` in bits per byte.
+fn shannon_entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::group_message::MalformedFrame;
+    use crate::storage::secure_keystore::{KeyMetadata, KeyType, KeyUsage};
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    const SECRET: &[u8] = b"correct horse battery staple, definitely a secret";
+
+    #[test]
+    fn plaintext_keystore_entry_is_critical() {
+        let dir = TempDir::new().unwrap();
+
+        let sealed_path = dir.path().join("sealed.db");
+        let mut sealed = SecureKeyStore::new(&sealed_path, b"test-keystore-passphrase").unwrap();
+        sealed
+            .store_key(
+                "identity",
+                SECRET,
+                KeyType::IdentityKey,
+                KeyMetadata {
+                    algorithm: "bincode".into(),
+                    key_size: SECRET.len(),
+                    usage: vec![KeyUsage::Encryption],
+                    expiry: None,
+                    tags: HashMap::new(),
+                },
+            )
+            .unwrap();
+        let config = AppConfig::default();
+        let mut auditor = SecurityAuditor::new();
+        auditor.register_check(Box::new(KeystoreEncryptionCheck));
+        let report = auditor.run(&AuditContext {
+            keystore: Some(&sealed),
+            config: &config,
+        });
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+        assert_eq!(report.overall_score, 100);
+
+        let plain_path = dir.path().join("plain.db");
+        SecureKeyStore::write_unsealed_for_test(&plain_path, &[("identity", SECRET)]).unwrap();
+        let plain = SecureKeyStore::new(&plain_path, b"test-keystore-passphrase").unwrap();
+        let report = auditor.run(&AuditContext {
+            keystore: Some(&plain),
+            config: &config,
+        });
+        assert!(report.has_severity(Severity::Critical));
+        assert!(report.findings[0].description.contains("identity"));
+        assert!(report.overall_score < 100);
+    }
+
+    #[test]
+    fn disabled_cover_traffic_is_reported() {
+        let auditor = SecurityAuditor::with_default_checks();
+
+        let config = AppConfig::default();
+        let ctx = AuditContext {
+            keystore: None,
+            config: &config,
+        };
+        assert!(auditor.run(&ctx).findings.is_empty());
+
+        let config = AppConfig {
+            enable_cover_traffic: false,
+            ..AppConfig::default()
+        };
+        let report = auditor.run(&AuditContext {
+            keystore: None,
+            config: &config,
+        });
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].check, "config");
+        assert!(report.findings[0].description.contains("cover traffic"));
+        assert_eq!(report.overall_score, 90);
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected_and_lax_parsers_reported() {
+        let config = AppConfig::default();
+        let ctx = AuditContext {
+            keystore: None,
+            config: &config,
+        };
+        let findings = InputValidationCheck::default().execute(&ctx).unwrap();
+        assert!(findings.is_empty(), "{findings:?}");
+
+        let (group_id, inner, _) = InputValidationCheck::valid_frame().unwrap();
+        let mut huge = inner[..72].to_vec();
+        huge.extend_from_slice(&u64::MAX.to_le_bytes());
+        let wire = seal_outer_envelope(&group_id, &VALIDATION_GROUP_KEY, &huge).unwrap();
+        let err = InputValidationCheck::parse_frame(&wire).unwrap_err();
+        assert!(err.downcast_ref::<MalformedFrame>().is_some(), "{err:#}");
+
+        // Plain bincode lets trailing bytes through; a parser that
+        // panics on empty input is worse.
+        let lax = InputValidationCheck {
+            parse: |wire| {
+                let (_, inner) = open_outer_envelope(wire, |_| Some(VALIDATION_GROUP_KEY))?;
+                Ok(bincode::deserialize(&inner)?)
+            },
+        };
+        let findings = lax.execute(&ctx).unwrap();
+        assert_eq!(findings.len(), 1, "{findings:?}");
+        assert_eq!(findings[0].severity, Severity::High);
+        assert!(findings[0].description.contains("trailing bytes"));
+
+        let panicky = InputValidationCheck {
+            parse: |wire| {
+                assert!(!wire.is_empty());
+                InputValidationCheck::parse_frame(wire)
+            },
+        };
+        let findings = panicky.execute(&ctx).unwrap();
+        assert_eq!(findings.len(), 1, "{findings:?}");
+        assert_eq!(findings[0].severity, Severity::Critical);
+        assert!(findings[0].description.contains("empty frame"));
+    }
+
+    #[test]
+    fn report_json_has_the_bridge_shape() {
+        let config = AppConfig {
+            enable_cover_traffic: false,
+            ..AppConfig::default()
+        };
+        let report = SecurityAuditor::with_default_checks().run(&AuditContext {
+            keystore: None,
+            config: &config,
+        });
+        assert_eq!(
+            report.to_json(),
+            serde_json::json!({
+                "overall_score": 90,
+                "findings": [{
+                    "check": "config",
+                    "severity": "medium",
+                    "description": report.findings[0].description,
+                }],
+            })
+        );
+    }
+}
@@ -1,10 +1,13 @@
+// Runtime self-checks (keystore at rest, ...).
+pub mod audit;
 // `secure_keystore` lives under `crate::storage::secure_keystore`.
 // The previous duplicate copy here triggered an E0119 (conflicting
 // Drop impls for `SecureKeyStore`); single source of truth wins.
 pub mod secure_rng;
+// Chunked AEAD for payloads too large to seal in one piece.
+pub mod stream_aead;
 
-// Page-locked buffers via libc mlock/munlock. Behind the `legacy`
-// feature: it depends on the old `secrecy::Secret` type and pulls in
+// `SecureString` for passphrases. The page-locked mlock/munlock
+// buffers inside stay behind the `legacy` feature: they pull in
 // platform-specific unsafe blocks the modern modules don't need.
-#[cfg(feature = "legacy")]
 pub mod secure_memory;
@@ -1,5 +1,7 @@
+//! Page-locked allocations via libc mlock/munlock, kept for porting
+//! reference. Nothing on the default build calls into it.
+
 use anyhow::{Context, Result};
-use secrecy::{ExposeSecret, SecretBox};
 use std::alloc::{self, Layout};
 use std::ptr::NonNull;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -313,43 +315,6 @@ impl Drop for SecureBuffer {
     }
 }
 
-/// A secure string. The underlying [`SecretBox`] zeroises on drop so
-/// no extra `ZeroizeOnDrop` derive is needed (and would conflict).
-pub struct SecureString {
-    inner: SecretBox<String>,
-}
-
-impl SecureString {
-    /// Create a new secure string
-    pub fn new(s: String) -> Self {
-        SecureString {
-            inner: SecretBox::new(Box::new(s)),
-        }
-    }
-
-    /// Create a secure string from a str
-    pub fn from_str(s: &str) -> Self {
-        SecureString {
-            inner: SecretBox::new(Box::new(s.to_string())),
-        }
-    }
-
-    /// Expose the string contents (use carefully)
-    pub fn expose_secret(&self) -> &str {
-        self.inner.expose_secret()
-    }
-
-    /// Get the length of the string
-    pub fn len(&self) -> usize {
-        self.inner.expose_secret().len()
-    }
-
-    /// Check if the string is empty
-    pub fn is_empty(&self) -> bool {
-        self.inner.expose_secret().is_empty()
-    }
-}
-
 /// Global secure allocator instance
 static GLOBAL_ALLOCATOR: std::sync::OnceLock<SecureAllocator> = std::sync::OnceLock::new();
 
@@ -384,13 +349,6 @@ mod tests {
         assert!(buffer.is_empty());
     }
 
-    #[test]
-    fn test_secure_string() {
-        let secure_str = SecureString::from_str("secret password");
-        assert_eq!(secure_str.expose_secret(), "secret password");
-        assert_eq!(secure_str.len(), 15);
-    }
-
     #[test]
     fn test_memory_region_zeroization() {
         let allocator = SecureAllocator::new();
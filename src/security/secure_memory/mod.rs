@@ -0,0 +1,56 @@
+#[cfg(feature = "legacy")]
+mod legacy;
+#[cfg(feature = "legacy")]
+pub use legacy::{
+    allocate_secure, global_allocator, SecureAllocator, SecureBuffer, SecureMemoryRegion,
+};
+
+use secrecy::{ExposeSecret, SecretBox};
+
+/// A secure string. The underlying [`SecretBox`] zeroises on drop so
+/// no extra `ZeroizeOnDrop` derive is needed (and would conflict).
+pub struct SecureString {
+    inner: SecretBox<String>,
+}
+
+impl SecureString {
+    /// Create a new secure string
+    pub fn new(s: String) -> Self {
+        SecureString {
+            inner: SecretBox::new(Box::new(s)),
+        }
+    }
+
+    /// Expose the string contents (use carefully)
+    pub fn expose_secret(&self) -> &str {
+        self.inner.expose_secret()
+    }
+
+    /// Get the length of the string
+    pub fn len(&self) -> usize {
+        self.inner.expose_secret().len()
+    }
+
+    /// Check if the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.inner.expose_secret().is_empty()
+    }
+}
+
+impl From<&str> for SecureString {
+    fn from(s: &str) -> Self {
+        SecureString::new(s.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secure_string() {
+        let secure_str = SecureString::from("secret password");
+        assert_eq!(secure_str.expose_secret(), "secret password");
+        assert_eq!(secure_str.len(), 15);
+    }
+}
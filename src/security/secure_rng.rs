@@ -7,12 +7,43 @@ use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use zeroize::Zeroize;
 
+/// Raw entropy feeding [`SecureRng`]'s seed. The OS RNG in
+/// production; tests substitute broken sources to exercise the health
+/// tests.
+pub trait EntropySource: Send {
+    fn fill(&mut self, dest: &mut [u8]) -> Result<()>;
+}
+
+/// `getrandom`-backed entropy source.
+pub struct OsEntropy;
+
+impl EntropySource for OsEntropy {
+    fn fill(&mut self, dest: &mut [u8]) -> Result<()> {
+        getrandom(dest).context("Failed to get entropy from OS")
+    }
+}
+
+/// Outcome of [`SecureRng::health_check`]: the NIST SP 800-90B §4.4
+/// continuous health tests run over a fresh sample from the entropy
+/// source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RngHealth {
+    pub passed: bool,
+    /// Runs of identical bytes that reached the repetition-count cutoff.
+    pub repetition_failures: u32,
+    /// Windows in which one byte value reached the adaptive-proportion
+    /// cutoff.
+    pub proportion_failures: u32,
+}
+
 /// Enhanced secure random number generator with multiple entropy sources
 /// and protection against various attacks. ChaCha20Rng doesn't impl
 /// `Zeroize` so we drop the derive and zero the entropy_pool field
 /// manually in `Drop`.
 pub struct SecureRng {
     rng: ChaCha20Rng,
+    source: Box<dyn EntropySource>,
+    healthy: bool,
     entropy_pool: [u8; 64],
     reseed_counter: u64,
     last_reseed: u64,
@@ -30,26 +61,60 @@ impl SecureRng {
     const RESEED_THRESHOLD: u64 = 1_000_000; // Reseed after 1M bytes
     const RESEED_TIME_THRESHOLD: u64 = 3600; // Reseed after 1 hour
 
+    // SP 800-90B health-test parameters. We claim only H = 4 bits of
+    // min-entropy per source byte (conservative for the OS RNG) and a
+    // false-positive rate of alpha = 2^-20 per test:
+    //   RCT cutoff = 1 + ceil(20 / H)                 = 6
+    //   APT cutoff = 1 + CRITBINOM(512, 2^-H, 1 - alpha) = 62
+    const HEALTH_SAMPLE_BYTES: usize = 4096;
+    const RCT_CUTOFF: u32 = 6;
+    const APT_WINDOW: usize = 512;
+    const APT_CUTOFF: u32 = 62;
+
     /// Create a new secure RNG with enhanced entropy collection
     pub fn new() -> Result<Self> {
+        Self::with_source(Box::new(OsEntropy))
+    }
+
+    /// Create an RNG seeded from `source`. The source is health-checked
+    /// first (SP 800-90B start-up test); if it fails, construction
+    /// still succeeds but every generate call errors until a
+    /// [`reseed`](Self::reseed) passes.
+    pub fn with_source(mut source: Box<dyn EntropySource>) -> Result<Self> {
+        let healthy = Self::run_health_tests(source.as_mut())?.passed;
+
         let mut seed = [0u8; 32];
-        Self::collect_high_quality_entropy(&mut seed)?;
+        Self::collect_high_quality_entropy(source.as_mut(), &mut seed)?;
 
         let mut entropy_pool = [0u8; 64];
         Self::collect_additional_entropy(&mut entropy_pool)?;
 
         let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
 
+        let rng = ChaCha20Rng::from_seed(seed);
+        seed.zeroize();
         Ok(SecureRng {
-            rng: ChaCha20Rng::from_seed(seed),
+            rng,
+            source,
+            healthy,
             entropy_pool,
             reseed_counter: 0,
             last_reseed: current_time,
         })
     }
 
+    /// Run the repetition-count and adaptive-proportion tests on a
+    /// fresh sample from the entropy source. A failure marks the RNG
+    /// unhealthy (generation errors); a pass clears that.
+    pub fn health_check(&mut self) -> Result<RngHealth> {
+        let health = Self::run_health_tests(self.source.as_mut())?;
+        self.healthy = health.passed;
+        Ok(health)
+    }
+
     /// Generate random bytes with automatic reseeding
     pub fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<()> {
+        self.ensure_healthy()?;
         // Check if reseeding is needed
         if self.should_reseed()? {
             self.reseed()?;
@@ -63,6 +128,7 @@ impl SecureRng {
 
     /// Generate a random u64
     pub fn next_u64(&mut self) -> Result<u64> {
+        self.ensure_healthy()?;
         if self.should_reseed()? {
             self.reseed()?;
         }
@@ -73,6 +139,7 @@ impl SecureRng {
 
     /// Generate a random u32
     pub fn next_u32(&mut self) -> Result<u32> {
+        self.ensure_healthy()?;
         if self.should_reseed()? {
             self.reseed()?;
         }
@@ -81,10 +148,16 @@ impl SecureRng {
         Ok(self.rng.next_u32())
     }
 
-    /// Force a reseed operation
+    /// Force a reseed operation. The entropy source is health-checked
+    /// first; a failing source is not used and leaves the RNG unhealthy.
     pub fn reseed(&mut self) -> Result<()> {
+        if !self.health_check()?.passed {
+            return Err(anyhow::anyhow!(
+                "RNG entropy source failed health check; refusing to reseed"
+            ));
+        }
         let mut new_seed = [0u8; 32];
-        Self::collect_high_quality_entropy(&mut new_seed)?;
+        Self::collect_high_quality_entropy(self.source.as_mut(), &mut new_seed)?;
 
         // Mix with current state for forward security
         let mut hasher = Hasher::new();
@@ -107,6 +180,53 @@ impl SecureRng {
         Ok(())
     }
 
+    fn ensure_healthy(&self) -> Result<()> {
+        if self.healthy {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "RNG entropy source failed health check; refusing to generate"
+            ))
+        }
+    }
+
+    fn run_health_tests(source: &mut dyn EntropySource) -> Result<RngHealth> {
+        let mut sample = vec![0u8; Self::HEALTH_SAMPLE_BYTES];
+        source.fill(&mut sample)?;
+
+        // Repetition count test (SP 800-90B §4.4.1).
+        let mut repetition_failures = 0u32;
+        let mut run = 1u32;
+        for pair in sample.windows(2) {
+            if pair[0] == pair[1] {
+                run += 1;
+                if run >= Self::RCT_CUTOFF {
+                    repetition_failures += 1;
+                    run = 1;
+                }
+            } else {
+                run = 1;
+            }
+        }
+
+        // Adaptive proportion test (SP 800-90B §4.4.2), over
+        // non-overlapping windows.
+        let proportion_failures = sample
+            .chunks_exact(Self::APT_WINDOW)
+            .filter(|window| {
+                let first = window[0];
+                window.iter().filter(|&&b| b == first).count() as u32 >= Self::APT_CUTOFF
+            })
+            .count() as u32;
+
+        sample.zeroize();
+        Ok(RngHealth {
+            passed: repetition_failures == 0 && proportion_failures == 0,
+            repetition_failures,
+            proportion_failures,
+        })
+    }
+
     /// Check if reseeding is needed based on usage or time
     fn should_reseed(&self) -> Result<bool> {
         let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
@@ -116,9 +236,12 @@ impl SecureRng {
     }
 
     /// Collect high-quality entropy from multiple sources
-    fn collect_high_quality_entropy(buffer: &mut [u8; 32]) -> Result<()> {
-        // Primary entropy from OS
-        getrandom(buffer).context("Failed to get entropy from OS")?;
+    fn collect_high_quality_entropy(
+        source: &mut dyn EntropySource,
+        buffer: &mut [u8; 32],
+    ) -> Result<()> {
+        // Primary entropy from the source (the OS by default)
+        source.fill(buffer)?;
 
         // Additional entropy mixing
         let mut hasher = Hasher::new();
@@ -346,6 +469,57 @@ mod tests {
         assert!(value != 0); // Extremely unlikely to be zero
     }
 
+    /// Source that returns the same byte forever.
+    struct StuckSource(u8);
+
+    impl EntropySource for StuckSource {
+        fn fill(&mut self, dest: &mut [u8]) -> Result<()> {
+            dest.fill(self.0);
+            Ok(())
+        }
+    }
+
+    /// Source that never repeats consecutively but only emits two values.
+    struct AlternatingSource(u8);
+
+    impl EntropySource for AlternatingSource {
+        fn fill(&mut self, dest: &mut [u8]) -> Result<()> {
+            for b in dest {
+                self.0 ^= 1;
+                *b = self.0;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_health_check_passes_for_os_entropy() {
+        let mut rng = SecureRng::new().expect("Should create RNG");
+        let health = rng.health_check().expect("Should run health check");
+        assert!(health.passed, "{health:?}");
+    }
+
+    #[test]
+    fn test_stuck_source_fails_repetition_test_and_blocks_output() {
+        let mut rng = SecureRng::with_source(Box::new(StuckSource(0x42))).unwrap();
+        let health = rng.health_check().unwrap();
+        assert!(!health.passed);
+        assert!(health.repetition_failures > 0);
+
+        assert!(rng.next_u64().is_err());
+        assert!(rng.fill_bytes(&mut [0u8; 16]).is_err());
+        assert!(rng.reseed().is_err());
+    }
+
+    #[test]
+    fn test_low_diversity_source_fails_proportion_test() {
+        let mut rng = SecureRng::with_source(Box::new(AlternatingSource(0))).unwrap();
+        let health = rng.health_check().unwrap();
+        assert_eq!(health.repetition_failures, 0);
+        assert!(health.proportion_failures > 0);
+        assert!(rng.next_u32().is_err());
+    }
+
     #[test]
     fn test_global_rng() {
         let global_rng = GlobalSecureRng::instance();
@@ -0,0 +1,124 @@
+//! Versioned framing for records persisted with bincode.
+//!
+//! Plain bincode carries no schema information, so changing a stored
+//! struct turns every older keystore entry into a confusing decode
+//! error (or, worse, a successful decode of the wrong shape). Records
+//! written through [`encode`] are laid out as
+//!
+//! ```text
+//! MAGIC (4 bytes) || schema version (u16 LE) || bincode(value)
+//! ```
+//!
+//! and [`decode`] checks both before touching the body. A version it
+//! doesn't know fails with [`UnsupportedVersion`], which callers can
+//! downcast to tell "written by a newer build" from corruption. A type
+//! whose layout has changed reads its older versions with
+//! [`schema_version`] and [`decode_legacy`] and migrates them.
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Leading bytes of every framed record.
+pub const MAGIC: &[u8; 4] = b"QBRC";
+
+const HEADER_LEN: usize = MAGIC.len() + 2;
+
+/// A type persisted through [`encode`] / [`decode`]. Bump
+/// `SCHEMA_VERSION` whenever its serialized shape changes.
+pub trait Versioned: Serialize + DeserializeOwned {
+    const SCHEMA_VERSION: u16;
+}
+
+/// A framed record whose schema version this build can't read.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("unsupported version {found} for stored record (this build reads version {expected})")]
+pub struct UnsupportedVersion {
+    pub found: u16,
+    pub expected: u16,
+}
+
+/// `true` if `data` starts with a framing header. Lets callers fall back
+/// to plain bincode for records written before framing existed.
+pub fn is_framed(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && data.starts_with(MAGIC)
+}
+
+pub fn encode<T: Versioned>(value: &T) -> Result<Vec<u8>> {
+    let body = bincode::serialize(value).context("encode stored record")?;
+    let mut out = Vec::with_capacity(HEADER_LEN + body.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&T::SCHEMA_VERSION.to_le_bytes());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Schema version in a framed record's header, or `None` if `data`
+/// isn't framed.
+pub fn schema_version(data: &[u8]) -> Option<u16> {
+    is_framed(data).then(|| u16::from_le_bytes([data[MAGIC.len()], data[MAGIC.len() + 1]]))
+}
+
+pub fn decode<T: Versioned>(data: &[u8]) -> Result<T> {
+    let found = schema_version(data).context("stored record has no framing header")?;
+    if found != T::SCHEMA_VERSION {
+        return Err(UnsupportedVersion {
+            found,
+            expected: T::SCHEMA_VERSION,
+        }
+        .into());
+    }
+    bincode::deserialize(&data[HEADER_LEN..]).context("decode stored record")
+}
+
+/// Decode the body of a record framed at an older schema `version` as
+/// `T`, that version's layout. Fails unless the header says `version`.
+pub fn decode_legacy<T: DeserializeOwned>(data: &[u8], version: u16) -> Result<T> {
+    if schema_version(data) != Some(version) {
+        bail!("stored record is not framed at version {version}");
+    }
+    bincode::deserialize(&data[HEADER_LEN..]).context("decode stored record")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        name: String,
+        count: u32,
+    }
+
+    impl Versioned for Record {
+        const SCHEMA_VERSION: u16 = 3;
+    }
+
+    #[test]
+    fn round_trips_and_rejects_other_versions() {
+        let record = Record {
+            name: "group".to_string(),
+            count: 7,
+        };
+        let mut bytes = encode(&record).unwrap();
+        assert!(is_framed(&bytes));
+        assert_eq!(decode::<Record>(&bytes).unwrap(), record);
+
+        bytes[MAGIC.len()] = 4;
+        let err = decode::<Record>(&bytes).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<UnsupportedVersion>(),
+            Some(&UnsupportedVersion {
+                found: 4,
+                expected: 3
+            })
+        );
+        assert!(err.to_string().contains("unsupported version"));
+
+        // Records from before framing are refused, not misread.
+        let legacy = bincode::serialize(&record).unwrap();
+        assert!(!is_framed(&legacy));
+        assert!(decode::<Record>(&legacy).is_err());
+    }
+}
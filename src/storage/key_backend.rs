@@ -0,0 +1,276 @@
+//! Where a [`SecureKeyStore`](super::SecureKeyStore) keeps its bytes.
+//!
+//! The keystore does all the cryptography itself and hands a backend
+//! only opaque, already-sealed records: the entry table, the wrapped
+//! master key, and the wrapped next master key while a rotation runs.
+//! A backend is therefore a small named-record store; swapping it moves
+//! where those records live (a file, memory, a platform keychain)
+//! without touching how they are protected.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use zeroize::Zeroizing;
+
+/// Named-record storage behind a keystore.
+///
+/// `store` must replace a record atomically: a reader sees the old
+/// bytes or the new ones, never a mix. The keystore's crash safety
+/// (transactions, rotation, passphrase changes) rests on that.
+pub trait KeyBackend {
+    fn store(&mut self, name: &str, data: &[u8]) -> Result<()>;
+    fn retrieve(&self, name: &str) -> Result<Option<Vec<u8>>>;
+    /// Returns whether the record existed.
+    fn delete(&mut self, name: &str) -> Result<bool>;
+    fn list(&self) -> Result<Vec<String>>;
+}
+
+/// Default backend: the entry table at the keystore path and every
+/// other record next to it as `<path stem>.<name>`, so `ks.db` keeps
+/// its master key in `ks.master`. Writes go to a temp file that is
+/// renamed over the record.
+pub struct FileBackend {
+    path: PathBuf,
+}
+
+impl FileBackend {
+    /// Record name of the entry table, stored at the path itself.
+    pub const ENTRIES: &'static str = "entries";
+
+    /// Creates the parent directory if needed.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create storage directory")?;
+        }
+        Ok(FileBackend { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn record_path(&self, name: &str) -> PathBuf {
+        if name == Self::ENTRIES {
+            self.path.clone()
+        } else {
+            self.path.with_extension(name)
+        }
+    }
+}
+
+impl KeyBackend for FileBackend {
+    fn store(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let path = self.record_path(name);
+        let mut tmp = OsString::from(&path);
+        tmp.push(".tmp");
+        fs::write(&tmp, data).with_context(|| format!("Failed to write keystore record {name}"))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to replace keystore record {name}"))
+    }
+
+    fn retrieve(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.record_path(name)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read keystore record {name}")),
+        }
+    }
+
+    fn delete(&mut self, name: &str) -> Result<bool> {
+        match fs::remove_file(self.record_path(name)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("Failed to delete keystore record {name}")),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let (Some(dir), Some(file_name), Some(stem)) = (
+            self.path.parent(),
+            self.path.file_name(),
+            self.path.file_stem(),
+        ) else {
+            return Ok(Vec::new());
+        };
+        let prefix = format!("{}.", stem.to_string_lossy());
+        let mut names = Vec::new();
+        for dir_entry in fs::read_dir(dir).context("Failed to list storage directory")? {
+            let name = dir_entry?.file_name();
+            if name == file_name {
+                names.push(Self::ENTRIES.to_string());
+                continue;
+            }
+            let name = name.to_string_lossy();
+            if let Some(record) = name.strip_prefix(&prefix) {
+                if !record.ends_with(".tmp") {
+                    names.push(record.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+/// Records held in memory only. Clones share the same records, so a
+/// keystore can be dropped and reopened over a clone — which is what
+/// tests and ephemeral sessions want.
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    records: Arc<Mutex<HashMap<String, Zeroizing<Vec<u8>>>>>,
+}
+
+impl KeyBackend for MemoryBackend {
+    fn store(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        self.records
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Zeroizing::new(data.to_vec()));
+        Ok(())
+    }
+
+    fn retrieve(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .records
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|data| data.to_vec()))
+    }
+
+    fn delete(&mut self, name: &str) -> Result<bool> {
+        Ok(self.records.lock().unwrap().remove(name).is_some())
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self.records.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+}
+
+/// Desktop OS keychain (Secret Service, macOS Keychain, Windows
+/// Credential Manager), one item per record under `service`.
+///
+/// Not wired up yet: every call fails until the platform bindings
+/// land, so selecting it is an explicit, visible error rather than a
+/// silent fallback to disk.
+pub struct OsKeychainBackend {
+    pub service: String,
+}
+
+/// Android Keystore, reached through the JNI bridge so the master-key
+/// record can live in the TEE / StrongBox.
+///
+/// Not wired up yet: every call fails until the Kotlin side exposes
+/// the record calls. Until then Android keeps using [`FileBackend`]
+/// with a Keystore-held passphrase (see `nativeInitialize`).
+pub struct AndroidKeystoreBackend {
+    pub alias_prefix: String,
+}
+
+fn not_implemented(what: &str) -> anyhow::Error {
+    anyhow::anyhow!("{what} backend is not implemented yet")
+}
+
+macro_rules! unavailable_backend {
+    ($backend:ty, $what:literal) => {
+        impl KeyBackend for $backend {
+            fn store(&mut self, _name: &str, _data: &[u8]) -> Result<()> {
+                Err(not_implemented($what))
+            }
+
+            fn retrieve(&self, _name: &str) -> Result<Option<Vec<u8>>> {
+                Err(not_implemented($what))
+            }
+
+            fn delete(&mut self, _name: &str) -> Result<bool> {
+                Err(not_implemented($what))
+            }
+
+            fn list(&self) -> Result<Vec<String>> {
+                Err(not_implemented($what))
+            }
+        }
+    };
+}
+
+unavailable_backend!(OsKeychainBackend, "OS keychain");
+unavailable_backend!(AndroidKeystoreBackend, "Android Keystore");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::secure_keystore::{KeyMetadata, KeyType, KeyUsage, SecureKeyStore};
+    use tempfile::TempDir;
+
+    const SECRET: &[u8] = b"prekey secret that must never be stored in the clear";
+
+    fn metadata() -> KeyMetadata {
+        KeyMetadata {
+            algorithm: "bincode".into(),
+            key_size: SECRET.len(),
+            usage: vec![KeyUsage::Encryption],
+            expiry: None,
+            tags: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn keystore_runs_over_memory_backend() {
+        let backend = MemoryBackend::default();
+        {
+            let mut ks = SecureKeyStore::with_backend(backend.clone(), b"platform secret").unwrap();
+            ks.store_key("prekey", SECRET, KeyType::PreKey, metadata())
+                .unwrap();
+            ks.rotate_master_key().unwrap();
+        }
+
+        assert_eq!(backend.list().unwrap(), vec!["entries", "master"]);
+        for name in backend.list().unwrap() {
+            let record = backend.retrieve(&name).unwrap().unwrap();
+            assert!(!record.windows(SECRET.len()).any(|w| w == SECRET));
+        }
+
+        assert!(SecureKeyStore::with_backend(backend.clone(), b"other secret").is_err());
+        let mut ks = SecureKeyStore::with_backend(backend, b"platform secret").unwrap();
+        let key = ks.retrieve_key("prekey").unwrap().unwrap();
+        assert_eq!(
+            secrecy::ExposeSecret::expose_secret(&key).as_slice(),
+            SECRET
+        );
+    }
+
+    #[test]
+    fn file_backend_lists_and_deletes_its_records() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("ks.db");
+        std::fs::write(dir.path().join("unrelated.db"), b"x").unwrap();
+        {
+            let mut ks = SecureKeyStore::new(&path, b"test-keystore-passphrase").unwrap();
+            ks.store_key("prekey", SECRET, KeyType::PreKey, metadata())
+                .unwrap();
+        }
+
+        let mut backend = FileBackend::new(&path).unwrap();
+        assert_eq!(backend.list().unwrap(), vec!["entries", "master"]);
+        assert!(path.with_extension("master").exists());
+        assert!(backend.delete("master").unwrap());
+        assert!(!backend.delete("master").unwrap());
+        assert_eq!(backend.retrieve("master").unwrap(), None);
+    }
+
+    #[test]
+    fn unimplemented_platform_backends_fail_loudly() {
+        let backend = OsKeychainBackend {
+            service: "qubee".into(),
+        };
+        let err = SecureKeyStore::with_backend(backend, b"platform secret")
+            .err()
+            .expect("stub backend must not open");
+        assert!(err.to_string().contains("not implemented"));
+    }
+}
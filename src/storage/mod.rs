@@ -1,3 +1,10 @@
+pub mod framed;
+pub mod key_backend;
+pub mod search_index;
 pub mod secure_keystore;
 
-pub use secure_keystore::{KeyMetadata, KeyType, KeyUsage, SecureKeyStore, SecureKeystore};
+pub use key_backend::{FileBackend, KeyBackend, MemoryBackend};
+pub use search_index::SearchIndex;
+pub use secure_keystore::{
+    KeyMetadata, KeyType, KeyUsage, RotationProgress, SecureKeyStore, SecureKeystore,
+};
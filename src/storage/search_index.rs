@@ -0,0 +1,297 @@
+//! Encrypted, blind-indexed search over local message history.
+//!
+//! Search needs *some* per-word structure on disk, and storing
+//! plaintext for it would undo encryption at rest. Instead each word is
+//! normalised and turned into a **blind token**:
+//!
+//! ```text
+//! token = BLAKE3-keyed(blind_key, conversation_id || 0x00 || word)
+//! ```
+//!
+//! and the index maps `token → sealed posting list`, where the posting
+//! list (the message ids containing that word) is ChaCha20-Poly1305
+//! encrypted under a second key with the token as AAD. Both keys come
+//! from the identity via [`IdentityKeyPair::derive_subkey`], so the
+//! index file is useless without the identity's private keys.
+//!
+//! # What still leaks
+//!
+//! Blind indexing is deterministic, so someone holding the index file
+//! (but not the keys) learns:
+//!
+//! * the number of distinct words per conversation,
+//! * roughly how many messages contain each (unknown) word, from the
+//!   sealed posting-list length,
+//! * whether two conversations' indexes changed at the same time.
+//!
+//! They don't learn the words, and the per-conversation token prefix
+//! stops them from correlating the same word across conversations.
+//! An attacker who can watch the file change *while* the user searches
+//! also learns which tokens were queried (access pattern). That is the
+//! trade-off every practical searchable-encryption scheme makes; if it
+//! isn't acceptable, don't enable search.
+
+use anyhow::{anyhow, Context, Result};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use secrecy::{ExposeSecret, SecretBox};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::identity::conversation_id::ConversationId;
+use crate::identity::identity_key::IdentityKeyPair;
+use crate::security::secure_rng;
+
+const BLIND_KEY_INFO: &[u8] = b"qubee search index blind key v1";
+const POSTINGS_KEY_INFO: &[u8] = b"qubee search index postings key v1";
+
+/// Words longer than this are truncated before blinding. Keeps one
+/// pasted blob from producing an arbitrarily long token input.
+const MAX_WORD_CHARS: usize = 64;
+
+/// Message identifier as stored in the index.
+pub type MessageId = [u8; 16];
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SealedPostings {
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+}
+
+/// On-disk searchable index for one identity's message history.
+pub struct SearchIndex {
+    path: PathBuf,
+    blind_key: SecretBox<[u8; 32]>,
+    postings_key: SecretBox<[u8; 32]>,
+    entries: HashMap<[u8; 32], SealedPostings>,
+}
+
+/// Lowercase, split on anything that isn't alphanumeric, drop
+/// single-character words, truncate long ones. Duplicates collapse.
+fn normalize(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() > 1)
+        .map(|w| w.to_lowercase().chars().take(MAX_WORD_CHARS).collect())
+        .collect()
+}
+
+impl SearchIndex {
+    /// Open (or create) the index at `path`, keyed from `identity`.
+    pub fn open<P: AsRef<Path>>(path: P, identity: &IdentityKeyPair) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create search index directory")?;
+        }
+        let entries = if path.exists() {
+            let data = fs::read(&path).context("Failed to read search index")?;
+            if data.is_empty() {
+                HashMap::new()
+            } else {
+                bincode::deserialize(&data).context("Failed to deserialize search index")?
+            }
+        } else {
+            HashMap::new()
+        };
+
+        Ok(SearchIndex {
+            path,
+            blind_key: SecretBox::new(Box::new(identity.derive_subkey(BLIND_KEY_INFO)?)),
+            postings_key: SecretBox::new(Box::new(identity.derive_subkey(POSTINGS_KEY_INFO)?)),
+            entries,
+        })
+    }
+
+    /// Add `message_id` under every word of `text` in `conversation`.
+    pub fn index_message(
+        &mut self,
+        conversation: &ConversationId,
+        message_id: MessageId,
+        text: &str,
+    ) -> Result<()> {
+        for word in normalize(text) {
+            let token = self.blind_token(conversation, &word);
+            let mut ids = self.open_postings(&token)?;
+            if ids.insert(message_id) {
+                self.seal_postings(token, &ids)?;
+            }
+        }
+        self.save()
+    }
+
+    /// Remove `message_id` from the index. Needs the message's text to
+    /// know which tokens to touch — the index can't enumerate them.
+    pub fn remove_message(
+        &mut self,
+        conversation: &ConversationId,
+        message_id: MessageId,
+        text: &str,
+    ) -> Result<()> {
+        for word in normalize(text) {
+            let token = self.blind_token(conversation, &word);
+            let mut ids = self.open_postings(&token)?;
+            if !ids.remove(&message_id) {
+                continue;
+            }
+            if ids.is_empty() {
+                self.entries.remove(&token);
+            } else {
+                self.seal_postings(token, &ids)?;
+            }
+        }
+        self.save()
+    }
+
+    /// Ids of messages in `conversation` containing *every* word of
+    /// `query`, in byte order. An empty query matches nothing.
+    pub fn search_messages(
+        &self,
+        conversation: &ConversationId,
+        query: &str,
+    ) -> Result<Vec<MessageId>> {
+        let mut result: Option<BTreeSet<MessageId>> = None;
+        for word in normalize(query) {
+            let ids = self.open_postings(&self.blind_token(conversation, &word))?;
+            let narrowed = match result {
+                None => ids,
+                Some(prev) => prev.intersection(&ids).copied().collect(),
+            };
+            if narrowed.is_empty() {
+                return Ok(Vec::new());
+            }
+            result = Some(narrowed);
+        }
+        Ok(result.unwrap_or_default().into_iter().collect())
+    }
+
+    fn blind_token(&self, conversation: &ConversationId, word: &str) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_keyed(self.blind_key.expose_secret());
+        hasher.update(conversation.as_bytes());
+        hasher.update(&[0u8]);
+        hasher.update(word.as_bytes());
+        *hasher.finalize().as_bytes()
+    }
+
+    fn open_postings(&self, token: &[u8; 32]) -> Result<BTreeSet<MessageId>> {
+        let Some(sealed) = self.entries.get(token) else {
+            return Ok(BTreeSet::new());
+        };
+        let cipher = ChaCha20Poly1305::new(self.postings_key.expose_secret().into());
+        let plain = cipher
+            .decrypt(
+                Nonce::from_slice(&sealed.nonce),
+                Payload {
+                    msg: &sealed.ciphertext,
+                    aad: token,
+                },
+            )
+            .map_err(|_| anyhow!("search index entry failed to decrypt"))?;
+        bincode::deserialize(&plain).context("search index postings deserialize")
+    }
+
+    fn seal_postings(&mut self, token: [u8; 32], ids: &BTreeSet<MessageId>) -> Result<()> {
+        let plain = bincode::serialize(ids).context("search index postings serialize")?;
+        let nonce = secure_rng::random::array::<12>()?;
+        let cipher = ChaCha20Poly1305::new(self.postings_key.expose_secret().into());
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plain,
+                    aad: &token,
+                },
+            )
+            .map_err(|e| anyhow!("search index encryption failed: {}", e))?;
+        self.entries
+            .insert(token, SealedPostings { nonce, ciphertext });
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        let data = bincode::serialize(&self.entries).context("Failed to serialize search index")?;
+        fs::write(&self.path, data).context("Failed to write search index")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::identity_key::{DeviceId, IdentityId};
+    use tempfile::TempDir;
+
+    fn conversation(kp: &IdentityKeyPair, peer: u8) -> ConversationId {
+        ConversationId::derive(
+            &kp.identity_id(),
+            &DeviceId::from([1u8; 16]),
+            &IdentityId::from([peer; 32]),
+            &DeviceId::from([peer; 16]),
+        )
+    }
+
+    #[test]
+    fn search_matches_all_words_and_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("search.idx");
+        let kp = IdentityKeyPair::generate().unwrap();
+        let conv = conversation(&kp, 2);
+        let other = conversation(&kp, 3);
+
+        {
+            let mut index = SearchIndex::open(&path, &kp).unwrap();
+            index
+                .index_message(&conv, [1; 16], "Lunch on Friday?")
+                .unwrap();
+            index
+                .index_message(&conv, [2; 16], "friday works, lunch at noon")
+                .unwrap();
+            index
+                .index_message(&other, [3; 16], "lunch friday")
+                .unwrap();
+        }
+
+        let mut index = SearchIndex::open(&path, &kp).unwrap();
+        assert_eq!(
+            index.search_messages(&conv, "FRIDAY lunch").unwrap(),
+            vec![[1; 16], [2; 16]]
+        );
+        assert_eq!(index.search_messages(&conv, "noon").unwrap(), vec![[2; 16]]);
+        assert!(index.search_messages(&conv, "dinner").unwrap().is_empty());
+
+        index
+            .remove_message(&conv, [2; 16], "friday works, lunch at noon")
+            .unwrap();
+        assert_eq!(
+            index.search_messages(&conv, "lunch").unwrap(),
+            vec![[1; 16]]
+        );
+    }
+
+    #[test]
+    fn index_file_holds_no_plaintext_and_needs_the_identity() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("search.idx");
+        let kp = IdentityKeyPair::generate().unwrap();
+        let conv = conversation(&kp, 2);
+
+        let mut index = SearchIndex::open(&path, &kp).unwrap();
+        index
+            .index_message(&conv, [7; 16], "supercalifragilistic")
+            .unwrap();
+
+        let on_disk = fs::read(&path).unwrap();
+        assert!(!on_disk
+            .windows(b"supercalifragilistic".len())
+            .any(|w| w == b"supercalifragilistic"));
+
+        let stranger = IdentityKeyPair::generate().unwrap();
+        let foreign = SearchIndex::open(&path, &stranger).unwrap();
+        assert!(foreign
+            .search_messages(&conv, "supercalifragilistic")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -9,7 +9,9 @@
 use qubee_crypto::groups::group_handshake::{
     generate_ephemeral_kyber, sign_request_join, GroupHandshake, MemberAddedBody, RequestJoinBody,
 };
-use qubee_crypto::groups::group_manager::{GroupManager, GroupSettings, GroupType, MemberStatus};
+use qubee_crypto::groups::group_manager::{
+    GroupManager, GroupSettings, GroupType, MemberStatus, MAX_LAMPORT_JUMP,
+};
 use qubee_crypto::groups::group_message::{decrypt_group_message, encrypt_group_message};
 use qubee_crypto::groups::handshake_handlers::{
     process_join_accepted, process_member_added, process_request_join, HandshakeOutcome,
@@ -212,6 +214,18 @@ fn forge_message_with_generation(
     group_id: qubee_crypto::groups::group_manager::GroupId,
     plaintext: &[u8],
     generation: u64,
+) -> Vec<u8> {
+    forge_message(gm, sender_kp, group_id, plaintext, generation, 1)
+}
+
+/// [`forge_message_with_generation`] with an explicit Lamport clock.
+fn forge_message(
+    gm: &GroupManager,
+    sender_kp: &IdentityKeyPair,
+    group_id: qubee_crypto::groups::group_manager::GroupId,
+    plaintext: &[u8],
+    generation: u64,
+    lamport: u64,
 ) -> Vec<u8> {
     use qubee_crypto::groups::group_message::{
         canonical_group_message, seal_outer_envelope, GroupMessageBody, GroupMessageEnvelope,
@@ -221,6 +235,7 @@ fn forge_message_with_generation(
         group_id,
         sender_id: sender_kp.identity_id(),
         generation,
+        lamport,
         aead_payload,
         timestamp: std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -325,6 +340,66 @@ fn future_generation_is_rejected() {
     );
 }
 
+#[test]
+fn message_from_previous_epoch_decrypts_after_two_rotations() {
+    let (_alice_dir, alice_kp, mut alice_gm) = fresh_device("alice");
+    let alice_id = alice_kp.identity_id();
+    let group_id = alice_gm
+        .create_group(
+            alice_id,
+            alice_kp.public_key(),
+            "Test Group".to_string(),
+            String::new(),
+            GroupType::Private,
+            GroupSettings::default(),
+        )
+        .unwrap();
+    alice_gm.ensure_group_key(group_id).unwrap();
+    let invitation = alice_gm
+        .create_invitation(group_id, alice_id, None, None)
+        .unwrap();
+    let (_bob_dir, _bob_kp, mut bob_gm, _ma_body, _ma_sig) = join_bob_to_alice(
+        &alice_kp,
+        &mut alice_gm,
+        group_id,
+        invitation.invitation_code,
+        invitation.inviter_name,
+    );
+
+    // Alice rotates and Bob installs the delivered key, as
+    // `process_key_rotation` would.
+    let rotate = |alice_gm: &mut GroupManager, bob_gm: &mut GroupManager| {
+        alice_gm
+            .rotate_group_key_after_removal(group_id, alice_id)
+            .unwrap();
+        let key = alice_gm.export_group_key(&group_id).unwrap();
+        bob_gm.install_group_key(group_id, &key).unwrap();
+    };
+
+    let before = encrypt_group_message(&alice_gm, &alice_kp, group_id, b"epoch N-2").unwrap();
+    rotate(&mut alice_gm, &mut bob_gm);
+    let in_flight = encrypt_group_message(&alice_gm, &alice_kp, group_id, b"epoch N-1").unwrap();
+    rotate(&mut alice_gm, &mut bob_gm);
+    let current = encrypt_group_message(&alice_gm, &alice_kp, group_id, b"epoch N").unwrap();
+
+    for (wire, expected) in [
+        (&current, b"epoch N".as_slice()),
+        (&in_flight, b"epoch N-1".as_slice()),
+        (&before, b"epoch N-2".as_slice()),
+    ] {
+        let decrypted = decrypt_group_message(&bob_gm, wire).expect("retained epoch decrypts");
+        assert_eq!(decrypted.plaintext, expected);
+        assert!(qubee_crypto::groups::group_message::extract_message_id(&bob_gm, wire).is_some());
+    }
+
+    // Once the key ages out of the history the frame is gone for good.
+    for _ in 0..qubee_crypto::groups::group_crypto::GROUP_KEY_HISTORY {
+        rotate(&mut alice_gm, &mut bob_gm);
+    }
+    assert!(decrypt_group_message(&bob_gm, &in_flight).is_err());
+    assert!(qubee_crypto::groups::group_message::extract_message_id(&bob_gm, &in_flight).is_none());
+}
+
 #[test]
 fn wire_format_magic_prefix_is_stable() {
     // Stability check: a sealed GroupMessageEnvelope frame begins with
@@ -703,6 +778,7 @@ fn message_older_than_max_age_is_rejected() {
         group_id,
         sender_id: alice_id,
         generation: alice_gm.get_group(&group_id).unwrap().version,
+        lamport: 1,
         aead_payload,
         timestamp: std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -1504,3 +1580,120 @@ fn sealed_envelope_tampering_is_detected() {
         "outer AEAD must reject single-bit ciphertext tampering",
     );
 }
+
+/// Alice and Bob each send before seeing the other's message, then
+/// both receive everything in opposite arrival orders. Sorting by the
+/// Lamport order key gives both replicas the same timeline, and a
+/// reply sent afterwards sorts after both.
+#[test]
+fn concurrent_messages_get_the_same_order_on_every_replica() {
+    let (_alice_dir, alice_kp, mut alice_gm) = fresh_device("alice");
+    let alice_id = alice_kp.identity_id();
+    let group_id = alice_gm
+        .create_group(
+            alice_id,
+            alice_kp.public_key(),
+            "Test Group".to_string(),
+            String::new(),
+            GroupType::Private,
+            GroupSettings::default(),
+        )
+        .unwrap();
+    alice_gm.ensure_group_key(group_id).unwrap();
+    let invitation = alice_gm
+        .create_invitation(group_id, alice_id, None, None)
+        .unwrap();
+    let (_bob_dir, bob_kp, bob_gm, _ma_body, _ma_sig) = join_bob_to_alice(
+        &alice_kp,
+        &mut alice_gm,
+        group_id,
+        invitation.invitation_code,
+        invitation.inviter_name,
+    );
+
+    let from_alice = encrypt_group_message(&alice_gm, &alice_kp, group_id, b"alice 1").unwrap();
+    let from_bob = encrypt_group_message(&bob_gm, &bob_kp, group_id, b"bob 1").unwrap();
+    assert_eq!(alice_gm.current_lamport(&group_id), 1);
+    assert_eq!(bob_gm.current_lamport(&group_id), 1);
+
+    let timeline = |gm: &GroupManager, arrivals: [&[u8]; 2]| {
+        let mut messages: Vec<_> = arrivals
+            .iter()
+            .map(|wire| decrypt_group_message(gm, wire).unwrap())
+            .collect();
+        messages.sort_by_key(|m| m.order_key());
+        messages
+            .into_iter()
+            .map(|m| m.plaintext)
+            .collect::<Vec<_>>()
+    };
+    let on_alice = timeline(&alice_gm, [&from_alice, &from_bob]);
+    let on_bob = timeline(&bob_gm, [&from_bob, &from_alice]);
+    assert_eq!(on_alice, on_bob);
+
+    let reply = encrypt_group_message(&alice_gm, &alice_kp, group_id, b"alice 2").unwrap();
+    let reply = decrypt_group_message(&bob_gm, &reply).unwrap();
+    assert_eq!(reply.lamport, 2);
+    assert_eq!(bob_gm.current_lamport(&group_id), 2);
+}
+
+/// A device that just joined starts with no clock while the group's
+/// has long passed `MAX_LAMPORT_JUMP`. The first message seeds the
+/// joiner's clock; a later jump past the bound is still delivered and
+/// only moves the clock by the bound.
+#[test]
+fn new_joiner_accepts_a_group_clock_far_past_the_jump_bound() {
+    let (_alice_dir, alice_kp, mut alice_gm) = fresh_device("alice");
+    let alice_id = alice_kp.identity_id();
+    let group_id = alice_gm
+        .create_group(
+            alice_id,
+            alice_kp.public_key(),
+            "Test Group".to_string(),
+            String::new(),
+            GroupType::Private,
+            GroupSettings::default(),
+        )
+        .unwrap();
+    alice_gm.ensure_group_key(group_id).unwrap();
+    let invitation = alice_gm
+        .create_invitation(group_id, alice_id, None, None)
+        .unwrap();
+    let (_bob_dir, _bob_kp, bob_gm, _ma_body, _ma_sig) = join_bob_to_alice(
+        &alice_kp,
+        &mut alice_gm,
+        group_id,
+        invitation.invitation_code,
+        invitation.inviter_name,
+    );
+    assert_eq!(bob_gm.current_lamport(&group_id), 0);
+
+    let generation = alice_gm.get_group(&group_id).unwrap().version;
+    let group_clock = 5 * MAX_LAMPORT_JUMP;
+    let wire = forge_message(
+        &alice_gm,
+        &alice_kp,
+        group_id,
+        b"hi",
+        generation,
+        group_clock,
+    );
+    let received = decrypt_group_message(&bob_gm, &wire).unwrap();
+    assert_eq!(received.plaintext, b"hi");
+    assert_eq!(bob_gm.current_lamport(&group_id), group_clock);
+
+    let wire = forge_message(
+        &alice_gm,
+        &alice_kp,
+        group_id,
+        b"far ahead",
+        generation,
+        u64::MAX,
+    );
+    let received = decrypt_group_message(&bob_gm, &wire).unwrap();
+    assert_eq!(received.plaintext, b"far ahead");
+    assert_eq!(
+        bob_gm.current_lamport(&group_id),
+        group_clock + MAX_LAMPORT_JUMP
+    );
+}
@@ -14,18 +14,26 @@
 //! Don't just edit the magic bytes in place — that means devices
 //! running the old code will silently drop frames from the new code.
 
+use qubee_crypto::groups::group_crypto::GroupCrypto;
 use qubee_crypto::groups::group_handshake::{
     canonical_join_accepted, canonical_join_rejected, canonical_key_rotation,
     canonical_member_added, canonical_request_join, canonical_role_change,
     generate_ephemeral_kyber, GroupMemberSummary, JoinAcceptedBody, JoinRejectedBody,
     KeyRotationBody, MemberAddedBody, RequestJoinBody, RoleChangeBody, HANDSHAKE_MAGIC,
 };
-use qubee_crypto::groups::group_manager::GroupId;
+use qubee_crypto::groups::group_manager::{GroupId, GroupManager, GroupSettings, GroupType};
 use qubee_crypto::groups::group_message::{
     canonical_group_message, GroupMessageBody, MAGIC_GROUP_MESSAGE,
 };
 use qubee_crypto::groups::group_permissions::Role;
+use qubee_crypto::groups::signed_invite::{
+    canonical_signed_invite, parse_invite_link, SignedInviteBody,
+};
 use qubee_crypto::identity::identity_key::{IdentityId, IdentityKeyPair};
+use qubee_crypto::identity::signal_protocol::SignalProtocol;
+use qubee_crypto::network::fragmentation::{fragment, MAGIC_FRAGMENT};
+use qubee_crypto::secure_message::{message_aad, MessageContext};
+use qubee_crypto::storage::secure_keystore::SecureKeyStore;
 
 #[test]
 fn handshake_magic_is_pinned() {
@@ -41,6 +49,16 @@ fn group_message_magic_is_pinned() {
     assert_eq!(MAGIC_GROUP_MESSAGE, b"QUBEE_GMS\x02");
 }
 
+#[test]
+fn fragment_header_layout_is_pinned() {
+    assert_eq!(MAGIC_FRAGMENT, b"QUBEE_FRG\x01");
+    let frames = fragment(&[0xAB; 120], 96).unwrap();
+    // magic(10) || message_id(32) || total(u16 LE) || index(u16 LE)
+    assert_eq!(frames.len(), 3);
+    assert_eq!(&frames[1][42..46], &[3, 0, 1, 0]);
+    assert_eq!(frames[1].len(), 96);
+}
+
 #[test]
 fn canonical_request_join_starts_with_versioned_tag() {
     let kp = IdentityKeyPair::generate().unwrap();
@@ -104,16 +122,159 @@ fn canonical_key_rotation_starts_with_versioned_tag() {
 }
 
 #[test]
-fn canonical_group_message_starts_with_versioned_tag() {
+fn canonical_group_message_bytes_are_pinned() {
     let body = GroupMessageBody {
-        group_id: GroupId::from_bytes([0u8; 32]),
-        sender_id: IdentityId::from([0u8; 32]),
-        generation: 1,
-        aead_payload: vec![0u8; 12],
-        timestamp: 0,
+        group_id: GroupId::from_bytes([0x11; 32]),
+        sender_id: IdentityId::from([0x22; 32]),
+        generation: 3,
+        lamport: 0x0102_0304_0506_0708,
+        aead_payload: vec![0xAA; 4],
+        timestamp: 5,
     };
-    let canonical = canonical_group_message(&body);
-    assert!(canonical.starts_with(b"qubee_group_message_v1"));
+    // tag || 0 || group_id || 0 || sender_id || 0 || generation(u64 LE)
+    // || 0 || lamport(u64 LE) || 0 || timestamp(u64 LE) || 0
+    // || len(u32 LE) || aead_payload
+    let mut expected = b"qubee_group_message_v3\x00".to_vec();
+    expected.extend_from_slice(&[0x11; 32]);
+    expected.push(0);
+    expected.extend_from_slice(&[0x22; 32]);
+    expected.extend_from_slice(b"\x00\x03\x00\x00\x00\x00\x00\x00\x00");
+    expected.extend_from_slice(b"\x00\x08\x07\x06\x05\x04\x03\x02\x01");
+    expected.extend_from_slice(b"\x00\x05\x00\x00\x00\x00\x00\x00\x00");
+    expected.extend_from_slice(b"\x00\x04\x00\x00\x00\xAA\xAA\xAA\xAA");
+    assert_eq!(canonical_group_message(&body), expected);
+}
+
+#[test]
+fn canonical_signed_invite_bytes_are_pinned() {
+    let inviter_key = IdentityKeyPair::generate().unwrap().public_key();
+    let body = SignedInviteBody {
+        group_id: GroupId::from_bytes([0x11; 32]),
+        group_name: "Grp".to_string(),
+        inviter_key: inviter_key.clone(),
+        inviter_name: "Al".to_string(),
+        invitation_code: "c0de".to_string(),
+        issued_at: 1,
+        expires_at: 2,
+    };
+    // tag || 0 || group_id || 0 || len || group_name || inviter_key
+    // || 0 || len || inviter_name || len || invitation_code
+    // || issued_at(u64 LE) || expires_at(u64 LE)
+    let mut expected = b"qubee_signed_invite_v1\x00".to_vec();
+    expected.extend_from_slice(&[0x11; 32]);
+    expected.extend_from_slice(b"\x00\x03\x00\x00\x00Grp");
+    expected.extend_from_slice(&inviter_key.to_bytes());
+    expected.extend_from_slice(b"\x00\x02\x00\x00\x00Al\x04\x00\x00\x00c0de");
+    expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(canonical_signed_invite(&body).unwrap(), expected);
+}
+
+#[test]
+fn device_revocation_signed_bytes_are_pinned() {
+    let kp = IdentityKeyPair::generate().unwrap();
+    let device_id = kp.derive_device_key(b"phone").unwrap().device_id();
+    let revocation = kp.revoke_device(device_id).unwrap();
+
+    // tag || 0 || identity_id || device_id || revoked_at(u64 LE)
+    let mut expected = b"qubee device revocation v1\x00".to_vec();
+    expected.extend_from_slice(kp.identity_id().as_ref());
+    expected.extend_from_slice(device_id.as_ref());
+    expected.extend_from_slice(&revocation.revoked_at.to_le_bytes());
+    assert!(kp
+        .public_key()
+        .verify_with_max_age(&expected, &revocation.signature, u64::MAX)
+        .unwrap());
+}
+
+#[test]
+fn one_time_prekey_signed_bytes_are_pinned() {
+    use pqcrypto_traits::kem::PublicKey as _;
+    let kp = IdentityKeyPair::generate().unwrap();
+    let identity_id = kp.identity_id();
+    let mut protocol = SignalProtocol::new(kp, b"phone").unwrap();
+    let otk = protocol.generate_one_time_prekeys(1).unwrap().remove(0);
+    let device = protocol.device_key().public_key();
+
+    // tag || 0 || id(u32 LE) || x25519 || ml-kem || created_at(u64 LE)
+    // || device_id || identity_id
+    let mut expected = b"qubee one-time prekey v1\x00".to_vec();
+    expected.extend_from_slice(&otk.id.to_le_bytes());
+    expected.extend_from_slice(otk.x25519_public.as_bytes());
+    expected.extend_from_slice(otk.kyber_public.as_bytes());
+    expected.extend_from_slice(&otk.created_at.to_le_bytes());
+    expected.extend_from_slice(device.device_id.as_ref());
+    expected.extend_from_slice(identity_id.as_ref());
+    assert!(protocol
+        .identity_keypair()
+        .public_key()
+        .verify_with_max_age(&expected, &otk.signature, u64::MAX)
+        .unwrap());
+}
+
+#[test]
+fn group_sender_message_signed_bytes_are_pinned() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let keystore = SecureKeyStore::new(dir.path().join("gm.db"), b"passphrase").unwrap();
+    let mut gm = GroupManager::new(keystore).unwrap();
+    let alice = IdentityKeyPair::generate().unwrap();
+    let group_id = gm
+        .create_group(
+            alice.identity_id(),
+            alice.public_key(),
+            "G".to_string(),
+            String::new(),
+            GroupType::Private,
+            GroupSettings::default(),
+        )
+        .unwrap();
+    let (ciphertext, signature) = gm
+        .encrypt_group_message_authenticated(&group_id, &alice, b"hi")
+        .unwrap();
+
+    // ciphertext = sender_id || epoch(u64 LE) || index(u32 LE) || nonce(12) || aead
+    assert_eq!(&ciphertext[..32], alice.identity_id().as_ref());
+    assert_eq!(&ciphertext[32..44], &[0u8; 12]);
+    assert_eq!(ciphertext.len(), 44 + 12 + 2 + 16);
+
+    // tag || 0 || group_id || len(ciphertext, u64 LE) || ciphertext
+    let mut expected = b"qubee_group_sender_message_v1\x00".to_vec();
+    expected.extend_from_slice(group_id.as_ref());
+    expected.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    expected.extend_from_slice(&ciphertext);
+    assert!(alice
+        .public_key()
+        .verify_with_max_age(&expected, &signature, u64::MAX)
+        .unwrap());
+}
+
+#[test]
+fn message_aad_bytes_are_pinned() {
+    // tag || 0 || len(header, u32 LE) || header
+    let mut expected = b"qubee message aad v1\x00\x03\x00\x00\x00hdr".to_vec();
+    assert_eq!(message_aad(b"hdr", None), expected);
+
+    // ... || len(sender) || sender || len(conversation) || conversation
+    // || timestamp(u64 LE)
+    let context = MessageContext {
+        sender_id: "al".to_string(),
+        conversation_id: "c".to_string(),
+        timestamp: 5,
+    };
+    expected.extend_from_slice(b"\x02\x00\x00\x00al\x01\x00\x00\x00c");
+    expected.extend_from_slice(&[5, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(message_aad(b"hdr", Some(&context)), expected);
+}
+
+#[test]
+fn group_aead_payload_layout_is_pinned() {
+    let mut crypto = GroupCrypto::new().unwrap();
+    let group_id = GroupId::from_bytes([0u8; 32]);
+    crypto.create_group_key(group_id, 1).unwrap();
+    crypto.rotate_group_key(group_id, 2).unwrap();
+    let payload = crypto.encrypt_message(&group_id, b"hello").unwrap();
+    // epoch(u64 LE) || nonce(12) || ciphertext(5) || tag(16)
+    assert_eq!(&payload[..8], &[1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(payload.len(), 8 + 12 + 5 + 16);
 }
 
 #[test]
@@ -280,6 +441,7 @@ proptest! {
         group_seed in any::<[u8; 32]>(),
         sender_seed in any::<[u8; 32]>(),
         generation in 0u64..=1_000_000,
+        lamport in any::<u64>(),
         aead_payload in proptest::collection::vec(any::<u8>(), 0..1024),
         timestamp in 0u64..=4_000_000_000,
     ) {
@@ -288,6 +450,7 @@ proptest! {
             group_id: GroupId::from_bytes(group_seed),
             sender_id: IdentityId::from(sender_seed),
             generation,
+            lamport,
             aead_payload,
             timestamp,
         };
@@ -316,6 +479,7 @@ proptest! {
         prop_assert_eq!(decoded.body.group_id, body.group_id);
         prop_assert_eq!(decoded.body.sender_id, body.sender_id);
         prop_assert_eq!(decoded.body.generation, body.generation);
+        prop_assert_eq!(decoded.body.lamport, body.lamport);
         prop_assert_eq!(decoded.body.aead_payload, body.aead_payload);
         prop_assert_eq!(decoded.body.timestamp, body.timestamp);
     }
@@ -389,3 +553,129 @@ proptest! {
         }
     }
 }
+
+// ---------------------------------------------------------------------
+// Deep-link decoders. Invite, join and identity links arrive by QR
+// code or NFC tag, i.e. from anyone in camera range. Whatever the token
+// holds, the decoders must return an error — never panic, never
+// allocate the length a crafted prefix claims.
+// ---------------------------------------------------------------------
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use qubee_crypto::groups::group_invite::InvitePayload;
+use qubee_crypto::onboarding::OnboardingBundle;
+
+proptest! {
+    #![proptest_config(config_64())]
+
+    #[test]
+    fn link_decoders_reject_arbitrary_payloads(
+        bytes in proptest::collection::vec(any::<u8>(), 0..4096),
+    ) {
+        let token = URL_SAFE_NO_PAD.encode(&bytes);
+        let invite = format!("qubee://invite/{token}");
+        let identity = format!("qubee://identity/{token}");
+        let join = format!("qubee://join/{token}");
+        prop_assert!(InvitePayload::from_invite_link(&invite).is_err());
+        prop_assert!(OnboardingBundle::from_share_link(&identity).is_err());
+        prop_assert!(parse_invite_link(&join).is_err());
+    }
+
+    #[test]
+    fn link_decoders_reject_arbitrary_tokens(token in "[\\PC]{0,512}") {
+        let invite = format!("qubee://invite/{token}");
+        let identity = format!("qubee://identity/{token}");
+        let join = format!("qubee://join/{token}");
+        prop_assert!(InvitePayload::from_invite_link(&invite).is_err());
+        prop_assert!(OnboardingBundle::from_share_link(&identity).is_err());
+        prop_assert!(parse_invite_link(&join).is_err());
+    }
+}
+
+// ---------------------------------------------------------------------
+// Call signaling. Only built with `--features calling`.
+// ---------------------------------------------------------------------
+
+#[cfg(feature = "calling")]
+mod signaling {
+    use qubee_crypto::calling::call_manager::{CallId, CallSettings, CallType};
+    use qubee_crypto::calling::peer_connection::ICECandidate;
+    use qubee_crypto::calling::signaling::{canonical_signaling_message, SignalingMessage};
+    use qubee_crypto::identity::identity_key::IdentityId;
+
+    const RECIPIENT: [u8; 32] = [0x33; 32];
+    const SENDER: [u8; 32] = [0x22; 32];
+    const CALL: [u8; 16] = [0x44; 16];
+
+    /// tag || 0 || recipient || 0 || call_id || 0 || sender
+    fn prefix(tag: &[u8]) -> Vec<u8> {
+        let mut out = tag.to_vec();
+        out.push(0);
+        out.extend_from_slice(&RECIPIENT);
+        out.push(0);
+        out.extend_from_slice(&CALL);
+        out.push(0);
+        out.extend_from_slice(&SENDER);
+        out
+    }
+
+    fn canonical(message: &SignalingMessage) -> Vec<u8> {
+        canonical_signaling_message(message, &IdentityId::from(RECIPIENT))
+    }
+
+    #[test]
+    fn call_invitation_bytes_are_pinned() {
+        let message = SignalingMessage::CallInvitation {
+            call_id: CallId::from(CALL),
+            caller: IdentityId::from(SENDER),
+            call_type: CallType::VideoCall,
+            settings: CallSettings::default(),
+        };
+        // ... || 0 || call_type(1) || 0 || max_participants(present, u64 LE)
+        // || flags(5) || video_quality(1) || audio_quality(1)
+        // || bandwidth_limit(absent)
+        let mut expected = prefix(b"qubee_signaling_call_invitation_v2");
+        expected.extend_from_slice(&[0, 1, 0, 1, 8, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[1, 0, 0, 1, 1, 5, 4, 0]);
+        assert_eq!(canonical(&message), expected);
+    }
+
+    #[test]
+    fn sdp_and_ice_bytes_are_pinned() {
+        let offer = SignalingMessage::SdpOffer {
+            call_id: CallId::from(CALL),
+            sdp: "v=0".to_string(),
+            sender: IdentityId::from(SENDER),
+        };
+        // ... || 0 || len(u32 LE) || sdp
+        let mut expected = prefix(b"qubee_signaling_sdp_offer_v2");
+        expected.extend_from_slice(b"\x00\x03\x00\x00\x00v=0");
+        assert_eq!(canonical(&offer), expected);
+
+        let candidate = SignalingMessage::IceCandidate {
+            call_id: CallId::from(CALL),
+            candidate: ICECandidate {
+                sdp_mid: "0".to_string(),
+                sdp_mline_index: 2,
+                candidate: "c".to_string(),
+            },
+            sender: IdentityId::from(SENDER),
+        };
+        // ... || 0 || len || sdp_mid || 0 || mline(u32 LE) || 0 || len || candidate
+        let mut expected = prefix(b"qubee_signaling_ice_candidate_v2");
+        expected.extend_from_slice(b"\x00\x01\x00\x00\x000");
+        expected.extend_from_slice(b"\x00\x02\x00\x00\x00");
+        expected.extend_from_slice(b"\x00\x01\x00\x00\x00c");
+        assert_eq!(canonical(&candidate), expected);
+    }
+
+    #[test]
+    fn hang_up_bytes_are_pinned() {
+        let message = SignalingMessage::HangUp {
+            call_id: CallId::from(CALL),
+            sender: IdentityId::from(SENDER),
+        };
+        assert_eq!(canonical(&message), prefix(b"qubee_signaling_hang_up_v2"));
+    }
+}